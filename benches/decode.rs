@@ -0,0 +1,139 @@
+//! Compare reading a module through the borrowing `Buffer` with reading it through the
+//! copying one it replaced, then time `ModuleNode::load` on the same module.
+//!
+//! Run with `cargo bench --bench decode`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use toy_wasm_runtime::exec::buffer::Buffer;
+use toy_wasm_runtime::exec::builder::ModuleBuilder;
+use toy_wasm_runtime::exec::code_section::Op;
+use toy_wasm_runtime::exec::module::ModuleNode;
+use toy_wasm_runtime::exec::type_section::ValType;
+
+const RUNS: u32 = 10;
+
+/// The buffer before it borrowed its bytes, copying them on every read.
+mod copying {
+    use anyhow::{anyhow, Result};
+
+    pub struct Buffer {
+        cursor: u32,
+        buf: Vec<u8>,
+    }
+    impl Buffer {
+        pub fn new(buf: Vec<u8>) -> Buffer {
+            Buffer { cursor: 0, buf }
+        }
+
+        pub fn eof(&self) -> bool {
+            self.cursor >= self.buf.len() as u32
+        }
+
+        pub fn read_byte(&mut self) -> Result<u8> {
+            Ok(self.read_bytes(1)?[0])
+        }
+
+        pub fn read_bytes(&mut self, size: u32) -> Result<Vec<u8>> {
+            if (self.buf.len() as u32) < (self.cursor + size) {
+                return Err(anyhow!("Buffer too small"));
+            }
+            let slice = self.buf[self.cursor as usize..(self.cursor + size) as usize].to_vec();
+            self.cursor += size;
+            Ok(slice)
+        }
+
+        pub fn read_buffer(&mut self, size: u32) -> Result<Buffer> {
+            Ok(Buffer::new(self.read_bytes(size)?))
+        }
+
+        pub fn read_u32(&mut self) -> Result<u32> {
+            let mut result = 0_u32;
+            let mut shift = 0_u32;
+            loop {
+                let byte = self.read_byte()?;
+                result |= ((byte & 0b01111111) as u32) << shift;
+                shift += 7;
+                if byte & 0b10000000 == 0 {
+                    return Ok(result);
+                }
+            }
+        }
+    }
+}
+
+/// Read the sections of a module, and the function bodies byte by byte, the way the
+/// decoder does, giving the sum of the bytes read.
+macro_rules! walk {
+    ($buf:expr) => {{
+        let mut buf = $buf;
+        let mut sum = 0_u64;
+        buf.read_bytes(8).unwrap();
+        while !buf.eof() {
+            let id = buf.read_byte().unwrap();
+            let size = buf.read_u32().unwrap();
+            let mut section = buf.read_buffer(size).unwrap();
+            if id != 10 {
+                sum += section.read_bytes(size).unwrap().len() as u64;
+                continue;
+            }
+            for _ in 0..section.read_u32().unwrap() {
+                let size = section.read_u32().unwrap();
+                let mut body = section.read_buffer(size).unwrap();
+                while !body.eof() {
+                    sum += body.read_byte().unwrap() as u64;
+                }
+            }
+        }
+        sum
+    }};
+}
+
+/// Functions adding up constants and a 1 MB data segment.
+fn large_module() -> Vec<u8> {
+    let mut builder = ModuleBuilder::new();
+    builder.memory(16, None);
+    builder.data(0, 0, &vec![0xab; 1 << 20]);
+    for n in 0..5_000 {
+        builder.func(&[ValType::I32], &[ValType::I32], |f| {
+            f.local_get(0);
+            for i in 0..100 {
+                f.i32_const(n * i).op(Op::I32Add);
+            }
+        });
+    }
+    builder.to_bytes().unwrap()
+}
+
+/// Average time of `f` over `RUNS` runs, after an untimed one warming up the allocator.
+fn time(f: &dyn Fn() -> u64) -> Duration {
+    black_box(f());
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        black_box(f());
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let bytes = large_module();
+    let borrowed = || walk!(Buffer::new(black_box(&bytes)));
+    let copying = || walk!(copying::Buffer::new(black_box(&bytes).clone()));
+    assert_eq!(borrowed(), copying());
+
+    let (borrowed, copying) = (time(&borrowed), time(&copying));
+    let load = time(&|| {
+        let mut module = ModuleNode::new();
+        module.load(&mut Buffer::new(black_box(&bytes))).unwrap();
+        module.sections.len() as u64
+    });
+    println!(
+        "{:.1} MB: reading {:?} borrowed, {:?} copying ({:.1}x), ModuleNode::load {:?}",
+        bytes.len() as f64 / 1e6,
+        borrowed,
+        copying,
+        copying.as_secs_f64() / borrowed.as_secs_f64(),
+        load
+    );
+}
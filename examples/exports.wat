@@ -0,0 +1,13 @@
+(module
+  (table (export "table") 2 funcref)
+  (memory (export "memory") 1)
+  (global $counter (export "counter") (mut i32) (i32.const 0))
+  (global (export "answer") i64 (i64.const 42))
+  (func (export "incr") (result i32)
+    (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+    (global.get $counter)
+  )
+  (func (export "load") (param $addr i32) (result i32)
+    (i32.load (local.get $addr))
+  )
+)
@@ -0,0 +1,12 @@
+(module
+  (memory (export "memory") 1 2)
+  (table (export "table") 1 4 funcref)
+  (global $counter (export "counter") (mut i32) (i32.const 10))
+  (func (export "add") (param $a i32) (param $b i32) (result i32)
+    (i32.add (local.get $a) (local.get $b))
+  )
+  (func (export "incr") (result i32)
+    (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+    (global.get $counter)
+  )
+)
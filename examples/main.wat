@@ -0,0 +1,18 @@
+(module
+  (import "lib" "add" (func $add (param i32 i32) (result i32)))
+  (import "lib" "memory" (memory 1))
+  (import "lib" "table" (table 1 funcref))
+  (import "lib" "counter" (global $counter (mut i32)))
+  (func $add_counter (export "add_counter") (param $x i32) (result i32)
+    (call $add (local.get $x) (global.get $counter))
+  )
+  (func (export "store") (param $addr i32) (param $value i32)
+    (i32.store (local.get $addr) (local.get $value))
+  )
+  (func (export "bump")
+    (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+  )
+  (func (export "table_size") (result i32)
+    (table.size)
+  )
+)
@@ -0,0 +1,25 @@
+(module
+  (type (func (result i32)))
+  (type (func (param i32) (result i32)))
+  (type (func (param i32 i32)))
+  (table 1 funcref)
+  (memory 1 4)
+  (func (export "grow") (param $delta i32) (result i32)
+    (memory.grow (local.get $delta))
+  )
+  (func (export "size") (result i32)
+    (memory.size)
+  )
+  (func (export "store") (param $addr i32) (param $value i32)
+    (i32.store (local.get $addr) (local.get $value))
+  )
+  (func (export "load") (param $addr i32) (result i32)
+    (i32.load (local.get $addr))
+  )
+  (func (export "grow_table") (param $delta i32) (result i32)
+    (table.grow (ref.null func) (local.get $delta))
+  )
+  (func (export "table_size") (result i32)
+    (table.size)
+  )
+)
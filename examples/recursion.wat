@@ -0,0 +1,13 @@
+(module
+  (func $count (export "count") (param $n i32) (result i32)
+    (if (result i32) (i32.eqz (local.get $n))
+      (then (i32.const 0))
+      (else
+        (i32.add
+          (i32.const 1)
+          (call $count (i32.sub (local.get $n) (i32.const 1)))
+        )
+      )
+    )
+  )
+)
@@ -0,0 +1,40 @@
+;; Exercises every directive of the wast subcommand.
+(module $math
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1)))
+  (func (export "div") (param i32 i32) (result i32)
+    (i32.div_s (local.get 0) (local.get 1)))
+  (func (export "id") (param f32) (result f32)
+    (local.get 0))
+  (func $loop (export "loop") (call $loop))
+  (global (export "answer") i32 (i32.const 42)))
+
+(assert_return (invoke "add" (i32.const 1) (i32.const 2)) (i32.const 3))
+(assert_return (invoke "id" (f32.const 1.5)) (f32.const 1.5))
+(assert_return (invoke "id" (f32.const -nan)) (f32.const nan:canonical))
+(assert_return (get "answer") (i32.const 42))
+(assert_trap (invoke "div" (i32.const 1) (i32.const 0)) "integer divide by zero")
+(assert_trap (invoke $math "div" (i32.const 0x80000000) (i32.const -1)) "integer overflow")
+(assert_exhaustion (invoke "loop") "call stack exhausted")
+
+(register "math" $math)
+
+(module
+  (import "math" "add" (func $add (param i32 i32) (result i32)))
+  (import "spectest" "print_i32" (func $print (param i32)))
+  (import "spectest" "global_i32" (global $g i32))
+  (import "spectest" "memory" (memory 1))
+  (import "spectest" "table" (table 10 funcref))
+  (func (export "add_global") (param i32) (result i32)
+    (call $add (local.get 0) (global.get $g)))
+  (func (export "print") (param i32)
+    (call $print (local.get 0))))
+
+(invoke "print" (i32.const 7))
+(assert_return (invoke "add_global" (i32.const 1)) (i32.const 667))
+(assert_return (invoke "print" (i32.const 8)))
+
+(assert_malformed (module quote "(func (i32.const))") "unexpected token")
+(assert_malformed (module binary "\00asn" "\01\00\00\00") "magic header not detected")
+(assert_invalid (module (func (call 5))) "unknown function")
+(assert_invalid (module (func (result i32) (i64.const 0))) "type mismatch")
@@ -0,0 +1,5 @@
+(module
+  (func (export "spin")
+    (loop $loop (br $loop))
+  )
+)
@@ -0,0 +1,10 @@
+(module
+  (global $calls (mut i32) (i32.const 0))
+  (func $init
+    (global.set $calls (i32.add (global.get $calls) (i32.const 1)))
+  )
+  (func (export "calls") (result i32)
+    (global.get $calls)
+  )
+  (start $init)
+)
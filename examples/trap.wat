@@ -0,0 +1,13 @@
+(module
+  (func $div_s (param $a i32) (param $b i32) (result i32)
+    (i32.div_s (local.get $a) (local.get $b))
+  )
+
+  (func $div (export "div") (param $a i32) (param $b i32) (result i32)
+    (call $div_s (local.get $a) (local.get $b))
+  )
+
+  (func (export "unreachable")
+    (unreachable)
+  )
+)
@@ -1,44 +1,814 @@
-use anyhow::Result;
-use std::fs;
-use std::io::ErrorKind;
-use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
 
+use crate::debugger::Debugger;
 use crate::errors::ExecError;
 use crate::exec::buffer::Buffer;
+use crate::exec::coverage::Coverage;
+use crate::exec::export_section::ExportKind;
+use crate::exec::import_section::ImportDescNode;
+use crate::exec::limits::{StoreLimits, DEFAULT_MAX_MEMORY_SIZE, DEFAULT_MAX_TABLE_ELEMENTS};
+use crate::exec::linker::Linker;
 use crate::exec::module::ModuleNode;
+use crate::exec::profile::Profiler;
+use crate::exec::section::{section_name, SectionNode};
+use crate::exec::store::Store;
+use crate::exec::stream::StreamDecoder;
+use crate::exec::trace::{Tracer, WriteTracer};
+use crate::exec::trap::{Trap, TrapKind};
+use crate::exec::type_section::{
+    FunctionTypeNode, GlobalTypeNode, LimitsNode, MemoryTypeNode, NumType, TableTypeNode, ValType,
+};
+use crate::exec::validate::{validate, validate_sections, ValidationError};
+use crate::exec::value::Value;
+use crate::json::Json;
+use crate::repl::Repl;
+use crate::wasi::ctx::WasiCtx;
+use crate::wasi::preview1;
+use crate::wast::runner::{run_script, Summary};
+use crate::wat;
+use crate::wat::printer::print_signature;
 
+/// Exit status of a command failing for any other reason than the ones below.
+pub const EXIT_FAILURE: u8 = 1;
+/// Exit status when the module could not be decoded.
+pub const EXIT_MALFORMED: u8 = 3;
+/// Exit status when the module did not pass validation.
+pub const EXIT_INVALID: u8 = 4;
+/// Exit status when the executed Wasm code trapped.
+pub const EXIT_TRAP: u8 = 5;
+
+/// Exit status of the process when a command fails with `error`.
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+    if let Some(trap) = error.downcast_ref::<Trap>() {
+        return match trap.kind {
+            // The status of WASI `proc_exit`. Statuses past a byte are clamped instead of
+            // truncated, which could turn a failure such as 256 into success.
+            TrapKind::Exit(status) => status.min(u8::MAX as u32) as u8,
+            _ => EXIT_TRAP,
+        };
+    }
+    match error.downcast_ref::<ExecError>() {
+        Some(ExecError::Malformed(_)) => EXIT_MALFORMED,
+        Some(ExecError::Invalid(_)) => EXIT_INVALID,
+        _ => EXIT_FAILURE,
+    }
+}
+
+/// Output of `inspect --print`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PrintFormat {
+    /// Number of entries of each section and the exports.
+    Summary,
+    /// Offsets and sizes of the sections and function bodies.
+    Headers,
+    /// Types, functions, imports, exports and other entries as JSON, see `module_json`.
+    Json,
+    /// Debug representation of the decoded nodes.
+    Debug,
+    /// WebAssembly text format.
+    Wat,
+}
+
+/// Options of `--trace`.
+#[derive(Default)]
+pub struct TraceOptions {
+    /// File the trace is written to instead of stderr.
+    pub output: Option<PathBuf>,
+    /// Only trace the function with this index or name.
+    pub func: Option<String>,
+}
+
+/// Options of `--profile`.
+#[derive(Default)]
+pub struct ProfileOptions {
+    /// File the folded stacks are written to, for flame graph tools.
+    pub folded: Option<PathBuf>,
+}
+
+/// Options of `--coverage`.
+#[derive(Default)]
+pub struct CoverageOptions {
+    /// File the lcov records are written to.
+    pub lcov: Option<PathBuf>,
+}
+
+#[derive(Default)]
 pub struct ExecInput {
     pub path: PathBuf,
-    pub print: bool,
-}
-
-pub fn exec(input: ExecInput) -> Result<ModuleNode> {
-    let file = fs::read(&input.path);
-    let file_content = match file {
-        Ok(file) => file,
-        Err(e) => {
-            if e.kind() == ErrorKind::NotFound {
-                let p = input
-                    .path
-                    .file_name()
-                    .unwrap()
-                    .to_string_lossy()
-                    .into_owned();
-                return Err(ExecError::FileNotFound(p).into());
+    /// Exported function to call, `_start` by default.
+    pub invoke: Option<String>,
+    pub args: Vec<String>,
+    pub fuel: Option<u64>,
+    pub timeout: Option<Duration>,
+    pub max_memory: Option<usize>,
+    pub max_table_elements: Option<u32>,
+    pub trace: Option<TraceOptions>,
+    pub profile: Option<ProfileOptions>,
+    pub coverage: Option<CoverageOptions>,
+}
+
+/// Run a module, printing the results of the called function.
+/// Its start function, if any, runs first when it is instantiated.
+pub fn exec(input: ExecInput) -> Result<Vec<Value>> {
+    let module = load_valid_module(&input.path)?;
+
+    // A module with a start function but no `_start` export is run by instantiating it.
+    let exports = module.export_section().map_or(&[][..], |e| &e.exports);
+    let name = match input.invoke.as_deref() {
+        Some(name) => Some(name),
+        None if module.start_section().is_some() && !exports.iter().any(|e| e.name == START) => {
+            None
+        }
+        None => Some(START),
+    };
+    let results = match invoke(module, name, &input) {
+        Err(e)
+            if e.downcast_ref::<Trap>()
+                .is_some_and(|t| t.kind == TrapKind::Exit(0)) =>
+        {
+            Vec::new()
+        }
+        results => results?,
+    };
+    // WASI commands print their own output.
+    if input.invoke.is_some() || !results.is_empty() {
+        let printed: Vec<String> = results.iter().map(|v| v.to_string()).collect();
+        println!("{}", printed.join(" "));
+    }
+    Ok(results)
+}
+
+/// Export called by `run` without `--invoke`, the entry point of WASI commands.
+const START: &str = "_start";
+
+/// WASI functions the module can import, with the command line arguments of `run`
+/// when `_start` is called.
+fn wasi_ctx(input: &ExecInput) -> WasiCtx {
+    let mut ctx = WasiCtx::new();
+    let mut args = vec![input.path.display().to_string()];
+    if input.invoke.is_none() {
+        args.extend(input.args.iter().cloned());
+    }
+    ctx.set_args(args);
+    ctx
+}
+
+/// Decode the module at `path`, translating it first if it is in the text format.
+/// Binary modules are decoded while being read, from stdin if `path` is `-`.
+pub fn load_module(path: &Path) -> Result<ModuleNode> {
+    // Modules in the text format are translated to the binary format first.
+    if is_text(path) {
+        let text = read_text(path)?;
+        let bytes =
+            wat::module::parse_str(&text).map_err(|e| ExecError::Malformed(e.to_string()))?;
+        let mut module = ModuleNode::new();
+        module.load(&mut Buffer::new(&bytes)).map_err(malformed)?;
+        return Ok(module);
+    }
+
+    StreamDecoder::new(open_file(path)?)
+        .decode()
+        .map_err(malformed)
+}
+
+/// Like `load_module` followed by `check_module`, except that the function bodies of
+/// binary modules are validated as soon as they are decoded.
+pub fn load_valid_module(path: &Path) -> Result<ModuleNode> {
+    if is_text(path) {
+        let module = load_module(path)?;
+        check_module(&module)?;
+        return Ok(module);
+    }
+    decode_valid(open_file(path)?)
+}
+
+/// Decode a binary module from `reader`, failing at the first invalid function body
+/// without reading the rest.
+pub fn decode_valid(reader: impl Read) -> Result<ModuleNode> {
+    let module = StreamDecoder::validating(reader)
+        .decode()
+        .map_err(malformed)?;
+    let errors = validate_sections(&module);
+    match errors.as_slice() {
+        [] => Ok(module),
+        errors => Err(invalid(errors)),
+    }
+}
+
+/// A decoding error as `ExecError::Malformed`, unless it already is an `ExecError`.
+fn malformed(e: anyhow::Error) -> anyhow::Error {
+    match e.downcast::<ExecError>() {
+        Ok(e) => e.into(),
+        Err(e) => ExecError::Malformed(e.to_string()).into(),
+    }
+}
+
+fn is_text(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "wat")
+}
+
+/// Path read from stdin instead of a file.
+const STDIN: &str = "-";
+
+fn open_file(path: &Path) -> Result<Box<dyn Read>> {
+    if path.as_os_str() == STDIN {
+        return Ok(Box::new(io::stdin()));
+    }
+    let file = File::open(path).map_err(|e| file_error(path, e))?;
+    Ok(Box::new(file))
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    open_file(path)?
+        .read_to_end(&mut bytes)
+        .map_err(|e| file_error(path, e))?;
+    Ok(bytes)
+}
+
+/// Read a module in the text format, which must be UTF-8.
+fn read_text(path: &Path) -> Result<String> {
+    String::from_utf8(read_file(path)?)
+        .map_err(|e| ExecError::Malformed(format!("not UTF-8: {}", e)).into())
+}
+
+fn file_error(path: &Path, e: io::Error) -> anyhow::Error {
+    if e.kind() == ErrorKind::NotFound {
+        let p = path.file_name().unwrap().to_string_lossy().into_owned();
+        return ExecError::FileNotFound(p).into();
+    }
+    ExecError::Unknown.into()
+}
+
+/// Fail with the first validation error of `module`, if any.
+pub fn check_module(module: &ModuleNode) -> Result<()> {
+    let errors = validate(module);
+    match errors.as_slice() {
+        [] => Ok(()),
+        errors => Err(invalid(errors)),
+    }
+}
+
+/// `ExecError::Invalid` with the first of `errors`, which is not empty.
+fn invalid(errors: &[ValidationError]) -> anyhow::Error {
+    let message = match errors {
+        [error] => error.to_string(),
+        [error, rest @ ..] => format!("{} (and {} more errors)", error, rest.len()),
+        [] => unreachable!("no validation errors"),
+    };
+    ExecError::Invalid(message).into()
+}
+
+/// Print the module at `path` in `format`.
+pub fn inspect(path: &Path, format: PrintFormat) -> Result<()> {
+    let module = load_module(path)?;
+    match format {
+        PrintFormat::Summary => print!("{}", summary(&module)),
+        PrintFormat::Headers => print!("{}", headers(&module)),
+        PrintFormat::Json => println!("{}", module_json(&module)),
+        PrintFormat::Debug => print_module(module),
+        PrintFormat::Wat => print!("{}", wat::printer::print_module(&module)),
+    }
+    Ok(())
+}
+
+/// Number of entries of each section and the exports with their types.
+pub fn summary(module: &ModuleNode) -> String {
+    let count = |len: Option<usize>| len.unwrap_or(0);
+    let imported_funcs = module.imported_func_count() as usize;
+    let funcs = count(module.function_section().map(|f| f.type_indices.len()));
+    let customs: Vec<&str> = module
+        .sections
+        .iter()
+        .filter_map(|s| match s {
+            SectionNode::CustomSectionNode(c) => Some(c.name.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    let mut text = String::new();
+    let version = module
+        .version
+        .iter()
+        .rev()
+        .fold(0, |v, b| v << 8 | *b as u32);
+    let _ = writeln!(text, "version: {}", version);
+    let counts = [
+        (
+            "types",
+            count(module.type_section().map(|t| t.func_types.len())),
+        ),
+        (
+            "imports",
+            count(module.import_section().map(|i| i.imports.len())),
+        ),
+        ("functions", imported_funcs + funcs),
+        (
+            "tables",
+            count(module.table_section().map(|t| t.table_types.len())),
+        ),
+        (
+            "memories",
+            count(module.memory_section().map(|m| m.mem_types.len())),
+        ),
+        (
+            "globals",
+            count(module.global_section().map(|g| g.globals.len())),
+        ),
+        (
+            "data segments",
+            count(module.data_section().map(|d| d.segments.len())),
+        ),
+    ];
+    for (name, count) in counts {
+        let _ = writeln!(text, "{}: {}", name, count);
+    }
+    let _ = writeln!(text, "custom sections: [{}]", customs.join(", "));
+    let exports = module.export_section().map_or(&[][..], |e| &e.exports);
+    let _ = writeln!(text, "exports: {}", exports.len());
+    for export in exports {
+        let index = export.desc.index;
+        let desc = match export.desc.kind() {
+            Ok(ExportKind::Func) => {
+                let signature = module.func_type(index).map(print_signature);
+                let text = format!("func {} {}", index, signature.unwrap_or_default());
+                text.trim_end().to_string()
             }
-            return Err(ExecError::Unknown.into());
+            Ok(ExportKind::Table) => format!("table {}", index),
+            Ok(ExportKind::Memory) => format!("memory {}", index),
+            Ok(ExportKind::Global) => format!("global {}", index),
+            Err(_) => format!("unknown {}", index),
+        };
+        let _ = writeln!(text, "  {}: {}", export.name, desc);
+    }
+    text
+}
+
+/// Offsets, sizes and entry counts of the sections and of the function bodies,
+/// in the style of `wasm-objdump -h -x`.
+pub fn headers(module: &ModuleNode) -> String {
+    // Built sections have no header, their offsets are those of the encoded module.
+    if module.sections.iter().any(|s| s.header().is_none()) {
+        let mut encoded = ModuleNode::new();
+        if encoded.load(&mut Buffer::new(&module.to_bytes())).is_ok() {
+            return headers(&encoded);
+        }
+    }
+    let mut text = String::from("Sections:\n");
+    for section in &module.sections {
+        let Some(header) = section.header() else {
+            continue;
+        };
+        let _ = write!(
+            text,
+            "{:>3} {:<10} start={:#010x} end={:#010x} (size={:#010x})",
+            header.id,
+            section_name(header.id),
+            header.offset,
+            header.offset + header.size,
+            header.size
+        );
+        let _ = match section {
+            SectionNode::CustomSectionNode(c) => writeln!(text, " \"{}\"", c.name),
+            SectionNode::StartSectionNode(s) => writeln!(text, " start: {}", s.func_idx),
+            section => writeln!(text, " count: {}", section.count().unwrap_or(0)),
+        };
+    }
+
+    if let Some(code) = module.code_section() {
+        let names = module.name_section().unwrap_or_default();
+        let imported_funcs = module.imported_func_count();
+        let _ = writeln!(text, "Code[{}]:", code.codes.len());
+        for (idx, body) in code.codes.iter().enumerate() {
+            let func_idx = imported_funcs + idx as u32;
+            let name = names
+                .func_names
+                .get(&func_idx)
+                .map_or(String::new(), |name| format!(" <{}>", name));
+            let locals: u32 = body.func.locals.iter().map(|l| l.num).sum();
+            let _ = writeln!(
+                text,
+                " - func[{}]{} start={:#010x} end={:#010x} (size={:#010x}) locals: {}",
+                func_idx,
+                name,
+                body.span.start,
+                body.span.end,
+                body.span.len(),
+                locals
+            );
         }
+    }
+    text
+}
+
+/// Decoded module as JSON, the fields of which are only ever added to:
+///
+/// - `schema`: version of this layout, currently 1
+/// - `version`: binary format version
+/// - `types`: `{params, results}` of each function type
+/// - `imports`: `{module, name, kind}`, plus `type` for functions, `ref_type`, `min`
+///   and `max` for tables, `min` and `max` for memories, `type` and `mutable` for globals
+/// - `functions`: `{index, name, type, params, results, imported}`, plus `locals` and
+///   `code_size` in bytes for the ones defined in the module, `null` for imported ones
+/// - `tables`: `{ref_type, min, max}`, `memories`: `{min, max}`, `globals`: `{type, mutable}`
+///   of the ones defined in the module
+/// - `exports`: `{name, kind, index}`
+/// - `custom_sections`: `{name, size}` where `size` is the one of the data after the name
+pub fn module_json(module: &ModuleNode) -> Json {
+    let val_types = |types: &[ValType]| -> Json {
+        types
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>()
+            .into()
+    };
+    let limits = |limits: &LimitsNode| [("min", limits.min.into()), ("max", limits.max.into())];
+    let table = |table: &TableTypeNode| {
+        let [min, max] = limits(&table.limits);
+        Json::object([("ref_type", table.ref_type.to_string().into()), min, max])
     };
+    let memory = |memory: &MemoryTypeNode| Json::object(limits(&memory.limits));
+    let global = |global: &GlobalTypeNode| {
+        Json::object([
+            ("type", global.val_type.to_string().into()),
+            ("mutable", global.mutable.into()),
+        ])
+    };
+
+    let func_types = module.type_section().map_or(&[][..], |t| &t.func_types);
+    let types: Vec<Json> = func_types
+        .iter()
+        .map(|t| {
+            Json::object([
+                ("params", val_types(&t.param_type.val_types)),
+                ("results", val_types(&t.result_type.val_types)),
+            ])
+        })
+        .collect();
+
+    let imports = module.import_section().map_or(&[][..], |i| &i.imports);
+    let import_json: Vec<Json> = imports
+        .iter()
+        .map(|import| {
+            let mut members = vec![
+                ("module".to_string(), import.module.as_str().into()),
+                ("name".to_string(), import.name.as_str().into()),
+                ("kind".to_string(), import.desc.kind().to_string().into()),
+            ];
+            let desc = match &import.desc {
+                ImportDescNode::Func(type_idx) => Json::object([("type", (*type_idx).into())]),
+                ImportDescNode::Table(t) => table(t),
+                ImportDescNode::Memory(m) => memory(m),
+                ImportDescNode::Global(g) => global(g),
+            };
+            if let Json::Object(desc) = desc {
+                members.extend(desc);
+            }
+            Json::Object(members)
+        })
+        .collect();
+
+    // Imported functions come first in the function index space.
+    let imported_types = imports.iter().filter_map(|i| match i.desc {
+        ImportDescNode::Func(type_idx) => Some((type_idx, None)),
+        _ => None,
+    });
+    let type_indices = module
+        .function_section()
+        .map_or(&[][..], |f| &f.type_indices);
+    let codes = module.code_section().map_or(&[][..], |c| &c.codes);
+    let defined_types = type_indices
+        .iter()
+        .enumerate()
+        .map(|(idx, type_idx)| (*type_idx, Some(codes.get(idx))));
+    let names = module.name_section().unwrap_or_default();
+    let functions: Vec<Json> = imported_types
+        .chain(defined_types)
+        .enumerate()
+        .map(|(func_idx, (type_idx, code))| {
+            let func_type = func_types.get(type_idx as usize);
+            let signature = |types: fn(&FunctionTypeNode) -> &[ValType]| {
+                func_type.map_or(Json::Null, |t| val_types(types(t)))
+            };
+            let imported = code.is_none();
+            let code = code.flatten();
+            let locals = code.map(|c| c.func.locals.iter().map(|l| l.num).sum::<u32>());
+            Json::object([
+                ("index", func_idx.into()),
+                (
+                    "name",
+                    names.func_names.get(&(func_idx as u32)).cloned().into(),
+                ),
+                ("type", type_idx.into()),
+                ("params", signature(|t| &t.param_type.val_types)),
+                ("results", signature(|t| &t.result_type.val_types)),
+                ("imported", imported.into()),
+                ("locals", locals.into()),
+                ("code_size", code.map(|c| c.size).into()),
+            ])
+        })
+        .collect();
+
+    let tables = module.table_section().map_or(&[][..], |t| &t.table_types);
+    let memories = module.memory_section().map_or(&[][..], |m| &m.mem_types);
+    let globals = module.global_section().map_or(&[][..], |g| &g.globals);
+    let exports = module.export_section().map_or(&[][..], |e| &e.exports);
+    let export_json: Vec<Json> = exports
+        .iter()
+        .map(|export| {
+            let kind = export
+                .desc
+                .kind()
+                .map_or("unknown".to_string(), |k| k.to_string());
+            Json::object([
+                ("name", export.name.as_str().into()),
+                ("kind", kind.into()),
+                ("index", export.desc.index.into()),
+            ])
+        })
+        .collect();
+    let customs: Vec<Json> = module
+        .sections
+        .iter()
+        .filter_map(|s| match s {
+            SectionNode::CustomSectionNode(c) => Some(Json::object([
+                ("name", c.name.as_str().into()),
+                ("size", c.data.len().into()),
+            ])),
+            _ => None,
+        })
+        .collect();
+
+    let version = module
+        .version
+        .iter()
+        .rev()
+        .fold(0, |v, b| v << 8 | *b as u32);
+    Json::object([
+        ("schema", 1u32.into()),
+        ("version", version.into()),
+        ("types", types.into()),
+        ("imports", import_json.into()),
+        ("functions", functions.into()),
+        (
+            "tables",
+            tables.iter().map(table).collect::<Vec<_>>().into(),
+        ),
+        (
+            "memories",
+            memories.iter().map(memory).collect::<Vec<_>>().into(),
+        ),
+        (
+            "globals",
+            globals
+                .iter()
+                .map(|g| global(&g.global_type))
+                .collect::<Vec<_>>()
+                .into(),
+        ),
+        ("exports", export_json.into()),
+        ("custom_sections", customs.into()),
+    ])
+}
+
+/// Check that the module at `path` is valid, printing each error.
+pub fn check(path: &Path) -> Result<()> {
+    let module = load_module(path)?;
+    let errors = validate(&module);
+    for error in &errors {
+        eprintln!("{}: {}", path.display(), error);
+    }
+    if !errors.is_empty() {
+        let message = format!("{} errors in {}", errors.len(), path.display());
+        return Err(ExecError::Invalid(message).into());
+    }
+    Ok(())
+}
+
+/// Print the sections of the module at `path` with their offsets and contents in hex.
+pub fn dump(path: &Path) -> Result<()> {
+    let bytes = read_file(path)?;
+    print!("{}", dump_sections(&bytes)?);
+    Ok(())
+}
+
+/// Header and hex listing of each section, at absolute offsets in `bytes`.
+/// Sections are not decoded so that malformed ones can be looked at too.
+pub fn dump_sections(bytes: &[u8]) -> Result<String> {
+    let mut buf = Buffer::new(bytes);
+    let mut text = String::new();
+    let header = buf.read_bytes(8).map_err(malformed)?;
+    let _ = writeln!(text, "header:");
+    write_hex(&mut text, 0, header);
+    while !buf.eof() {
+        let start = buf.cursor() as usize;
+        let id = buf.read_byte().map_err(malformed)?;
+        let size = buf.read_u32().map_err(malformed)?;
+        let contents = buf.cursor() as usize;
+        let payload = buf.read_bytes(size).map_err(malformed)?;
+        let _ = writeln!(
+            text,
+            "section {} ({}) at {:#010x}, contents at {:#010x}, {} bytes:",
+            id,
+            section_name(id),
+            start,
+            contents,
+            size
+        );
+        write_hex(&mut text, contents, payload);
+    }
+    Ok(text)
+}
+
+/// Append `bytes` 16 per line, each line starting with the offset of its first byte.
+pub fn write_hex(text: &mut String, offset: usize, bytes: &[u8]) {
+    for (row, chunk) in bytes.chunks(HEX_ROW).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let _ = writeln!(text, "{:#010x}: {}", offset + row * HEX_ROW, hex.join(" "));
+    }
+}
 
-    let mut buffer = Buffer::new(file_content);
+/// Bytes per line of `write_hex`.
+const HEX_ROW: usize = 16;
+
+/// Translate the text format module at `input` to the binary format,
+/// written to `output` or next to `input` with the `.wasm` extension.
+pub fn wat2wasm(input: &Path, output: Option<&Path>) -> Result<()> {
+    let text = read_text(input)?;
+    let bytes = wat::module::parse_str(&text).map_err(|e| ExecError::Malformed(e.to_string()))?;
     let mut module = ModuleNode::new();
-    module.load(&mut buffer)?;
+    module.load(&mut Buffer::new(&bytes)).map_err(malformed)?;
+    check_module(&module)?;
+    let output = output.map_or(input.with_extension("wasm"), Path::to_path_buf);
+    fs::write(output, bytes)?;
+    Ok(())
+}
+
+/// Print the binary module at `input` in the text format, to `output` or stdout.
+pub fn wasm2wat(input: &Path, output: Option<&Path>) -> Result<()> {
+    let module = load_module(input)?;
+    let text = wat::printer::print_module(&module);
+    match output {
+        Some(output) => fs::write(output, text)?,
+        None => print!("{}", text),
+    }
+    Ok(())
+}
+
+/// Debug the exports of a module from a prompt on stdin.
+pub fn debug(path: &Path) -> Result<()> {
+    let module = load_valid_module(path)?;
+    let mut debugger = Debugger::new(module)?;
+    debugger.run(&mut io::stdin().lock(), &mut io::stdout().lock())
+}
+
+/// Load modules and invoke their exports from a prompt on stdin.
+pub fn repl() -> Result<()> {
+    Repl::new().run(&mut io::stdin().lock(), &mut io::stdout().lock())
+}
+
+/// Run a spec test script, printing its failures and a summary per directive.
+pub fn wast(path: &Path) -> Result<Summary> {
+    let src = fs::read_to_string(path).map_err(|e| match e.kind() {
+        ErrorKind::NotFound => ExecError::FileNotFound(path.display().to_string()),
+        _ => ExecError::Unknown,
+    })?;
+    let summary = run_script(&src)?;
+    for failure in &summary.failures {
+        println!("{}:{}", path.display(), failure);
+    }
+    print!("{}", summary);
+    if summary.failed() > 0 {
+        return Err(anyhow!(
+            "{} of {} directives failed",
+            summary.failed(),
+            summary.passed() + summary.failed()
+        ));
+    }
+    Ok(summary)
+}
+
+/// Instantiate `module` then call its export `name`, if any.
+fn invoke(module: ModuleNode, name: Option<&str>, input: &ExecInput) -> Result<Vec<Value>> {
+    let mut store = Store::new();
+    if let Some(fuel) = input.fuel {
+        store.set_fuel(fuel);
+    }
+    // Kept until the end of the call so that the timeout cannot fire after it.
+    let _timer = input
+        .timeout
+        .map(|timeout| store.interrupt_handle().interrupt_after(timeout));
+    if input.max_memory.is_some() || input.max_table_elements.is_some() {
+        store.set_limiter(StoreLimits {
+            max_memory_size: input.max_memory.or(Some(DEFAULT_MAX_MEMORY_SIZE)),
+            max_table_elements: input
+                .max_table_elements
+                .or(Some(DEFAULT_MAX_TABLE_ELEMENTS)),
+            ..Default::default()
+        });
+    }
+    let mut tracers: Vec<Box<dyn Tracer>> = Vec::new();
+    if let Some(trace) = &input.trace {
+        let out: Box<dyn Write> = match &trace.output {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(io::stderr()),
+        };
+        let tracer = WriteTracer::new(BufWriter::new(out), trace.func.clone());
+        tracers.push(Box::new(tracer));
+    }
+    let profiler = Rc::new(RefCell::new(Profiler::new()));
+    if input.profile.is_some() {
+        tracers.push(Box::new(profiler.clone()));
+    }
+    let coverage = Rc::new(RefCell::new(Coverage::new()));
+    if input.coverage.is_some() {
+        tracers.push(Box::new(coverage.clone()));
+    }
+    if !tracers.is_empty() {
+        store.set_tracer(tracers);
+    }
+    let mut linker = Linker::new();
+    let wasi = preview1::instantiate(&mut store, wasi_ctx(input));
+    linker.register(preview1::MODULE, wasi);
+    let instance = linker.instantiate(&mut store, module)?;
+    let results = match name {
+        // The arguments went to the WASI command instead.
+        Some(START) if input.invoke.is_none() => instance.invoke(&mut store, START, &[]),
+        Some(name) => {
+            let addr = instance
+                .get_func(&store, name)
+                .ok_or(ExecError::ExportNotFound(name.to_string()))?;
+            let args = parse_args(
+                name,
+                &store.funcs[addr].func_type.param_type.val_types,
+                &input.args,
+            )?;
+            instance.invoke(&mut store, name, &args)
+        }
+        None => Ok(Vec::new()),
+    };
+    if input.fuel.is_some() {
+        eprintln!(
+            "fuel consumed: {}, remaining: {}",
+            store.fuel_consumed(),
+            store.fuel_remaining().unwrap_or(0)
+        );
+    }
+    if let Some(profile) = &input.profile {
+        let profiler = profiler.borrow();
+        eprint!("{}", profiler.report());
+        if let Some(path) = &profile.folded {
+            fs::write(path, profiler.folded())?;
+        }
+    }
+    if let Some(options) = &input.coverage {
+        let coverage = coverage.borrow();
+        let instance = instance.data(&store);
+        eprint!("{}", coverage.listing(instance));
+        if let Some(path) = &options.lcov {
+            let source = input.path.display().to_string();
+            fs::write(path, coverage.lcov(instance, &source))?;
+        }
+    }
+    results
+}
 
-    if input.print {
-        print_module(module.clone());
+/// Arguments of the function `name` taking `params`, parsed from the command line.
+pub fn parse_args(name: &str, params: &[ValType], args: &[impl AsRef<str>]) -> Result<Vec<Value>> {
+    if params.len() != args.len() {
+        return Err(ExecError::InvalidArguments(format!(
+            "{} expects {} arguments, got {}",
+            name,
+            params.len(),
+            args.len()
+        ))
+        .into());
     }
+    params
+        .iter()
+        .zip(args)
+        .map(|(val_type, arg)| parse_arg(val_type, arg.as_ref()))
+        .collect()
+}
 
-    return Ok(module);
+fn parse_arg(val_type: &ValType, arg: &str) -> Result<Value> {
+    let invalid = || ExecError::InvalidArguments(format!("{} is not a valid {:?}", arg, val_type));
+    let value = match val_type {
+        ValType::NumType(NumType::I32(_)) => Value::I32(arg.parse().map_err(|_| invalid())?),
+        ValType::NumType(NumType::I64(_)) => Value::I64(arg.parse().map_err(|_| invalid())?),
+        ValType::NumType(NumType::F32(_)) => Value::F32(arg.parse().map_err(|_| invalid())?),
+        ValType::NumType(NumType::F64(_)) => Value::F64(arg.parse().map_err(|_| invalid())?),
+        ValType::RefType(_) => return Err(invalid().into()),
+    };
+    Ok(value)
 }
 
 fn print_module(module: ModuleNode) {
@@ -50,12 +820,15 @@ fn print_module(module: ModuleNode) {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::path::PathBuf;
+    use crate::exec::custom_section::CustomSectionNode;
+    use crate::exec::store::PAGE_SIZE;
+    use crate::exec::trap::{Trap, TrapKind};
+    use std::path::{Path, PathBuf};
 
     #[test]
-    fn test_exec_const() {
+    fn test_load_const() {
         let path = PathBuf::from("examples/const.wasm");
-        let result = exec(ExecInput { path, print: false });
+        let result = load_module(&path);
         assert!(result.is_ok());
 
         let module = result.unwrap();
@@ -64,9 +837,9 @@ mod tests {
     }
 
     #[test]
-    fn test_exec_local_var() {
+    fn test_load_local_var() {
         let path = PathBuf::from("examples/local_var.wasm");
-        let result = exec(ExecInput { path, print: false });
+        let result = load_module(&path);
         assert!(result.is_ok());
 
         let module = result.unwrap();
@@ -75,9 +848,9 @@ mod tests {
     }
 
     #[test]
-    fn test_exec_add() {
+    fn test_load_add() {
         let path = PathBuf::from("examples/add.wasm");
-        let result = exec(ExecInput { path, print: false });
+        let result = load_module(&path);
         assert!(result.is_ok());
 
         let module = result.unwrap();
@@ -86,9 +859,9 @@ mod tests {
     }
 
     #[test]
-    fn test_exec_if() {
+    fn test_load_if() {
         let path = PathBuf::from("examples/if.wasm");
-        let result = exec(ExecInput { path, print: false });
+        let result = load_module(&path);
         assert!(result.is_ok());
 
         let module = result.unwrap();
@@ -97,9 +870,9 @@ mod tests {
     }
 
     #[test]
-    fn test_exec_loop() {
+    fn test_load_loop() {
         let path = PathBuf::from("examples/loop.wasm");
-        let result = exec(ExecInput { path, print: false });
+        let result = load_module(&path);
         assert!(result.is_ok());
 
         let module = result.unwrap();
@@ -108,13 +881,386 @@ mod tests {
     }
 
     #[test]
-    fn test_exec_call() {
+    fn test_load_call() {
         let path = PathBuf::from("examples/call.wasm");
-        let result = exec(ExecInput { path, print: false });
+        let result = load_module(&path);
         assert!(result.is_ok());
 
         let module = result.unwrap();
 
         assert!(module.sections.len() == 4);
     }
+
+    #[test]
+    fn test_exec_invoke() {
+        let path = PathBuf::from("examples/call.wasm");
+        let result = exec(ExecInput {
+            path,
+            invoke: Some("add42".to_string()),
+            args: vec!["-2".to_string()],
+            ..Default::default()
+        });
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_exec_wat() {
+        let path = PathBuf::from("examples/add.wat");
+        let result = exec(ExecInput {
+            path,
+            invoke: Some("add".to_string()),
+            args: vec!["1".to_string(), "2".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(result.unwrap(), [Value::I32(3)]);
+    }
+
+    #[test]
+    fn test_exec_start() {
+        let input = |path: &str, invoke: Option<&str>| ExecInput {
+            path: PathBuf::from(path),
+            invoke: invoke.map(str::to_string),
+            ..Default::default()
+        };
+        // Without `_start`, running the module only calls its start function.
+        assert_eq!(exec(input("examples/start.wat", None)).unwrap(), []);
+        let results = exec(input("examples/start.wat", Some("calls"))).unwrap();
+        assert_eq!(results, [Value::I32(1)]);
+
+        let path = std::env::temp_dir().join(format!("start-trap-{}.wat", std::process::id()));
+        fs::write(&path, "(module (func $f unreachable) (start $f))").unwrap();
+        let error = exec(input(path.to_str().unwrap(), None)).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(exit_code(&error), EXIT_TRAP);
+    }
+
+    #[test]
+    fn test_exec_wasi() {
+        let path = std::env::temp_dir().join(format!("wasi-{}.wat", std::process::id()));
+        let input = |args: &[&str]| ExecInput {
+            path: path.clone(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            ..Default::default()
+        };
+        // Exits with the number of arguments after the first one, the path.
+        let wat = r#"(module
+          (import "wasi_snapshot_preview1" "args_sizes_get"
+            (func $args_sizes_get (param i32 i32) (result i32)))
+          (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+          (memory 1)
+          (func (export "_start")
+            (drop (call $args_sizes_get (i32.const 0) (i32.const 4)))
+            (call $proc_exit (i32.sub (i32.load (i32.const 0)) (i32.const 1)))))
+        "#;
+        fs::write(&path, wat).unwrap();
+        let error = exec(input(&["a", "b"])).unwrap_err();
+        let results = exec(input(&[]));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(exit_code(&error), 2);
+        assert_eq!(results.unwrap(), []);
+    }
+
+    #[test]
+    fn test_exec_invoke_trap() {
+        let path = PathBuf::from("examples/trap.wasm");
+        let result = exec(ExecInput {
+            path,
+            invoke: Some("div".to_string()),
+            args: vec!["1".to_string(), "0".to_string()],
+            ..Default::default()
+        });
+        assert!(result.unwrap_err().downcast_ref::<Trap>().is_some());
+    }
+
+    #[test]
+    fn test_exec_invoke_out_of_fuel() {
+        let path = PathBuf::from("examples/loop.wasm");
+        let result = exec(ExecInput {
+            path,
+            invoke: Some("loop".to_string()),
+            fuel: Some(10),
+            ..Default::default()
+        });
+        let trap = result.unwrap_err().downcast::<Trap>().unwrap();
+        assert_eq!(trap.kind, TrapKind::OutOfFuel);
+    }
+
+    #[test]
+    fn test_exec_invoke_timeout() {
+        let path = PathBuf::from("examples/spin.wasm");
+        let result = exec(ExecInput {
+            path,
+            invoke: Some("spin".to_string()),
+            timeout: Some(Duration::from_millis(10)),
+            ..Default::default()
+        });
+        let trap = result.unwrap_err().downcast::<Trap>().unwrap();
+        assert_eq!(trap.kind, TrapKind::Interrupted);
+    }
+
+    #[test]
+    fn test_exec_invoke_max_memory() {
+        let input = |max_memory| ExecInput {
+            path: PathBuf::from("examples/memory.wasm"),
+            invoke: Some("grow".to_string()),
+            args: vec!["1".to_string()],
+            max_memory: Some(max_memory),
+            ..Default::default()
+        };
+        assert_eq!(exec(input(2 * PAGE_SIZE)).unwrap(), [Value::I32(1)]);
+        // `memory.grow` fails past the limit instead of trapping.
+        assert_eq!(exec(input(PAGE_SIZE)).unwrap(), [Value::I32(-1)]);
+        // The initial page does not fit.
+        assert!(exec(input(PAGE_SIZE / 2)).is_err());
+    }
+
+    #[test]
+    fn test_wast() {
+        let summary = wast(Path::new("examples/spec.wast")).unwrap();
+        assert_eq!(summary.failed(), 0);
+        assert_eq!(summary.passed(), 17);
+
+        let result = wast(Path::new("examples/missing.wast"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_decode_valid() {
+        let bytes = fs::read("examples/call.wasm").unwrap();
+        let module = decode_valid(&bytes[..]).unwrap();
+        assert_eq!(module.code_section().unwrap().codes.len(), 2);
+
+        let bytes = wat::module::parse_str(
+            "(module (func (result i32) (i64.const 0)) (func (result i32) (i32.const 0)))",
+        )
+        .unwrap();
+        let first_body = decode_valid(&bytes[..]).map(|_| ()).unwrap_err();
+        assert_eq!(exit_code(&first_body), EXIT_INVALID);
+
+        // The invalid body fails before the reader is asked for the next one.
+        let mut module = ModuleNode::new();
+        module.load(&mut Buffer::new(&bytes)).unwrap();
+        let end = module.code_section().unwrap().codes[0].span.end as usize;
+        let reader = bytes[..end].chain(Failing);
+        let error = decode_valid(reader).map(|_| ()).unwrap_err();
+        assert_eq!(error.to_string(), first_body.to_string());
+
+        // A function section after the code section is malformed, not an unknown function.
+        let mut sections = module.sections.clone();
+        let funcs = sections.iter().position(|s| s.id() == 3).unwrap();
+        let func_section = sections.remove(funcs);
+        sections.push(func_section);
+        module.sections = sections;
+        let error = decode_valid(&module.to_bytes()[..])
+            .map(|_| ())
+            .unwrap_err();
+        assert_eq!(exit_code(&error), EXIT_MALFORMED);
+        assert_eq!(
+            error.to_string(),
+            "Malformed module: Function and code section have inconsistent lengths: 0 and 2"
+        );
+    }
+
+    /// Reader failing, standing for input that never arrives.
+    struct Failing;
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("read past the invalid body"))
+        }
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let input = |path: &str, invoke: Option<&str>| ExecInput {
+            path: PathBuf::from(path),
+            invoke: invoke.map(str::to_string),
+            args: vec!["1".to_string(), "0".to_string()],
+            ..Default::default()
+        };
+        let error = exec(input("examples/trap.wasm", Some("div"))).unwrap_err();
+        assert_eq!(exit_code(&error), EXIT_TRAP);
+        // Without `--invoke`, `_start` is called.
+        let error = exec(input("examples/trap.wasm", None)).unwrap_err();
+        assert_eq!(error.to_string(), "Export not found: _start");
+        assert_eq!(exit_code(&error), EXIT_FAILURE);
+
+        let dir = std::env::temp_dir();
+        let malformed = dir.join(format!("malformed-{}.wat", std::process::id()));
+        fs::write(&malformed, "(module (func (i32.const)))").unwrap();
+        let invalid = dir.join(format!("invalid-{}.wat", std::process::id()));
+        fs::write(&invalid, "(module (func (result i32) (i64.const 0)))").unwrap();
+        let not_utf8 = dir.join(format!("not-utf8-{}.wat", std::process::id()));
+        fs::write(&not_utf8, b"(module \xff)").unwrap();
+        let malformed_error = check(&malformed).unwrap_err();
+        let invalid_error = check(&invalid).unwrap_err();
+        let not_utf8_error = check(&not_utf8).unwrap_err();
+        fs::remove_file(&malformed).unwrap();
+        fs::remove_file(&invalid).unwrap();
+        fs::remove_file(&not_utf8).unwrap();
+        assert_eq!(exit_code(&malformed_error), EXIT_MALFORMED);
+        assert_eq!(exit_code(&invalid_error), EXIT_INVALID);
+        assert_eq!(exit_code(&not_utf8_error), EXIT_MALFORMED);
+        assert!(not_utf8_error.to_string().contains("not UTF-8"));
+
+        let exit = |status| anyhow::Error::new(Trap::new(TrapKind::Exit(status)));
+        assert_eq!(exit_code(&exit(0)), 0);
+        assert_eq!(exit_code(&exit(42)), 42);
+        assert_eq!(exit_code(&exit(256)), 255);
+        assert_eq!(exit_code(&exit(u32::MAX)), 255);
+    }
+
+    #[test]
+    fn test_summary() {
+        let module = load_module(Path::new("examples/exports.wat")).unwrap();
+        assert_eq!(
+            summary(&module),
+            "version: 1\n\
+             types: 2\n\
+             imports: 0\n\
+             functions: 2\n\
+             tables: 1\n\
+             memories: 1\n\
+             globals: 2\n\
+             data segments: 0\n\
+             custom sections: [name]\n\
+             exports: 6\n  \
+             table: table 0\n  \
+             memory: memory 0\n  \
+             counter: global 0\n  \
+             answer: global 1\n  \
+             incr: func 0 (result i32)\n  \
+             load: func 1 (param i32) (result i32)\n"
+        );
+    }
+
+    #[test]
+    fn test_headers() {
+        let module = load_module(Path::new("examples/call.wasm")).unwrap();
+        assert_eq!(
+            headers(&module),
+            "Sections:\n  \
+             1 type       start=0x0000000a end=0x00000016 (size=0x0000000c) count: 2\n  \
+             3 function   start=0x00000018 end=0x0000001b (size=0x00000003) count: 2\n  \
+             7 export     start=0x0000001d end=0x00000026 (size=0x00000009) count: 1\n \
+             10 code       start=0x00000028 end=0x0000003a (size=0x00000012) count: 2\n\
+             Code[2]:\n \
+             - func[0] start=0x0000002a end=0x00000031 (size=0x00000007) locals: 0\n \
+             - func[1] start=0x00000032 end=0x0000003a (size=0x00000008) locals: 0\n"
+        );
+        // Custom sections show their name and functions theirs from the name section.
+        let module = load_module(Path::new("examples/recursion.wat")).unwrap();
+        let text = headers(&module);
+        assert!(text.contains("(size=0x00000017) \"name\"\n"));
+        assert!(text.contains(" - func[0] <count> start=0x00000023"));
+
+        // Sections added after decoding are shown at their encoded offsets.
+        let mut module = load_module(Path::new("examples/call.wasm")).unwrap();
+        let mut custom = CustomSectionNode::new();
+        custom.name = "extra".to_string();
+        module.sections.push(SectionNode::CustomSectionNode(custom));
+        let text = headers(&module);
+        assert!(text.contains(" 10 code       start=0x00000028 end=0x0000003a"));
+        assert!(text.contains(
+            "  0 custom     start=0x0000003c end=0x00000042 (size=0x00000006) \"extra\"\n"
+        ));
+    }
+
+    #[test]
+    fn test_module_json() {
+        let module = load_module(Path::new("examples/const.wasm")).unwrap();
+        assert_eq!(
+            module_json(&module).to_string(),
+            r#"{
+  "schema": 1,
+  "version": 1,
+  "types": [
+    {
+      "params": [],
+      "results": ["i32"]
+    }
+  ],
+  "imports": [],
+  "functions": [
+    {
+      "index": 0,
+      "name": null,
+      "type": 0,
+      "params": [],
+      "results": ["i32"],
+      "imported": false,
+      "locals": 0,
+      "code_size": 4
+    }
+  ],
+  "tables": [],
+  "memories": [],
+  "globals": [],
+  "exports": [],
+  "custom_sections": []
+}"#
+        );
+
+        let module = load_module(Path::new("examples/main.wat")).unwrap();
+        let Json::Object(members) = module_json(&module) else {
+            panic!("not an object");
+        };
+        let member = |name: &str| members.iter().find(|(n, _)| n == name).unwrap().1.clone();
+        let Json::Array(imports) = member("imports") else {
+            panic!("imports not an array");
+        };
+        assert_eq!(
+            imports[3],
+            Json::object([
+                ("module", "lib".into()),
+                ("name", "counter".into()),
+                ("kind", "global".into()),
+                ("type", "i32".into()),
+                ("mutable", true.into()),
+            ])
+        );
+        let Json::Array(functions) = member("functions") else {
+            panic!("functions not an array");
+        };
+        assert_eq!(functions.len(), 5);
+        let Json::Object(add) = &functions[0] else {
+            panic!("function not an object");
+        };
+        assert_eq!(add[1], ("name".to_string(), "add".into()));
+        assert_eq!(add[5], ("imported".to_string(), true.into()));
+        assert_eq!(add[7], ("code_size".to_string(), Json::Null));
+    }
+
+    #[test]
+    fn test_dump_sections() {
+        let bytes = fs::read("examples/const.wasm").unwrap();
+        assert_eq!(
+            dump_sections(&bytes).unwrap(),
+            "header:\n\
+             0x00000000: 00 61 73 6d 01 00 00 00\n\
+             section 1 (type) at 0x00000008, contents at 0x0000000a, 5 bytes:\n\
+             0x0000000a: 01 60 00 01 7f\n\
+             section 3 (function) at 0x0000000f, contents at 0x00000011, 2 bytes:\n\
+             0x00000011: 01 00\n\
+             section 10 (code) at 0x00000013, contents at 0x00000015, 6 bytes:\n\
+             0x00000015: 01 04 00 41 2a 0b\n"
+        );
+        let error = dump_sections(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(exit_code(&error), EXIT_MALFORMED);
+    }
+
+    #[test]
+    fn test_conversions() {
+        let dir = std::env::temp_dir();
+        let wasm = dir.join(format!("convert-{}.wasm", std::process::id()));
+        let wat = dir.join(format!("convert-{}.wat", std::process::id()));
+        wat2wasm(Path::new("examples/call.wat"), Some(&wasm)).unwrap();
+        let bytes = fs::read(&wasm).unwrap();
+        wasm2wat(&wasm, Some(&wat)).unwrap();
+        // The printed module translates back to the same binary.
+        wat2wasm(&wat, None).unwrap();
+        let round_trip = fs::read(&wasm).unwrap();
+        fs::remove_file(&wasm).unwrap();
+        fs::remove_file(&wat).unwrap();
+        assert_eq!(round_trip, bytes);
+    }
 }
@@ -0,0 +1,550 @@
+use anyhow::{anyhow, Result};
+use std::io::{BufRead, Write};
+
+use crate::cmd::parse_args;
+use crate::errors::ExecError;
+use crate::exec::code_section::FuncNode;
+use crate::exec::custom_section::NameSectionNode;
+use crate::exec::export_section::ExportKind;
+use crate::exec::instance::Instance;
+use crate::exec::machine::{Machine, Position};
+use crate::exec::module::ModuleNode;
+use crate::exec::store::{FuncAddr, MemInst, Store};
+use crate::exec::trap::Trap;
+use crate::exec::value::Value;
+use crate::wat::number::parse_u32;
+use crate::wat::printer::print_instr;
+
+const PROMPT: &str = "(wdb) ";
+/// Bytes per line of `memory`.
+const MEMORY_ROW: usize = 16;
+
+/// Commands only available while a call is paused.
+const SESSION_COMMANDS: [&str; 17] = [
+    "step", "s", "next", "n", "out", "o", "continue", "c", "kill", "where", "bt", "list", "l",
+    "locals", "stack", "globals", "memory",
+];
+
+const HELP: &str = "\
+break <func> [offset]   set a breakpoint, at the first instruction by default
+delete <n>              delete breakpoint n
+breakpoints             list the breakpoints
+call <export> [args]    call an export, stopping at breakpoints
+start <export> [args]   call an export, stopping at its first instruction
+step, s                 execute one instruction, entering calls
+next, n                 execute one instruction, stepping over calls
+out, o                  run until the current function returns
+continue, c             run until a breakpoint or the end of the call
+kill                    abort the call
+where, bt               show the active functions
+list, l                 show the instructions of the current function
+locals                  show the locals of the current function
+stack                   show the operand stack of the current function
+globals                 show the globals
+memory <addr> [len]     show len bytes of memory from addr
+quit, q                 exit";
+
+/// Instruction execution stops at, in a function defined by the debugged module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub func_idx: u32,
+    pub offset: u32,
+}
+
+/// How far `resume` runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resume {
+    Step,
+    Next,
+    Out,
+    Continue,
+}
+
+/// Debugged module, with the names its functions are shown and looked up by
+/// and the breakpoints set in it.
+struct Program {
+    module: ModuleNode,
+    names: NameSectionNode,
+    breakpoints: Vec<Breakpoint>,
+}
+impl Program {
+    fn set_breakpoint(&mut self, func: &str, offset: &[&str], out: &mut impl Write) -> Result<()> {
+        let func_idx = self.resolve_func(func)?;
+        let code = self.code(func_idx)?;
+        let offsets: Vec<u32> = code.expr.instructions().iter().map(|i| i.0).collect();
+        let offset = match offset {
+            [] => *offsets
+                .first()
+                .ok_or(anyhow!("{} has no instructions", func))?,
+            [offset] => {
+                let offset = parse_u32(offset).ok_or(anyhow!("invalid offset {}", offset))?;
+                if !offsets.contains(&offset) {
+                    return Err(anyhow!("no instruction at {:#06x} in {}", offset, func));
+                }
+                offset
+            }
+            _ => return Err(anyhow!("usage: break <func> [offset]")),
+        };
+        self.breakpoints.push(Breakpoint { func_idx, offset });
+        writeln!(
+            out,
+            "breakpoint {} at {}",
+            self.breakpoints.len() - 1,
+            self.describe(func_idx, Some(offset))
+        )?;
+        Ok(())
+    }
+
+    fn delete_breakpoint(&mut self, n: &str) -> Result<()> {
+        let n: usize = n.parse().map_err(|_| anyhow!("invalid breakpoint {}", n))?;
+        if n >= self.breakpoints.len() {
+            return Err(anyhow!("no breakpoint {}", n));
+        }
+        self.breakpoints.remove(n);
+        Ok(())
+    }
+
+    fn list_breakpoints(&self, out: &mut impl Write) -> Result<()> {
+        for (n, breakpoint) in self.breakpoints.iter().enumerate() {
+            let at = self.describe(breakpoint.func_idx, Some(breakpoint.offset));
+            writeln!(out, "{}: {}", n, at)?;
+        }
+        Ok(())
+    }
+
+    /// Function index of `func`, given as an index, a name from the name section or an export.
+    fn resolve_func(&self, func: &str) -> Result<u32> {
+        if let Ok(idx) = func.parse() {
+            return Ok(idx);
+        }
+        let named = self.names.func_names.iter().find(|(_, name)| *name == func);
+        if let Some((idx, _)) = named {
+            return Ok(*idx);
+        }
+        let exports = self.module.export_section().map_or(&[][..], |e| &e.exports);
+        exports
+            .iter()
+            .find(|e| e.name == func && e.desc.kind().ok() == Some(ExportKind::Func))
+            .map(|e| e.desc.index)
+            .ok_or(anyhow!("unknown function {}", func))
+    }
+
+    fn code(&self, func_idx: u32) -> Result<&FuncNode> {
+        let code_idx = func_idx
+            .checked_sub(self.module.imported_func_count())
+            .ok_or(anyhow!("func[{}] is imported", func_idx))?;
+        let codes = self.module.code_section().map_or(&[][..], |c| &c.codes);
+        codes
+            .get(code_idx as usize)
+            .map(|code| &code.func)
+            .ok_or(anyhow!("unknown function {}", func_idx))
+    }
+
+    /// `func[idx] <name> @ offset`, the offset being omitted when `None`.
+    fn describe(&self, func_idx: u32, offset: Option<u32>) -> String {
+        let mut text = match self.names.func_names.get(&func_idx) {
+            Some(name) => format!("func[{}] <{}>", func_idx, name),
+            None => format!("func[{}]", func_idx),
+        };
+        if let Some(offset) = offset {
+            text.push_str(&format!(" @ {:#06x}", offset));
+        }
+        text
+    }
+}
+
+/// Command-line debugger running the exports of a module one instruction at a time.
+pub struct Debugger {
+    program: Program,
+    store: Store,
+    instance: Instance,
+}
+impl Debugger {
+    pub fn new(module: ModuleNode) -> Result<Debugger> {
+        let mut store = Store::new();
+        let instance = Instance::new(&mut store, module.clone())?;
+        Ok(Debugger {
+            program: Program {
+                names: module.name_section().unwrap_or_default(),
+                module,
+                breakpoints: Vec::new(),
+            },
+            store,
+            instance,
+        })
+    }
+
+    /// Read commands from `input` until `quit` or its end.
+    pub fn run(&mut self, input: &mut impl BufRead, out: &mut impl Write) -> Result<()> {
+        while let Some(line) = prompt(PROMPT, input, out)? {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let result = match words.as_slice() {
+                [] => Ok(()),
+                ["quit" | "q"] => return Ok(()),
+                ["help" | "h"] => writeln!(out, "{}", HELP).map_err(Into::into),
+                ["break" | "b", func, offset @ ..] => {
+                    self.program.set_breakpoint(func, offset, out)
+                }
+                ["delete" | "d", n] => self.program.delete_breakpoint(n),
+                ["breakpoints"] => self.program.list_breakpoints(out),
+                ["call", name, args @ ..] => match self.call(name, args, false, input, out) {
+                    Ok(true) => return Ok(()),
+                    result => result.map(|_| ()),
+                },
+                ["start", name, args @ ..] => match self.call(name, args, true, input, out) {
+                    Ok(true) => return Ok(()),
+                    result => result.map(|_| ()),
+                },
+                [command, ..] if SESSION_COMMANDS.contains(command) => {
+                    Err(anyhow!("no call is running, see `call` and `start`"))
+                }
+                [command, ..] => Err(anyhow!("unknown command `{}`, try `help`", command)),
+            };
+            if let Err(e) = result {
+                writeln!(out, "error: {}", e)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Call the export `name`, reading commands until it returns or traps.
+    /// Returns whether `quit` was entered meanwhile.
+    fn call(
+        &mut self,
+        name: &str,
+        args: &[&str],
+        stop_at_entry: bool,
+        input: &mut impl BufRead,
+        out: &mut impl Write,
+    ) -> Result<bool> {
+        let addr = self
+            .instance
+            .get_func(&self.store, name)
+            .ok_or(ExecError::ExportNotFound(name.to_string()))?;
+        let params = &self.store.funcs[addr].func_type.param_type.val_types;
+        let args = parse_args(name, params, args)?;
+
+        Session {
+            program: &mut self.program,
+            machine: Machine::new(&mut self.store),
+            addr,
+        }
+        .run(&args, stop_at_entry, input, out)
+    }
+}
+
+/// Call being debugged.
+struct Session<'d, 'a> {
+    program: &'d mut Program,
+    machine: Machine<'a>,
+    addr: FuncAddr,
+}
+impl Session<'_, '_> {
+    fn run(
+        mut self,
+        args: &[Value],
+        stop_at_entry: bool,
+        input: &mut impl BufRead,
+        out: &mut impl Write,
+    ) -> Result<bool> {
+        let started = self.machine.start(self.addr, args);
+        let mode = if stop_at_entry {
+            Resume::Step
+        } else {
+            Resume::Continue
+        };
+        // Stop right away at an entry breakpoint, or at the entry when asked to.
+        let at_entry = self
+            .machine
+            .position()
+            .filter(|p| p.offset.is_some())
+            .is_some_and(|p| stop_at_entry || self.at_breakpoint(&p).is_some());
+        let mut result = match started {
+            Ok(()) if at_entry => Ok(()),
+            Ok(()) => self.resume(mode),
+            Err(trap) => Err(trap),
+        };
+        loop {
+            if let Err(trap) = result {
+                writeln!(out, "{}", trap)?;
+                return Ok(false);
+            }
+            if self.machine.is_finished() {
+                let results: Vec<String> = self
+                    .machine
+                    .results(self.addr)
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect();
+                writeln!(out, "returned [{}]", results.join(" "))?;
+                return Ok(false);
+            }
+            self.show_position(out)?;
+
+            result = loop {
+                let Some(line) = prompt(PROMPT, input, out)? else {
+                    return Ok(true);
+                };
+                let words: Vec<&str> = line.split_whitespace().collect();
+                let inspected = match words.as_slice() {
+                    ["quit" | "q"] => return Ok(true),
+                    ["break" | "b", func, offset @ ..] => {
+                        self.program.set_breakpoint(func, offset, out)
+                    }
+                    ["delete" | "d", n] => self.program.delete_breakpoint(n),
+                    ["breakpoints"] => self.program.list_breakpoints(out),
+                    ["step" | "s"] => break self.resume(Resume::Step),
+                    ["next" | "n"] => break self.resume(Resume::Next),
+                    ["out" | "o"] => break self.resume(Resume::Out),
+                    ["continue" | "c"] => break self.resume(Resume::Continue),
+                    ["kill"] => {
+                        writeln!(out, "call aborted")?;
+                        return Ok(false);
+                    }
+                    words => self.inspect(words, out),
+                };
+                if let Err(e) = inspected {
+                    writeln!(out, "error: {}", e)?;
+                }
+            };
+        }
+    }
+
+    /// Run the commands that do not resume execution.
+    fn inspect(&self, words: &[&str], out: &mut impl Write) -> Result<()> {
+        match words {
+            [] => {}
+            ["help" | "h"] => writeln!(out, "{}", HELP)?,
+            ["where" | "bt"] => {
+                for (i, frame) in self.machine.backtrace().iter().enumerate() {
+                    // The innermost function is about to execute its next instruction.
+                    let offset = match i {
+                        0 => self.machine.position().and_then(|p| p.offset),
+                        _ => Some(frame.offset),
+                    };
+                    writeln!(
+                        out,
+                        "#{} {}",
+                        i,
+                        self.program.describe(frame.func_idx, offset)
+                    )?;
+                }
+            }
+            ["list" | "l"] => {
+                let position = self.machine.position().ok_or(anyhow!("not running"))?;
+                let code = self.program.code(position.func_idx)?;
+                for (offset, depth, intrinsic) in code.expr.instructions() {
+                    let marker = if Some(offset) == position.offset {
+                        "=>"
+                    } else if self.program.breakpoints.contains(&Breakpoint {
+                        func_idx: position.func_idx,
+                        offset,
+                    }) {
+                        " *"
+                    } else {
+                        "  "
+                    };
+                    let indent = "  ".repeat(depth);
+                    writeln!(
+                        out,
+                        "{} {:#06x}  {}{}",
+                        marker,
+                        offset,
+                        indent,
+                        print_instr(intrinsic)
+                    )?;
+                }
+            }
+            ["locals"] => {
+                let position = self.machine.position().ok_or(anyhow!("not running"))?;
+                let names = self.program.names.local_names.get(&position.func_idx);
+                for (idx, value) in self.machine.locals().iter().enumerate() {
+                    match names.and_then(|names| names.get(&(idx as u32))) {
+                        Some(name) => writeln!(out, "{} ${}: {}", idx, name, value)?,
+                        None => writeln!(out, "{}: {}", idx, value)?,
+                    }
+                }
+            }
+            ["stack"] => {
+                let values: Vec<String> =
+                    self.machine.stack().iter().map(|v| v.to_string()).collect();
+                writeln!(out, "[{}]", values.join(" "))?;
+            }
+            ["globals"] => {
+                for (idx, value) in self.machine.globals().iter().enumerate() {
+                    writeln!(out, "{}: {}", idx, value)?;
+                }
+            }
+            ["memory", range @ ..] => {
+                let memory = self.machine.memory().ok_or(anyhow!("no memory"))?;
+                dump_memory(memory, range, out)?;
+            }
+            [command, ..] => return Err(anyhow!("unknown command `{}`, try `help`", command)),
+        }
+        Ok(())
+    }
+
+    /// Execute instructions until `mode` is satisfied, a breakpoint is reached or the call ends.
+    fn resume(&mut self, mode: Resume) -> Result<(), Trap> {
+        let depth = self.machine.call_depth();
+        loop {
+            self.machine.step()?;
+            let Some(position) = self.machine.position() else {
+                return Ok(());
+            };
+            // Only pause before instructions, not when leaving blocks.
+            if position.offset.is_none() {
+                continue;
+            }
+            let call_depth = self.machine.call_depth();
+            let done = match mode {
+                Resume::Step => true,
+                Resume::Next => call_depth <= depth,
+                Resume::Out => call_depth < depth,
+                Resume::Continue => false,
+            };
+            if done || self.at_breakpoint(&position).is_some() {
+                return Ok(());
+            }
+        }
+    }
+
+    fn at_breakpoint(&self, position: &Position) -> Option<usize> {
+        let offset = position.offset?;
+        self.program
+            .breakpoints
+            .iter()
+            .position(|b| b.func_idx == position.func_idx && b.offset == offset)
+    }
+
+    fn show_position(&self, out: &mut impl Write) -> Result<()> {
+        let Some(position) = self.machine.position() else {
+            return Ok(());
+        };
+        if let Some(n) = self.at_breakpoint(&position) {
+            writeln!(out, "breakpoint {} hit", n)?;
+        }
+        let at = self.program.describe(position.func_idx, position.offset);
+        match position.intrinsic {
+            Some(intrinsic) => writeln!(out, "{}: {}", at, print_instr(intrinsic))?,
+            None => writeln!(out, "{}", at)?,
+        }
+        Ok(())
+    }
+}
+
+/// Hex dump of the memory range given as `<addr> [len]`, 16 bytes per line.
+pub fn dump_memory(memory: &MemInst, range: &[&str], out: &mut impl Write) -> Result<()> {
+    let (addr, len) = match range {
+        [addr] => (addr, None),
+        [addr, len] => (addr, Some(len)),
+        _ => return Err(anyhow!("usage: memory <addr> [len]")),
+    };
+    let addr = parse_u32(addr).ok_or(anyhow!("invalid address {}", addr))? as usize;
+    let len = match len {
+        Some(len) => parse_u32(len).ok_or(anyhow!("invalid length {}", len))? as usize,
+        None => MEMORY_ROW,
+    };
+    let bytes = memory.read_bytes(addr, len)?;
+    for (row, chunk) in bytes.chunks(MEMORY_ROW).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        writeln!(out, "{:#010x}: {}", addr + row * MEMORY_ROW, hex.join(" "))?;
+    }
+    Ok(())
+}
+
+/// Print `prompt` and read the next line, `None` at the end of `input`.
+pub fn prompt(
+    prompt: &str,
+    input: &mut impl BufRead,
+    out: &mut impl Write,
+) -> Result<Option<String>> {
+    write!(out, "{}", prompt)?;
+    out.flush()?;
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::load_module;
+    use std::path::Path;
+
+    /// Output of the debugger reading `commands`, without its prompts.
+    fn debug(path: &str, commands: &str) -> String {
+        let mut debugger = Debugger::new(load_module(Path::new(path)).unwrap()).unwrap();
+        let mut out = Vec::new();
+        debugger.run(&mut commands.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap().replace(PROMPT, "")
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let commands = "break count\ncall count 1\nlocals\ncontinue\nbt\ndelete 0\ncontinue\n";
+        assert_eq!(
+            debug("examples/recursion.wat", commands),
+            "breakpoint 0 at func[0] <count> @ 0x0024\n\
+             breakpoint 0 hit\n\
+             func[0] <count> @ 0x0024: local.get 0\n\
+             0 $n: 1:i32\n\
+             breakpoint 0 hit\n\
+             func[0] <count> @ 0x0024: local.get 0\n\
+             #0 func[0] <count> @ 0x0024\n\
+             #1 func[0] <count> @ 0x0033\n\
+             returned [1:i32]\n"
+        );
+    }
+
+    #[test]
+    fn test_stepping() {
+        let output = debug(
+            "examples/recursion.wat",
+            "break 0 0x32\ncall count 1\nstack\nnext\nstep\nout\nstack\nstep\n",
+        );
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[2..],
+            [
+                "func[0] <count> @ 0x0032: i32.sub",
+                "[1:i32 1:i32 1:i32]",
+                "func[0] <count> @ 0x0033: call 0",
+                "func[0] <count> @ 0x0024: local.get 0",
+                "func[0] <count> @ 0x0035: i32.add",
+                "[1:i32 0:i32]",
+                "returned [1:i32]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_memory() {
+        let commands = "call store 4 258\nstart load 4\nmemory 0 8\nkill\nmemory 0\n";
+        assert_eq!(
+            debug("examples/memory.wat", commands),
+            "returned []\n\
+             func[3] @ 0x0083: local.get 0\n\
+             0x00000000: 00 00 00 00 02 01 00 00\n\
+             call aborted\n\
+             error: no call is running, see `call` and `start`\n"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let commands = "break 0 0x02\nbreak nope\ncall div 1\ncall div 1 0\n";
+        assert_eq!(
+            debug("examples/trap.wat", commands),
+            "error: no instruction at 0x0002 in 0\n\
+             error: unknown function nope\n\
+             error: Invalid arguments: div expects 2 arguments, got 1\n\
+             wasm trap: integer divide by zero\n\
+             wasm backtrace:\n  \
+             0: func[0] <div_s> @ 0x003a\n  \
+             1: func[1] <div> @ 0x0042\n"
+        );
+    }
+}
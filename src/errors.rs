@@ -4,6 +4,20 @@ use thiserror::Error;
 pub enum ExecError {
     #[error("File not found: {0}")]
     FileNotFound(String),
+    #[error("Export not found: {0}")]
+    ExportNotFound(String),
+    #[error("Invalid arguments: {0}")]
+    InvalidArguments(String),
+    #[error("Malformed module: {0}")]
+    Malformed(String),
+    #[error("Invalid module: {0}")]
+    Invalid(String),
+    #[error("Unknown import: {0}")]
+    UnknownImport(String),
+    #[error("Incompatible import type: {0}")]
+    IncompatibleImportType(String),
+    #[error("Type mismatch: {0}")]
+    TypeMismatch(String),
     #[error("Unknown error")]
     Unknown,
 }
@@ -1,16 +1,64 @@
 use anyhow::{anyhow, Result};
 
+/// Range of bytes a node was decoded from, as offsets in the module.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: u32,
+    /// Offset just past the last byte.
+    pub end: u32,
+}
+impl Span {
+    pub fn len(&self) -> u32 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Reader over borrowed bytes, so that sections and function bodies are decoded without
+/// copying them.
 #[derive(Debug)]
-pub struct Buffer {
+pub struct Buffer<'a> {
     cursor: u32,
-    buf: Vec<u8>,
+    /// Offset in the module of the first byte, for buffers made by `read_buffer`.
+    start: u32,
+    buf: &'a [u8],
 }
 
 type ReadVecFn<T> = Box<dyn Fn(&mut Buffer) -> Result<T>>;
 
-impl Buffer {
-    pub fn new(buf: Vec<u8>) -> Buffer {
-        Buffer { cursor: 0, buf }
+impl<'a> Buffer<'a> {
+    pub fn new(buf: &'a [u8]) -> Buffer<'a> {
+        Buffer::at(buf, 0)
+    }
+
+    /// Buffer over `buf`, which is found at `start` in the module.
+    pub fn at(buf: &'a [u8], start: u32) -> Buffer<'a> {
+        Buffer {
+            cursor: 0,
+            start,
+            buf,
+        }
+    }
+
+    pub fn cursor(&self) -> u32 {
+        self.cursor
+    }
+
+    /// Offset in the module of the next byte to read, unlike `cursor` which starts at 0 in
+    /// every buffer.
+    pub fn offset(&self) -> u32 {
+        self.start + self.cursor
+    }
+
+    /// Span from `start` to the next byte to read.
+    pub fn span_from(&self, start: u32) -> Span {
+        Span {
+            start,
+            end: self.offset(),
+        }
     }
 
     pub fn byte_len(&self) -> u64 {
@@ -22,59 +70,106 @@ impl Buffer {
     }
 
     pub fn read_byte(&mut self) -> Result<u8> {
-        let buf_slice = self.read_bytes(1)?;
-        Ok(buf_slice[0])
+        let byte = *self
+            .buf
+            .get(self.cursor as usize)
+            .ok_or_else(|| anyhow!("Buffer too small"))?;
+        self.cursor += 1;
+        Ok(byte)
     }
 
-    pub fn read_bytes(&mut self, size: u32) -> Result<Vec<u8>> {
-        if (self.buf.len() as u32) < (self.cursor + size) {
+    pub fn read_bytes(&mut self, size: u32) -> Result<&'a [u8]> {
+        let end = self.cursor as usize + size as usize;
+        if self.buf.len() < end {
             return Err(anyhow!("Buffer too small"));
         }
 
-        let slice = self.buf[self.cursor as usize..(self.cursor + size) as usize].to_vec();
+        let slice = &self.buf[self.cursor as usize..end];
         self.cursor += size;
         Ok(slice)
     }
 
-    pub fn read_buffer(&mut self, size: u32) -> Result<Buffer> {
-        let buf_slice = self.read_bytes(size)?;
-        Ok(Buffer::new(buf_slice))
+    pub fn read_buffer(&mut self, size: u32) -> Result<Buffer<'a>> {
+        let start = self.offset();
+        Ok(Buffer::at(self.read_bytes(size)?, start))
     }
 
-    /// Read a 32-bit unsigned integer from the buffer.
+    /// Read a 32-bit unsigned integer from the buffer, in at most 5 bytes.
     /// https://en.wikipedia.org/wiki/LEB128
+    /// https://webassembly.github.io/spec/core/binary/values.html#integers
     pub fn read_u32(&mut self) -> Result<u32> {
         let mut result = 0_u32;
-        let mut shift = 0_u32;
-        loop {
+        for shift in (0..32).step_by(7) {
             let byte = self.read_byte()?;
             result |= ((byte & 0b01111111) as u32) << shift;
-            shift += 7;
             // if the top bit of the byte is 0, return result.
             if (0b10000000 & byte) == 0 {
+                // the last byte only has 4 bits left for the value.
+                if shift == 28 && byte & 0b01110000 != 0 {
+                    return Err(anyhow!("integer too large"));
+                }
                 return Ok(result);
             }
         }
+        Err(anyhow!("integer representation too long"))
     }
 
-    /// Read a 32-bit signed integer from the buffer.
+    /// Read a 32-bit signed integer from the buffer, in at most 5 bytes.
     /// https://en.wikipedia.org/wiki/LEB128
     pub fn read_i32(&mut self) -> Result<i32> {
         let mut result = 0_i32;
-        let mut shift = 0_i32;
-        loop {
+        for shift in (0..32).step_by(7) {
             let byte = self.read_byte()?;
             result |= ((byte & 0b01111111) as i32) << shift;
-            shift += 7;
             // if the top bit of the byte is 0, return result.
             if (0b10000000 & byte) == 0 {
+                // the last byte has 4 bits for the value, the others extending its sign.
+                if shift == 28 && !matches!(byte & 0b01111000, 0 | 0b01111000) {
+                    return Err(anyhow!("integer too large"));
+                }
+                // sign extend negative numbers
+                if (shift < 25) && ((byte & 0b01000000) != 0) {
+                    result |= !0 << (shift + 7);
+                }
+                return Ok(result);
+            }
+        }
+        Err(anyhow!("integer representation too long"))
+    }
+
+    /// Read a 64-bit signed integer from the buffer, in at most 10 bytes.
+    /// https://en.wikipedia.org/wiki/LEB128
+    pub fn read_i64(&mut self) -> Result<i64> {
+        let mut result = 0_i64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.read_byte()?;
+            result |= ((byte & 0b01111111) as i64) << shift;
+            // if the top bit of the byte is 0, return result.
+            if (0b10000000 & byte) == 0 {
+                // the last byte has 1 bit for the value, the others extending its sign.
+                if shift == 63 && !matches!(byte & 0b01111111, 0 | 0b01111111) {
+                    return Err(anyhow!("integer too large"));
+                }
                 // sign extend negative numbers
-                if (shift < 32) && ((byte & 0b01000000) != 0) {
-                    result |= !0 << shift;
+                if (shift < 57) && ((byte & 0b01000000) != 0) {
+                    result |= !0 << (shift + 7);
                 }
                 return Ok(result);
             }
         }
+        Err(anyhow!("integer representation too long"))
+    }
+
+    /// Read a little-endian IEEE 754 single precision float.
+    pub fn read_f32(&mut self) -> Result<f32> {
+        let bytes = self.read_bytes(4)?;
+        Ok(f32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Read a little-endian IEEE 754 double precision float.
+    pub fn read_f64(&mut self) -> Result<f64> {
+        let bytes = self.read_bytes(8)?;
+        Ok(f64::from_le_bytes(bytes.try_into().unwrap()))
     }
 
     pub fn read_vec<T>(&mut self, f: ReadVecFn<T>) -> Result<Vec<T>> {
@@ -89,8 +184,8 @@ impl Buffer {
     pub fn read_name(&mut self) -> Result<String> {
         let size = self.read_u32()?;
         let bytes = self.read_bytes(size)?;
-        let name = String::from_utf8(bytes)?;
-        Ok(name)
+        let name = std::str::from_utf8(bytes)?;
+        Ok(name.to_string())
     }
 }
 
@@ -99,30 +194,73 @@ mod tests {
     use super::*;
     use rstest::rstest;
 
+    #[test]
+    fn test_read_buffer_offset() {
+        let mut buffer = Buffer::new(&[0, 1, 2, 3, 4, 5]);
+        buffer.read_byte().unwrap();
+        let mut inner = buffer.read_buffer(4).unwrap();
+        inner.read_byte().unwrap();
+        let mut nested = inner.read_buffer(2).unwrap();
+        assert_eq!((nested.cursor(), nested.offset()), (0, 2));
+        assert_eq!(nested.read_byte().unwrap(), 2);
+        assert_eq!(nested.span_from(2), Span { start: 2, end: 3 });
+        assert_eq!(buffer.offset(), 5);
+    }
+
     /// test cases from https://github.com/xtuc/webassemblyjs/blob/master/packages/leb128/test/index.js
     #[rstest(
         input,
         expected,
-        case(vec![0b00000000], 0),
-        case(vec![0b00001000], 8),
-        case(vec![0b10000000, 0b01111111], 16256),
-        case(vec![0b11100101, 0b10001110, 0b00100110], 624485),
-        case(vec![0b10000000, 0b10000000, 0b10000000, 0b01001111], 165675008),
-        case(vec![0b10001001, 0b10000000, 0b10000000, 0b10000000, 0b00000000], 9)
+        case(vec![0b00000000], Ok(0)),
+        case(vec![0b00001000], Ok(8)),
+        case(vec![0b10000000, 0b01111111], Ok(16256)),
+        case(vec![0b11100101, 0b10001110, 0b00100110], Ok(624485)),
+        case(vec![0b10000000, 0b10000000, 0b10000000, 0b01001111], Ok(165675008)),
+        case(vec![0b10001001, 0b10000000, 0b10000000, 0b10000000, 0b00000000], Ok(9)),
+        case(vec![0xff, 0xff, 0xff, 0xff, 0x0f], Ok(u32::MAX)),
+        case(vec![0xff, 0xff, 0xff, 0xff, 0x1f], Err("integer too large")),
+        case(vec![0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01], Err("integer representation too long")),
+        case(vec![0x80, 0x80], Err("Buffer too small"))
+    )]
+    fn test_read_u32(input: Vec<u8>, expected: Result<u32, &str>) {
+        let mut buffer = Buffer::new(&input);
+        let result = buffer.read_u32().map_err(|e| e.to_string());
+        assert_eq!(result, expected.map_err(str::to_string));
+    }
+
+    #[rstest(
+        input,
+        expected,
+        case(vec![0x7eu8], Ok(-2)),
+        case(vec![0b10000001, 0b01111111], Ok(-127)),
+        case(vec![0xff, 0xff, 0xff, 0xff, 0x07], Ok(i32::MAX)),
+        case(vec![0x80, 0x80, 0x80, 0x80, 0x78], Ok(i32::MIN)),
+        case(vec![0xff, 0xff, 0xff, 0xff, 0x0f], Err("integer too large")),
+        case(vec![0x80, 0x80, 0x80, 0x80, 0x70], Err("integer too large")),
+        case(vec![0x80, 0x80, 0x80, 0x80, 0x80, 0x00], Err("integer representation too long"))
     )]
-    fn test_read_u32(input: Vec<u8>, expected: u32) {
-        let mut buffer = Buffer::new(input);
-        assert_eq!(buffer.read_u32().unwrap(), expected);
+    fn test_read_i32(input: Vec<u8>, expected: Result<i32, &str>) {
+        let mut buffer = Buffer::new(&input);
+        let result = buffer.read_i32().map_err(|e| e.to_string());
+        assert_eq!(result, expected.map_err(str::to_string));
     }
 
     #[rstest(
         input,
         expected,
-        case(vec![0x7eu8], -2),
-        case(vec![0b10000001, 0b01111111], -127)
+        case(vec![0x7eu8], Ok(-2)),
+        case(vec![0xff, 0xff, 0xff, 0xff, 0x0f], Ok(0xffff_ffff)),
+        case(vec![0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f], Ok(i64::MIN)),
+        case(vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00], Ok(i64::MAX)),
+        case(vec![0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x02], Err("integer too large")),
+        case(
+            [vec![0x80; 11], vec![0x01]].concat(),
+            Err("integer representation too long")
+        )
     )]
-    fn test_read_i32(input: Vec<u8>, expected: i32) {
-        let mut buffer = Buffer::new(input);
-        assert_eq!(buffer.read_i32().unwrap(), expected);
+    fn test_read_i64(input: Vec<u8>, expected: Result<i64, &str>) {
+        let mut buffer = Buffer::new(&input);
+        let result = buffer.read_i64().map_err(|e| e.to_string());
+        assert_eq!(result, expected.map_err(str::to_string));
     }
 }
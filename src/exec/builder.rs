@@ -0,0 +1,614 @@
+use anyhow::{anyhow, Result};
+
+use crate::exec::buffer::Buffer;
+use crate::exec::code_section::{
+    BlockIntrinsicNode, BlockType, BrIfIntrinsicNode, BrIntrinsicNode, CallIntrinsicNode, CodeNode,
+    CodeSectionNode, ExprNode, F32ConstIntrinsicNode, F64ConstIntrinsicNode, FuncIdx, FuncNode,
+    GlobalGetIntrinsicNode, GlobalIdx, GlobalSetIntrinsicNode, I32ConstIntrinsicNode,
+    I32LoadIntrinsicNode, I32StoreIntrinsicNode, I64ConstIntrinsicNode, IfIntrinsicNode,
+    IntrinsicNode, LocalGetIntrinsicNode, LocalNode, LocalSetIntrinsicNode, LoopIntrinsicNode,
+    MemArgNode, Op,
+};
+use crate::exec::data_section::{DataMode, DataNode, DataSectionNode};
+use crate::exec::export_section::{ExportKind, ExportNode, ExportSectionNode};
+use crate::exec::func_section::{FunctionSectionNode, TypeIdx};
+use crate::exec::memory_section::MemorySectionNode;
+use crate::exec::module::ModuleNode;
+use crate::exec::section::SectionNode;
+use crate::exec::type_section::{FunctionTypeNode, MemoryTypeNode, TypeSectionNode, ValType};
+
+/// Builds a module from code, e.g. for generated modules.
+///
+/// ```ignore
+/// let mut builder = ModuleBuilder::new();
+/// let sum = builder.func(&[ValType::I32], &[ValType::I32], |f| {
+///     let sum = f.local(ValType::I32);
+///     f.block(BlockType::Empty, |f, done| {
+///         f.loop_(BlockType::Empty, |f, next| {
+///             f.local_get(0).op(Op::I32Eqa).br_if(done);
+///             // sum += n; n -= 1
+///             f.br(next);
+///         });
+///     });
+///     f.local_get(sum);
+/// });
+/// builder.export_func("sum", sum);
+/// let module = builder.build()?;
+/// ```
+#[derive(Debug, Default)]
+pub struct ModuleBuilder {
+    types: Vec<FunctionTypeNode>,
+    funcs: Vec<(TypeIdx, FuncNode)>,
+    memories: Vec<MemoryTypeNode>,
+    exports: Vec<ExportNode>,
+    data: Vec<DataNode>,
+    /// First misuse of the builder, reported by `build`.
+    error: Option<anyhow::Error>,
+}
+impl ModuleBuilder {
+    pub fn new() -> ModuleBuilder {
+        ModuleBuilder::default()
+    }
+
+    /// Index of the function type, added unless an identical one exists.
+    pub fn add_type(&mut self, params: &[ValType], results: &[ValType]) -> TypeIdx {
+        let mut func_type = FunctionTypeNode::new();
+        func_type.param_type.val_types = params.to_vec();
+        func_type.result_type.val_types = results.to_vec();
+        match self.types.iter().position(|t| *t == func_type) {
+            Some(idx) => idx as TypeIdx,
+            None => {
+                self.types.push(func_type);
+                self.types.len() as TypeIdx - 1
+            }
+        }
+    }
+
+    /// Add a function whose body is produced by `body`, params being the first locals.
+    pub fn func(
+        &mut self,
+        params: &[ValType],
+        results: &[ValType],
+        body: impl FnOnce(&mut FuncBuilder),
+    ) -> FuncIdx {
+        let type_idx = self.add_type(params, results);
+        let func_idx = self.funcs.len() as FuncIdx;
+        let mut func = FuncBuilder::new(func_idx, params.len() as u32);
+        body(&mut func);
+        let (node, error) = func.finish();
+        if self.error.is_none() {
+            self.error = error;
+        }
+        self.funcs.push((type_idx, node));
+        func_idx
+    }
+
+    pub fn memory(&mut self, min: u32, max: Option<u32>) -> u32 {
+        let mut mem_type = MemoryTypeNode::new();
+        mem_type.limits.min = min;
+        mem_type.limits.max = max;
+        self.memories.push(mem_type);
+        self.memories.len() as u32 - 1
+    }
+
+    /// Bytes copied into memory `mem_idx` at `offset` when the module is instantiated.
+    pub fn data(&mut self, mem_idx: u32, offset: u32, bytes: &[u8]) {
+        let mut expr = ExprNode::new();
+        let mut val = I32ConstIntrinsicNode::new();
+        val.val = offset as i32;
+        expr.intrinsics
+            .push(IntrinsicNode::I32ConstIntrinsicNode(val));
+        expr.end_op = Some(Op::End);
+        let mut segment = DataNode::new();
+        segment.mode = DataMode::Active {
+            mem_idx,
+            offset: expr,
+        };
+        segment.init = bytes.to_vec();
+        self.data.push(segment);
+    }
+
+    pub fn export_func(&mut self, name: &str, func_idx: FuncIdx) {
+        self.export(name, ExportKind::Func, func_idx);
+    }
+
+    pub fn export_memory(&mut self, name: &str, mem_idx: u32) {
+        self.export(name, ExportKind::Memory, mem_idx);
+    }
+
+    fn export(&mut self, name: &str, kind: ExportKind, index: u32) {
+        let mut export = ExportNode::new();
+        export.name = name.to_string();
+        export.desc.tag = kind as u8;
+        export.desc.index = index;
+        self.exports.push(export);
+    }
+
+    /// Encode the module in the binary format.
+    pub fn to_bytes(self) -> Result<Vec<u8>> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        let mut module = ModuleNode::new();
+        module.magic = vec![0x00, 0x61, 0x73, 0x6d];
+        module.version = vec![0x01, 0x00, 0x00, 0x00];
+        let sections = &mut module.sections;
+        if !self.types.is_empty() {
+            let mut section = TypeSectionNode::new();
+            section.func_types = self.types;
+            sections.push(SectionNode::TypeSectionNode(section));
+        }
+        let (type_indices, funcs): (Vec<_>, Vec<_>) = self.funcs.into_iter().unzip();
+        if !type_indices.is_empty() {
+            let mut section = FunctionSectionNode::new();
+            section.type_indices = type_indices;
+            sections.push(SectionNode::FunctionSectionNode(section));
+        }
+        if !self.memories.is_empty() {
+            let mut section = MemorySectionNode::new();
+            section.mem_types = self.memories;
+            sections.push(SectionNode::MemorySectionNode(section));
+        }
+        if !self.exports.is_empty() {
+            let mut section = ExportSectionNode::new();
+            section.exports = self.exports;
+            sections.push(SectionNode::ExportSectionNode(section));
+        }
+        if !funcs.is_empty() {
+            let mut section = CodeSectionNode::new();
+            section.codes = funcs
+                .into_iter()
+                .map(|func| {
+                    let mut code = CodeNode::new();
+                    code.func = func;
+                    code
+                })
+                .collect();
+            sections.push(SectionNode::CodeSectionNode(section));
+        }
+        if !self.data.is_empty() {
+            let mut section = DataSectionNode::new();
+            section.segments = self.data;
+            sections.push(SectionNode::DataSectionNode(section));
+        }
+        Ok(module.to_bytes())
+    }
+
+    /// Build the module, decoded back from its bytes so code offsets are set as by `load`.
+    pub fn build(self) -> Result<ModuleNode> {
+        let mut module = ModuleNode::new();
+        module.load(&mut Buffer::new(&self.to_bytes()?))?;
+        Ok(module)
+    }
+}
+
+/// Block a `br` can target, valid only within the block it was given for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label {
+    id: usize,
+}
+
+/// Builds the locals and instructions of a function.
+#[derive(Debug)]
+pub struct FuncBuilder {
+    func_idx: FuncIdx,
+    local_count: u32,
+    locals: Vec<LocalNode>,
+    /// Instructions of the enclosing blocks, the function body first.
+    exprs: Vec<Vec<IntrinsicNode>>,
+    /// Label of each enclosing block, innermost last.
+    labels: Vec<Label>,
+    next_label: usize,
+    error: Option<anyhow::Error>,
+}
+impl FuncBuilder {
+    fn new(func_idx: FuncIdx, param_count: u32) -> FuncBuilder {
+        FuncBuilder {
+            func_idx,
+            local_count: param_count,
+            locals: Vec::new(),
+            exprs: vec![Vec::new()],
+            labels: Vec::new(),
+            next_label: 0,
+            error: None,
+        }
+    }
+
+    fn finish(mut self) -> (FuncNode, Option<anyhow::Error>) {
+        let mut func = FuncNode::new();
+        func.locals = self.locals;
+        func.expr = expr(self.exprs.pop().unwrap_or_default(), Op::End);
+        (func, self.error)
+    }
+
+    /// Index of the function being built, e.g. for recursive calls.
+    pub fn func_idx(&self) -> FuncIdx {
+        self.func_idx
+    }
+
+    /// Declare a local, returning its index.
+    pub fn local(&mut self, val_type: ValType) -> u32 {
+        match self.locals.last_mut() {
+            Some(local) if local.val_type == val_type => local.num += 1,
+            _ => {
+                let mut local = LocalNode::new();
+                local.num = 1;
+                local.val_type = val_type;
+                self.locals.push(local);
+            }
+        }
+        self.local_count += 1;
+        self.local_count - 1
+    }
+
+    fn push(&mut self, intrinsic: IntrinsicNode) -> &mut Self {
+        if let Some(expr) = self.exprs.last_mut() {
+            expr.push(intrinsic);
+        }
+        self
+    }
+
+    /// Instruction without immediates, e.g. `Op::I32Add`.
+    pub fn op(&mut self, op: Op) -> &mut Self {
+        if has_immediates(op) && self.error.is_none() {
+            self.error = Some(anyhow!("{} takes immediates", op.name()));
+        }
+        self.push(IntrinsicNode::new(op))
+    }
+
+    pub fn i32_const(&mut self, val: i32) -> &mut Self {
+        let mut node = I32ConstIntrinsicNode::new();
+        node.val = val;
+        self.push(IntrinsicNode::I32ConstIntrinsicNode(node))
+    }
+
+    pub fn i64_const(&mut self, val: i64) -> &mut Self {
+        let mut node = I64ConstIntrinsicNode::new();
+        node.val = val;
+        self.push(IntrinsicNode::I64ConstIntrinsicNode(node))
+    }
+
+    pub fn f32_const(&mut self, val: f32) -> &mut Self {
+        let mut node = F32ConstIntrinsicNode::new();
+        node.val = val;
+        self.push(IntrinsicNode::F32ConstIntrinsicNode(node))
+    }
+
+    pub fn f64_const(&mut self, val: f64) -> &mut Self {
+        let mut node = F64ConstIntrinsicNode::new();
+        node.val = val;
+        self.push(IntrinsicNode::F64ConstIntrinsicNode(node))
+    }
+
+    pub fn local_get(&mut self, local_idx: u32) -> &mut Self {
+        let mut node = LocalGetIntrinsicNode::new();
+        node.local_idx = local_idx;
+        self.push(IntrinsicNode::LocalGetIntrinsicNode(node))
+    }
+
+    pub fn local_set(&mut self, local_idx: u32) -> &mut Self {
+        let mut node = LocalSetIntrinsicNode::new();
+        node.local_idx = local_idx;
+        self.push(IntrinsicNode::LocalSetIntrinsicNode(node))
+    }
+
+    pub fn global_get(&mut self, global_idx: GlobalIdx) -> &mut Self {
+        let mut node = GlobalGetIntrinsicNode::new();
+        node.global_idx = global_idx;
+        self.push(IntrinsicNode::GlobalGetIntrinsicNode(node))
+    }
+
+    pub fn global_set(&mut self, global_idx: GlobalIdx) -> &mut Self {
+        let mut node = GlobalSetIntrinsicNode::new();
+        node.global_idx = global_idx;
+        self.push(IntrinsicNode::GlobalSetIntrinsicNode(node))
+    }
+
+    pub fn call(&mut self, func_idx: FuncIdx) -> &mut Self {
+        let mut node = CallIntrinsicNode::new();
+        node.func_idx = func_idx;
+        self.push(IntrinsicNode::CallIntrinsicNode(node))
+    }
+
+    /// `i32.load` with the natural alignment.
+    pub fn i32_load(&mut self, offset: u32) -> &mut Self {
+        let mut node = I32LoadIntrinsicNode::new();
+        node.mem_arg = mem_arg(offset);
+        self.push(IntrinsicNode::I32LoadIntrinsicNode(node))
+    }
+
+    /// `i32.store` with the natural alignment.
+    pub fn i32_store(&mut self, offset: u32) -> &mut Self {
+        let mut node = I32StoreIntrinsicNode::new();
+        node.mem_arg = mem_arg(offset);
+        self.push(IntrinsicNode::I32StoreIntrinsicNode(node))
+    }
+
+    /// Depth of `label` from the innermost block.
+    fn depth(&mut self, label: Label) -> u32 {
+        match self.labels.iter().rev().position(|l| *l == label) {
+            Some(depth) => depth as u32,
+            None => {
+                if self.error.is_none() {
+                    self.error = Some(anyhow!("Branch to a label outside its block"));
+                }
+                0
+            }
+        }
+    }
+
+    pub fn br(&mut self, label: Label) -> &mut Self {
+        let mut node = BrIntrinsicNode::new();
+        node.label_idx = self.depth(label);
+        self.push(IntrinsicNode::BrIntrinsicNode(node))
+    }
+
+    pub fn br_if(&mut self, label: Label) -> &mut Self {
+        let mut node = BrIfIntrinsicNode::new();
+        node.label_idx = self.depth(label);
+        self.push(IntrinsicNode::BrIfIntrinsicNode(node))
+    }
+
+    /// Build the instructions of a nested block, `body` being given the label of the block.
+    fn nested(&mut self, body: impl FnOnce(&mut FuncBuilder, Label)) -> Vec<IntrinsicNode> {
+        let label = Label {
+            id: self.next_label,
+        };
+        self.next_label += 1;
+        self.labels.push(label);
+        self.exprs.push(Vec::new());
+        body(self, label);
+        self.labels.pop();
+        self.exprs.pop().unwrap_or_default()
+    }
+
+    /// `block`, which `br` to its label exits.
+    pub fn block(
+        &mut self,
+        block_type: BlockType,
+        body: impl FnOnce(&mut FuncBuilder, Label),
+    ) -> &mut Self {
+        let mut node = BlockIntrinsicNode::new();
+        node.block_type = block_type;
+        node.expr = expr(self.nested(body), Op::End);
+        self.push(IntrinsicNode::BlockIntrinsicNode(node))
+    }
+
+    /// `loop`, which `br` to its label restarts.
+    pub fn loop_(
+        &mut self,
+        block_type: BlockType,
+        body: impl FnOnce(&mut FuncBuilder, Label),
+    ) -> &mut Self {
+        let mut node = LoopIntrinsicNode::new();
+        node.block_type = block_type;
+        node.expr = expr(self.nested(body), Op::End);
+        self.push(IntrinsicNode::LoopIntrinsicNode(node))
+    }
+
+    pub fn if_(
+        &mut self,
+        block_type: BlockType,
+        then: impl FnOnce(&mut FuncBuilder, Label),
+    ) -> &mut Self {
+        let mut node = IfIntrinsicNode::new();
+        node.block_type = block_type;
+        node.then_expr = expr(self.nested(then), Op::End);
+        self.push(IntrinsicNode::IfIntrinsicNode(node))
+    }
+
+    pub fn if_else(
+        &mut self,
+        block_type: BlockType,
+        then: impl FnOnce(&mut FuncBuilder, Label),
+        else_: impl FnOnce(&mut FuncBuilder, Label),
+    ) -> &mut Self {
+        let mut node = IfIntrinsicNode::new();
+        node.block_type = block_type;
+        node.then_expr = expr(self.nested(then), Op::Else);
+        node.else_expr = expr(self.nested(else_), Op::End);
+        self.push(IntrinsicNode::IfIntrinsicNode(node))
+    }
+}
+
+fn expr(intrinsics: Vec<IntrinsicNode>, end_op: Op) -> ExprNode {
+    let mut expr = ExprNode::new();
+    expr.intrinsics = intrinsics;
+    expr.end_op = Some(end_op);
+    expr
+}
+
+fn mem_arg(offset: u32) -> MemArgNode {
+    let mut mem_arg = MemArgNode::new();
+    mem_arg.align = 2;
+    mem_arg.offset = offset;
+    mem_arg
+}
+
+/// Ops which `FuncBuilder::op` cannot build as they need immediates or a body.
+fn has_immediates(op: Op) -> bool {
+    !matches!(
+        op,
+        Op::Unreachable
+            | Op::Nop
+            | Op::Return
+            | Op::Drop
+            | Op::I32Eqa
+            | Op::I32LtS
+            | Op::I32GeS
+            | Op::I32Add
+            | Op::I32Sub
+            | Op::I32Mul
+            | Op::I32DivS
+            | Op::I32DivU
+            | Op::I32Rems
+            | Op::I32RemU
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::code_section::S33;
+    use crate::exec::instance::Instance;
+    use crate::exec::store::Store;
+    use crate::exec::validate::validate;
+    use crate::exec::value::Value;
+    use crate::wat::module::parse_str;
+
+    fn instantiate(builder: ModuleBuilder) -> (Store, Instance) {
+        let module = builder.build().unwrap();
+        let mut store = Store::new();
+        let instance = Instance::new(&mut store, module).unwrap();
+        (store, instance)
+    }
+
+    /// Sum of 1..=n, branching out of the block and back to the loop by label.
+    fn sum(builder: &mut ModuleBuilder) -> FuncIdx {
+        builder.func(&[ValType::I32], &[ValType::I32], |f| {
+            let sum = f.local(ValType::I32);
+            f.block(BlockType::Empty, |f, done| {
+                f.loop_(BlockType::Empty, |f, next| {
+                    f.local_get(0).op(Op::I32Eqa).br_if(done);
+                    f.local_get(sum).local_get(0).op(Op::I32Add).local_set(sum);
+                    f.local_get(0).i32_const(1).op(Op::I32Sub).local_set(0);
+                    f.br(next);
+                });
+            });
+            f.local_get(sum);
+        })
+    }
+
+    #[test]
+    fn test_build() {
+        let mut builder = ModuleBuilder::new();
+        let sum = sum(&mut builder);
+        builder.export_func("sum", sum);
+        let (mut store, instance) = instantiate(builder);
+        let results = instance.invoke(&mut store, "sum", &[Value::I32(10)]);
+        assert_eq!(results.unwrap(), vec![Value::I32(55)]);
+    }
+
+    #[test]
+    fn test_same_bytes_as_text() {
+        let mut builder = ModuleBuilder::new();
+        let sum = sum(&mut builder);
+        builder.export_func("sum", sum);
+        let text = r#"
+            (func (export "sum") (param $n i32) (result i32) (local $sum i32)
+              block $done
+                loop $next
+                  (br_if $done (i32.eqz (local.get $n)))
+                  (local.set $sum (i32.add (local.get $sum) (local.get $n)))
+                  (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                  br $next
+                end
+              end
+              local.get $sum)
+        "#;
+        let mut expected = ModuleNode::new();
+        expected
+            .load(&mut Buffer::new(&parse_str(text).unwrap()))
+            .unwrap();
+        // The text format adds a name section.
+        expected
+            .sections
+            .retain(|s| !matches!(s, SectionNode::CustomSectionNode(_)));
+        assert_eq!(builder.to_bytes().unwrap(), expected.to_bytes());
+    }
+
+    #[test]
+    fn test_if_else_and_recursion() {
+        let mut builder = ModuleBuilder::new();
+        let fac = builder.func(&[ValType::I32], &[ValType::I32], |f| {
+            let fac = f.func_idx();
+            f.local_get(0).i32_const(2).op(Op::I32LtS);
+            f.if_else(
+                BlockType::ValType(ValType::I32),
+                |f, _| {
+                    f.i32_const(1);
+                },
+                |f, _| {
+                    f.local_get(0).local_get(0).i32_const(1).op(Op::I32Sub);
+                    f.call(fac).op(Op::I32Mul);
+                },
+            );
+        });
+        builder.export_func("fac", fac);
+        let (mut store, instance) = instantiate(builder);
+        let results = instance.invoke(&mut store, "fac", &[Value::I32(5)]);
+        assert_eq!(results.unwrap(), vec![Value::I32(120)]);
+    }
+
+    #[test]
+    fn test_block_type_index() {
+        let mut builder = ModuleBuilder::new();
+        let pair = builder.add_type(&[ValType::I32], &[ValType::I32, ValType::I32]);
+        let count = builder.add_type(&[ValType::I32, ValType::I32], &[ValType::I32]);
+        // Sum of 1..=n, keeping (sum, n) on the stack as the params of the loop.
+        let sum = builder.func(&[ValType::I32], &[ValType::I32], |f| {
+            f.local_get(0);
+            f.block(BlockType::S33(pair as S33), |f, _| {
+                f.local_get(0);
+            });
+            f.loop_(BlockType::S33(count as S33), |f, next| {
+                let n = f.local(ValType::I32);
+                f.i32_const(1).op(Op::I32Sub).local_set(n);
+                f.local_get(n).op(Op::I32Add);
+                f.local_get(n).local_get(n).br_if(next);
+                f.op(Op::Drop);
+            });
+        });
+        builder.export_func("sum", sum);
+        let module = builder.build().unwrap();
+        assert!(validate(&module).is_empty());
+        let mut store = Store::new();
+        let instance = Instance::new(&mut store, module).unwrap();
+        let results = instance.invoke(&mut store, "sum", &[Value::I32(4)]);
+        assert_eq!(results.unwrap(), vec![Value::I32(10)]);
+    }
+
+    #[test]
+    fn test_memory_and_data() {
+        let mut builder = ModuleBuilder::new();
+        let mem = builder.memory(1, Some(1));
+        builder.data(mem, 8, &42_i32.to_le_bytes());
+        let load = builder.func(&[], &[ValType::I32], |f| {
+            f.i32_const(0).i32_load(8);
+        });
+        builder.export_func("load", load);
+        builder.export_memory("memory", mem);
+        let (mut store, instance) = instantiate(builder);
+        let results = instance.invoke(&mut store, "load", &[]);
+        assert_eq!(results.unwrap(), vec![Value::I32(42)]);
+        let memory = instance.get_memory(&store, "memory").unwrap();
+        assert_eq!(store.memories[memory].read::<i32>(8), Ok(42));
+    }
+
+    #[test]
+    fn test_data_out_of_bounds() {
+        let mut builder = ModuleBuilder::new();
+        let mem = builder.memory(1, None);
+        builder.data(mem, 65535, &[1, 2]);
+        let module = builder.build().unwrap();
+        assert!(Instance::new(&mut Store::new(), module).is_err());
+    }
+
+    #[test]
+    fn test_label_outside_block() {
+        let mut builder = ModuleBuilder::new();
+        builder.func(&[], &[], |f| {
+            let mut escaped = None;
+            f.block(BlockType::Empty, |_, label| escaped = Some(label));
+            f.br(escaped.unwrap());
+        });
+        assert!(builder.build().is_err());
+
+        let mut builder = ModuleBuilder::new();
+        builder.func(&[], &[], |f| {
+            f.op(Op::Call);
+        });
+        assert!(builder.build().is_err());
+    }
+}
@@ -1,11 +1,16 @@
 use anyhow::{anyhow, Result};
 
-use crate::exec::buffer::Buffer;
-use crate::exec::type_section::{NumType, ValType};
+use crate::errors::ExecError;
+use crate::exec::buffer::{Buffer, Span};
+use crate::exec::func_section::TypeIdx;
+use crate::exec::section::SectionHeader;
+use crate::exec::type_section::{NumType, RefType, ValType, FUNC_REF};
+use crate::exec::writer::Writer;
 
 #[derive(Debug, Clone)]
 pub struct CodeSectionNode {
-    codes: Vec<CodeNode>,
+    pub codes: Vec<CodeNode>,
+    pub header: Option<SectionHeader>,
 }
 impl Default for CodeSectionNode {
     fn default() -> Self {
@@ -14,7 +19,10 @@ impl Default for CodeSectionNode {
 }
 impl CodeSectionNode {
     pub fn new() -> CodeSectionNode {
-        CodeSectionNode { codes: Vec::new() }
+        CodeSectionNode {
+            codes: Vec::new(),
+            header: None,
+        }
     }
 
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
@@ -27,12 +35,18 @@ impl CodeSectionNode {
         self.codes = buf.read_vec::<CodeNode>(Box::new(f))?;
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_vec(&self.codes, |w, item| item.write(w));
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct CodeNode {
-    size: u32,
-    func: FuncNode,
+    pub size: u32,
+    /// Bytes of the body, after its size.
+    pub span: Span,
+    pub func: FuncNode,
 }
 impl Default for CodeNode {
     fn default() -> Self {
@@ -43,22 +57,36 @@ impl CodeNode {
     pub fn new() -> CodeNode {
         CodeNode {
             size: 0,
+            span: Span::default(),
             func: FuncNode::new(),
         }
     }
 
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
         self.size = buf.read_u32()?;
+        let start = buf.offset();
         let mut func_buf = buf.read_buffer(self.size)?;
+        self.span = buf.span_from(start);
         self.func.load(&mut func_buf)?;
+        if !func_buf.eof() {
+            return Err(ExecError::Malformed("section size mismatch".to_string()).into());
+        }
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        // The size is recomputed so edited bodies stay consistent.
+        let mut func = Writer::new();
+        self.func.write(&mut func);
+        writer.write_u32(func.len() as u32);
+        writer.write_bytes(&func.into_bytes());
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct FuncNode {
-    locals: Vec<LocalNode>,
-    expr: ExprNode,
+    pub locals: Vec<LocalNode>,
+    pub expr: ExprNode,
 }
 impl Default for FuncNode {
     fn default() -> Self {
@@ -85,12 +113,17 @@ impl FuncNode {
 
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_vec(&self.locals, |w, item| item.write(w));
+        self.expr.write(writer);
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct LocalNode {
-    num: u32,
-    val_type: ValType,
+    pub num: u32,
+    pub val_type: ValType,
 }
 impl Default for LocalNode {
     fn default() -> Self {
@@ -108,16 +141,21 @@ impl LocalNode {
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
         self.num = buf.read_u32()?;
         let val_type = buf.read_byte()?;
-        self.val_type =
-            ValType::from_u8(val_type).ok_or(anyhow!("Invalid value type: {}", val_type))?;
+        self.val_type = ValType::from_u8(val_type)
+            .ok_or_else(|| anyhow!("Invalid value type: {}", val_type))?;
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_u32(self.num);
+        writer.write_byte(self.val_type.to_u8());
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ExprNode {
-    intrinsics: Vec<IntrinsicNode>,
-    end_op: Option<Op>,
+    pub intrinsics: Vec<IntrinsicNode>,
+    pub end_op: Option<Op>,
 }
 impl Default for ExprNode {
     fn default() -> Self {
@@ -133,33 +171,106 @@ impl ExprNode {
     }
 
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.load_until(buf, false)
+    }
+
+    /// Load instructions up to an `end`, or up to an `else` if `else_ends` is set,
+    /// as for the then branch of an `if`.
+    fn load_until(&mut self, buf: &mut Buffer, else_ends: bool) -> Result<()> {
         loop {
+            let start = buf.offset();
             let op_byte = buf.read_byte()?;
+            if op_byte == Op::Else as u8 && !else_ends {
+                return Err(ExecError::Malformed("unexpected else".to_string()).into());
+            }
             if op_byte == Op::End as u8 || op_byte == Op::Else as u8 {
                 self.end_op = Op::from_u8(op_byte);
                 break;
             }
 
-            let opcode = Op::from_u8(op_byte).ok_or(anyhow!("Invalid opcode: {}", op_byte))?;
+            let opcode = if op_byte == PREFIX_FC {
+                let sub_op = buf.read_u32()?;
+                Op::from_fc(sub_op).ok_or_else(|| anyhow!("Invalid opcode: 0xfc {}", sub_op))?
+            } else {
+                Op::from_u8(op_byte).ok_or_else(|| anyhow!("Invalid opcode: {}", op_byte))?
+            };
             let mut intrinsic = IntrinsicNode::new(opcode);
             intrinsic.load(buf)?;
+            intrinsic.set_span(buf.span_from(start));
             self.intrinsics.push(intrinsic);
         }
 
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        for intrinsic in &self.intrinsics {
+            intrinsic.write(writer);
+        }
+        self.end_op.unwrap_or(Op::End).write(writer);
+    }
+
+    /// Offset, block depth and node of every intrinsic, nested blocks included, in code order.
+    pub fn instructions(&self) -> Vec<(u32, usize, &IntrinsicNode)> {
+        let mut instructions = Vec::new();
+        self.collect_instructions(0, &mut instructions);
+        instructions
+    }
+
+    fn collect_instructions<'a>(
+        &'a self,
+        depth: usize,
+        instructions: &mut Vec<(u32, usize, &'a IntrinsicNode)>,
+    ) {
+        for intrinsic in &self.intrinsics {
+            let Some(span) = intrinsic.span() else {
+                continue;
+            };
+            instructions.push((span.start, depth, intrinsic));
+            match intrinsic {
+                IntrinsicNode::BlockIntrinsicNode(node) => {
+                    node.expr.collect_instructions(depth + 1, instructions)
+                }
+                IntrinsicNode::LoopIntrinsicNode(node) => {
+                    node.expr.collect_instructions(depth + 1, instructions)
+                }
+                IntrinsicNode::IfIntrinsicNode(node) => {
+                    node.then_expr.collect_instructions(depth + 1, instructions);
+                    node.else_expr.collect_instructions(depth + 1, instructions);
+                }
+                _ => {}
+            }
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Clone)]
+/// Prefix of the opcodes encoded as `0xfc` followed by a u32.
+const PREFIX_FC: u8 = 0xfc;
+
+/// Single byte opcodes are stored as is, `0xfc` prefixed ones as `0xfcXX`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum Op {
+    Unreachable = 0x00,
+    Nop = 0x01,
+    Return = 0x0f,
+    Drop = 0x1a,
     LocalGet = 0x20,
     LocalSet = 0x21,
+    I32Load = 0x28,
+    I32Store = 0x36,
+    MemorySize = 0x3f,
+    MemoryGrow = 0x40,
     I32Const = 0x41,
     I32Eqa = 0x45,
     I32LtS = 0x48,
     I32GeS = 0x4e,
     I32Add = 0x6a,
+    I32Sub = 0x6b,
+    I32Mul = 0x6c,
+    I32DivS = 0x6d,
+    I32DivU = 0x6e,
     I32Rems = 0x6f,
+    I32RemU = 0x70,
     If = 0x04,
     Else = 0x05,
     Block = 0x02,
@@ -168,18 +279,48 @@ pub enum Op {
     BrIf = 0x0d,
     Call = 0x10,
     End = 0x0b,
+    RefNull = 0xd0,
+    TableGrow = 0xfc0f,
+    TableSize = 0xfc10,
+    GlobalGet = 0x23,
+    GlobalSet = 0x24,
+    I64Const = 0x42,
+    F32Const = 0x43,
+    F64Const = 0x44,
+    CallIndirect = 0x11,
+    I32TruncF32S = 0xa8,
+    I32TruncF32U = 0xa9,
+    I32TruncF64S = 0xaa,
+    I32TruncF64U = 0xab,
+    I64TruncF32S = 0xae,
+    I64TruncF32U = 0xaf,
+    I64TruncF64S = 0xb0,
+    I64TruncF64U = 0xb1,
 }
 impl Op {
     pub fn from_u8(value: u8) -> Option<Op> {
         match value {
+            0x00 => Some(Op::Unreachable),
+            0x01 => Some(Op::Nop),
+            0x0f => Some(Op::Return),
+            0x1a => Some(Op::Drop),
             0x20 => Some(Op::LocalGet),
             0x21 => Some(Op::LocalSet),
+            0x28 => Some(Op::I32Load),
+            0x36 => Some(Op::I32Store),
+            0x3f => Some(Op::MemorySize),
+            0x40 => Some(Op::MemoryGrow),
             0x41 => Some(Op::I32Const),
             0x45 => Some(Op::I32Eqa),
             0x48 => Some(Op::I32LtS),
             0x4e => Some(Op::I32GeS),
             0x6a => Some(Op::I32Add),
+            0x6b => Some(Op::I32Sub),
+            0x6c => Some(Op::I32Mul),
+            0x6d => Some(Op::I32DivS),
+            0x6e => Some(Op::I32DivU),
             0x6f => Some(Op::I32Rems),
+            0x70 => Some(Op::I32RemU),
             0x04 => Some(Op::If),
             0x05 => Some(Op::Else),
             0x02 => Some(Op::Block),
@@ -188,72 +329,564 @@ impl Op {
             0x0d => Some(Op::BrIf),
             0x10 => Some(Op::Call),
             0x0b => Some(Op::End),
+            0xd0 => Some(Op::RefNull),
+            0x23 => Some(Op::GlobalGet),
+            0x24 => Some(Op::GlobalSet),
+            0x42 => Some(Op::I64Const),
+            0x43 => Some(Op::F32Const),
+            0x44 => Some(Op::F64Const),
+            0x11 => Some(Op::CallIndirect),
+            0xa8 => Some(Op::I32TruncF32S),
+            0xa9 => Some(Op::I32TruncF32U),
+            0xaa => Some(Op::I32TruncF64S),
+            0xab => Some(Op::I32TruncF64U),
+            0xae => Some(Op::I64TruncF32S),
+            0xaf => Some(Op::I64TruncF32U),
+            0xb0 => Some(Op::I64TruncF64S),
+            0xb1 => Some(Op::I64TruncF64U),
             _ => None,
         }
     }
+
+    pub fn from_fc(value: u32) -> Option<Op> {
+        match value {
+            15 => Some(Op::TableGrow),
+            16 => Some(Op::TableSize),
+            _ => None,
+        }
+    }
+
+    /// Every opcode, in declaration order.
+    pub const ALL: &'static [Op] = &[
+        Op::Unreachable,
+        Op::Nop,
+        Op::Return,
+        Op::Drop,
+        Op::LocalGet,
+        Op::LocalSet,
+        Op::I32Load,
+        Op::I32Store,
+        Op::MemorySize,
+        Op::MemoryGrow,
+        Op::I32Const,
+        Op::I32Eqa,
+        Op::I32LtS,
+        Op::I32GeS,
+        Op::I32Add,
+        Op::I32Sub,
+        Op::I32Mul,
+        Op::I32DivS,
+        Op::I32DivU,
+        Op::I32Rems,
+        Op::I32RemU,
+        Op::If,
+        Op::Else,
+        Op::Block,
+        Op::Loop,
+        Op::Br,
+        Op::BrIf,
+        Op::Call,
+        Op::End,
+        Op::RefNull,
+        Op::TableGrow,
+        Op::TableSize,
+        Op::GlobalGet,
+        Op::GlobalSet,
+        Op::I64Const,
+        Op::F32Const,
+        Op::F64Const,
+        Op::CallIndirect,
+        Op::I32TruncF32S,
+        Op::I32TruncF32U,
+        Op::I32TruncF64S,
+        Op::I32TruncF64U,
+        Op::I64TruncF32S,
+        Op::I64TruncF32U,
+        Op::I64TruncF64S,
+        Op::I64TruncF64U,
+    ];
+
+    /// Name of the instruction in the text format.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Op::Unreachable => "unreachable",
+            Op::Nop => "nop",
+            Op::Return => "return",
+            Op::Drop => "drop",
+            Op::LocalGet => "local.get",
+            Op::LocalSet => "local.set",
+            Op::I32Load => "i32.load",
+            Op::I32Store => "i32.store",
+            Op::MemorySize => "memory.size",
+            Op::MemoryGrow => "memory.grow",
+            Op::I32Const => "i32.const",
+            Op::I32Eqa => "i32.eqz",
+            Op::I32LtS => "i32.lt_s",
+            Op::I32GeS => "i32.ge_s",
+            Op::I32Add => "i32.add",
+            Op::I32Sub => "i32.sub",
+            Op::I32Mul => "i32.mul",
+            Op::I32DivS => "i32.div_s",
+            Op::I32DivU => "i32.div_u",
+            Op::I32Rems => "i32.rem_s",
+            Op::I32RemU => "i32.rem_u",
+            Op::If => "if",
+            Op::Else => "else",
+            Op::Block => "block",
+            Op::Loop => "loop",
+            Op::Br => "br",
+            Op::BrIf => "br_if",
+            Op::Call => "call",
+            Op::End => "end",
+            Op::RefNull => "ref.null",
+            Op::TableGrow => "table.grow",
+            Op::TableSize => "table.size",
+            Op::GlobalGet => "global.get",
+            Op::GlobalSet => "global.set",
+            Op::I64Const => "i64.const",
+            Op::F32Const => "f32.const",
+            Op::F64Const => "f64.const",
+            Op::CallIndirect => "call_indirect",
+            Op::I32TruncF32S => "i32.trunc_f32_s",
+            Op::I32TruncF32U => "i32.trunc_f32_u",
+            Op::I32TruncF64S => "i32.trunc_f64_s",
+            Op::I32TruncF64U => "i32.trunc_f64_u",
+            Op::I64TruncF32S => "i64.trunc_f32_s",
+            Op::I64TruncF32U => "i64.trunc_f32_u",
+            Op::I64TruncF64S => "i64.trunc_f64_s",
+            Op::I64TruncF64U => "i64.trunc_f64_u",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Op> {
+        Op::ALL.iter().copied().find(|op| op.name() == name)
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        let code = *self as u32;
+        if code > 0xff {
+            writer.write_byte(PREFIX_FC);
+            writer.write_u32(code & 0xff);
+        } else {
+            writer.write_byte(code as u8);
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub enum IntrinsicNode {
+    UnreachableIntrinsicNode(UnreachableIntrinsicNode),
+    NopIntrinsicNode(NopIntrinsicNode),
+    ReturnIntrinsicNode(ReturnIntrinsicNode),
+    DropIntrinsicNode(DropIntrinsicNode),
     LocalGetIntrinsicNode(LocalGetIntrinsicNode),
     LocalSetIntrinsicNode(LocalSetIntrinsicNode),
+    I32LoadIntrinsicNode(I32LoadIntrinsicNode),
+    I32StoreIntrinsicNode(I32StoreIntrinsicNode),
+    MemorySizeIntrinsicNode(MemorySizeIntrinsicNode),
+    MemoryGrowIntrinsicNode(MemoryGrowIntrinsicNode),
     I32ConstIntrinsicNode(I32ConstIntrinsicNode),
     I32EqaIntrinsicNode(I32EqaIntrinsicNode),
     I32LtSIntrinsicNode(I32LtSIntrinsicNode),
     I32GeSIntrinsicNode(I32GeSIntrinsicNode),
     I32AddIntrinsicNode(I32AddIntrinsicNode),
+    I32SubIntrinsicNode(I32SubIntrinsicNode),
+    I32MulIntrinsicNode(I32MulIntrinsicNode),
+    I32DivSIntrinsicNode(I32DivSIntrinsicNode),
+    I32DivUIntrinsicNode(I32DivUIntrinsicNode),
     I32RemsIntrinsicNode(I32RemsIntrinsicNode),
+    I32RemUIntrinsicNode(I32RemUIntrinsicNode),
     IfIntrinsicNode(IfIntrinsicNode),
     BlockIntrinsicNode(BlockIntrinsicNode),
     LoopIntrinsicNode(LoopIntrinsicNode),
     BrIntrinsicNode(BrIntrinsicNode),
     BrIfIntrinsicNode(BrIfIntrinsicNode),
     CallIntrinsicNode(CallIntrinsicNode),
+    RefNullIntrinsicNode(RefNullIntrinsicNode),
+    TableGrowIntrinsicNode(TableGrowIntrinsicNode),
+    TableSizeIntrinsicNode(TableSizeIntrinsicNode),
+    GlobalGetIntrinsicNode(GlobalGetIntrinsicNode),
+    GlobalSetIntrinsicNode(GlobalSetIntrinsicNode),
+    I64ConstIntrinsicNode(I64ConstIntrinsicNode),
+    F32ConstIntrinsicNode(F32ConstIntrinsicNode),
+    F64ConstIntrinsicNode(F64ConstIntrinsicNode),
+    CallIndirectIntrinsicNode(CallIndirectIntrinsicNode),
+    I32TruncF32SIntrinsicNode(I32TruncF32SIntrinsicNode),
+    I32TruncF32UIntrinsicNode(I32TruncF32UIntrinsicNode),
+    I32TruncF64SIntrinsicNode(I32TruncF64SIntrinsicNode),
+    I32TruncF64UIntrinsicNode(I32TruncF64UIntrinsicNode),
+    I64TruncF32SIntrinsicNode(I64TruncF32SIntrinsicNode),
+    I64TruncF32UIntrinsicNode(I64TruncF32UIntrinsicNode),
+    I64TruncF64SIntrinsicNode(I64TruncF64SIntrinsicNode),
+    I64TruncF64UIntrinsicNode(I64TruncF64UIntrinsicNode),
 }
 impl IntrinsicNode {
     pub fn new(opcode: Op) -> IntrinsicNode {
         match opcode {
+            Op::Unreachable => {
+                IntrinsicNode::UnreachableIntrinsicNode(UnreachableIntrinsicNode::new())
+            }
+            Op::Nop => IntrinsicNode::NopIntrinsicNode(NopIntrinsicNode::new()),
+            Op::Return => IntrinsicNode::ReturnIntrinsicNode(ReturnIntrinsicNode::new()),
+            Op::Drop => IntrinsicNode::DropIntrinsicNode(DropIntrinsicNode::new()),
             Op::I32Const => IntrinsicNode::I32ConstIntrinsicNode(I32ConstIntrinsicNode::new()),
             Op::LocalGet => IntrinsicNode::LocalGetIntrinsicNode(LocalGetIntrinsicNode::new()),
             Op::LocalSet => IntrinsicNode::LocalSetIntrinsicNode(LocalSetIntrinsicNode::new()),
+            Op::I32Load => IntrinsicNode::I32LoadIntrinsicNode(I32LoadIntrinsicNode::new()),
+            Op::I32Store => IntrinsicNode::I32StoreIntrinsicNode(I32StoreIntrinsicNode::new()),
+            Op::MemorySize => {
+                IntrinsicNode::MemorySizeIntrinsicNode(MemorySizeIntrinsicNode::new())
+            }
+            Op::MemoryGrow => {
+                IntrinsicNode::MemoryGrowIntrinsicNode(MemoryGrowIntrinsicNode::new())
+            }
             Op::I32Eqa => IntrinsicNode::I32EqaIntrinsicNode(I32EqaIntrinsicNode::new()),
             Op::I32LtS => IntrinsicNode::I32LtSIntrinsicNode(I32LtSIntrinsicNode::new()),
             Op::I32GeS => IntrinsicNode::I32GeSIntrinsicNode(I32GeSIntrinsicNode::new()),
             Op::I32Add => IntrinsicNode::I32AddIntrinsicNode(I32AddIntrinsicNode::new()),
+            Op::I32Sub => IntrinsicNode::I32SubIntrinsicNode(I32SubIntrinsicNode::new()),
+            Op::I32Mul => IntrinsicNode::I32MulIntrinsicNode(I32MulIntrinsicNode::new()),
+            Op::I32DivS => IntrinsicNode::I32DivSIntrinsicNode(I32DivSIntrinsicNode::new()),
+            Op::I32DivU => IntrinsicNode::I32DivUIntrinsicNode(I32DivUIntrinsicNode::new()),
             Op::I32Rems => IntrinsicNode::I32RemsIntrinsicNode(I32RemsIntrinsicNode::new()),
+            Op::I32RemU => IntrinsicNode::I32RemUIntrinsicNode(I32RemUIntrinsicNode::new()),
             Op::If => IntrinsicNode::IfIntrinsicNode(IfIntrinsicNode::new()),
             Op::Block => IntrinsicNode::BlockIntrinsicNode(BlockIntrinsicNode::new()),
             Op::Loop => IntrinsicNode::LoopIntrinsicNode(LoopIntrinsicNode::new()),
             Op::Br => IntrinsicNode::BrIntrinsicNode(BrIntrinsicNode::new()),
             Op::BrIf => IntrinsicNode::BrIfIntrinsicNode(BrIfIntrinsicNode::new()),
             Op::Call => IntrinsicNode::CallIntrinsicNode(CallIntrinsicNode::new()),
+            Op::RefNull => IntrinsicNode::RefNullIntrinsicNode(RefNullIntrinsicNode::new()),
+            Op::TableGrow => IntrinsicNode::TableGrowIntrinsicNode(TableGrowIntrinsicNode::new()),
+            Op::TableSize => IntrinsicNode::TableSizeIntrinsicNode(TableSizeIntrinsicNode::new()),
+            Op::GlobalGet => IntrinsicNode::GlobalGetIntrinsicNode(GlobalGetIntrinsicNode::new()),
+            Op::GlobalSet => IntrinsicNode::GlobalSetIntrinsicNode(GlobalSetIntrinsicNode::new()),
+            Op::I64Const => IntrinsicNode::I64ConstIntrinsicNode(I64ConstIntrinsicNode::new()),
+            Op::F32Const => IntrinsicNode::F32ConstIntrinsicNode(F32ConstIntrinsicNode::new()),
+            Op::F64Const => IntrinsicNode::F64ConstIntrinsicNode(F64ConstIntrinsicNode::new()),
+            Op::CallIndirect => {
+                IntrinsicNode::CallIndirectIntrinsicNode(CallIndirectIntrinsicNode::new())
+            }
+            Op::I32TruncF32S => {
+                IntrinsicNode::I32TruncF32SIntrinsicNode(I32TruncF32SIntrinsicNode::new())
+            }
+            Op::I32TruncF32U => {
+                IntrinsicNode::I32TruncF32UIntrinsicNode(I32TruncF32UIntrinsicNode::new())
+            }
+            Op::I32TruncF64S => {
+                IntrinsicNode::I32TruncF64SIntrinsicNode(I32TruncF64SIntrinsicNode::new())
+            }
+            Op::I32TruncF64U => {
+                IntrinsicNode::I32TruncF64UIntrinsicNode(I32TruncF64UIntrinsicNode::new())
+            }
+            Op::I64TruncF32S => {
+                IntrinsicNode::I64TruncF32SIntrinsicNode(I64TruncF32SIntrinsicNode::new())
+            }
+            Op::I64TruncF32U => {
+                IntrinsicNode::I64TruncF32UIntrinsicNode(I64TruncF32UIntrinsicNode::new())
+            }
+            Op::I64TruncF64S => {
+                IntrinsicNode::I64TruncF64SIntrinsicNode(I64TruncF64SIntrinsicNode::new())
+            }
+            Op::I64TruncF64U => {
+                IntrinsicNode::I64TruncF64UIntrinsicNode(I64TruncF64UIntrinsicNode::new())
+            }
             _ => panic!("Invalid opcode"), // TODO
         }
     }
 
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
         match self {
+            IntrinsicNode::UnreachableIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::NopIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::ReturnIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::DropIntrinsicNode(_) => Ok(()),
             IntrinsicNode::I32ConstIntrinsicNode(i) => i.load(buf),
             IntrinsicNode::LocalGetIntrinsicNode(l) => l.load(buf),
             IntrinsicNode::LocalSetIntrinsicNode(l) => l.load(buf),
+            IntrinsicNode::I32LoadIntrinsicNode(i) => i.load(buf),
+            IntrinsicNode::I32StoreIntrinsicNode(i) => i.load(buf),
+            IntrinsicNode::MemorySizeIntrinsicNode(m) => m.load(buf),
+            IntrinsicNode::MemoryGrowIntrinsicNode(m) => m.load(buf),
             IntrinsicNode::I32EqaIntrinsicNode(_) => Ok(()),
             IntrinsicNode::I32LtSIntrinsicNode(_) => Ok(()),
             IntrinsicNode::I32GeSIntrinsicNode(_) => Ok(()),
             IntrinsicNode::I32AddIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::I32SubIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::I32MulIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::I32DivSIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::I32DivUIntrinsicNode(_) => Ok(()),
             IntrinsicNode::I32RemsIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::I32RemUIntrinsicNode(_) => Ok(()),
             IntrinsicNode::IfIntrinsicNode(i) => i.load(buf),
             IntrinsicNode::BlockIntrinsicNode(b) => b.load(buf),
             IntrinsicNode::LoopIntrinsicNode(l) => l.load(buf),
             IntrinsicNode::BrIntrinsicNode(b) => b.load(buf),
             IntrinsicNode::BrIfIntrinsicNode(b) => b.load(buf),
             IntrinsicNode::CallIntrinsicNode(c) => c.load(buf),
+            IntrinsicNode::RefNullIntrinsicNode(r) => r.load(buf),
+            IntrinsicNode::TableGrowIntrinsicNode(t) => t.load(buf),
+            IntrinsicNode::TableSizeIntrinsicNode(t) => t.load(buf),
+            IntrinsicNode::GlobalGetIntrinsicNode(i) => i.load(buf),
+            IntrinsicNode::GlobalSetIntrinsicNode(i) => i.load(buf),
+            IntrinsicNode::I64ConstIntrinsicNode(i) => i.load(buf),
+            IntrinsicNode::F32ConstIntrinsicNode(i) => i.load(buf),
+            IntrinsicNode::F64ConstIntrinsicNode(i) => i.load(buf),
+            IntrinsicNode::CallIndirectIntrinsicNode(c) => c.load(buf),
+            IntrinsicNode::I32TruncF32SIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::I32TruncF32UIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::I32TruncF64SIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::I32TruncF64UIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::I64TruncF32SIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::I64TruncF32UIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::I64TruncF64SIntrinsicNode(_) => Ok(()),
+            IntrinsicNode::I64TruncF64UIntrinsicNode(_) => Ok(()),
+        }
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        self.opcode().write(writer);
+        match self {
+            IntrinsicNode::I32ConstIntrinsicNode(i) => i.write(writer),
+            IntrinsicNode::LocalGetIntrinsicNode(l) => l.write(writer),
+            IntrinsicNode::LocalSetIntrinsicNode(l) => l.write(writer),
+            IntrinsicNode::I32LoadIntrinsicNode(i) => i.write(writer),
+            IntrinsicNode::I32StoreIntrinsicNode(i) => i.write(writer),
+            IntrinsicNode::MemorySizeIntrinsicNode(m) => m.write(writer),
+            IntrinsicNode::MemoryGrowIntrinsicNode(m) => m.write(writer),
+            IntrinsicNode::IfIntrinsicNode(i) => i.write(writer),
+            IntrinsicNode::BlockIntrinsicNode(b) => b.write(writer),
+            IntrinsicNode::LoopIntrinsicNode(l) => l.write(writer),
+            IntrinsicNode::BrIntrinsicNode(b) => b.write(writer),
+            IntrinsicNode::BrIfIntrinsicNode(b) => b.write(writer),
+            IntrinsicNode::CallIntrinsicNode(c) => c.write(writer),
+            IntrinsicNode::RefNullIntrinsicNode(r) => r.write(writer),
+            IntrinsicNode::TableGrowIntrinsicNode(t) => t.write(writer),
+            IntrinsicNode::TableSizeIntrinsicNode(t) => t.write(writer),
+            IntrinsicNode::GlobalGetIntrinsicNode(i) => i.write(writer),
+            IntrinsicNode::GlobalSetIntrinsicNode(i) => i.write(writer),
+            IntrinsicNode::I64ConstIntrinsicNode(i) => i.write(writer),
+            IntrinsicNode::F32ConstIntrinsicNode(i) => i.write(writer),
+            IntrinsicNode::F64ConstIntrinsicNode(i) => i.write(writer),
+            IntrinsicNode::CallIndirectIntrinsicNode(c) => c.write(writer),
+            _ => {}
         }
     }
+
+    pub fn opcode(&self) -> Op {
+        match self {
+            IntrinsicNode::UnreachableIntrinsicNode(_) => Op::Unreachable,
+            IntrinsicNode::NopIntrinsicNode(_) => Op::Nop,
+            IntrinsicNode::ReturnIntrinsicNode(_) => Op::Return,
+            IntrinsicNode::DropIntrinsicNode(_) => Op::Drop,
+            IntrinsicNode::I32ConstIntrinsicNode(_) => Op::I32Const,
+            IntrinsicNode::LocalGetIntrinsicNode(_) => Op::LocalGet,
+            IntrinsicNode::LocalSetIntrinsicNode(_) => Op::LocalSet,
+            IntrinsicNode::I32LoadIntrinsicNode(_) => Op::I32Load,
+            IntrinsicNode::I32StoreIntrinsicNode(_) => Op::I32Store,
+            IntrinsicNode::MemorySizeIntrinsicNode(_) => Op::MemorySize,
+            IntrinsicNode::MemoryGrowIntrinsicNode(_) => Op::MemoryGrow,
+            IntrinsicNode::I32EqaIntrinsicNode(_) => Op::I32Eqa,
+            IntrinsicNode::I32LtSIntrinsicNode(_) => Op::I32LtS,
+            IntrinsicNode::I32GeSIntrinsicNode(_) => Op::I32GeS,
+            IntrinsicNode::I32AddIntrinsicNode(_) => Op::I32Add,
+            IntrinsicNode::I32SubIntrinsicNode(_) => Op::I32Sub,
+            IntrinsicNode::I32MulIntrinsicNode(_) => Op::I32Mul,
+            IntrinsicNode::I32DivSIntrinsicNode(_) => Op::I32DivS,
+            IntrinsicNode::I32DivUIntrinsicNode(_) => Op::I32DivU,
+            IntrinsicNode::I32RemsIntrinsicNode(_) => Op::I32Rems,
+            IntrinsicNode::I32RemUIntrinsicNode(_) => Op::I32RemU,
+            IntrinsicNode::IfIntrinsicNode(_) => Op::If,
+            IntrinsicNode::BlockIntrinsicNode(_) => Op::Block,
+            IntrinsicNode::LoopIntrinsicNode(_) => Op::Loop,
+            IntrinsicNode::BrIntrinsicNode(_) => Op::Br,
+            IntrinsicNode::BrIfIntrinsicNode(_) => Op::BrIf,
+            IntrinsicNode::CallIntrinsicNode(_) => Op::Call,
+            IntrinsicNode::RefNullIntrinsicNode(_) => Op::RefNull,
+            IntrinsicNode::TableGrowIntrinsicNode(_) => Op::TableGrow,
+            IntrinsicNode::TableSizeIntrinsicNode(_) => Op::TableSize,
+            IntrinsicNode::GlobalGetIntrinsicNode(_) => Op::GlobalGet,
+            IntrinsicNode::GlobalSetIntrinsicNode(_) => Op::GlobalSet,
+            IntrinsicNode::I64ConstIntrinsicNode(_) => Op::I64Const,
+            IntrinsicNode::F32ConstIntrinsicNode(_) => Op::F32Const,
+            IntrinsicNode::F64ConstIntrinsicNode(_) => Op::F64Const,
+            IntrinsicNode::CallIndirectIntrinsicNode(_) => Op::CallIndirect,
+            IntrinsicNode::I32TruncF32SIntrinsicNode(_) => Op::I32TruncF32S,
+            IntrinsicNode::I32TruncF32UIntrinsicNode(_) => Op::I32TruncF32U,
+            IntrinsicNode::I32TruncF64SIntrinsicNode(_) => Op::I32TruncF64S,
+            IntrinsicNode::I32TruncF64UIntrinsicNode(_) => Op::I32TruncF64U,
+            IntrinsicNode::I64TruncF32SIntrinsicNode(_) => Op::I64TruncF32S,
+            IntrinsicNode::I64TruncF32UIntrinsicNode(_) => Op::I64TruncF32U,
+            IntrinsicNode::I64TruncF64SIntrinsicNode(_) => Op::I64TruncF64S,
+            IntrinsicNode::I64TruncF64UIntrinsicNode(_) => Op::I64TruncF64U,
+        }
+    }
+
+    /// Bytes of the intrinsic with its immediates, up to the `end` of nested blocks.
+    /// None unless it was decoded.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            IntrinsicNode::UnreachableIntrinsicNode(n) => n.span,
+            IntrinsicNode::NopIntrinsicNode(n) => n.span,
+            IntrinsicNode::ReturnIntrinsicNode(n) => n.span,
+            IntrinsicNode::DropIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32ConstIntrinsicNode(n) => n.span,
+            IntrinsicNode::LocalGetIntrinsicNode(n) => n.span,
+            IntrinsicNode::LocalSetIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32LoadIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32StoreIntrinsicNode(n) => n.span,
+            IntrinsicNode::MemorySizeIntrinsicNode(n) => n.span,
+            IntrinsicNode::MemoryGrowIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32EqaIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32LtSIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32GeSIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32AddIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32SubIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32MulIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32DivSIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32DivUIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32RemsIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32RemUIntrinsicNode(n) => n.span,
+            IntrinsicNode::IfIntrinsicNode(n) => n.span,
+            IntrinsicNode::BlockIntrinsicNode(n) => n.span,
+            IntrinsicNode::LoopIntrinsicNode(n) => n.span,
+            IntrinsicNode::BrIntrinsicNode(n) => n.span,
+            IntrinsicNode::BrIfIntrinsicNode(n) => n.span,
+            IntrinsicNode::CallIntrinsicNode(n) => n.span,
+            IntrinsicNode::RefNullIntrinsicNode(n) => n.span,
+            IntrinsicNode::TableGrowIntrinsicNode(n) => n.span,
+            IntrinsicNode::TableSizeIntrinsicNode(n) => n.span,
+            IntrinsicNode::GlobalGetIntrinsicNode(n) => n.span,
+            IntrinsicNode::GlobalSetIntrinsicNode(n) => n.span,
+            IntrinsicNode::I64ConstIntrinsicNode(n) => n.span,
+            IntrinsicNode::F32ConstIntrinsicNode(n) => n.span,
+            IntrinsicNode::F64ConstIntrinsicNode(n) => n.span,
+            IntrinsicNode::CallIndirectIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32TruncF32SIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32TruncF32UIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32TruncF64SIntrinsicNode(n) => n.span,
+            IntrinsicNode::I32TruncF64UIntrinsicNode(n) => n.span,
+            IntrinsicNode::I64TruncF32SIntrinsicNode(n) => n.span,
+            IntrinsicNode::I64TruncF32UIntrinsicNode(n) => n.span,
+            IntrinsicNode::I64TruncF64SIntrinsicNode(n) => n.span,
+            IntrinsicNode::I64TruncF64UIntrinsicNode(n) => n.span,
+        }
+    }
+
+    pub fn set_span(&mut self, span: Span) {
+        match self {
+            IntrinsicNode::UnreachableIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::NopIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::ReturnIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::DropIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32ConstIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::LocalGetIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::LocalSetIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32LoadIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32StoreIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::MemorySizeIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::MemoryGrowIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32EqaIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32LtSIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32GeSIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32AddIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32SubIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32MulIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32DivSIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32DivUIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32RemsIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32RemUIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::IfIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::BlockIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::LoopIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::BrIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::BrIfIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::CallIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::RefNullIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::TableGrowIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::TableSizeIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::GlobalGetIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::GlobalSetIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I64ConstIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::F32ConstIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::F64ConstIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::CallIndirectIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32TruncF32SIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32TruncF32UIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32TruncF64SIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I32TruncF64UIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I64TruncF32SIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I64TruncF32UIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I64TruncF64SIntrinsicNode(n) => n.span = Some(span),
+            IntrinsicNode::I64TruncF64UIntrinsicNode(n) => n.span = Some(span),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UnreachableIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for UnreachableIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl UnreachableIntrinsicNode {
+    pub fn new() -> UnreachableIntrinsicNode {
+        UnreachableIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NopIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for NopIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl NopIntrinsicNode {
+    pub fn new() -> NopIntrinsicNode {
+        NopIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReturnIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for ReturnIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl ReturnIntrinsicNode {
+    pub fn new() -> ReturnIntrinsicNode {
+        ReturnIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DropIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for DropIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl DropIntrinsicNode {
+    pub fn new() -> DropIntrinsicNode {
+        DropIntrinsicNode { span: None }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct I32ConstIntrinsicNode {
-    val: i32,
+    pub val: i32,
+    pub span: Option<Span>,
 }
 impl Default for I32ConstIntrinsicNode {
     fn default() -> Self {
@@ -262,18 +895,104 @@ impl Default for I32ConstIntrinsicNode {
 }
 impl I32ConstIntrinsicNode {
     pub fn new() -> I32ConstIntrinsicNode {
-        I32ConstIntrinsicNode { val: 0 }
+        I32ConstIntrinsicNode { val: 0, span: None }
     }
 
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
         self.val = buf.read_i32()?;
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_i32(self.val);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I64ConstIntrinsicNode {
+    pub val: i64,
+    pub span: Option<Span>,
+}
+impl Default for I64ConstIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I64ConstIntrinsicNode {
+    pub fn new() -> I64ConstIntrinsicNode {
+        I64ConstIntrinsicNode { val: 0, span: None }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.val = buf.read_i64()?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_i64(self.val);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct F32ConstIntrinsicNode {
+    pub val: f32,
+    pub span: Option<Span>,
+}
+impl Default for F32ConstIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl F32ConstIntrinsicNode {
+    pub fn new() -> F32ConstIntrinsicNode {
+        F32ConstIntrinsicNode {
+            val: 0.0,
+            span: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.val = buf.read_f32()?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_f32(self.val);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct F64ConstIntrinsicNode {
+    pub val: f64,
+    pub span: Option<Span>,
+}
+impl Default for F64ConstIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl F64ConstIntrinsicNode {
+    pub fn new() -> F64ConstIntrinsicNode {
+        F64ConstIntrinsicNode {
+            val: 0.0,
+            span: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.val = buf.read_f64()?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_f64(self.val);
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct LocalGetIntrinsicNode {
-    local_idx: u32,
+    pub local_idx: u32,
+    pub span: Option<Span>,
 }
 impl Default for LocalGetIntrinsicNode {
     fn default() -> Self {
@@ -282,18 +1001,26 @@ impl Default for LocalGetIntrinsicNode {
 }
 impl LocalGetIntrinsicNode {
     pub fn new() -> LocalGetIntrinsicNode {
-        LocalGetIntrinsicNode { local_idx: 0 }
+        LocalGetIntrinsicNode {
+            local_idx: 0,
+            span: None,
+        }
     }
 
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
         self.local_idx = buf.read_u32()?;
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_u32(self.local_idx);
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct LocalSetIntrinsicNode {
-    local_idx: u32,
+    pub local_idx: u32,
+    pub span: Option<Span>,
 }
 impl Default for LocalSetIntrinsicNode {
     fn default() -> Self {
@@ -302,17 +1029,227 @@ impl Default for LocalSetIntrinsicNode {
 }
 impl LocalSetIntrinsicNode {
     pub fn new() -> LocalSetIntrinsicNode {
-        LocalSetIntrinsicNode { local_idx: 0 }
+        LocalSetIntrinsicNode {
+            local_idx: 0,
+            span: None,
+        }
     }
 
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
         self.local_idx = buf.read_u32()?;
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_u32(self.local_idx);
+    }
+}
+
+pub type GlobalIdx = u32;
+
+#[derive(Debug, Clone)]
+pub struct GlobalGetIntrinsicNode {
+    pub global_idx: GlobalIdx,
+    pub span: Option<Span>,
+}
+impl Default for GlobalGetIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl GlobalGetIntrinsicNode {
+    pub fn new() -> GlobalGetIntrinsicNode {
+        GlobalGetIntrinsicNode {
+            global_idx: 0,
+            span: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.global_idx = buf.read_u32()?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_u32(self.global_idx);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GlobalSetIntrinsicNode {
+    pub global_idx: GlobalIdx,
+    pub span: Option<Span>,
+}
+impl Default for GlobalSetIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl GlobalSetIntrinsicNode {
+    pub fn new() -> GlobalSetIntrinsicNode {
+        GlobalSetIntrinsicNode {
+            global_idx: 0,
+            span: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.global_idx = buf.read_u32()?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_u32(self.global_idx);
+    }
+}
+
+/// https://webassembly.github.io/spec/core/binary/instructions.html#memory-instructions
+#[derive(Debug, Clone)]
+pub struct MemArgNode {
+    pub align: u32,
+    pub offset: u32,
+}
+impl Default for MemArgNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MemArgNode {
+    pub fn new() -> MemArgNode {
+        MemArgNode {
+            align: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.align = buf.read_u32()?;
+        self.offset = buf.read_u32()?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_u32(self.align);
+        writer.write_u32(self.offset);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I32LoadIntrinsicNode {
+    pub mem_arg: MemArgNode,
+    pub span: Option<Span>,
+}
+impl Default for I32LoadIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I32LoadIntrinsicNode {
+    pub fn new() -> I32LoadIntrinsicNode {
+        I32LoadIntrinsicNode {
+            mem_arg: MemArgNode::new(),
+            span: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.mem_arg.load(buf)
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        self.mem_arg.write(writer);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I32StoreIntrinsicNode {
+    pub mem_arg: MemArgNode,
+    pub span: Option<Span>,
+}
+impl Default for I32StoreIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I32StoreIntrinsicNode {
+    pub fn new() -> I32StoreIntrinsicNode {
+        I32StoreIntrinsicNode {
+            mem_arg: MemArgNode::new(),
+            span: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.mem_arg.load(buf)
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        self.mem_arg.write(writer);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MemorySizeIntrinsicNode {
+    pub mem_idx: u32,
+    pub span: Option<Span>,
+}
+impl Default for MemorySizeIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MemorySizeIntrinsicNode {
+    pub fn new() -> MemorySizeIntrinsicNode {
+        MemorySizeIntrinsicNode {
+            mem_idx: 0,
+            span: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        // Only memory 0 exists until multi-memory, encoded as a single byte.
+        self.mem_idx = buf.read_byte()? as u32;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_byte(self.mem_idx as u8);
+    }
 }
 
 #[derive(Debug, Clone)]
-pub struct I32EqaIntrinsicNode {}
+pub struct MemoryGrowIntrinsicNode {
+    pub mem_idx: u32,
+    pub span: Option<Span>,
+}
+impl Default for MemoryGrowIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MemoryGrowIntrinsicNode {
+    pub fn new() -> MemoryGrowIntrinsicNode {
+        MemoryGrowIntrinsicNode {
+            mem_idx: 0,
+            span: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        // Only memory 0 exists until multi-memory, encoded as a single byte.
+        self.mem_idx = buf.read_byte()? as u32;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_byte(self.mem_idx as u8);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I32EqaIntrinsicNode {
+    pub span: Option<Span>,
+}
 impl Default for I32EqaIntrinsicNode {
     fn default() -> Self {
         Self::new()
@@ -320,12 +1257,14 @@ impl Default for I32EqaIntrinsicNode {
 }
 impl I32EqaIntrinsicNode {
     pub fn new() -> I32EqaIntrinsicNode {
-        I32EqaIntrinsicNode {}
+        I32EqaIntrinsicNode { span: None }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct I32LtSIntrinsicNode {}
+pub struct I32LtSIntrinsicNode {
+    pub span: Option<Span>,
+}
 impl Default for I32LtSIntrinsicNode {
     fn default() -> Self {
         Self::new()
@@ -333,12 +1272,14 @@ impl Default for I32LtSIntrinsicNode {
 }
 impl I32LtSIntrinsicNode {
     pub fn new() -> I32LtSIntrinsicNode {
-        I32LtSIntrinsicNode {}
+        I32LtSIntrinsicNode { span: None }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct I32GeSIntrinsicNode {}
+pub struct I32GeSIntrinsicNode {
+    pub span: Option<Span>,
+}
 impl Default for I32GeSIntrinsicNode {
     fn default() -> Self {
         Self::new()
@@ -346,12 +1287,14 @@ impl Default for I32GeSIntrinsicNode {
 }
 impl I32GeSIntrinsicNode {
     pub fn new() -> I32GeSIntrinsicNode {
-        I32GeSIntrinsicNode {}
+        I32GeSIntrinsicNode { span: None }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct I32AddIntrinsicNode {}
+pub struct I32AddIntrinsicNode {
+    pub span: Option<Span>,
+}
 impl Default for I32AddIntrinsicNode {
     fn default() -> Self {
         Self::new()
@@ -359,12 +1302,14 @@ impl Default for I32AddIntrinsicNode {
 }
 impl I32AddIntrinsicNode {
     pub fn new() -> I32AddIntrinsicNode {
-        I32AddIntrinsicNode {}
+        I32AddIntrinsicNode { span: None }
     }
 }
 
 #[derive(Debug, Clone)]
-pub struct I32RemsIntrinsicNode {}
+pub struct I32RemsIntrinsicNode {
+    pub span: Option<Span>,
+}
 impl Default for I32RemsIntrinsicNode {
     fn default() -> Self {
         Self::new()
@@ -372,7 +1317,82 @@ impl Default for I32RemsIntrinsicNode {
 }
 impl I32RemsIntrinsicNode {
     pub fn new() -> I32RemsIntrinsicNode {
-        I32RemsIntrinsicNode {}
+        I32RemsIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I32SubIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for I32SubIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I32SubIntrinsicNode {
+    pub fn new() -> I32SubIntrinsicNode {
+        I32SubIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I32MulIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for I32MulIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I32MulIntrinsicNode {
+    pub fn new() -> I32MulIntrinsicNode {
+        I32MulIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I32DivSIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for I32DivSIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I32DivSIntrinsicNode {
+    pub fn new() -> I32DivSIntrinsicNode {
+        I32DivSIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I32DivUIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for I32DivUIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I32DivUIntrinsicNode {
+    pub fn new() -> I32DivUIntrinsicNode {
+        I32DivUIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I32RemUIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for I32RemUIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I32RemUIntrinsicNode {
+    pub fn new() -> I32RemUIntrinsicNode {
+        I32RemUIntrinsicNode { span: None }
     }
 }
 
@@ -396,13 +1416,38 @@ impl BlockType {
             }
         }
     }
+
+    /// Decode a block type, encoded as a signed 33-bit integer: negative for the
+    /// one-byte encodings, a type index otherwise.
+    pub fn load(buf: &mut Buffer) -> Result<BlockType> {
+        let value = buf.read_i64()?;
+        if value >= 0 {
+            let type_idx =
+                S33::try_from(value).map_err(|_| anyhow!("Invalid block type: {}", value))?;
+            return Ok(BlockType::S33(type_idx));
+        }
+        if value < -0x40 {
+            return Err(anyhow!("Invalid block type: {}", value));
+        }
+        let byte = (value & 0x7f) as u8;
+        BlockType::from_u8(byte).ok_or_else(|| anyhow!("Invalid block type: {}", byte))
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        match self {
+            BlockType::Empty => writer.write_byte(0x40),
+            BlockType::ValType(val_type) => writer.write_byte(val_type.to_u8()),
+            BlockType::S33(type_idx) => writer.write_i64(*type_idx as i64),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct IfIntrinsicNode {
-    block_type: BlockType,
-    then_expr: ExprNode,
-    else_expr: ExprNode,
+    pub block_type: BlockType,
+    pub then_expr: ExprNode,
+    pub else_expr: ExprNode,
+    pub span: Option<Span>,
 }
 impl Default for IfIntrinsicNode {
     fn default() -> Self {
@@ -415,16 +1460,15 @@ impl IfIntrinsicNode {
             block_type: BlockType::Empty,
             then_expr: ExprNode::new(),
             else_expr: ExprNode::new(),
+            span: None,
         }
     }
 
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
-        let byte = buf.read_byte()?;
-        self.block_type =
-            BlockType::from_u8(byte).ok_or(anyhow!("Invalid block type: {}", byte))?;
+        self.block_type = BlockType::load(buf)?;
 
         self.then_expr = ExprNode::new();
-        self.then_expr.load(buf)?;
+        self.then_expr.load_until(buf, true)?;
 
         if self.then_expr.end_op == Some(Op::Else) {
             self.else_expr = ExprNode::new();
@@ -433,12 +1477,21 @@ impl IfIntrinsicNode {
 
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        self.block_type.write(writer);
+        self.then_expr.write(writer);
+        if self.then_expr.end_op == Some(Op::Else) {
+            self.else_expr.write(writer);
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct BlockIntrinsicNode {
-    block_type: BlockType,
-    expr: ExprNode,
+    pub block_type: BlockType,
+    pub expr: ExprNode,
+    pub span: Option<Span>,
 }
 impl Default for BlockIntrinsicNode {
     fn default() -> Self {
@@ -450,25 +1503,30 @@ impl BlockIntrinsicNode {
         BlockIntrinsicNode {
             block_type: BlockType::Empty,
             expr: ExprNode::new(),
+            span: None,
         }
     }
 
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
-        let byte = buf.read_byte()?;
-        self.block_type =
-            BlockType::from_u8(byte).ok_or(anyhow!("Invalid block type: {}", byte))?;
+        self.block_type = BlockType::load(buf)?;
 
         self.expr = ExprNode::new();
         self.expr.load(buf)?;
 
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        self.block_type.write(writer);
+        self.expr.write(writer);
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct LoopIntrinsicNode {
-    block_type: BlockType,
-    expr: ExprNode,
+    pub block_type: BlockType,
+    pub expr: ExprNode,
+    pub span: Option<Span>,
 }
 impl Default for LoopIntrinsicNode {
     fn default() -> Self {
@@ -480,26 +1538,31 @@ impl LoopIntrinsicNode {
         LoopIntrinsicNode {
             block_type: BlockType::Empty,
             expr: ExprNode::new(),
+            span: None,
         }
     }
 
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
-        let byte = buf.read_byte()?;
-        self.block_type =
-            BlockType::from_u8(byte).ok_or(anyhow!("Invalid block type: {}", byte))?;
+        self.block_type = BlockType::load(buf)?;
 
         self.expr = ExprNode::new();
         self.expr.load(buf)?;
 
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        self.block_type.write(writer);
+        self.expr.write(writer);
+    }
 }
 
 type LabelIdx = u32;
 
 #[derive(Debug, Clone)]
 pub struct BrIntrinsicNode {
-    label_idx: LabelIdx,
+    pub label_idx: LabelIdx,
+    pub span: Option<Span>,
 }
 impl Default for BrIntrinsicNode {
     fn default() -> Self {
@@ -508,18 +1571,26 @@ impl Default for BrIntrinsicNode {
 }
 impl BrIntrinsicNode {
     pub fn new() -> BrIntrinsicNode {
-        BrIntrinsicNode { label_idx: 0 }
+        BrIntrinsicNode {
+            label_idx: 0,
+            span: None,
+        }
     }
 
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
         self.label_idx = buf.read_u32()?;
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_u32(self.label_idx);
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct BrIfIntrinsicNode {
-    label_idx: LabelIdx,
+    pub label_idx: LabelIdx,
+    pub span: Option<Span>,
 }
 impl Default for BrIfIntrinsicNode {
     fn default() -> Self {
@@ -528,20 +1599,28 @@ impl Default for BrIfIntrinsicNode {
 }
 impl BrIfIntrinsicNode {
     pub fn new() -> BrIfIntrinsicNode {
-        BrIfIntrinsicNode { label_idx: 0 }
+        BrIfIntrinsicNode {
+            label_idx: 0,
+            span: None,
+        }
     }
 
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
         self.label_idx = buf.read_u32()?;
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_u32(self.label_idx);
+    }
 }
 
-type FuncIdx = u32;
+pub type FuncIdx = u32;
 
 #[derive(Debug, Clone)]
 pub struct CallIntrinsicNode {
-    func_idx: FuncIdx,
+    pub func_idx: FuncIdx,
+    pub span: Option<Span>,
 }
 impl Default for CallIntrinsicNode {
     fn default() -> Self {
@@ -550,11 +1629,258 @@ impl Default for CallIntrinsicNode {
 }
 impl CallIntrinsicNode {
     pub fn new() -> CallIntrinsicNode {
-        CallIntrinsicNode { func_idx: 0 }
+        CallIntrinsicNode {
+            func_idx: 0,
+            span: None,
+        }
     }
 
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
         self.func_idx = buf.read_u32()?;
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_u32(self.func_idx);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RefNullIntrinsicNode {
+    pub ref_type: RefType,
+    pub span: Option<Span>,
+}
+impl Default for RefNullIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl RefNullIntrinsicNode {
+    pub fn new() -> RefNullIntrinsicNode {
+        RefNullIntrinsicNode {
+            ref_type: RefType::FuncRef(FUNC_REF),
+            span: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        let byte = buf.read_byte()?;
+        self.ref_type =
+            RefType::from_u8(byte).ok_or_else(|| anyhow!("Invalid reference type: {}", byte))?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_byte(self.ref_type.to_u8());
+    }
+}
+
+type TableIdx = u32;
+
+#[derive(Debug, Clone)]
+pub struct TableGrowIntrinsicNode {
+    pub table_idx: TableIdx,
+    pub span: Option<Span>,
+}
+impl Default for TableGrowIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl TableGrowIntrinsicNode {
+    pub fn new() -> TableGrowIntrinsicNode {
+        TableGrowIntrinsicNode {
+            table_idx: 0,
+            span: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.table_idx = buf.read_u32()?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_u32(self.table_idx);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TableSizeIntrinsicNode {
+    pub table_idx: TableIdx,
+    pub span: Option<Span>,
+}
+impl Default for TableSizeIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl TableSizeIntrinsicNode {
+    pub fn new() -> TableSizeIntrinsicNode {
+        TableSizeIntrinsicNode {
+            table_idx: 0,
+            span: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.table_idx = buf.read_u32()?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_u32(self.table_idx);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CallIndirectIntrinsicNode {
+    pub type_idx: TypeIdx,
+    pub table_idx: TableIdx,
+    pub span: Option<Span>,
+}
+impl Default for CallIndirectIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl CallIndirectIntrinsicNode {
+    pub fn new() -> CallIndirectIntrinsicNode {
+        CallIndirectIntrinsicNode {
+            type_idx: 0,
+            table_idx: 0,
+            span: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.type_idx = buf.read_u32()?;
+        self.table_idx = buf.read_u32()?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_u32(self.type_idx);
+        writer.write_u32(self.table_idx);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I32TruncF32SIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for I32TruncF32SIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I32TruncF32SIntrinsicNode {
+    pub fn new() -> I32TruncF32SIntrinsicNode {
+        I32TruncF32SIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I32TruncF32UIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for I32TruncF32UIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I32TruncF32UIntrinsicNode {
+    pub fn new() -> I32TruncF32UIntrinsicNode {
+        I32TruncF32UIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I32TruncF64SIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for I32TruncF64SIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I32TruncF64SIntrinsicNode {
+    pub fn new() -> I32TruncF64SIntrinsicNode {
+        I32TruncF64SIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I32TruncF64UIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for I32TruncF64UIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I32TruncF64UIntrinsicNode {
+    pub fn new() -> I32TruncF64UIntrinsicNode {
+        I32TruncF64UIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I64TruncF32SIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for I64TruncF32SIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I64TruncF32SIntrinsicNode {
+    pub fn new() -> I64TruncF32SIntrinsicNode {
+        I64TruncF32SIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I64TruncF32UIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for I64TruncF32UIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I64TruncF32UIntrinsicNode {
+    pub fn new() -> I64TruncF32UIntrinsicNode {
+        I64TruncF32UIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I64TruncF64SIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for I64TruncF64SIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I64TruncF64SIntrinsicNode {
+    pub fn new() -> I64TruncF64SIntrinsicNode {
+        I64TruncF64SIntrinsicNode { span: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct I64TruncF64UIntrinsicNode {
+    pub span: Option<Span>,
+}
+impl Default for I64TruncF64UIntrinsicNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl I64TruncF64UIntrinsicNode {
+    pub fn new() -> I64TruncF64UIntrinsicNode {
+        I64TruncF64UIntrinsicNode { span: None }
+    }
 }
@@ -0,0 +1,396 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::exec::code_section::{ExprNode, IntrinsicNode};
+use crate::exec::module::ModuleNode;
+use crate::exec::store::{FuncAddr, InstanceData};
+use crate::exec::trace::{CallEvent, InstructionEvent, Tracer};
+use crate::exec::value::Value;
+use crate::wat::printer::print_instr;
+
+const INDENT: &str = "  ";
+
+/// What ran of one function.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FuncCoverage {
+    pub calls: u64,
+    /// Times each executed instruction ran, by offset.
+    pub hits: BTreeMap<u32, u64>,
+    /// Times each executed `if` and `br_if` was taken and not taken, by offset.
+    /// `if` is taken when it runs its `then` branch.
+    pub branches: BTreeMap<u32, (u64, u64)>,
+}
+
+/// `Tracer` recording the instructions executed in the Wasm functions of a store,
+/// identified by their address. Host functions are ignored.
+#[derive(Debug, Default)]
+pub struct Coverage {
+    funcs: BTreeMap<FuncAddr, FuncCoverage>,
+}
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    /// `None` if the function never ran.
+    pub fn func(&self, addr: FuncAddr) -> Option<&FuncCoverage> {
+        self.funcs.get(&addr)
+    }
+
+    /// Coverage of the function `func_idx` of `instance`.
+    fn func_of(&self, instance: &InstanceData, func_idx: u32) -> Option<&FuncCoverage> {
+        let addr = instance.func_addrs.get(func_idx as usize)?;
+        self.funcs.get(addr)
+    }
+
+    /// Functions defined by `instance` in the text format, each instruction prefixed with its
+    /// hit count and offset, `#####` marking the ones that never ran.
+    pub fn listing(&self, instance: &InstanceData) -> String {
+        let mut listing = String::new();
+        for func in funcs(&instance.module) {
+            let coverage = self.func_of(instance, func.idx);
+            let (hit, total) = func.instructions(coverage);
+            let (branches_hit, branches) = func.branches(coverage);
+            let _ = writeln!(
+                listing,
+                ";; {}: {}/{} instructions, {}/{} branches",
+                func.name, hit, total, branches_hit, branches
+            );
+            for line in &func.lines {
+                let indent = INDENT.repeat(line.depth);
+                let Some((offset, intrinsic)) = line.instr else {
+                    let _ = writeln!(listing, "{:>8} {:>6} | {}{}", "", "", indent, line.text);
+                    continue;
+                };
+                let count = match coverage.and_then(|c| c.hits.get(&offset)) {
+                    Some(count) => count.to_string(),
+                    None => "#####".to_string(),
+                };
+                let _ = write!(
+                    listing,
+                    "{:>8} {:#06x} | {}{}",
+                    count, offset, indent, line.text
+                );
+                if is_branch(intrinsic) {
+                    let (taken, not_taken) = coverage
+                        .and_then(|c| c.branches.get(&offset))
+                        .copied()
+                        .unwrap_or_default();
+                    let _ = write!(listing, " ;; taken {}, not taken {}", taken, not_taken);
+                }
+                listing.push('\n');
+            }
+        }
+        listing
+    }
+
+    /// One lcov record per function defined by `instance`, instruction offsets standing for
+    /// line numbers. `source` names the module, e.g. its path.
+    pub fn lcov(&self, instance: &InstanceData, source: &str) -> String {
+        let mut lcov = String::new();
+        for func in funcs(&instance.module) {
+            let coverage = self.func_of(instance, func.idx);
+            let calls = coverage.map_or(0, |c| c.calls);
+            let first = func.lines.iter().find_map(|l| l.instr).map_or(0, |i| i.0);
+            let _ = writeln!(lcov, "TN:");
+            let _ = writeln!(lcov, "SF:{}:func[{}]", source, func.idx);
+            let _ = writeln!(lcov, "FN:{},{}", first, func.name);
+            let _ = writeln!(lcov, "FNDA:{},{}", calls, func.name);
+            let _ = writeln!(lcov, "FNF:1");
+            let _ = writeln!(lcov, "FNH:{}", u32::from(calls > 0));
+            for (offset, intrinsic) in func.lines.iter().filter_map(|l| l.instr) {
+                if !is_branch(intrinsic) {
+                    continue;
+                }
+                // Branches of instructions that never ran are `-`, not 0.
+                match coverage.and_then(|c| c.branches.get(&offset)) {
+                    Some((taken, not_taken)) => {
+                        let _ = writeln!(lcov, "BRDA:{},0,0,{}", offset, taken);
+                        let _ = writeln!(lcov, "BRDA:{},0,1,{}", offset, not_taken);
+                    }
+                    None => {
+                        let _ = writeln!(lcov, "BRDA:{},0,0,-", offset);
+                        let _ = writeln!(lcov, "BRDA:{},0,1,-", offset);
+                    }
+                }
+            }
+            let (branches_hit, branches) = func.branches(coverage);
+            let _ = writeln!(lcov, "BRF:{}", branches);
+            let _ = writeln!(lcov, "BRH:{}", branches_hit);
+            for (offset, _) in func.lines.iter().filter_map(|l| l.instr) {
+                let count = coverage.and_then(|c| c.hits.get(&offset)).unwrap_or(&0);
+                let _ = writeln!(lcov, "DA:{},{}", offset, count);
+            }
+            let (hit, total) = func.instructions(coverage);
+            let _ = writeln!(lcov, "LF:{}", total);
+            let _ = writeln!(lcov, "LH:{}", hit);
+            let _ = writeln!(lcov, "end_of_record");
+        }
+        lcov
+    }
+}
+impl Tracer for Coverage {
+    fn instruction(&mut self, event: &InstructionEvent) {
+        let func = self.funcs.entry(event.func).or_default();
+        *func.hits.entry(event.offset).or_default() += 1;
+        if is_branch(event.intrinsic) {
+            let branch = func.branches.entry(event.offset).or_default();
+            // The condition is on top of the stack.
+            match event.stack.last() {
+                Some(Value::I32(0)) => branch.1 += 1,
+                Some(_) => branch.0 += 1,
+                None => {}
+            }
+        }
+    }
+
+    fn enter(&mut self, event: &CallEvent) {
+        if !event.is_host {
+            self.funcs.entry(event.func).or_default().calls += 1;
+        }
+    }
+}
+
+fn is_branch(intrinsic: &IntrinsicNode) -> bool {
+    matches!(
+        intrinsic,
+        IntrinsicNode::IfIntrinsicNode(_) | IntrinsicNode::BrIfIntrinsicNode(_)
+    )
+}
+
+/// Line of a function listing, an instruction or the `else` and `end` of a block.
+struct Line<'a> {
+    depth: usize,
+    instr: Option<(u32, &'a IntrinsicNode)>,
+    text: String,
+}
+
+/// Function defined by a module, flattened to lines.
+struct Func<'a> {
+    idx: u32,
+    name: String,
+    lines: Vec<Line<'a>>,
+}
+impl Func<'_> {
+    /// Number of instructions that ran, and of instructions.
+    fn instructions(&self, coverage: Option<&FuncCoverage>) -> (usize, usize) {
+        let offsets: Vec<u32> = self
+            .lines
+            .iter()
+            .filter_map(|l| l.instr)
+            .map(|i| i.0)
+            .collect();
+        let hit = offsets
+            .iter()
+            .filter(|offset| coverage.is_some_and(|c| c.hits.contains_key(offset)))
+            .count();
+        (hit, offsets.len())
+    }
+
+    /// Number of branch directions taken at least once, and of branch directions.
+    fn branches(&self, coverage: Option<&FuncCoverage>) -> (usize, usize) {
+        let branches: Vec<u32> = self
+            .lines
+            .iter()
+            .filter_map(|l| l.instr)
+            .filter(|(_, intrinsic)| is_branch(intrinsic))
+            .map(|i| i.0)
+            .collect();
+        let hit = branches
+            .iter()
+            .filter_map(|offset| coverage.and_then(|c| c.branches.get(offset)))
+            .map(|(taken, not_taken)| usize::from(*taken > 0) + usize::from(*not_taken > 0))
+            .sum();
+        (hit, branches.len() * 2)
+    }
+}
+
+fn funcs(module: &ModuleNode) -> Vec<Func<'_>> {
+    let Some(code_section) = module.code_section() else {
+        return Vec::new();
+    };
+    let names = module.name_section().unwrap_or_default();
+    let imported_funcs = module.imported_func_count();
+    code_section
+        .codes
+        .iter()
+        .enumerate()
+        .map(|(i, code)| {
+            let idx = imported_funcs + i as u32;
+            let name = match names.func_names.get(&idx) {
+                Some(name) => format!("func[{}] <{}>", idx, name),
+                None => format!("func[{}]", idx),
+            };
+            let mut lines = Vec::new();
+            flatten(&code.func.expr, 0, &mut lines);
+            Func { idx, name, lines }
+        })
+        .collect()
+}
+
+fn flatten<'a>(expr: &'a ExprNode, depth: usize, lines: &mut Vec<Line<'a>>) {
+    for intrinsic in &expr.intrinsics {
+        let Some(span) = intrinsic.span() else {
+            continue;
+        };
+        lines.push(Line {
+            depth,
+            instr: Some((span.start, intrinsic)),
+            text: print_instr(intrinsic),
+        });
+        let end = |lines: &mut Vec<Line>, text: &str| {
+            lines.push(Line {
+                depth,
+                instr: None,
+                text: text.to_string(),
+            });
+        };
+        match intrinsic {
+            IntrinsicNode::BlockIntrinsicNode(node) => {
+                flatten(&node.expr, depth + 1, lines);
+                end(lines, "end");
+            }
+            IntrinsicNode::LoopIntrinsicNode(node) => {
+                flatten(&node.expr, depth + 1, lines);
+                end(lines, "end");
+            }
+            IntrinsicNode::IfIntrinsicNode(node) => {
+                flatten(&node.then_expr, depth + 1, lines);
+                if !node.else_expr.intrinsics.is_empty() {
+                    end(lines, "else");
+                    flatten(&node.else_expr, depth + 1, lines);
+                }
+                end(lines, "end");
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::buffer::Buffer;
+    use crate::exec::instance::Instance;
+    use crate::exec::linker::Linker;
+    use crate::exec::store::Store;
+    use crate::wasi::ctx::WasiCtx;
+    use crate::wasi::preview1;
+    use crate::wat::module::parse_str;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    const WAT: &str = r#"
+        (func $abs (export "abs") (param i32) (result i32)
+          (if (result i32) (i32.lt_s (local.get 0) (i32.const 0))
+            (then (i32.sub (i32.const 0) (local.get 0)))
+            (else (local.get 0))))
+        (func $unused (unreachable))
+    "#;
+
+    fn cover(args: &[i32]) -> (InstanceData, Coverage) {
+        let mut module = ModuleNode::new();
+        module
+            .load(&mut Buffer::new(&parse_str(WAT).unwrap()))
+            .unwrap();
+        let mut store = Store::new();
+        let coverage = Rc::new(RefCell::new(Coverage::new()));
+        store.set_tracer(coverage.clone());
+        let instance = Instance::new(&mut store, module.clone()).unwrap();
+        for arg in args {
+            instance
+                .invoke(&mut store, "abs", &[Value::I32(*arg)])
+                .unwrap();
+        }
+        let instance = instance.data(&store).clone();
+        drop(store);
+        (instance, Rc::into_inner(coverage).unwrap().into_inner())
+    }
+
+    #[test]
+    fn test_coverage() {
+        let (instance, coverage) = cover(&[5, 7]);
+        let abs = coverage.func(instance.func_addrs[0]).unwrap();
+        assert_eq!(abs.calls, 2);
+        assert_eq!(abs.branches.values().collect::<Vec<_>>(), [&(0, 2)]);
+        assert_eq!(abs.hits.len(), 5);
+        assert!(coverage.func(instance.func_addrs[1]).is_none());
+    }
+
+    #[test]
+    fn test_host_functions_are_ignored() {
+        let wat = r#"(module
+          (import "wasi_snapshot_preview1" "args_sizes_get"
+            (func $args_sizes_get (param i32 i32) (result i32)))
+          (memory 1)
+          (func $start (export "_start")
+            (drop (call $args_sizes_get (i32.const 0) (i32.const 4)))))
+        "#;
+        let mut module = ModuleNode::new();
+        module
+            .load(&mut Buffer::new(&parse_str(wat).unwrap()))
+            .unwrap();
+        let mut store = Store::new();
+        let coverage = Rc::new(RefCell::new(Coverage::new()));
+        store.set_tracer(coverage.clone());
+        let mut linker = Linker::new();
+        let wasi = preview1::instantiate(&mut store, WasiCtx::new());
+        linker.register(preview1::MODULE, wasi);
+        let instance = linker.instantiate(&mut store, module).unwrap();
+        instance.invoke(&mut store, "_start", &[]).unwrap();
+
+        let instance = instance.data(&store);
+        let coverage = coverage.borrow();
+        assert!(coverage.func(instance.func_addrs[0]).is_none());
+        assert_eq!(coverage.func(instance.func_addrs[1]).unwrap().calls, 1);
+        assert_eq!(
+            coverage.listing(instance),
+            [
+                ";; func[1] <start>: 4/4 instructions, 0/0 branches",
+                "       1 0x0059 | i32.const 0",
+                "       1 0x005b | i32.const 4",
+                "       1 0x005d | call 0",
+                "       1 0x005f | drop",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_listing() {
+        let (instance, coverage) = cover(&[5, 7, -1]);
+        assert_eq!(
+            coverage.listing(&instance),
+            [
+                ";; func[0] <abs>: 8/8 instructions, 2/2 branches",
+                "       3 0x0026 | local.get 0",
+                "       3 0x0028 | i32.const 0",
+                "       3 0x002a | i32.lt_s",
+                "       3 0x002b | if (result i32) ;; taken 1, not taken 2",
+                "       1 0x002d |   i32.const 0",
+                "       1 0x002f |   local.get 0",
+                "       1 0x0031 |   i32.sub",
+                "                | else",
+                "       2 0x0033 |   local.get 0",
+                "                | end",
+                ";; func[1] <unused>: 0/1 instructions, 0/0 branches",
+                "   ##### 0x0039 | unreachable",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_lcov() {
+        let (instance, coverage) = cover(&[5]);
+        let lcov = coverage.lcov(&instance, "abs.wasm");
+        let records: Vec<&str> = lcov.split("end_of_record\n").collect();
+        assert_eq!(records.len(), 3);
+        assert!(records[0].starts_with("TN:\nSF:abs.wasm:func[0]\nFN:38,func[0] <abs>\nFNDA:1,"));
+        assert!(records[0].contains("BRDA:43,0,0,0\nBRDA:43,0,1,1\nBRF:2\nBRH:1\n"));
+        assert!(records[0].ends_with("DA:51,1\nLF:8\nLH:5\n"));
+        assert!(records[1].contains("FNDA:0,func[1] <unused>\nFNF:1\nFNH:0\n"));
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+
+use crate::exec::buffer::Buffer;
+use crate::exec::section::SectionHeader;
+use crate::exec::writer::Writer;
+
+#[derive(Debug, Clone)]
+pub struct CustomSectionNode {
+    pub name: String,
+    pub data: Vec<u8>,
+    pub header: Option<SectionHeader>,
+}
+impl Default for CustomSectionNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl CustomSectionNode {
+    pub fn new() -> CustomSectionNode {
+        CustomSectionNode {
+            name: String::new(),
+            data: Vec::new(),
+            header: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.name = buf.read_name()?;
+        let size = buf.byte_len() as u32 - buf.cursor();
+        self.data = buf.read_bytes(size)?.to_vec();
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_name(&self.name);
+        writer.write_bytes(&self.data);
+    }
+
+    /// Decode the contents of the "name" custom section.
+    /// Malformed name sections are ignored as the spec allows.
+    pub fn name_section(&self) -> Option<NameSectionNode> {
+        if self.name != "name" {
+            return None;
+        }
+        let mut names = NameSectionNode::new();
+        names.load(&mut Buffer::new(&self.data)).ok()?;
+        Some(names)
+    }
+}
+
+type NameMap = BTreeMap<u32, String>;
+
+/// https://webassembly.github.io/spec/core/appendix/custom.html#name-section
+#[derive(Debug, Clone, Default)]
+pub struct NameSectionNode {
+    pub module_name: Option<String>,
+    pub func_names: NameMap,
+    pub local_names: BTreeMap<u32, NameMap>,
+}
+impl NameSectionNode {
+    pub fn new() -> NameSectionNode {
+        NameSectionNode::default()
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        while !buf.eof() {
+            let id = buf.read_byte()?;
+            let size = buf.read_u32()?;
+            let mut sub_buf = buf.read_buffer(size)?;
+            match id {
+                0 => self.module_name = Some(sub_buf.read_name()?),
+                1 => self.func_names = Self::load_name_map(&mut sub_buf)?,
+                2 => {
+                    let f = |buf: &mut Buffer| -> Result<(u32, NameMap)> {
+                        let func_idx = buf.read_u32()?;
+                        Ok((func_idx, Self::load_name_map(buf)?))
+                    };
+                    self.local_names = sub_buf.read_vec(Box::new(f))?.into_iter().collect();
+                }
+                // Other subsections are defined by extensions and skipped.
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn load_name_map(buf: &mut Buffer) -> Result<NameMap> {
+        let f = |buf: &mut Buffer| -> Result<(u32, String)> {
+            let idx = buf.read_u32()?;
+            let name = buf.read_name()?;
+            Ok((idx, name))
+        };
+        let entries = buf.read_vec(Box::new(f))?;
+        let map: NameMap = entries.iter().cloned().collect();
+        if map.len() != entries.len() {
+            return Err(anyhow!("Duplicate index in name map"));
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_name_section() {
+        let data = vec![
+            // module name: "m"
+            0x00, 0x02, 0x01, b'm', //
+            // function names: 0 => "f"
+            0x01, 0x04, 0x01, 0x00, 0x01, b'f', //
+            // local names: func 0 => { 1 => "x" }
+            0x02, 0x06, 0x01, 0x00, 0x01, 0x01, 0x01, b'x',
+        ];
+        let section = CustomSectionNode {
+            name: "name".to_string(),
+            data,
+            header: None,
+        };
+        let names = section.name_section().unwrap();
+
+        assert_eq!(names.module_name, Some("m".to_string()));
+        assert_eq!(names.func_names.get(&0), Some(&"f".to_string()));
+        assert_eq!(names.local_names[&0].get(&1), Some(&"x".to_string()));
+    }
+}
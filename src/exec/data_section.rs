@@ -0,0 +1,102 @@
+use anyhow::{anyhow, Result};
+
+use crate::exec::buffer::Buffer;
+use crate::exec::code_section::ExprNode;
+use crate::exec::section::SectionHeader;
+use crate::exec::writer::Writer;
+
+#[derive(Debug, Clone)]
+pub struct DataSectionNode {
+    pub segments: Vec<DataNode>,
+    pub header: Option<SectionHeader>,
+}
+impl Default for DataSectionNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl DataSectionNode {
+    pub fn new() -> DataSectionNode {
+        DataSectionNode {
+            segments: Vec::new(),
+            header: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        let f = |buf: &mut Buffer| -> Result<DataNode> {
+            let mut segment = DataNode::new();
+            segment.load(buf)?;
+            Ok(segment)
+        };
+        self.segments = buf.read_vec::<DataNode>(Box::new(f))?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_vec(&self.segments, |w, item| item.write(w));
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum DataMode {
+    /// Only copied into a memory by `memory.init`.
+    Passive,
+    /// Copied into memory `mem_idx` at `offset` during instantiation.
+    Active { mem_idx: u32, offset: ExprNode },
+}
+
+/// https://webassembly.github.io/spec/core/binary/modules.html#data-section
+#[derive(Debug, Clone)]
+pub struct DataNode {
+    pub mode: DataMode,
+    pub init: Vec<u8>,
+}
+impl Default for DataNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl DataNode {
+    pub fn new() -> DataNode {
+        DataNode {
+            mode: DataMode::Passive,
+            init: Vec::new(),
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        let flag = buf.read_u32()?;
+        self.mode = match flag {
+            0x00 | 0x02 => {
+                let mem_idx = if flag == 0x02 { buf.read_u32()? } else { 0 };
+                let mut offset = ExprNode::new();
+                offset.load(buf)?;
+                DataMode::Active { mem_idx, offset }
+            }
+            0x01 => DataMode::Passive,
+            _ => return Err(anyhow!("Invalid data segment flag: {}", flag)),
+        };
+        let size = buf.read_u32()?;
+        self.init = buf.read_bytes(size)?.to_vec();
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        match &self.mode {
+            DataMode::Passive => writer.write_u32(0x01),
+            DataMode::Active { mem_idx, offset } => {
+                // Memory 0 has the shorter encoding.
+                if *mem_idx == 0 {
+                    writer.write_u32(0x00);
+                } else {
+                    writer.write_u32(0x02);
+                    writer.write_u32(*mem_idx);
+                }
+                offset.write(writer);
+            }
+        }
+        writer.write_u32(self.init.len() as u32);
+        writer.write_bytes(&self.init);
+    }
+}
@@ -1,10 +1,14 @@
 use anyhow::{anyhow, Result};
+use std::fmt;
 
 use crate::exec::buffer::Buffer;
+use crate::exec::section::SectionHeader;
+use crate::exec::writer::Writer;
 
 #[derive(Debug, Clone)]
 pub struct ExportSectionNode {
-    exports: Vec<ExportNode>,
+    pub exports: Vec<ExportNode>,
+    pub header: Option<SectionHeader>,
 }
 impl Default for ExportSectionNode {
     fn default() -> Self {
@@ -15,6 +19,7 @@ impl ExportSectionNode {
     pub fn new() -> ExportSectionNode {
         ExportSectionNode {
             exports: Vec::new(),
+            header: None,
         }
     }
 
@@ -27,12 +32,16 @@ impl ExportSectionNode {
         self.exports = buf.read_vec::<ExportNode>(Box::new(f))?;
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_vec(&self.exports, |w, item| item.write(w));
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ExportNode {
-    name: String,
-    desc: ExportDescNode,
+    pub name: String,
+    pub desc: ExportDescNode,
 }
 impl Default for ExportNode {
     fn default() -> Self {
@@ -52,12 +61,50 @@ impl ExportNode {
         self.desc.load(buf)?;
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_name(&self.name);
+        self.desc.write(writer);
+    }
+}
+
+/// What an export refers to, encoded as `ExportDescNode::tag`.
+/// https://webassembly.github.io/spec/core/binary/modules.html#export-section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportKind {
+    Func = 0x00,
+    Table = 0x01,
+    Memory = 0x02,
+    Global = 0x03,
+}
+impl ExportKind {
+    pub fn from_u8(value: u8) -> Option<ExportKind> {
+        match value {
+            0x00 => Some(ExportKind::Func),
+            0x01 => Some(ExportKind::Table),
+            0x02 => Some(ExportKind::Memory),
+            0x03 => Some(ExportKind::Global),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ExportKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keyword = match self {
+            ExportKind::Func => "func",
+            ExportKind::Table => "table",
+            ExportKind::Memory => "memory",
+            ExportKind::Global => "global",
+        };
+        write!(f, "{}", keyword)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ExportDescNode {
-    tag: u8,
-    index: u32,
+    pub tag: u8,
+    pub index: u32,
 }
 impl Default for ExportDescNode {
     fn default() -> Self {
@@ -69,9 +116,18 @@ impl ExportDescNode {
         ExportDescNode { tag: 0, index: 0 }
     }
 
+    pub fn kind(&self) -> Result<ExportKind> {
+        ExportKind::from_u8(self.tag).ok_or_else(|| anyhow!("Invalid export kind: {}", self.tag))
+    }
+
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
         self.tag = buf.read_byte()?;
         self.index = buf.read_u32()?;
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_byte(self.tag);
+        writer.write_u32(self.index);
+    }
 }
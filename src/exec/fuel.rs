@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use crate::exec::code_section::Op;
+use crate::exec::trap::TrapKind;
+
+/// Amount of fuel each instruction consumes.
+#[derive(Debug, Clone)]
+pub struct FuelCosts {
+    costs: HashMap<Op, u64>,
+}
+impl Default for FuelCosts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl FuelCosts {
+    /// Every instruction costs 1.
+    pub fn new() -> FuelCosts {
+        FuelCosts {
+            costs: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, op: Op) -> u64 {
+        self.costs.get(&op).copied().unwrap_or(1)
+    }
+
+    pub fn set(&mut self, op: Op, cost: u64) {
+        self.costs.insert(op, cost);
+    }
+}
+
+/// Fuel accounting of a `Store`.
+/// Metering is disabled until a budget is set with `set_remaining`.
+#[derive(Debug, Clone, Default)]
+pub struct Fuel {
+    remaining: Option<u64>,
+    consumed: u64,
+    costs: FuelCosts,
+}
+impl Fuel {
+    pub fn new() -> Fuel {
+        Fuel::default()
+    }
+
+    pub fn remaining(&self) -> Option<u64> {
+        self.remaining
+    }
+
+    pub fn set_remaining(&mut self, fuel: u64) {
+        self.remaining = Some(fuel);
+    }
+
+    /// Total fuel consumed by executed instructions, whether metering is enabled or not.
+    pub fn consumed(&self) -> u64 {
+        self.consumed
+    }
+
+    pub fn set_costs(&mut self, costs: FuelCosts) {
+        self.costs = costs;
+    }
+
+    /// Charge for executing `op`.
+    /// Nothing is consumed if the remaining fuel does not cover the cost.
+    pub fn consume(&mut self, op: Op) -> Result<(), TrapKind> {
+        let cost = self.costs.get(op);
+        if let Some(remaining) = self.remaining {
+            self.remaining = Some(remaining.checked_sub(cost).ok_or(TrapKind::OutOfFuel)?);
+        }
+        self.consumed += cost;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consume() {
+        let mut costs = FuelCosts::new();
+        costs.set(Op::Call, 3);
+        let mut fuel = Fuel::new();
+        fuel.set_costs(costs);
+        fuel.set_remaining(4);
+
+        assert_eq!(fuel.consume(Op::Call), Ok(()));
+        assert_eq!(fuel.consume(Op::I32Add), Ok(()));
+        assert_eq!(fuel.consume(Op::I32Add), Err(TrapKind::OutOfFuel));
+        assert_eq!(fuel.remaining(), Some(0));
+        assert_eq!(fuel.consumed(), 4);
+    }
+}
@@ -1,12 +1,15 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 
 use crate::exec::buffer::Buffer;
+use crate::exec::section::SectionHeader;
+use crate::exec::writer::Writer;
 
-type TypeIdx = u32;
+pub type TypeIdx = u32;
 
 #[derive(Debug, Clone)]
 pub struct FunctionSectionNode {
-    type_indices: Vec<TypeIdx>,
+    pub type_indices: Vec<TypeIdx>,
+    pub header: Option<SectionHeader>,
 }
 impl Default for FunctionSectionNode {
     fn default() -> Self {
@@ -17,6 +20,7 @@ impl FunctionSectionNode {
     pub fn new() -> FunctionSectionNode {
         FunctionSectionNode {
             type_indices: Vec::new(),
+            header: None,
         }
     }
 
@@ -29,4 +33,8 @@ impl FunctionSectionNode {
         self.type_indices = buf.read_vec::<TypeIdx>(Box::new(f))?;
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_vec(&self.type_indices, |w, type_idx| w.write_u32(*type_idx));
+    }
 }
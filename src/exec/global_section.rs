@@ -0,0 +1,72 @@
+use anyhow::Result;
+
+use crate::exec::buffer::Buffer;
+use crate::exec::code_section::ExprNode;
+use crate::exec::section::SectionHeader;
+use crate::exec::type_section::GlobalTypeNode;
+use crate::exec::writer::Writer;
+
+#[derive(Debug, Clone)]
+pub struct GlobalSectionNode {
+    pub globals: Vec<GlobalNode>,
+    pub header: Option<SectionHeader>,
+}
+impl Default for GlobalSectionNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl GlobalSectionNode {
+    pub fn new() -> GlobalSectionNode {
+        GlobalSectionNode {
+            globals: Vec::new(),
+            header: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        let f = |buf: &mut Buffer| -> Result<GlobalNode> {
+            let mut global = GlobalNode::new();
+            global.load(buf)?;
+            Ok(global)
+        };
+        self.globals = buf.read_vec::<GlobalNode>(Box::new(f))?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_vec(&self.globals, |w, item| item.write(w));
+    }
+}
+
+/// https://webassembly.github.io/spec/core/binary/modules.html#global-section
+#[derive(Debug, Clone)]
+pub struct GlobalNode {
+    pub global_type: GlobalTypeNode,
+    /// Constant expression computing the initial value.
+    pub init: ExprNode,
+}
+impl Default for GlobalNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl GlobalNode {
+    pub fn new() -> GlobalNode {
+        GlobalNode {
+            global_type: GlobalTypeNode::new(),
+            init: ExprNode::new(),
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.global_type.load(buf)?;
+        self.init.load(buf)?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        self.global_type.write(writer);
+        self.init.write(writer);
+    }
+}
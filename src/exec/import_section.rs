@@ -0,0 +1,131 @@
+use anyhow::{anyhow, Result};
+
+use crate::exec::buffer::Buffer;
+use crate::exec::export_section::ExportKind;
+use crate::exec::func_section::TypeIdx;
+use crate::exec::section::SectionHeader;
+use crate::exec::type_section::{GlobalTypeNode, MemoryTypeNode, TableTypeNode};
+use crate::exec::writer::Writer;
+
+#[derive(Debug, Clone)]
+pub struct ImportSectionNode {
+    pub imports: Vec<ImportNode>,
+    pub header: Option<SectionHeader>,
+}
+impl Default for ImportSectionNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl ImportSectionNode {
+    pub fn new() -> ImportSectionNode {
+        ImportSectionNode {
+            imports: Vec::new(),
+            header: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        let f = |buf: &mut Buffer| -> Result<ImportNode> {
+            let mut import = ImportNode::new();
+            import.load(buf)?;
+            Ok(import)
+        };
+        self.imports = buf.read_vec::<ImportNode>(Box::new(f))?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_vec(&self.imports, |w, item| item.write(w));
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportNode {
+    /// Name the exporting module was registered with.
+    pub module: String,
+    pub name: String,
+    pub desc: ImportDescNode,
+}
+impl Default for ImportNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl ImportNode {
+    pub fn new() -> ImportNode {
+        ImportNode {
+            module: String::new(),
+            name: String::new(),
+            desc: ImportDescNode::Func(0),
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.module = buf.read_name()?;
+        self.name = buf.read_name()?;
+        self.desc = ImportDescNode::load(buf)?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_name(&self.module);
+        writer.write_name(&self.name);
+        self.desc.write(writer);
+    }
+}
+
+/// Type the imported value must match.
+/// https://webassembly.github.io/spec/core/binary/modules.html#import-section
+#[derive(Debug, Clone)]
+pub enum ImportDescNode {
+    Func(TypeIdx),
+    Table(TableTypeNode),
+    Memory(MemoryTypeNode),
+    Global(GlobalTypeNode),
+}
+impl ImportDescNode {
+    pub fn kind(&self) -> ExportKind {
+        match self {
+            ImportDescNode::Func(_) => ExportKind::Func,
+            ImportDescNode::Table(_) => ExportKind::Table,
+            ImportDescNode::Memory(_) => ExportKind::Memory,
+            ImportDescNode::Global(_) => ExportKind::Global,
+        }
+    }
+
+    pub fn load(buf: &mut Buffer) -> Result<ImportDescNode> {
+        let tag = buf.read_byte()?;
+        let kind =
+            ExportKind::from_u8(tag).ok_or_else(|| anyhow!("Invalid import kind: {}", tag))?;
+        let desc = match kind {
+            ExportKind::Func => ImportDescNode::Func(buf.read_u32()?),
+            ExportKind::Table => {
+                let mut table_type = TableTypeNode::new();
+                table_type.load(buf)?;
+                ImportDescNode::Table(table_type)
+            }
+            ExportKind::Memory => {
+                let mut mem_type = MemoryTypeNode::new();
+                mem_type.load(buf)?;
+                ImportDescNode::Memory(mem_type)
+            }
+            ExportKind::Global => {
+                let mut global_type = GlobalTypeNode::new();
+                global_type.load(buf)?;
+                ImportDescNode::Global(global_type)
+            }
+        };
+        Ok(desc)
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_byte(self.kind() as u8);
+        match self {
+            ImportDescNode::Func(type_idx) => writer.write_u32(*type_idx),
+            ImportDescNode::Table(table_type) => table_type.write(writer),
+            ImportDescNode::Memory(mem_type) => mem_type.write(writer),
+            ImportDescNode::Global(global_type) => global_type.write(writer),
+        }
+    }
+}
@@ -0,0 +1,979 @@
+use anyhow::{anyhow, Result};
+use std::sync::Arc;
+
+use crate::errors::ExecError;
+use crate::exec::code_section::{ExprNode, IntrinsicNode};
+use crate::exec::data_section::DataMode;
+use crate::exec::export_section::ExportKind;
+use crate::exec::import_section::{ImportDescNode, ImportNode};
+use crate::exec::machine::Machine;
+use crate::exec::module::ModuleNode;
+use crate::exec::store::{
+    ExternVal, FuncAddr, FuncInst, GlobalAddr, GlobalInst, HostFunc, InstanceData, MemAddr,
+    MemInst, Store, TableAddr, TableInst,
+};
+use crate::exec::type_section::{FunctionTypeNode, LimitsNode};
+use crate::exec::typed_func::{TypedFunc, WasmParams, WasmResults};
+use crate::exec::value::Value;
+
+/// Export of a host instance.
+pub enum HostExtern {
+    Func(FunctionTypeNode, HostFunc),
+    Table(TableInst),
+    Memory(MemInst),
+    Global(GlobalInst),
+}
+
+/// Handle to a module instantiated in a `Store`.
+/// https://webassembly.github.io/spec/core/exec/runtime.html#module-instances
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instance {
+    index: usize,
+}
+impl Instance {
+    /// Instantiate a module without imports.
+    pub fn new(store: &mut Store, module: ModuleNode) -> Result<Instance> {
+        Instance::with_imports(store, module, &[])
+    }
+
+    /// Instantiate `module`, resolving its imports in order to `externs`.
+    /// See `Linker` to resolve imports by name.
+    /// https://webassembly.github.io/spec/core/exec/modules.html#instantiation
+    ///
+    /// If instantiation fails, what it added to the store is removed and given back to the
+    /// limiter, unless the start function left references to it in existing tables or globals.
+    pub fn with_imports(
+        store: &mut Store,
+        module: ModuleNode,
+        externs: &[ExternVal],
+    ) -> Result<Instance> {
+        let checkpoint = Checkpoint::of(store);
+        let result = Instance::instantiate(store, module, externs);
+        if result.is_err() && !checkpoint.is_referenced(store) {
+            checkpoint.restore(store);
+        }
+        result
+    }
+
+    fn instantiate(
+        store: &mut Store,
+        module: ModuleNode,
+        externs: &[ExternVal],
+    ) -> Result<Instance> {
+        let module = Arc::new(module);
+        let index = store.instances.len();
+
+        let func_types = module
+            .type_section()
+            .map(|t| t.func_types.clone())
+            .unwrap_or_default();
+
+        let mut func_addrs = Vec::new();
+        let mut table_addrs = Vec::new();
+        let mut mem_addrs = Vec::new();
+        let mut global_addrs = Vec::new();
+        let imports = module.import_section().map_or(&[][..], |i| &i.imports);
+        if let Some(import) = imports.get(externs.len()) {
+            let name = format!("{}.{}", import.module, import.name);
+            return Err(ExecError::UnknownImport(name).into());
+        }
+        for (import, extern_val) in imports.iter().zip(externs) {
+            check_import(store, &func_types, import, extern_val)?;
+            match *extern_val {
+                ExternVal::Func(addr) => func_addrs.push(addr),
+                ExternVal::Table(addr) => table_addrs.push(addr),
+                ExternVal::Memory(addr) => mem_addrs.push(addr),
+                ExternVal::Global(addr) => global_addrs.push(addr),
+            }
+        }
+        let imported_func_count = func_addrs.len() as u32;
+        let type_indices = module
+            .function_section()
+            .map(|f| f.type_indices.clone())
+            .unwrap_or_default();
+        let code_count = module.code_section().map_or(0, |c| c.codes.len());
+        if type_indices.len() != code_count {
+            return Err(anyhow!(
+                "Function and code section have inconsistent lengths: {} and {}",
+                type_indices.len(),
+                code_count
+            ));
+        }
+
+        let names = module.name_section().unwrap_or_default();
+        for (code_idx, type_idx) in type_indices.iter().enumerate() {
+            let func_type = func_types
+                .get(*type_idx as usize)
+                .ok_or(anyhow!("Unknown type: {}", type_idx))?;
+            let func_idx = imported_func_count + code_idx as u32;
+            func_addrs.push(store.funcs.len());
+            store.funcs.push(FuncInst::new(
+                func_type.clone(),
+                index,
+                func_idx,
+                names.func_names.get(&func_idx).cloned(),
+                module.clone(),
+                code_idx,
+            ));
+        }
+
+        for table_type in module.table_section().map_or(&[][..], |t| &t.table_types) {
+            table_addrs.push(store.tables.len());
+            let table = TableInst::new(table_type, store.limiter())?;
+            store.tables.push(table);
+        }
+
+        for mem_type in module.memory_section().map_or(&[][..], |m| &m.mem_types) {
+            mem_addrs.push(store.memories.len());
+            let mem = MemInst::new(mem_type, store.limiter())?;
+            store.memories.push(mem);
+        }
+
+        for global in module.global_section().map_or(&[][..], |g| &g.globals) {
+            let value = eval_const(store, &global_addrs, &global.init)?;
+            if value.val_type() != global.global_type.val_type {
+                return Err(anyhow!(
+                    "Global of type {:?} initialized with {}",
+                    global.global_type.val_type,
+                    value
+                ));
+            }
+            global_addrs.push(store.globals.len());
+            store
+                .globals
+                .push(GlobalInst::new(global.global_type.clone(), value));
+        }
+
+        for segment in module.data_section().map_or(&[][..], |d| &d.segments) {
+            let DataMode::Active { mem_idx, offset } = &segment.mode else {
+                continue;
+            };
+            let addr = mem_addrs
+                .get(*mem_idx as usize)
+                .ok_or(anyhow!("Unknown memory: {}", mem_idx))?;
+            let offset = match eval_const(store, &global_addrs, offset)? {
+                Value::I32(offset) => offset as u32 as usize,
+                value => return Err(anyhow!("Data segment offset of {}", value)),
+            };
+            store.memories[*addr].write_bytes(offset, &segment.init)?;
+        }
+
+        let mut exports = Vec::new();
+        for export in module.export_section().map_or(&[][..], |e| &e.exports) {
+            let index = export.desc.index;
+            let addr = |addrs: &[usize], kind: &str| {
+                addrs
+                    .get(index as usize)
+                    .copied()
+                    .ok_or(anyhow!("Unknown {}: {}", kind, index))
+            };
+            let value = match export.desc.kind()? {
+                ExportKind::Func => ExternVal::Func(addr(&func_addrs, "function")?),
+                ExportKind::Table => ExternVal::Table(addr(&table_addrs, "table")?),
+                ExportKind::Memory => ExternVal::Memory(addr(&mem_addrs, "memory")?),
+                ExportKind::Global => ExternVal::Global(addr(&global_addrs, "global")?),
+            };
+            exports.push((export.name.clone(), value));
+        }
+
+        let start = module
+            .start_section()
+            .map(|start| {
+                let func_idx = start.func_idx;
+                let addr = func_addrs.get(func_idx as usize).copied();
+                addr.ok_or(anyhow!("Unknown function: {}", func_idx))
+            })
+            .transpose()?;
+
+        store.instances.push(InstanceData {
+            module,
+            func_addrs,
+            mem_addrs,
+            table_addrs,
+            global_addrs,
+            exports,
+        });
+
+        if let Some(addr) = start {
+            Machine::new(store).invoke(addr, &[])?;
+        }
+        Ok(Instance { index })
+    }
+
+    /// Instance of a module implemented by the embedder, e.g. the `spectest` module.
+    pub fn host(store: &mut Store, exports: Vec<(String, HostExtern)>) -> Instance {
+        let index = store.instances.len();
+        let mut data = InstanceData {
+            module: Arc::new(ModuleNode::new()),
+            func_addrs: Vec::new(),
+            mem_addrs: Vec::new(),
+            table_addrs: Vec::new(),
+            global_addrs: Vec::new(),
+            exports: Vec::new(),
+        };
+        for (name, host_extern) in exports {
+            let value = match host_extern {
+                HostExtern::Func(func_type, func) => {
+                    let func_idx = data.func_addrs.len() as u32;
+                    let inst = FuncInst::host(func_type, index, func_idx, Some(name.clone()), func);
+                    data.func_addrs.push(store.funcs.len());
+                    store.funcs.push(inst);
+                    ExternVal::Func(store.funcs.len() - 1)
+                }
+                HostExtern::Table(table) => {
+                    data.table_addrs.push(store.tables.len());
+                    store.tables.push(table);
+                    ExternVal::Table(store.tables.len() - 1)
+                }
+                HostExtern::Memory(memory) => {
+                    data.mem_addrs.push(store.memories.len());
+                    store.memories.push(memory);
+                    ExternVal::Memory(store.memories.len() - 1)
+                }
+                HostExtern::Global(global) => {
+                    data.global_addrs.push(store.globals.len());
+                    store.globals.push(global);
+                    ExternVal::Global(store.globals.len() - 1)
+                }
+            };
+            data.exports.push((name, value));
+        }
+        store.instances.push(data);
+        Instance { index }
+    }
+
+    /// Addresses of the objects of the instance in `store`, and its exports.
+    pub fn data<'s>(&self, store: &'s Store) -> &'s InstanceData {
+        &store.instances[self.index]
+    }
+
+    pub fn get_export(&self, store: &Store, name: &str) -> Option<ExternVal> {
+        self.data(store)
+            .exports
+            .iter()
+            .find(|(export_name, _)| export_name == name)
+            .map(|(_, value)| *value)
+    }
+
+    pub fn get_func(&self, store: &Store, name: &str) -> Option<FuncAddr> {
+        match self.get_export(store, name)? {
+            ExternVal::Func(addr) => Some(addr),
+            _ => None,
+        }
+    }
+
+    /// Get the exported function `name`, checking once that its signature is `P -> R`.
+    pub fn get_typed_func<P: WasmParams, R: WasmResults>(
+        &self,
+        store: &Store,
+        name: &str,
+    ) -> Result<TypedFunc<P, R>> {
+        let addr = self
+            .get_func(store, name)
+            .ok_or(ExecError::ExportNotFound(name.to_string()))?;
+        let func_type = &store.funcs[addr].func_type;
+        let (params, results) = (P::val_types(), R::val_types());
+        if func_type.param_type.val_types != params || func_type.result_type.val_types != results {
+            return Err(ExecError::TypeMismatch(format!(
+                "{} is {:?} -> {:?}, not {:?} -> {:?}",
+                name,
+                func_type.param_type.val_types,
+                func_type.result_type.val_types,
+                params,
+                results
+            ))
+            .into());
+        }
+        Ok(TypedFunc::new(addr))
+    }
+
+    /// Address of the exported table `name` in `Store::tables`.
+    pub fn get_table(&self, store: &Store, name: &str) -> Option<TableAddr> {
+        match self.get_export(store, name)? {
+            ExternVal::Table(addr) => Some(addr),
+            _ => None,
+        }
+    }
+
+    /// Address of the exported memory `name` in `Store::memories`.
+    pub fn get_memory(&self, store: &Store, name: &str) -> Option<MemAddr> {
+        match self.get_export(store, name)? {
+            ExternVal::Memory(addr) => Some(addr),
+            _ => None,
+        }
+    }
+
+    /// Address of the exported global `name` in `Store::globals`.
+    pub fn get_global(&self, store: &Store, name: &str) -> Option<GlobalAddr> {
+        match self.get_export(store, name)? {
+            ExternVal::Global(addr) => Some(addr),
+            _ => None,
+        }
+    }
+
+    /// Call the exported function `name`.
+    /// Traps are returned as a `Trap` error.
+    pub fn invoke(&self, store: &mut Store, name: &str, args: &[Value]) -> Result<Vec<Value>> {
+        let addr = self
+            .get_func(store, name)
+            .ok_or(ExecError::ExportNotFound(name.to_string()))?;
+
+        let params = &store.funcs[addr].func_type.param_type.val_types;
+        let arg_types: Vec<_> = args.iter().map(|arg| arg.val_type()).collect();
+        if params != &arg_types {
+            return Err(ExecError::InvalidArguments(format!(
+                "expected {:?}, got {:?}",
+                params, arg_types
+            ))
+            .into());
+        }
+
+        let results = Machine::new(store).invoke(addr, args)?;
+        Ok(results)
+    }
+}
+
+/// Check that `extern_val` matches the type `import` expects.
+/// https://webassembly.github.io/spec/core/valid/types.html#import-subtyping
+fn check_import(
+    store: &Store,
+    func_types: &[FunctionTypeNode],
+    import: &ImportNode,
+    extern_val: &ExternVal,
+) -> Result<()> {
+    let compatible = match (&import.desc, *extern_val) {
+        (ImportDescNode::Func(type_idx), ExternVal::Func(addr)) => {
+            let func_type = func_types
+                .get(*type_idx as usize)
+                .ok_or(anyhow!("Unknown type: {}", type_idx))?;
+            &store.funcs[addr].func_type == func_type
+        }
+        (ImportDescNode::Table(table_type), ExternVal::Table(addr)) => {
+            let table = &store.tables[addr];
+            table.ref_type == table_type.ref_type
+                && limits_match(table.size(), table.max, &table_type.limits)
+        }
+        (ImportDescNode::Memory(mem_type), ExternVal::Memory(addr)) => {
+            let mem = &store.memories[addr];
+            limits_match(mem.size(), mem.max, &mem_type.limits)
+        }
+        (ImportDescNode::Global(global_type), ExternVal::Global(addr)) => {
+            &store.globals[addr].global_type == global_type
+        }
+        _ => false,
+    };
+    if !compatible {
+        let name = format!("{}.{}", import.module, import.name);
+        return Err(ExecError::IncompatibleImportType(name).into());
+    }
+    Ok(())
+}
+
+/// Whether a table or memory of `size` and `max` can be used where `expected` is.
+/// https://webassembly.github.io/spec/core/valid/types.html#limits
+fn limits_match(size: u32, max: Option<u32>, expected: &LimitsNode) -> bool {
+    let max_matches = match (max, expected.max) {
+        (_, None) => true,
+        (Some(max), Some(expected_max)) => max <= expected_max,
+        (None, Some(_)) => false,
+    };
+    size >= expected.min && max_matches
+}
+
+/// Evaluate the initializer of a global.
+/// `global_addrs` holds the globals imported and defined so far, which `global.get` may refer to.
+/// https://webassembly.github.io/spec/core/valid/instructions.html#constant-expressions
+fn eval_const(store: &Store, global_addrs: &[GlobalAddr], expr: &ExprNode) -> Result<Value> {
+    let value = match expr.intrinsics.as_slice() {
+        [IntrinsicNode::I32ConstIntrinsicNode(i)] => Value::I32(i.val),
+        [IntrinsicNode::I64ConstIntrinsicNode(i)] => Value::I64(i.val),
+        [IntrinsicNode::F32ConstIntrinsicNode(f)] => Value::F32(f.val),
+        [IntrinsicNode::F64ConstIntrinsicNode(f)] => Value::F64(f.val),
+        [IntrinsicNode::RefNullIntrinsicNode(r)] => Value::null_of(&r.ref_type),
+        [IntrinsicNode::GlobalGetIntrinsicNode(g)] => {
+            let addr = global_addrs
+                .get(g.global_idx as usize)
+                .ok_or(anyhow!("Unknown global: {}", g.global_idx))?;
+            store.globals[*addr].value
+        }
+        _ => return Err(anyhow!("Constant expression required")),
+    };
+    Ok(value)
+}
+
+/// Lengths of the vectors of a store before an instantiation.
+struct Checkpoint {
+    funcs: usize,
+    memories: usize,
+    tables: usize,
+    globals: usize,
+    instances: usize,
+}
+impl Checkpoint {
+    fn of(store: &Store) -> Checkpoint {
+        Checkpoint {
+            funcs: store.funcs.len(),
+            memories: store.memories.len(),
+            tables: store.tables.len(),
+            globals: store.globals.len(),
+            instances: store.instances.len(),
+        }
+    }
+
+    /// Whether a table or global created before the checkpoint refers to a function after it,
+    /// e.g. one the start function stored in an imported table.
+    fn is_referenced(&self, store: &Store) -> bool {
+        let is_new =
+            |value: &Value| matches!(value, Value::FuncRef(Some(addr)) if *addr >= self.funcs);
+        let tables = store.tables[..self.tables].iter().flat_map(|t| &t.elements);
+        let globals = store.globals[..self.globals].iter().map(|g| &g.value);
+        tables.chain(globals).any(is_new)
+    }
+
+    /// Drop everything created after the checkpoint, releasing its memories and tables.
+    fn restore(self, store: &mut Store) {
+        let memories = store.memories.split_off(self.memories);
+        let tables = store.tables.split_off(self.tables);
+        if let Some(limiter) = store.limiter() {
+            for mem in &memories {
+                limiter.memory_grow_failed(0, mem.data.len());
+            }
+            for table in &tables {
+                limiter.table_grow_failed(0, table.size());
+            }
+        }
+        store.funcs.truncate(self.funcs);
+        store.globals.truncate(self.globals);
+        store.instances.truncate(self.instances);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::buffer::Buffer;
+    use crate::exec::code_section::Op;
+    use crate::exec::fuel::FuelCosts;
+    use crate::exec::limits::{StackLimits, StoreLimits};
+    use crate::exec::store::PAGE_SIZE;
+    use crate::exec::trap::{FrameInfo, Trap, TrapKind};
+    use crate::wat::module::parse_str;
+    use rstest::rstest;
+    use std::fs;
+    use std::time::Duration;
+
+    fn load(path: &str) -> ModuleNode {
+        let mut module = ModuleNode::new();
+        module
+            .load(&mut Buffer::new(&fs::read(path).unwrap()))
+            .unwrap();
+        module
+    }
+
+    fn instantiate(path: &str) -> (Store, Instance) {
+        let mut store = Store::new();
+        let instance = Instance::new(&mut store, load(path)).unwrap();
+        (store, instance)
+    }
+
+    #[rstest(
+        path,
+        name,
+        args,
+        expected,
+        case("examples/add.wasm", "add", vec![Value::I32(1), Value::I32(2)], vec![Value::I32(3)]),
+        case("examples/call.wasm", "add42", vec![Value::I32(5)], vec![Value::I32(47)]),
+        case("examples/if.wasm", "ge10", vec![Value::I32(10)], vec![Value::I32(1)]),
+        case("examples/if.wasm", "ge10", vec![Value::I32(9)], vec![Value::I32(0)]),
+        case("examples/loop.wasm", "loop", vec![], vec![Value::I32(42)])
+    )]
+    fn test_invoke(path: &str, name: &str, args: Vec<Value>, expected: Vec<Value>) {
+        let (mut store, instance) = instantiate(path);
+        assert_eq!(instance.invoke(&mut store, name, &args).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_invoke_invalid_arguments() {
+        let (mut store, instance) = instantiate("examples/add.wasm");
+        assert!(instance
+            .invoke(&mut store, "add", &[Value::I32(1)])
+            .is_err());
+        assert!(instance.invoke(&mut store, "sub", &[]).is_err());
+    }
+
+    #[rstest(
+        args,
+        kind,
+        case(vec![Value::I32(1), Value::I32(0)], TrapKind::IntegerDivideByZero),
+        case(vec![Value::I32(i32::MIN), Value::I32(-1)], TrapKind::IntegerOverflow)
+    )]
+    fn test_trap_backtrace(args: Vec<Value>, kind: TrapKind) {
+        let (mut store, instance) = instantiate("examples/trap.wasm");
+        let err = instance.invoke(&mut store, "div", &args).unwrap_err();
+        let trap = err.downcast::<Trap>().unwrap();
+
+        assert_eq!(trap.kind, kind);
+        assert_eq!(
+            trap.backtrace,
+            vec![
+                FrameInfo {
+                    func_idx: 0,
+                    func_name: Some("div_s".to_string()),
+                    offset: 0x3a,
+                },
+                FrameInfo {
+                    func_idx: 1,
+                    func_name: Some("div".to_string()),
+                    offset: 0x42,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trap_unreachable() {
+        let (mut store, instance) = instantiate("examples/trap.wasm");
+        let err = instance.invoke(&mut store, "unreachable", &[]).unwrap_err();
+        let trap = err.downcast::<Trap>().unwrap();
+
+        assert_eq!(trap.kind, TrapKind::Unreachable);
+        assert_eq!(trap.backtrace.len(), 1);
+        assert_eq!(trap.backtrace[0].func_name, None);
+    }
+
+    #[rstest(
+        name,
+        arg,
+        expected,
+        case("i32_trunc_f32_s", Value::F32(-2_147_483_648.0), Ok(Value::I32(i32::MIN))),
+        case("i32_trunc_f32_s", Value::F32(2_147_483_648.0), Err(TrapKind::IntegerOverflow)),
+        case("i32_trunc_f64_u", Value::F64(-0.9), Ok(Value::I32(0))),
+        case("i32_trunc_f64_u", Value::F64(-1.0), Err(TrapKind::IntegerOverflow)),
+        case("i64_trunc_f64_s", Value::F64(f64::NAN), Err(TrapKind::InvalidConversionToInteger)),
+        case("i64_trunc_f64_u", Value::F64(1e19), Ok(Value::I64(10_000_000_000_000_000_000u64 as i64)))
+    )]
+    fn test_trunc(name: &str, arg: Value, expected: Result<Value, TrapKind>) {
+        let wat = r#"(module
+          (func (export "i32_trunc_f32_s") (param f32) (result i32)
+            (i32.trunc_f32_s (local.get 0)))
+          (func (export "i32_trunc_f64_u") (param f64) (result i32)
+            (i32.trunc_f64_u (local.get 0)))
+          (func (export "i64_trunc_f64_s") (param f64) (result i64)
+            (i64.trunc_f64_s (local.get 0)))
+          (func (export "i64_trunc_f64_u") (param f64) (result i64)
+            (i64.trunc_f64_u (local.get 0))))
+        "#;
+        let mut module = ModuleNode::new();
+        module
+            .load(&mut Buffer::new(&parse_str(wat).unwrap()))
+            .unwrap();
+        let mut store = Store::new();
+        let instance = Instance::new(&mut store, module).unwrap();
+        let result = instance
+            .invoke(&mut store, name, &[arg])
+            .map(|results| results[0])
+            .map_err(|err| err.downcast::<Trap>().unwrap().kind);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn test_call_indirect() {
+        let wat = r#"(module
+          (type $unary (func (param i32) (result i32)))
+          (type $nullary (func (result i32)))
+          (table (export "table") 3 funcref)
+          (func $double (export "double") (param i32) (result i32)
+            (i32.add (local.get 0) (local.get 0)))
+          (func (export "call") (param i32 i32) (result i32)
+            (call_indirect (type $unary) (local.get 1) (local.get 0)))
+          (func (export "call_nullary") (param i32) (result i32)
+            (call_indirect 0 (type $nullary) (local.get 0))))
+        "#;
+        let mut module = ModuleNode::new();
+        module
+            .load(&mut Buffer::new(&parse_str(wat).unwrap()))
+            .unwrap();
+        let mut store = Store::new();
+        let instance = Instance::new(&mut store, module).unwrap();
+        let table = instance.get_table(&store, "table").unwrap();
+        let double = instance.get_func(&store, "double").unwrap();
+        store.tables[table]
+            .set(0, Value::FuncRef(Some(double)))
+            .unwrap();
+
+        let mut call = |name, args: &[Value]| {
+            instance
+                .invoke(&mut store, name, args)
+                .map_err(|err| err.downcast::<Trap>().unwrap().kind)
+        };
+        assert_eq!(
+            call("call", &[Value::I32(0), Value::I32(21)]),
+            Ok(vec![Value::I32(42)])
+        );
+        assert_eq!(
+            call("call", &[Value::I32(1), Value::I32(21)]),
+            Err(TrapKind::UninitializedElement)
+        );
+        assert_eq!(
+            call("call", &[Value::I32(3), Value::I32(21)]),
+            Err(TrapKind::UndefinedElement)
+        );
+        assert_eq!(
+            call("call_nullary", &[Value::I32(0)]),
+            Err(TrapKind::IndirectCallTypeMismatch)
+        );
+    }
+
+    #[test]
+    fn test_fuel() {
+        let (mut store, instance) = instantiate("examples/loop.wasm");
+        store.set_fuel(80);
+        let results = instance.invoke(&mut store, "loop", &[]).unwrap();
+
+        assert_eq!(results, vec![Value::I32(42)]);
+        assert_eq!(store.fuel_consumed(), 50);
+        assert_eq!(store.fuel_remaining(), Some(30));
+
+        let err = instance.invoke(&mut store, "loop", &[]).unwrap_err();
+        let trap = err.downcast::<Trap>().unwrap();
+        assert_eq!(trap.kind, TrapKind::OutOfFuel);
+        assert_eq!(store.fuel_remaining(), Some(0));
+    }
+
+    #[test]
+    fn test_fuel_costs() {
+        let (mut store, instance) = instantiate("examples/loop.wasm");
+        let mut costs = FuelCosts::new();
+        costs.set(Op::I32Add, 0);
+        costs.set(Op::Br, 10);
+        store.set_fuel_costs(costs);
+        instance.invoke(&mut store, "loop", &[]).unwrap();
+
+        // 6 additions are free and 3 branches cost 9 more each.
+        assert_eq!(store.fuel_consumed(), 50 - 6 + 3 * 9);
+        assert_eq!(store.fuel_remaining(), None);
+    }
+
+    #[test]
+    fn test_interrupt_from_another_thread() {
+        let (mut store, instance) = instantiate("examples/spin.wasm");
+        let handle = store.interrupt_handle();
+        let interrupter = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            handle.interrupt();
+        });
+
+        let err = instance.invoke(&mut store, "spin", &[]).unwrap_err();
+        interrupter.join().unwrap();
+        let trap = err.downcast::<Trap>().unwrap();
+        assert_eq!(trap.kind, TrapKind::Interrupted);
+        assert_eq!(trap.backtrace[0].offset, 0x23);
+
+        // The request is consumed by the interrupted execution.
+        let (mut store, instance) = instantiate("examples/add.wasm");
+        store.interrupt_handle().interrupt();
+        let args = [Value::I32(1), Value::I32(2)];
+        assert!(instance.invoke(&mut store, "add", &args).is_err());
+        assert!(instance.invoke(&mut store, "add", &args).is_ok());
+    }
+
+    #[test]
+    fn test_interrupt_after() {
+        let (mut store, instance) = instantiate("examples/add.wasm");
+        let args = [Value::I32(1), Value::I32(2)];
+        let timeout = Duration::from_millis(50);
+
+        // A call finishing before the timeout leaves the next one alone.
+        let timer = store.interrupt_handle().interrupt_after(timeout);
+        assert!(instance.invoke(&mut store, "add", &args).is_ok());
+        drop(timer);
+        std::thread::sleep(timeout * 2);
+        assert!(instance.invoke(&mut store, "add", &args).is_ok());
+
+        // Neither does a timeout that fired after the call but before the timer was dropped.
+        let timer = store.interrupt_handle().interrupt_after(Duration::ZERO);
+        std::thread::sleep(timeout);
+        drop(timer);
+        assert!(instance.invoke(&mut store, "add", &args).is_ok());
+
+        let (mut store, instance) = instantiate("examples/spin.wasm");
+        let _timer = store
+            .interrupt_handle()
+            .interrupt_after(Duration::from_millis(10));
+        let trap = instance.invoke(&mut store, "spin", &[]).unwrap_err();
+        assert_eq!(trap.downcast::<Trap>().unwrap().kind, TrapKind::Interrupted);
+    }
+
+    #[rstest(
+        n,
+        max_call_depth,
+        max_value_stack,
+        expected,
+        case(1_000, 10_000, 1 << 20, Ok(1_000)),
+        case(100_000, 10_000, 1 << 20, Err(TrapKind::StackOverflow)),
+        case(100_000, 200_000, 1 << 20, Ok(100_000)),
+        case(1_000, 10_000, 1_000, Err(TrapKind::StackOverflow))
+    )]
+    fn test_deep_recursion(
+        n: i32,
+        max_call_depth: usize,
+        max_value_stack: usize,
+        expected: Result<i32, TrapKind>,
+    ) {
+        let (mut store, instance) = instantiate("examples/recursion.wasm");
+        store.set_stack_limits(StackLimits {
+            max_call_depth,
+            max_value_stack,
+        });
+
+        let result = instance
+            .invoke(&mut store, "count", &[Value::I32(n)])
+            .map(|results| results[0])
+            .map_err(|err| err.downcast::<Trap>().unwrap().kind);
+        assert_eq!(result, expected.map(Value::I32));
+    }
+
+    #[test]
+    fn test_memory() {
+        let (mut store, instance) = instantiate("examples/memory.wasm");
+        let mut invoke = |name: &str, args: &[Value]| instance.invoke(&mut store, name, args);
+
+        invoke("store", &[Value::I32(PAGE_SIZE as i32 - 4), Value::I32(-2)]).unwrap();
+        let results = invoke("load", &[Value::I32(PAGE_SIZE as i32 - 4)]).unwrap();
+        assert_eq!(results, vec![Value::I32(-2)]);
+
+        let err = invoke("load", &[Value::I32(PAGE_SIZE as i32 - 3)]).unwrap_err();
+        let trap = err.downcast::<Trap>().unwrap();
+        assert_eq!(trap.kind, TrapKind::MemoryOutOfBounds);
+    }
+
+    #[rstest(
+        limits,
+        name,
+        delta,
+        expected,
+        size,
+        // The memory has a maximum of 4 pages.
+        case(StoreLimits::default(), "grow", 3, 1, "size"),
+        case(StoreLimits::default(), "grow", 4, -1, "size"),
+        case(StoreLimits { max_memory_size: Some(2 * PAGE_SIZE), ..Default::default() }, "grow", 2, -1, "size"),
+        case(StoreLimits { max_memory_size: Some(2 * PAGE_SIZE), ..Default::default() }, "grow", 1, 1, "size"),
+        case(StoreLimits::default(), "grow_table", 10, 1, "table_size"),
+        case(StoreLimits { max_table_elements: Some(10), ..Default::default() }, "grow_table", 10, -1, "table_size"),
+        case(StoreLimits { max_table_elements: Some(10), ..Default::default() }, "grow_table", 9, 1, "table_size")
+    )]
+    fn test_resource_limiter(
+        limits: StoreLimits,
+        name: &str,
+        delta: i32,
+        expected: i32,
+        size: &str,
+    ) {
+        let mut store = Store::new();
+        store.set_limiter(limits);
+        let instance = Instance::new(&mut store, load("examples/memory.wasm")).unwrap();
+
+        let results = instance
+            .invoke(&mut store, name, &[Value::I32(delta)])
+            .unwrap();
+        assert_eq!(results, vec![Value::I32(expected)]);
+
+        let results = instance.invoke(&mut store, size, &[]).unwrap();
+        let grown = if expected == -1 { 0 } else { delta };
+        assert_eq!(results, vec![Value::I32(1 + grown)]);
+    }
+
+    #[test]
+    fn test_huge_growth_fails() {
+        let wat = r#"(module
+          (memory 1)
+          (table 1 funcref)
+          (func (export "grow_memory") (param i32) (result i32)
+            (memory.grow (local.get 0)))
+          (func (export "grow_table") (param i32) (result i32)
+            (table.grow (ref.null func) (local.get 0))))
+        "#;
+        let mut module = ModuleNode::new();
+        module
+            .load(&mut Buffer::new(&parse_str(wat).unwrap()))
+            .unwrap();
+        let mut store = Store::new();
+        let instance = Instance::new(&mut store, module).unwrap();
+        let mut grow = |name, delta| instance.invoke(&mut store, name, &[Value::I32(delta)]);
+
+        assert_eq!(grow("grow_table", 2_000_000_000).unwrap(), [Value::I32(-1)]);
+        assert_eq!(grow("grow_memory", 65_000).unwrap(), [Value::I32(-1)]);
+        assert_eq!(grow("grow_table", 1).unwrap(), [Value::I32(1)]);
+    }
+
+    #[test]
+    fn test_resource_limiter_totals() {
+        let wat = r#"(module
+          (table $a 5 funcref)
+          (table $b 5 funcref)
+          (func (export "grow_a") (param i32) (result i32)
+            (table.grow $a (ref.null func) (local.get 0)))
+          (func (export "grow_b") (param i32) (result i32)
+            (table.grow $b (ref.null func) (local.get 0))))
+        "#;
+        let module = || {
+            let mut module = ModuleNode::new();
+            module
+                .load(&mut Buffer::new(&parse_str(wat).unwrap()))
+                .unwrap();
+            module
+        };
+        let limits = |max| StoreLimits {
+            max_table_elements: Some(max),
+            ..Default::default()
+        };
+
+        // The tables fit one at a time, not together.
+        let mut store = Store::new();
+        store.set_limiter(limits(8));
+        assert!(Instance::new(&mut store, module()).is_err());
+
+        let mut store = Store::new();
+        store.set_limiter(limits(12));
+        let instance = Instance::new(&mut store, module()).unwrap();
+        let mut grow = |name, delta| instance.invoke(&mut store, name, &[Value::I32(delta)]);
+        assert_eq!(grow("grow_a", 2).unwrap(), [Value::I32(5)]);
+        assert_eq!(grow("grow_b", 1).unwrap(), [Value::I32(-1)]);
+    }
+
+    #[test]
+    fn test_failed_instantiation_releases_resources() {
+        let module = |wat| {
+            let mut module = ModuleNode::new();
+            module
+                .load(&mut Buffer::new(&parse_str(wat).unwrap()))
+                .unwrap();
+            module
+        };
+        let trapping = r#"(module
+          (memory 1)
+          (table 10 funcref)
+          (func $start unreachable)
+          (start $start))
+        "#;
+        let mut store = Store::new();
+        store.set_limiter(StoreLimits {
+            max_memory_size: Some(PAGE_SIZE),
+            max_table_elements: Some(10),
+            ..Default::default()
+        });
+
+        for _ in 0..3 {
+            assert!(Instance::new(&mut store, module(trapping)).is_err());
+            assert!(store.memories.is_empty());
+            assert!(store.tables.is_empty());
+            assert!(store.funcs.is_empty());
+            assert!(store.instances.is_empty());
+        }
+        let fitting = "(module (memory 1) (table 10 funcref))";
+        assert!(Instance::new(&mut store, module(fitting)).is_ok());
+    }
+
+    #[test]
+    fn test_resource_limiter_denies_instantiation() {
+        let mut store = Store::new();
+        store.set_limiter(StoreLimits {
+            max_table_elements: Some(0),
+            ..Default::default()
+        });
+        assert!(Instance::new(&mut store, load("examples/memory.wasm")).is_err());
+    }
+
+    #[test]
+    fn test_exported_memory() {
+        let (mut store, instance) = instantiate("examples/exports.wasm");
+        let addr = instance.get_memory(&store, "memory").unwrap();
+        assert_eq!(instance.get_memory(&store, "table"), None);
+
+        let memory = &mut store.memories[addr];
+        memory.write_bytes(8, &[1, 2, 3, 4]).unwrap();
+        assert_eq!(memory.read::<u32>(8), Ok(0x04030201));
+        memory.write(16, -1.5_f64).unwrap();
+        assert_eq!(memory.read::<f64>(16), Ok(-1.5));
+        assert_eq!(memory.read_bytes(16, 2), Ok(&[0, 0][..]));
+        let end = PAGE_SIZE - 2;
+        assert_eq!(memory.read::<i32>(end), Err(TrapKind::MemoryOutOfBounds));
+        assert_eq!(memory.write(end, 0_i32), Err(TrapKind::MemoryOutOfBounds));
+
+        memory.write(PAGE_SIZE - 4, 7_i32).unwrap();
+        let args = [Value::I32(PAGE_SIZE as i32 - 4)];
+        let results = instance.invoke(&mut store, "load", &args).unwrap();
+        assert_eq!(results, vec![Value::I32(7)]);
+
+        assert_eq!(store.grow_memory(addr, 1), Some(1));
+        assert_eq!(store.memories[addr].read::<i32>(end), Ok(0));
+    }
+
+    #[test]
+    fn test_exported_globals() {
+        let (mut store, instance) = instantiate("examples/exports.wasm");
+        let counter = instance.get_global(&store, "counter").unwrap();
+        let answer = instance.get_global(&store, "answer").unwrap();
+        assert_eq!(store.globals[answer].get(), Value::I64(42));
+
+        instance.invoke(&mut store, "incr", &[]).unwrap();
+        assert_eq!(store.globals[counter].get(), Value::I32(1));
+
+        store.globals[counter].set(Value::I32(10)).unwrap();
+        let results = instance.invoke(&mut store, "incr", &[]).unwrap();
+        assert_eq!(results, vec![Value::I32(11)]);
+
+        assert!(store.globals[counter].set(Value::I64(10)).is_err());
+        assert!(store.globals[answer].set(Value::I64(0)).is_err());
+        assert_eq!(store.globals[answer].get(), Value::I64(42));
+    }
+
+    #[test]
+    fn test_exported_table() {
+        let (mut store, instance) = instantiate("examples/exports.wasm");
+        let addr = instance.get_table(&store, "table").unwrap();
+        let func = instance.get_func(&store, "incr").unwrap();
+
+        let table = &mut store.tables[addr];
+        assert_eq!(table.get(1), Ok(Value::FuncRef(None)));
+        table.set(1, Value::FuncRef(Some(func))).unwrap();
+        assert_eq!(table.get(1), Ok(Value::FuncRef(Some(func))));
+        assert_eq!(table.get(2), Err(TrapKind::TableOutOfBounds));
+        assert_eq!(table.set(0, Value::I32(0)), Err(TrapKind::TypeMismatch));
+
+        let init = Value::FuncRef(Some(func));
+        assert_eq!(store.grow_table(addr, 3, init).unwrap(), Some(2));
+        assert_eq!(store.tables[addr].get(4), Ok(init));
+        assert!(store.grow_table(addr, 1, Value::ExternRef(None)).is_err());
+    }
+
+    #[rstest(
+        size,
+        max,
+        expected_min,
+        expected_max,
+        matches,
+        case(1, None, 1, None, true),
+        case(2, None, 1, None, true),
+        case(1, None, 2, None, false),
+        case(1, Some(2), 1, Some(2), true),
+        case(1, Some(2), 1, Some(3), true),
+        case(1, Some(3), 1, Some(2), false),
+        case(1, None, 1, Some(2), false),
+        case(1, Some(2), 1, None, true)
+    )]
+    fn test_limits_match(
+        size: u32,
+        max: Option<u32>,
+        expected_min: u32,
+        expected_max: Option<u32>,
+        matches: bool,
+    ) {
+        let expected = LimitsNode {
+            min: expected_min,
+            max: expected_max,
+        };
+        assert_eq!(limits_match(size, max, &expected), matches);
+    }
+}
@@ -0,0 +1,69 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Handle to stop the execution of a `Store` from another thread.
+/// The running code stops with an `Interrupted` trap at the next loop
+/// back-edge or call. If nothing is running, the next execution is interrupted.
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+    flag: Arc<AtomicBool>,
+}
+impl InterruptHandle {
+    pub fn new() -> InterruptHandle {
+        InterruptHandle::default()
+    }
+
+    pub fn interrupt(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    /// Interrupt after `timeout` has elapsed, from a background thread,
+    /// unless the returned timer is dropped first.
+    pub fn interrupt_after(&self, timeout: Duration) -> InterruptTimer {
+        let handle = self.clone();
+        let (cancel, cancelled) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || {
+            // Dropping the sender disconnects the channel, waking the thread up early.
+            let fired = cancelled.recv_timeout(timeout) == Err(RecvTimeoutError::Timeout);
+            if fired {
+                handle.interrupt();
+            }
+            fired
+        });
+        InterruptTimer {
+            handle: self.clone(),
+            cancel: Some(cancel),
+            thread: Some(thread),
+        }
+    }
+
+    /// Return whether an interruption was requested, clearing the request.
+    pub fn take(&self) -> bool {
+        self.flag.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Pending interruption of `InterruptHandle::interrupt_after`, cancelled on drop.
+/// An interruption that fired but was not consumed by an execution is cleared too,
+/// so that it cannot stop a later one.
+#[derive(Debug)]
+pub struct InterruptTimer {
+    handle: InterruptHandle,
+    cancel: Option<Sender<()>>,
+    thread: Option<JoinHandle<bool>>,
+}
+impl Drop for InterruptTimer {
+    fn drop(&mut self) {
+        self.cancel.take();
+        let fired = self
+            .thread
+            .take()
+            .is_some_and(|t| t.join().unwrap_or(false));
+        if fired {
+            self.handle.take();
+        }
+    }
+}
@@ -0,0 +1,92 @@
+/// Bounds on the stacks of the interpreter.
+/// Exceeding either of them raises a `StackOverflow` trap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StackLimits {
+    /// Maximum number of active calls.
+    pub max_call_depth: usize,
+    /// Maximum number of values on the operand stack, including the locals of active calls.
+    pub max_value_stack: usize,
+}
+impl Default for StackLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl StackLimits {
+    pub fn new() -> StackLimits {
+        StackLimits {
+            max_call_depth: 10_000,
+            max_value_stack: 1 << 20,
+        }
+    }
+}
+
+/// Size up to which the memories can grow in a store without a limiter, in bytes.
+pub const DEFAULT_MAX_MEMORY_SIZE: usize = 1 << 30;
+/// Number of elements up to which the tables can grow in a store without a limiter.
+pub const DEFAULT_MAX_TABLE_ELEMENTS: u32 = 10_000_000;
+
+/// Hook consulted whenever a memory or a table is created or grown,
+/// so the embedder can cap the resources a store uses.
+pub trait ResourceLimiter: Send {
+    /// Sizes are in bytes. Returning false denies the growth.
+    fn memory_growing(&mut self, current: usize, desired: usize, maximum: Option<usize>) -> bool;
+
+    /// Sizes are in elements. Returning false denies the growth.
+    fn table_growing(&mut self, current: u32, desired: u32, maximum: Option<u32>) -> bool;
+
+    /// A growth allowed by `memory_growing` failed, the memory stays `current` bytes long.
+    /// Also called with `current` 0 when a failed instantiation drops the memory.
+    fn memory_grow_failed(&mut self, _current: usize, _desired: usize) {}
+
+    /// A growth allowed by `table_growing` failed, the table stays `current` elements long.
+    /// Also called with `current` 0 when a failed instantiation drops the table.
+    fn table_grow_failed(&mut self, _current: u32, _desired: u32) {}
+}
+
+/// `ResourceLimiter` capping the total size of the memories and of the tables it is consulted
+/// for, which are those of a single instance when the store holds only one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StoreLimits {
+    /// Maximum size of the linear memories together, in bytes.
+    pub max_memory_size: Option<usize>,
+    /// Maximum number of elements of the tables together.
+    pub max_table_elements: Option<u32>,
+    /// Size of the memories allowed so far, in bytes.
+    pub memory_size: usize,
+    /// Number of elements of the tables allowed so far.
+    pub table_elements: u32,
+}
+impl ResourceLimiter for StoreLimits {
+    fn memory_growing(&mut self, current: usize, desired: usize, _maximum: Option<usize>) -> bool {
+        let total = self.memory_size.saturating_sub(current) + desired;
+        if self.max_memory_size.is_some_and(|max| total > max) {
+            return false;
+        }
+        self.memory_size = total;
+        true
+    }
+
+    fn table_growing(&mut self, current: u32, desired: u32, _maximum: Option<u32>) -> bool {
+        let Some(total) = self
+            .table_elements
+            .saturating_sub(current)
+            .checked_add(desired)
+        else {
+            return false;
+        };
+        if self.max_table_elements.is_some_and(|max| total > max) {
+            return false;
+        }
+        self.table_elements = total;
+        true
+    }
+
+    fn memory_grow_failed(&mut self, current: usize, desired: usize) {
+        self.memory_size = self.memory_size.saturating_sub(desired) + current;
+    }
+
+    fn table_grow_failed(&mut self, current: u32, desired: u32) {
+        self.table_elements = self.table_elements.saturating_sub(desired) + current;
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::Result;
+use std::collections::HashMap;
+
+use crate::errors::ExecError;
+use crate::exec::instance::Instance;
+use crate::exec::module::ModuleNode;
+use crate::exec::store::{ExternVal, Store};
+
+/// Resolves the imports of a module to the exports of the instances registered by name.
+#[derive(Debug, Clone, Default)]
+pub struct Linker {
+    instances: HashMap<String, Instance>,
+}
+impl Linker {
+    pub fn new() -> Linker {
+        Linker::default()
+    }
+
+    /// Make the exports of `instance` importable from the module `name`,
+    /// replacing any instance previously registered under that name.
+    pub fn register(&mut self, name: &str, instance: Instance) {
+        self.instances.insert(name.to_string(), instance);
+    }
+
+    pub fn get_instance(&self, name: &str) -> Option<Instance> {
+        self.instances.get(name).copied()
+    }
+
+    /// Export `name` of the instance registered as `module`.
+    pub fn get(&self, store: &Store, module: &str, name: &str) -> Option<ExternVal> {
+        self.instances.get(module)?.get_export(store, name)
+    }
+
+    /// Instantiate `module`, checking the type of every import against the export it resolves to.
+    pub fn instantiate(&self, store: &mut Store, module: ModuleNode) -> Result<Instance> {
+        let imports = module.import_section().map_or(&[][..], |i| &i.imports);
+        let externs = imports
+            .iter()
+            .map(|import| {
+                self.get(store, &import.module, &import.name)
+                    .ok_or(ExecError::UnknownImport(format!(
+                        "{}.{}",
+                        import.module, import.name
+                    )))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Instance::with_imports(store, module, &externs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::buffer::Buffer;
+    use crate::exec::instance::HostExtern;
+    use crate::exec::store::HostFunc;
+    use crate::exec::trap::{Trap, TrapKind};
+    use crate::exec::type_section::{FunctionTypeNode, ValType};
+    use crate::exec::value::Value;
+    use crate::wat::module::parse_str;
+    use std::fs;
+    use std::sync::Arc;
+
+    fn load(path: &str) -> ModuleNode {
+        let mut module = ModuleNode::new();
+        module
+            .load(&mut Buffer::new(&fs::read(path).unwrap()))
+            .unwrap();
+        module
+    }
+
+    fn link() -> (Store, Instance, Instance) {
+        let mut store = Store::new();
+        let mut linker = Linker::new();
+        let lib = linker
+            .instantiate(&mut store, load("examples/lib.wasm"))
+            .unwrap();
+        linker.register("lib", lib);
+        let main = linker
+            .instantiate(&mut store, load("examples/main.wasm"))
+            .unwrap();
+        (store, lib, main)
+    }
+
+    #[test]
+    fn test_imported_func_and_global() {
+        let (mut store, lib, main) = link();
+        let results = main.invoke(&mut store, "add_counter", &[Value::I32(5)]);
+        assert_eq!(results.unwrap(), vec![Value::I32(15)]);
+
+        main.invoke(&mut store, "bump", &[]).unwrap();
+        let results = lib.invoke(&mut store, "incr", &[]).unwrap();
+        assert_eq!(results, vec![Value::I32(12)]);
+    }
+
+    #[test]
+    fn test_imported_memory_and_table() {
+        let (mut store, lib, main) = link();
+        let args = [Value::I32(4), Value::I32(-7)];
+        main.invoke(&mut store, "store", &args).unwrap();
+        let addr = lib.get_memory(&store, "memory").unwrap();
+        assert_eq!(store.memories[addr].read::<i32>(4), Ok(-7));
+
+        let addr = lib.get_table(&store, "table").unwrap();
+        store.grow_table(addr, 2, Value::FuncRef(None)).unwrap();
+        let results = main.invoke(&mut store, "table_size", &[]).unwrap();
+        assert_eq!(results, vec![Value::I32(3)]);
+    }
+
+    #[test]
+    fn test_unknown_import() {
+        let mut store = Store::new();
+        let linker = Linker::new();
+        let err = linker
+            .instantiate(&mut store, load("examples/main.wasm"))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast::<ExecError>().unwrap(),
+            ExecError::UnknownImport(name) if name == "lib.add"
+        ));
+    }
+
+    #[test]
+    fn test_incompatible_import_type() {
+        let (mut store, lib, _) = link();
+        let get = |name| lib.get_export(&store, name).unwrap();
+        // `incr` does not have the signature of `add`.
+        let externs = [get("incr"), get("memory"), get("table"), get("counter")];
+        let err =
+            Instance::with_imports(&mut store, load("examples/main.wasm"), &externs).unwrap_err();
+        assert!(matches!(
+            err.downcast::<ExecError>().unwrap(),
+            ExecError::IncompatibleImportType(name) if name == "lib.add"
+        ));
+    }
+
+    #[test]
+    fn test_host_func() {
+        let mut store = Store::new();
+        let mut func_type = FunctionTypeNode::new();
+        func_type.param_type.val_types = vec![ValType::I32];
+        func_type.result_type.val_types = vec![ValType::I32];
+        let double: HostFunc = Arc::new(|_, args| match args {
+            [Value::I32(0)] => Err(Trap::new(TrapKind::Unreachable)),
+            [Value::I32(v)] => Ok(vec![Value::I32(v * 2)]),
+            _ => Err(Trap::new(TrapKind::TypeMismatch)),
+        });
+        let host = Instance::host(
+            &mut store,
+            vec![("double".to_string(), HostExtern::Func(func_type, double))],
+        );
+        let mut linker = Linker::new();
+        linker.register("host", host);
+        let wat = r#"
+            (import "host" "double" (func $double (param i32) (result i32)))
+            (func (export "quadruple") (param i32) (result i32)
+              (call $double (call $double (local.get 0))))
+        "#;
+        let mut module = ModuleNode::new();
+        module
+            .load(&mut Buffer::new(&parse_str(wat).unwrap()))
+            .unwrap();
+        let instance = linker.instantiate(&mut store, module).unwrap();
+
+        let results = instance.invoke(&mut store, "quadruple", &[Value::I32(3)]);
+        assert_eq!(results.unwrap(), vec![Value::I32(12)]);
+        let err = instance
+            .invoke(&mut store, "quadruple", &[Value::I32(0)])
+            .unwrap_err();
+        assert_eq!(err.downcast::<Trap>().unwrap().kind, TrapKind::Unreachable);
+        let results = host.invoke(&mut store, "double", &[Value::I32(5)]);
+        assert_eq!(results.unwrap(), vec![Value::I32(10)]);
+    }
+}
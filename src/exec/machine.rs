@@ -0,0 +1,754 @@
+use crate::exec::code_section::{BlockType, ExprNode, IntrinsicNode};
+use crate::exec::fuel::Fuel;
+use crate::exec::interrupt::InterruptHandle;
+use crate::exec::limits::{ResourceLimiter, StackLimits};
+use crate::exec::store::{
+    Caller, FuncAddr, FuncInst, GlobalInst, InstanceData, MemInst, Store, TableInst,
+};
+use crate::exec::trace::{CallEvent, InstructionEvent, Tracer};
+use crate::exec::trap::{FrameInfo, Trap, TrapKind};
+use crate::exec::type_section::ValType;
+use crate::exec::typed_func::{WasmParams, WasmResults};
+use crate::exec::value::Value;
+
+/// Block being executed, i.e. the target of `br` instructions.
+#[derive(Debug)]
+struct Label<'a> {
+    expr: &'a ExprNode,
+    /// Index of the next intrinsic of `expr` to execute.
+    pc: usize,
+    /// Number of values a branch to this label carries, the params of a loop.
+    branch_arity: usize,
+    /// Number of values left when falling through the end of the label.
+    end_arity: usize,
+    /// Height of the operand stack when the label was entered.
+    height: usize,
+    is_loop: bool,
+}
+
+#[derive(Debug)]
+struct Frame<'a> {
+    func: FuncAddr,
+    locals: Vec<Value>,
+    /// The body of the function is the bottom label.
+    labels: Vec<Label<'a>>,
+}
+
+/// Where a paused `Machine` resumes, see `Machine::position`.
+#[derive(Debug, Clone, Copy)]
+pub struct Position<'a> {
+    pub func: FuncAddr,
+    pub func_idx: u32,
+    /// Offset of the next instruction in the module,
+    /// `None` when the next step leaves the innermost block.
+    pub offset: Option<u32>,
+    pub intrinsic: Option<&'a IntrinsicNode>,
+}
+
+/// Interpreter walking the decoded `ExprNode` trees.
+/// Calls and blocks are kept on explicit stacks so deep Wasm recursion
+/// never recurses on the host stack.
+pub struct Machine<'a> {
+    funcs: &'a [FuncInst],
+    memories: &'a mut [MemInst],
+    tables: &'a mut [TableInst],
+    globals: &'a mut [GlobalInst],
+    instances: &'a [InstanceData],
+    limiter: &'a mut Option<Box<dyn ResourceLimiter>>,
+    fuel: &'a mut Fuel,
+    interrupt: &'a InterruptHandle,
+    limits: &'a StackLimits,
+    tracer: &'a mut Option<Box<dyn Tracer>>,
+    stack: Vec<Value>,
+    frames: Vec<Frame<'a>>,
+    /// Number of locals of every frame, counted against `max_value_stack`.
+    locals_len: usize,
+}
+impl<'a> Machine<'a> {
+    pub fn new(store: &'a mut Store) -> Machine<'a> {
+        let Store {
+            funcs,
+            memories,
+            tables,
+            globals,
+            instances,
+            limiter,
+            fuel,
+            interrupt,
+            stack_limits,
+            tracer,
+        } = store;
+        Machine {
+            funcs,
+            memories,
+            tables,
+            globals,
+            instances,
+            limiter,
+            fuel,
+            interrupt,
+            limits: stack_limits,
+            tracer,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            locals_len: 0,
+        }
+    }
+
+    /// Call the function at `addr` and run it to completion.
+    pub fn invoke(&mut self, addr: FuncAddr, args: &[Value]) -> Result<Vec<Value>, Trap> {
+        self.stack.extend_from_slice(args);
+        self.run(addr)?;
+        Ok(self.results(addr))
+    }
+
+    /// Call the function at `addr` without running it, to execute it with `step`.
+    pub fn start(&mut self, addr: FuncAddr, args: &[Value]) -> Result<(), Trap> {
+        self.stack.extend_from_slice(args);
+        self.push_frame(addr).map_err(|kind| self.trap(kind))
+    }
+
+    /// Whether the started function returned, or trapped.
+    pub fn is_finished(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Results of the function at `addr` once it returned.
+    pub fn results(&mut self, addr: FuncAddr) -> Vec<Value> {
+        let arity = self.funcs[addr].func_type.result_type.val_types.len();
+        self.stack.split_off(self.stack.len().saturating_sub(arity))
+    }
+
+    /// Like `invoke`, moving the arguments and results straight from and to the operand stack.
+    /// The signature of the function must have been checked against `P` and `R`.
+    pub fn invoke_typed<P: WasmParams, R: WasmResults>(
+        &mut self,
+        addr: FuncAddr,
+        params: P,
+    ) -> Result<R, Trap> {
+        params.push(&mut self.stack);
+        self.run(addr)?;
+        R::pop(&mut self.stack).ok_or(Trap::new(TrapKind::TypeMismatch))
+    }
+
+    /// Run the function at `addr` to completion, its arguments being on top of the stack.
+    fn run(&mut self, addr: FuncAddr) -> Result<(), Trap> {
+        self.push_frame(addr).map_err(|kind| self.trap(kind))?;
+        while !self.frames.is_empty() {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Execute a single intrinsic, or leave the innermost block if it has none left.
+    /// A trap unwinds every active function.
+    pub fn step(&mut self) -> Result<(), Trap> {
+        let result = self.execute_next();
+        if result.is_err() {
+            // Let the tracer see every active function being left.
+            while let Some(frame) = self.frames.pop() {
+                self.locals_len -= frame.locals.len();
+                self.trace_exit(frame.func);
+            }
+        }
+        result
+    }
+
+    /// Next instruction to execute, `None` once finished.
+    pub fn position(&self) -> Option<Position<'a>> {
+        let frame = self.frames.last()?;
+        let label = frame.labels.last()?;
+        let expr: &'a ExprNode = label.expr;
+        let intrinsic = expr.intrinsics.get(label.pc);
+        Some(Position {
+            func: frame.func,
+            func_idx: self.funcs[frame.func].func_idx,
+            offset: intrinsic.and_then(|i| i.span()).map(|s| s.start),
+            intrinsic,
+        })
+    }
+
+    /// Number of active function calls.
+    pub fn call_depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Locals of the innermost function, its parameters first.
+    pub fn locals(&self) -> &[Value] {
+        self.frames.last().map_or(&[], |frame| &frame.locals)
+    }
+
+    /// Operand stack of the innermost function, its top being the last value.
+    pub fn stack(&self) -> &[Value] {
+        let height = self
+            .frames
+            .last()
+            .and_then(|frame| frame.labels.first())
+            .map_or(0, |label| label.height);
+        &self.stack[height.min(self.stack.len())..]
+    }
+
+    /// Globals of the instance of the innermost function.
+    pub fn globals(&self) -> Vec<Value> {
+        if self.frames.is_empty() {
+            return Vec::new();
+        }
+        let addrs = &self.instance().global_addrs;
+        addrs.iter().map(|addr| self.globals[*addr].get()).collect()
+    }
+
+    /// Memory of the instance of the innermost function.
+    pub fn memory(&self) -> Option<&MemInst> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        let addr = self.mem_addr().ok()?;
+        Some(&self.memories[addr])
+    }
+
+    fn execute_next(&mut self) -> Result<(), Trap> {
+        let frame = self.frames.last_mut().unwrap();
+        let label = frame.labels.last_mut().unwrap();
+        let expr = label.expr;
+        let Some(intrinsic) = expr.intrinsics.get(label.pc) else {
+            let arity = label.end_arity;
+            return self.exit_label(0, arity).map_err(|kind| self.trap(kind));
+        };
+        label.pc += 1;
+        if let Some(tracer) = self.tracer.as_mut() {
+            let func = &self.funcs[frame.func];
+            tracer.instruction(&InstructionEvent {
+                func: frame.func,
+                func_idx: func.func_idx,
+                func_name: func.name.as_deref(),
+                offset: intrinsic.span().map_or(0, |s| s.start),
+                intrinsic,
+                stack: &self.stack,
+            });
+        }
+        self.execute(intrinsic).map_err(|kind| self.trap(kind))?;
+
+        if self.stack.len() + self.locals_len > self.limits.max_value_stack {
+            return Err(self.trap(TrapKind::StackOverflow));
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, intrinsic: &'a IntrinsicNode) -> Result<(), TrapKind> {
+        self.fuel.consume(intrinsic.opcode())?;
+        match intrinsic {
+            IntrinsicNode::UnreachableIntrinsicNode(_) => return Err(TrapKind::Unreachable),
+            IntrinsicNode::NopIntrinsicNode(_) => {}
+            IntrinsicNode::ReturnIntrinsicNode(_) => {
+                let depth = self.frame().labels.len() - 1;
+                self.branch(depth as u32)?;
+            }
+            IntrinsicNode::DropIntrinsicNode(_) => {
+                self.pop()?;
+            }
+            IntrinsicNode::LocalGetIntrinsicNode(l) => {
+                let value = *self.local(l.local_idx)?;
+                self.stack.push(value);
+            }
+            IntrinsicNode::LocalSetIntrinsicNode(l) => {
+                let value = self.pop()?;
+                *self.local(l.local_idx)? = value;
+            }
+            IntrinsicNode::GlobalGetIntrinsicNode(g) => {
+                let addr = self.global_addr(g.global_idx)?;
+                self.stack.push(self.globals[addr].value);
+            }
+            IntrinsicNode::GlobalSetIntrinsicNode(g) => {
+                let value = self.pop()?;
+                let addr = self.global_addr(g.global_idx)?;
+                self.globals[addr].value = value;
+            }
+            IntrinsicNode::I32LoadIntrinsicNode(i) => {
+                let addr = self.pop_i32()?;
+                let bytes = self.memory_mut()?.load(addr, i.mem_arg.offset)?;
+                self.stack.push(Value::I32(i32::from_le_bytes(bytes)));
+            }
+            IntrinsicNode::I32StoreIntrinsicNode(i) => {
+                let value = self.pop_i32()?;
+                let addr = self.pop_i32()?;
+                let offset = i.mem_arg.offset;
+                self.memory_mut()?
+                    .store(addr, offset, value.to_le_bytes())?;
+            }
+            IntrinsicNode::MemorySizeIntrinsicNode(_) => {
+                let size = self.memory_mut()?.size();
+                self.stack.push(Value::I32(size as i32));
+            }
+            IntrinsicNode::MemoryGrowIntrinsicNode(_) => {
+                let delta = self.pop_i32()? as u32;
+                let addr = self.mem_addr()?;
+                let old_size = self.memories[addr].grow(
+                    delta,
+                    self.limiter
+                        .as_mut()
+                        .map(|l| l.as_mut() as &mut dyn ResourceLimiter),
+                );
+                self.stack
+                    .push(Value::I32(old_size.map_or(-1, |size| size as i32)));
+            }
+            IntrinsicNode::I32ConstIntrinsicNode(i) => self.stack.push(Value::I32(i.val)),
+            IntrinsicNode::I64ConstIntrinsicNode(i) => self.stack.push(Value::I64(i.val)),
+            IntrinsicNode::F32ConstIntrinsicNode(f) => self.stack.push(Value::F32(f.val)),
+            IntrinsicNode::F64ConstIntrinsicNode(f) => self.stack.push(Value::F64(f.val)),
+            IntrinsicNode::I32EqaIntrinsicNode(_) => {
+                let a = self.pop_i32()?;
+                self.stack.push(Value::I32((a == 0) as i32));
+            }
+            IntrinsicNode::I32LtSIntrinsicNode(_) => self.i32_binop(|a, b| Ok((a < b) as i32))?,
+            IntrinsicNode::I32GeSIntrinsicNode(_) => self.i32_binop(|a, b| Ok((a >= b) as i32))?,
+            IntrinsicNode::I32AddIntrinsicNode(_) => {
+                self.i32_binop(|a, b| Ok(a.wrapping_add(b)))?
+            }
+            IntrinsicNode::I32SubIntrinsicNode(_) => {
+                self.i32_binop(|a, b| Ok(a.wrapping_sub(b)))?
+            }
+            IntrinsicNode::I32MulIntrinsicNode(_) => {
+                self.i32_binop(|a, b| Ok(a.wrapping_mul(b)))?
+            }
+            IntrinsicNode::I32DivSIntrinsicNode(_) => self.i32_binop(|a, b| match b {
+                0 => Err(TrapKind::IntegerDivideByZero),
+                _ => a.checked_div(b).ok_or(TrapKind::IntegerOverflow),
+            })?,
+            IntrinsicNode::I32DivUIntrinsicNode(_) => self.i32_binop(|a, b| match b {
+                0 => Err(TrapKind::IntegerDivideByZero),
+                _ => Ok(((a as u32) / (b as u32)) as i32),
+            })?,
+            IntrinsicNode::I32RemsIntrinsicNode(_) => self.i32_binop(|a, b| match b {
+                0 => Err(TrapKind::IntegerDivideByZero),
+                _ => Ok(a.wrapping_rem(b)),
+            })?,
+            IntrinsicNode::I32RemUIntrinsicNode(_) => self.i32_binop(|a, b| match b {
+                0 => Err(TrapKind::IntegerDivideByZero),
+                _ => Ok(((a as u32) % (b as u32)) as i32),
+            })?,
+            IntrinsicNode::I32TruncF32SIntrinsicNode(_) => {
+                let v = trunc(self.pop_f32()? as f64, I32_RANGE)?;
+                self.stack.push(Value::I32(v as i32));
+            }
+            IntrinsicNode::I32TruncF32UIntrinsicNode(_) => {
+                let v = trunc(self.pop_f32()? as f64, U32_RANGE)?;
+                self.stack.push(Value::I32(v as u32 as i32));
+            }
+            IntrinsicNode::I32TruncF64SIntrinsicNode(_) => {
+                let v = trunc(self.pop_f64()?, I32_RANGE)?;
+                self.stack.push(Value::I32(v as i32));
+            }
+            IntrinsicNode::I32TruncF64UIntrinsicNode(_) => {
+                let v = trunc(self.pop_f64()?, U32_RANGE)?;
+                self.stack.push(Value::I32(v as u32 as i32));
+            }
+            IntrinsicNode::I64TruncF32SIntrinsicNode(_) => {
+                let v = trunc(self.pop_f32()? as f64, I64_RANGE)?;
+                self.stack.push(Value::I64(v as i64));
+            }
+            IntrinsicNode::I64TruncF32UIntrinsicNode(_) => {
+                let v = trunc(self.pop_f32()? as f64, U64_RANGE)?;
+                self.stack.push(Value::I64(v as u64 as i64));
+            }
+            IntrinsicNode::I64TruncF64SIntrinsicNode(_) => {
+                let v = trunc(self.pop_f64()?, I64_RANGE)?;
+                self.stack.push(Value::I64(v as i64));
+            }
+            IntrinsicNode::I64TruncF64UIntrinsicNode(_) => {
+                let v = trunc(self.pop_f64()?, U64_RANGE)?;
+                self.stack.push(Value::I64(v as u64 as i64));
+            }
+            IntrinsicNode::IfIntrinsicNode(i) => {
+                let cond = self.pop_i32()?;
+                let expr = if cond != 0 {
+                    &i.then_expr
+                } else {
+                    &i.else_expr
+                };
+                self.push_label(expr, &i.block_type, false)?;
+            }
+            IntrinsicNode::BlockIntrinsicNode(b) => {
+                self.push_label(&b.expr, &b.block_type, false)?
+            }
+            IntrinsicNode::LoopIntrinsicNode(l) => self.push_label(&l.expr, &l.block_type, true)?,
+            IntrinsicNode::BrIntrinsicNode(b) => self.branch(b.label_idx)?,
+            IntrinsicNode::BrIfIntrinsicNode(b) => {
+                if self.pop_i32()? != 0 {
+                    self.branch(b.label_idx)?;
+                }
+            }
+            IntrinsicNode::CallIntrinsicNode(c) => {
+                let addr = *self
+                    .instance()
+                    .func_addrs
+                    .get(c.func_idx as usize)
+                    .ok_or(TrapKind::TypeMismatch)?;
+                self.push_frame(addr)?;
+            }
+            IntrinsicNode::CallIndirectIntrinsicNode(c) => {
+                let idx = self.pop_i32()? as u32;
+                let table = &self.tables[self.table_addr(c.table_idx)?];
+                let element = table.get(idx).map_err(|_| TrapKind::UndefinedElement)?;
+                let Value::FuncRef(addr) = element else {
+                    return Err(TrapKind::TypeMismatch);
+                };
+                let addr = addr.ok_or(TrapKind::UninitializedElement)?;
+                let expected = self
+                    .instance()
+                    .module
+                    .type_section()
+                    .and_then(|t| t.func_types.get(c.type_idx as usize))
+                    .ok_or(TrapKind::TypeMismatch)?;
+                if self.funcs[addr].func_type != *expected {
+                    return Err(TrapKind::IndirectCallTypeMismatch);
+                }
+                self.push_frame(addr)?;
+            }
+            IntrinsicNode::RefNullIntrinsicNode(r) => self.stack.push(Value::null_of(&r.ref_type)),
+            IntrinsicNode::TableGrowIntrinsicNode(t) => {
+                let delta = self.pop_i32()? as u32;
+                let init = self.pop()?;
+                let addr = self.table_addr(t.table_idx)?;
+                let table = &mut self.tables[addr];
+                if init.val_type() != ValType::RefType(table.ref_type.clone()) {
+                    return Err(TrapKind::TypeMismatch);
+                }
+                let old_size = table.grow(
+                    delta,
+                    init,
+                    self.limiter
+                        .as_mut()
+                        .map(|l| l.as_mut() as &mut dyn ResourceLimiter),
+                );
+                self.stack
+                    .push(Value::I32(old_size.map_or(-1, |size| size as i32)));
+            }
+            IntrinsicNode::TableSizeIntrinsicNode(t) => {
+                let addr = self.table_addr(t.table_idx)?;
+                let size = self.tables[addr].size();
+                self.stack.push(Value::I32(size as i32));
+            }
+        }
+        Ok(())
+    }
+
+    fn push_frame(&mut self, addr: FuncAddr) -> Result<(), TrapKind> {
+        if self.interrupt.take() {
+            return Err(TrapKind::Interrupted);
+        }
+        if self.frames.len() >= self.limits.max_call_depth {
+            return Err(TrapKind::StackOverflow);
+        }
+
+        let func = &self.funcs[addr];
+        let param_count = func.func_type.param_type.val_types.len();
+        let param_start = self
+            .stack
+            .len()
+            .checked_sub(param_count)
+            .ok_or(TrapKind::TypeMismatch)?;
+        let Some(code) = func.code() else {
+            self.trace_enter(addr);
+            let result = self.call_host(addr, param_start);
+            self.trace_exit(addr);
+            return result;
+        };
+
+        // Check before allocating, the declared number of locals can be huge.
+        let local_count: u64 = code.locals.iter().map(|l| l.num as u64).sum();
+        let value_count = self.locals_len as u64 + self.stack.len() as u64 + local_count;
+        if value_count > self.limits.max_value_stack as u64 {
+            return Err(TrapKind::StackOverflow);
+        }
+
+        let mut locals = self.stack.split_off(param_start);
+        for local in &code.locals {
+            let value = Value::default_of(&local.val_type);
+            locals.extend(std::iter::repeat_n(value, local.num as usize));
+        }
+
+        let arity = func.func_type.result_type.val_types.len();
+        let body = Label {
+            expr: &code.expr,
+            pc: 0,
+            branch_arity: arity,
+            end_arity: arity,
+            height: self.stack.len(),
+            is_loop: false,
+        };
+        self.locals_len += locals.len();
+        self.frames.push(Frame {
+            func: addr,
+            locals,
+            labels: vec![body],
+        });
+        self.trace_enter(addr);
+        Ok(())
+    }
+
+    /// Call a host function right away, replacing its arguments with its results.
+    fn call_host(&mut self, addr: FuncAddr, param_start: usize) -> Result<(), TrapKind> {
+        let funcs = self.funcs;
+        let func = &funcs[addr];
+        let host_func = func.host_func().ok_or(TrapKind::TypeMismatch)?;
+        let args = self.stack.split_off(param_start);
+        let caller = self.frames.last().map(|frame| funcs[frame.func].instance);
+        let mem_addr = caller.and_then(|i| self.instances[i].mem_addrs.first());
+        let mut caller = Caller {
+            memory: mem_addr.map(|addr| &mut self.memories[*addr]),
+        };
+        let results = host_func(&mut caller, &args).map_err(|trap| trap.kind)?;
+        let result_types = &func.func_type.result_type.val_types;
+        let types_match = results.len() == result_types.len()
+            && results
+                .iter()
+                .zip(result_types)
+                .all(|(v, t)| v.val_type() == *t);
+        if !types_match {
+            return Err(TrapKind::TypeMismatch);
+        }
+        self.stack.extend(results);
+        Ok(())
+    }
+
+    fn push_label(
+        &mut self,
+        expr: &'a ExprNode,
+        block_type: &BlockType,
+        is_loop: bool,
+    ) -> Result<(), TrapKind> {
+        let (params, results) = match block_type {
+            BlockType::Empty => (0, 0),
+            BlockType::ValType(_) => (0, 1),
+            BlockType::S33(type_idx) => {
+                let func_type = self
+                    .instance()
+                    .module
+                    .type_section()
+                    .and_then(|t| t.func_types.get(*type_idx as usize))
+                    .ok_or(TrapKind::TypeMismatch)?;
+                (
+                    func_type.param_type.val_types.len(),
+                    func_type.result_type.val_types.len(),
+                )
+            }
+        };
+        // A branch to a loop jumps back to its start, carrying its params.
+        let branch_arity = if is_loop { params } else { results };
+        // The params belong to the block, so unwinding it drops them too.
+        let height = self
+            .stack
+            .len()
+            .checked_sub(params)
+            .ok_or(TrapKind::TypeMismatch)?;
+        self.frame_mut().labels.push(Label {
+            expr,
+            pc: 0,
+            branch_arity,
+            end_arity: results,
+            height,
+            is_loop,
+        });
+        Ok(())
+    }
+
+    /// Jump to the label `depth` levels out from the innermost one.
+    fn branch(&mut self, depth: u32) -> Result<(), TrapKind> {
+        let labels = &self.frame().labels;
+        let idx = labels
+            .len()
+            .checked_sub(depth as usize + 1)
+            .ok_or(TrapKind::TypeMismatch)?;
+        let arity = labels[idx].branch_arity;
+        if !labels[idx].is_loop {
+            return self.exit_label(depth as usize, arity);
+        }
+        if self.interrupt.take() {
+            return Err(TrapKind::Interrupted);
+        }
+
+        self.unwind(idx, arity)?;
+        let labels = &mut self.frame_mut().labels;
+        labels.truncate(idx + 1);
+        labels[idx].pc = 0;
+        Ok(())
+    }
+
+    /// Leave the label `depth` levels out from the innermost one, and every label inside it,
+    /// keeping `arity` values. Leaving the bottom label returns from the function.
+    fn exit_label(&mut self, depth: usize, arity: usize) -> Result<(), TrapKind> {
+        let idx = self.frame().labels.len() - 1 - depth;
+        self.unwind(idx, arity)?;
+        let frame = self.frame_mut();
+        frame.labels.truncate(idx);
+        if frame.labels.is_empty() {
+            let frame = self.frames.pop().unwrap();
+            self.locals_len -= frame.locals.len();
+            self.trace_exit(frame.func);
+        }
+        Ok(())
+    }
+
+    /// Reset the operand stack to the height of label `idx`, keeping the top `arity` values.
+    fn unwind(&mut self, idx: usize, arity: usize) -> Result<(), TrapKind> {
+        let height = self.frame().labels[idx].height;
+        let start = self
+            .stack
+            .len()
+            .checked_sub(arity)
+            .filter(|start| *start >= height)
+            .ok_or(TrapKind::TypeMismatch)?;
+        self.stack.drain(height..start);
+        Ok(())
+    }
+
+    fn trace_enter(&mut self, addr: FuncAddr) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            let func = &self.funcs[addr];
+            tracer.enter(&CallEvent {
+                func: addr,
+                func_idx: func.func_idx,
+                func_name: func.name.as_deref(),
+                is_host: func.host_func().is_some(),
+            });
+        }
+    }
+
+    fn trace_exit(&mut self, addr: FuncAddr) {
+        if let Some(tracer) = self.tracer.as_mut() {
+            let func = &self.funcs[addr];
+            tracer.exit(&CallEvent {
+                func: addr,
+                func_idx: func.func_idx,
+                func_name: func.name.as_deref(),
+                is_host: func.host_func().is_some(),
+            });
+        }
+    }
+
+    fn frame(&self) -> &Frame<'a> {
+        self.frames.last().unwrap()
+    }
+
+    fn frame_mut(&mut self) -> &mut Frame<'a> {
+        self.frames.last_mut().unwrap()
+    }
+
+    /// Instance the executing function belongs to.
+    fn instance(&self) -> &'a InstanceData {
+        let instances: &'a [InstanceData] = self.instances;
+        &instances[self.funcs[self.frame().func].instance]
+    }
+
+    fn mem_addr(&self) -> Result<usize, TrapKind> {
+        // Only memory 0 can be referenced until multi-memory.
+        let addr = self.instance().mem_addrs.first();
+        addr.copied().ok_or(TrapKind::TypeMismatch)
+    }
+
+    fn memory_mut(&mut self) -> Result<&mut MemInst, TrapKind> {
+        let addr = self.mem_addr()?;
+        Ok(&mut self.memories[addr])
+    }
+
+    fn table_addr(&self, idx: u32) -> Result<usize, TrapKind> {
+        let addr = self.instance().table_addrs.get(idx as usize);
+        addr.copied().ok_or(TrapKind::TypeMismatch)
+    }
+
+    fn global_addr(&self, idx: u32) -> Result<usize, TrapKind> {
+        let addr = self.instance().global_addrs.get(idx as usize);
+        addr.copied().ok_or(TrapKind::TypeMismatch)
+    }
+
+    fn local(&mut self, idx: u32) -> Result<&mut Value, TrapKind> {
+        self.frame_mut()
+            .locals
+            .get_mut(idx as usize)
+            .ok_or(TrapKind::TypeMismatch)
+    }
+
+    fn pop(&mut self) -> Result<Value, TrapKind> {
+        let height = self.frame().labels.last().map_or(0, |l| l.height);
+        if self.stack.len() <= height {
+            return Err(TrapKind::TypeMismatch);
+        }
+        Ok(self.stack.pop().unwrap())
+    }
+
+    fn pop_i32(&mut self) -> Result<i32, TrapKind> {
+        match self.pop()? {
+            Value::I32(v) => Ok(v),
+            _ => Err(TrapKind::TypeMismatch),
+        }
+    }
+
+    fn pop_f32(&mut self) -> Result<f32, TrapKind> {
+        match self.pop()? {
+            Value::F32(v) => Ok(v),
+            _ => Err(TrapKind::TypeMismatch),
+        }
+    }
+
+    fn pop_f64(&mut self) -> Result<f64, TrapKind> {
+        match self.pop()? {
+            Value::F64(v) => Ok(v),
+            _ => Err(TrapKind::TypeMismatch),
+        }
+    }
+
+    fn i32_binop(&mut self, f: impl Fn(i32, i32) -> Result<i32, TrapKind>) -> Result<(), TrapKind> {
+        let b = self.pop_i32()?;
+        let a = self.pop_i32()?;
+        self.stack.push(Value::I32(f(a, b)?));
+        Ok(())
+    }
+
+    /// Active functions, innermost first, each at the instruction it executes.
+    pub fn backtrace(&self) -> Vec<FrameInfo> {
+        self.frames
+            .iter()
+            .rev()
+            .map(|frame| {
+                let func = &self.funcs[frame.func];
+                // The instruction being executed is the one before `pc`.
+                let offset = frame
+                    .labels
+                    .last()
+                    .and_then(|l| l.expr.intrinsics.get(l.pc.checked_sub(1)?)?.span())
+                    .map_or(0, |s| s.start);
+                FrameInfo {
+                    func_idx: func.func_idx,
+                    func_name: func.name.clone(),
+                    offset,
+                }
+            })
+            .collect()
+    }
+
+    fn trap(&self, kind: TrapKind) -> Trap {
+        Trap {
+            kind,
+            backtrace: self.backtrace(),
+        }
+    }
+}
+
+/// Bounds, both excluded, of the floats that truncate to each integer type.
+const I32_RANGE: (f64, f64) = (-2_147_483_649.0, 2_147_483_648.0);
+const U32_RANGE: (f64, f64) = (-1.0, 4_294_967_296.0);
+/// The float just below `i64::MIN`, which is itself representable.
+const I64_RANGE: (f64, f64) = (-9_223_372_036_854_777_856.0, 9_223_372_036_854_775_808.0);
+const U64_RANGE: (f64, f64) = (-1.0, 18_446_744_073_709_551_616.0);
+
+/// Truncate `value` toward zero, failing if it is NaN or does not fit in `range`.
+fn trunc(value: f64, (min, max): (f64, f64)) -> Result<f64, TrapKind> {
+    if value.is_nan() {
+        return Err(TrapKind::InvalidConversionToInteger);
+    }
+    let value = value.trunc();
+    if value <= min || value >= max {
+        return Err(TrapKind::IntegerOverflow);
+    }
+    Ok(value)
+}
@@ -0,0 +1,39 @@
+use anyhow::Result;
+
+use crate::exec::buffer::Buffer;
+use crate::exec::section::SectionHeader;
+use crate::exec::type_section::MemoryTypeNode;
+use crate::exec::writer::Writer;
+
+#[derive(Debug, Clone)]
+pub struct MemorySectionNode {
+    pub mem_types: Vec<MemoryTypeNode>,
+    pub header: Option<SectionHeader>,
+}
+impl Default for MemorySectionNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MemorySectionNode {
+    pub fn new() -> MemorySectionNode {
+        MemorySectionNode {
+            mem_types: Vec::new(),
+            header: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        let f = |buf: &mut Buffer| -> Result<MemoryTypeNode> {
+            let mut mem_type = MemoryTypeNode::new();
+            mem_type.load(buf)?;
+            Ok(mem_type)
+        };
+        self.mem_types = buf.read_vec::<MemoryTypeNode>(Box::new(f))?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_vec(&self.mem_types, |w, item| item.write(w));
+    }
+}
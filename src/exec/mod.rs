@@ -1,7 +1,31 @@
 pub mod buffer;
+pub mod builder;
 pub mod code_section;
+pub mod coverage;
+pub mod custom_section;
+pub mod data_section;
 pub mod export_section;
+pub mod fuel;
 pub mod func_section;
+pub mod global_section;
+pub mod import_section;
+pub mod instance;
+pub mod interrupt;
+pub mod limits;
+pub mod linker;
+pub mod machine;
+pub mod memory_section;
 pub mod module;
+pub mod profile;
 pub mod section;
+pub mod start_section;
+pub mod store;
+pub mod stream;
+pub mod table_section;
+pub mod trace;
+pub mod trap;
 pub mod type_section;
+pub mod typed_func;
+pub mod validate;
+pub mod value;
+pub mod writer;
@@ -1,7 +1,20 @@
 use anyhow::{anyhow, Result};
 
+use crate::errors::ExecError;
 use crate::exec::buffer::Buffer;
-use crate::exec::section::SectionNode;
+use crate::exec::code_section::CodeSectionNode;
+use crate::exec::custom_section::NameSectionNode;
+use crate::exec::data_section::DataSectionNode;
+use crate::exec::export_section::ExportSectionNode;
+use crate::exec::func_section::FunctionSectionNode;
+use crate::exec::global_section::GlobalSectionNode;
+use crate::exec::import_section::{ImportDescNode, ImportSectionNode};
+use crate::exec::memory_section::MemorySectionNode;
+use crate::exec::section::{SectionHeader, SectionNode, SectionOrder};
+use crate::exec::start_section::StartSectionNode;
+use crate::exec::table_section::TableSectionNode;
+use crate::exec::type_section::{FunctionTypeNode, TypeSectionNode};
+use crate::exec::writer::Writer;
 
 #[derive(Debug, Clone)]
 pub struct ModuleNode {
@@ -29,33 +42,313 @@ impl ModuleNode {
     }
 
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
-        self.magic = buf.read_bytes(4)?;
+        self.magic = buf.read_bytes(4)?.to_vec();
         if self.magic != vec![0x00, 0x61, 0x73, 0x6d] {
             return Err(anyhow!("Invalid wasm magic number"));
         }
 
-        self.version = buf.read_bytes(4)?;
+        self.version = buf.read_bytes(4)?.to_vec();
 
+        let mut order = SectionOrder::default();
         loop {
             if buf.eof() {
                 break;
             }
 
             let section = self.load_section(buf)?;
+            order.check(section.id())?;
             self.sections.push(section);
         }
 
         Ok(())
     }
 
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_bytes(&self.magic);
+        writer.write_bytes(&self.version);
+        for section in &self.sections {
+            let mut payload = Writer::new();
+            section.write(&mut payload);
+            writer.write_section(section.id(), &payload.into_bytes());
+        }
+    }
+
+    /// Encode the module in the binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+        self.write(&mut writer);
+        writer.into_bytes()
+    }
+
     pub fn load_section(&mut self, buf: &mut Buffer) -> Result<SectionNode> {
+        let start = buf.offset();
         let section_id = buf.read_byte()?;
         let section_size = buf.read_u32()?;
+        let offset = buf.offset();
         let mut section_buf = buf.read_buffer(section_size)?;
 
         let mut section = SectionNode::create(section_id)?;
         section.load(&mut section_buf)?;
+        if !section_buf.eof() {
+            return Err(ExecError::Malformed("section size mismatch".to_string()).into());
+        }
+        section.set_header(SectionHeader {
+            id: section_id,
+            span: buf.span_from(start),
+            offset,
+            size: section_size,
+        });
 
         Ok(section)
     }
+
+    pub fn type_section(&self) -> Option<&TypeSectionNode> {
+        self.sections.iter().find_map(|s| match s {
+            SectionNode::TypeSectionNode(t) => Some(t),
+            _ => None,
+        })
+    }
+
+    pub fn import_section(&self) -> Option<&ImportSectionNode> {
+        self.sections.iter().find_map(|s| match s {
+            SectionNode::ImportSectionNode(i) => Some(i),
+            _ => None,
+        })
+    }
+
+    pub fn function_section(&self) -> Option<&FunctionSectionNode> {
+        self.sections.iter().find_map(|s| match s {
+            SectionNode::FunctionSectionNode(f) => Some(f),
+            _ => None,
+        })
+    }
+
+    pub fn table_section(&self) -> Option<&TableSectionNode> {
+        self.sections.iter().find_map(|s| match s {
+            SectionNode::TableSectionNode(t) => Some(t),
+            _ => None,
+        })
+    }
+
+    pub fn memory_section(&self) -> Option<&MemorySectionNode> {
+        self.sections.iter().find_map(|s| match s {
+            SectionNode::MemorySectionNode(m) => Some(m),
+            _ => None,
+        })
+    }
+
+    pub fn global_section(&self) -> Option<&GlobalSectionNode> {
+        self.sections.iter().find_map(|s| match s {
+            SectionNode::GlobalSectionNode(g) => Some(g),
+            _ => None,
+        })
+    }
+
+    pub fn data_section(&self) -> Option<&DataSectionNode> {
+        self.sections.iter().find_map(|s| match s {
+            SectionNode::DataSectionNode(d) => Some(d),
+            _ => None,
+        })
+    }
+
+    pub fn code_section(&self) -> Option<&CodeSectionNode> {
+        self.sections.iter().find_map(|s| match s {
+            SectionNode::CodeSectionNode(c) => Some(c),
+            _ => None,
+        })
+    }
+
+    pub fn export_section(&self) -> Option<&ExportSectionNode> {
+        self.sections.iter().find_map(|s| match s {
+            SectionNode::ExportSectionNode(e) => Some(e),
+            _ => None,
+        })
+    }
+
+    pub fn start_section(&self) -> Option<&StartSectionNode> {
+        self.sections.iter().find_map(|s| match s {
+            SectionNode::StartSectionNode(s) => Some(s),
+            _ => None,
+        })
+    }
+
+    pub fn name_section(&self) -> Option<NameSectionNode> {
+        self.sections.iter().find_map(|s| match s {
+            SectionNode::CustomSectionNode(c) => c.name_section(),
+            _ => None,
+        })
+    }
+
+    /// Type of the function `func_idx`, imported functions coming first.
+    pub fn func_type(&self, func_idx: u32) -> Option<&FunctionTypeNode> {
+        let imports = self.import_section().map_or(&[][..], |i| &i.imports);
+        let imported = imports.iter().filter_map(|i| match i.desc {
+            ImportDescNode::Func(type_idx) => Some(type_idx),
+            _ => None,
+        });
+        let defined = self.function_section().map_or(&[][..], |f| &f.type_indices);
+        let type_idx = imported
+            .chain(defined.iter().copied())
+            .nth(func_idx as usize)?;
+        self.type_section()?.func_types.get(type_idx as usize)
+    }
+
+    /// Number of imported functions, i.e. the index of the first function of the code section.
+    pub fn imported_func_count(&self) -> u32 {
+        let imports = self.import_section().map_or(&[][..], |i| &i.imports);
+        imports
+            .iter()
+            .filter(|i| matches!(i.desc, ImportDescNode::Func(_)))
+            .count() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::buffer::Span;
+    use crate::exec::code_section::{ExprNode, I32ConstIntrinsicNode, IntrinsicNode};
+    use std::fs;
+
+    fn decode(bytes: Vec<u8>) -> ModuleNode {
+        let mut module = ModuleNode::new();
+        module.load(&mut Buffer::new(&bytes)).unwrap();
+        module
+    }
+
+    /// Decoding then encoding gives back the exact bytes of every example,
+    /// and decoding the encoded bytes gives back the same module.
+    #[test]
+    fn test_round_trip() {
+        let mut paths: Vec<_> = fs::read_dir("examples")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+        for path in paths {
+            let bytes = fs::read(&path).unwrap();
+            let encoded = decode(bytes.clone()).to_bytes();
+            assert_eq!(encoded, bytes, "{}", path.display());
+            let decoded = decode(encoded);
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", decode(bytes)));
+        }
+    }
+
+    #[test]
+    fn test_write_recomputes_sizes() {
+        let mut module = decode(fs::read("examples/const.wasm").unwrap());
+        let Some(SectionNode::CodeSectionNode(code_section)) = module
+            .sections
+            .iter_mut()
+            .find(|s| matches!(s, SectionNode::CodeSectionNode(_)))
+        else {
+            panic!("no code section");
+        };
+        let expr = &mut code_section.codes[0].func.expr;
+        let mut val = I32ConstIntrinsicNode::new();
+        val.val = i32::MAX;
+        expr.intrinsics[0] = IntrinsicNode::I32ConstIntrinsicNode(val);
+
+        let module = decode(module.to_bytes());
+        let code = &module.code_section().unwrap().codes[0];
+        match &code.func.expr.intrinsics[0] {
+            IntrinsicNode::I32ConstIntrinsicNode(val) => assert_eq!(val.val, i32::MAX),
+            intrinsic => panic!("unexpected {:?}", intrinsic),
+        }
+    }
+
+    #[test]
+    fn test_spans() {
+        let module = decode(fs::read("examples/if.wasm").unwrap());
+        let span = |start, end| Span { start, end };
+        assert_eq!(
+            module.sections[0].header(),
+            Some(&SectionHeader {
+                id: 1,
+                span: span(0x08, 0x10),
+                offset: 0x0a,
+                size: 6,
+            })
+        );
+        assert_eq!(module.sections[3].header().unwrap().span, span(0x1e, 0x31));
+
+        let code = &module.code_section().unwrap().codes[0];
+        assert_eq!(code.span, span(0x22, 0x31));
+        let expr = &code.func.expr;
+        let spans = |expr: &ExprNode| -> Vec<_> {
+            expr.intrinsics.iter().map(|i| i.span().unwrap()).collect()
+        };
+        assert_eq!(
+            spans(expr),
+            [
+                span(0x23, 0x25),
+                span(0x25, 0x27),
+                span(0x27, 0x28),
+                // The `if` spans its branches up to its `end`.
+                span(0x28, 0x30),
+            ]
+        );
+        let IntrinsicNode::IfIntrinsicNode(node) = &expr.intrinsics[3] else {
+            panic!("not an if");
+        };
+        assert_eq!(spans(&node.then_expr), [span(0x2a, 0x2c)]);
+        assert_eq!(spans(&node.else_expr), [span(0x2d, 0x2f)]);
+    }
+
+    #[test]
+    fn test_size_mismatch() {
+        let header = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        let load = |sections: &[u8]| {
+            let bytes = [&header[..], sections].concat();
+            ModuleNode::new().load(&mut Buffer::new(&bytes))
+        };
+        // Junk after the only type.
+        let err = load(&[0x01, 0x06, 0x01, 0x60, 0x00, 0x00, 0xff, 0xff]).unwrap_err();
+        assert_eq!(err.to_string(), "Malformed module: section size mismatch");
+        // Bytes after the final `end` of a body.
+        let types = [0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03, 0x02, 0x01, 0x00];
+        let code = [0x0a, 0x06, 0x01, 0x04, 0x00, 0x0b, 0x01, 0x01];
+        let err = load(&[&types[..], &code].concat()).unwrap_err();
+        assert_eq!(err.to_string(), "Malformed module: section size mismatch");
+        let code = [0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b];
+        load(&[&types[..], &code].concat()).unwrap();
+    }
+
+    #[test]
+    fn test_unexpected_else() {
+        let header = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        let types = [0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03, 0x02, 0x01, 0x00];
+        let load = |body: &[u8]| {
+            let code = [
+                &[0x0a, body.len() as u8 + 2, 0x01, body.len() as u8][..],
+                body,
+            ];
+            let bytes = [&header[..], &types, &code.concat()].concat();
+            ModuleNode::new().load(&mut Buffer::new(&bytes))
+        };
+        for body in [
+            &[0x00, 0x05][..],
+            &[0x00, 0x02, 0x40, 0x05, 0x0b, 0x0b],
+            &[0x00, 0x03, 0x40, 0x05, 0x0b, 0x0b],
+            &[0x00, 0x41, 0x00, 0x04, 0x40, 0x05, 0x05, 0x0b, 0x0b],
+        ] {
+            let err = load(body).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Malformed module: unexpected else",
+                "{:02x?}",
+                body
+            );
+        }
+        load(&[0x00, 0x41, 0x00, 0x04, 0x40, 0x05, 0x0b, 0x0b]).unwrap();
+
+        // A global initialized by `i32.const 0 else`.
+        let global = [0x06, 0x06, 0x01, 0x7f, 0x00, 0x41, 0x00, 0x05];
+        let err = ModuleNode::new()
+            .load(&mut Buffer::new(&[&header[..], &global].concat()))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Malformed module: unexpected else");
+    }
 }
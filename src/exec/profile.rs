@@ -0,0 +1,225 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::exec::store::FuncAddr;
+use crate::exec::trace::{CallEvent, InstructionEvent, Tracer};
+
+/// What a `Profiler` measured for one function.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FuncProfile {
+    /// Name from the name section, or `func[idx]`.
+    pub name: String,
+    pub calls: u64,
+    /// Instructions executed by the function itself.
+    pub self_instructions: u64,
+    /// Instructions executed by the function and everything it called.
+    /// Recursive calls are only counted once, in their outermost activation.
+    pub inclusive_instructions: u64,
+    pub self_time: Duration,
+    /// Wall time between entering and leaving the function, like `inclusive_instructions`.
+    pub total_time: Duration,
+}
+
+/// Function being executed, as seen by the profiler.
+#[derive(Debug)]
+struct ActiveCall {
+    func: FuncAddr,
+    start: Instant,
+    /// Value of `Profiler::instructions` when the function was entered.
+    instructions_at_entry: u64,
+    self_instructions: u64,
+    /// Time spent in the functions it called.
+    child_time: Duration,
+    /// Names of the functions on the call stack joined by `;`, ending with this one.
+    path: String,
+}
+
+/// `Tracer` counting calls, instructions and time spent per function,
+/// and the instructions executed under each call stack for flame graphs.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    funcs: HashMap<FuncAddr, FuncProfile>,
+    stack: Vec<ActiveCall>,
+    /// Instructions executed so far.
+    instructions: u64,
+    /// Self instructions of each call stack path.
+    folded: BTreeMap<String, u64>,
+}
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /// Profile of every called function, sorted by decreasing self instructions.
+    pub fn funcs(&self) -> Vec<&FuncProfile> {
+        let mut funcs: Vec<&FuncProfile> = self.funcs.values().collect();
+        funcs.sort_by(|a, b| {
+            b.self_instructions
+                .cmp(&a.self_instructions)
+                .then(b.calls.cmp(&a.calls))
+                .then(a.name.cmp(&b.name))
+        });
+        funcs
+    }
+
+    /// Table of `funcs`, one function per line.
+    pub fn report(&self) -> String {
+        let mut report = format!(
+            "{:>10} {:>14} {:>14} {:>12} {:>12}  function\n",
+            "calls", "self instrs", "incl instrs", "self ms", "total ms"
+        );
+        for func in self.funcs() {
+            let _ = writeln!(
+                report,
+                "{:>10} {:>14} {:>14} {:>12.3} {:>12.3}  {}",
+                func.calls,
+                func.self_instructions,
+                func.inclusive_instructions,
+                func.self_time.as_secs_f64() * 1000.0,
+                func.total_time.as_secs_f64() * 1000.0,
+                func.name
+            );
+        }
+        report
+    }
+
+    /// Folded stacks weighted by executed instructions, e.g. `main;fib 120`,
+    /// the input format of `flamegraph.pl` and `inferno-flamegraph`.
+    pub fn folded(&self) -> String {
+        let mut folded = String::new();
+        for (path, count) in &self.folded {
+            let _ = writeln!(folded, "{} {}", path, count);
+        }
+        folded
+    }
+}
+impl Tracer for Profiler {
+    fn instruction(&mut self, _event: &InstructionEvent) {
+        self.instructions += 1;
+        if let Some(call) = self.stack.last_mut() {
+            call.self_instructions += 1;
+        }
+    }
+
+    fn enter(&mut self, event: &CallEvent) {
+        let name = match event.func_name {
+            Some(name) => name.to_string(),
+            None => format!("func[{}]", event.func_idx),
+        };
+        let path = match self.stack.last() {
+            Some(caller) => format!("{};{}", caller.path, name),
+            None => name.clone(),
+        };
+        let profile = self.funcs.entry(event.func).or_default();
+        profile.name = name;
+        profile.calls += 1;
+        self.stack.push(ActiveCall {
+            func: event.func,
+            start: Instant::now(),
+            instructions_at_entry: self.instructions,
+            self_instructions: 0,
+            child_time: Duration::ZERO,
+            path,
+        });
+    }
+
+    fn exit(&mut self, _event: &CallEvent) {
+        let Some(call) = self.stack.pop() else {
+            return;
+        };
+        let elapsed = call.start.elapsed();
+        let recursive = self.stack.iter().any(|c| c.func == call.func);
+        let profile = self.funcs.entry(call.func).or_default();
+        profile.self_instructions += call.self_instructions;
+        profile.self_time += elapsed.saturating_sub(call.child_time);
+        if !recursive {
+            profile.inclusive_instructions += self.instructions - call.instructions_at_entry;
+            profile.total_time += elapsed;
+        }
+        if call.self_instructions > 0 {
+            *self.folded.entry(call.path).or_default() += call.self_instructions;
+        }
+        if let Some(caller) = self.stack.last_mut() {
+            caller.child_time += elapsed;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::buffer::Buffer;
+    use crate::exec::instance::Instance;
+    use crate::exec::module::ModuleNode;
+    use crate::exec::store::Store;
+    use crate::exec::value::Value;
+    use crate::wat::module::parse_str;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn profile(wat: &str, name: &str, args: &[Value]) -> Profiler {
+        let mut module = ModuleNode::new();
+        module
+            .load(&mut Buffer::new(&parse_str(wat).unwrap()))
+            .unwrap();
+        let mut store = Store::new();
+        let profiler = Rc::new(RefCell::new(Profiler::new()));
+        store.set_tracer(profiler.clone());
+        let instance = Instance::new(&mut store, module).unwrap();
+        let _ = instance.invoke(&mut store, name, args);
+        drop(store);
+        Rc::into_inner(profiler).unwrap().into_inner()
+    }
+
+    fn counts(profiler: &Profiler) -> Vec<(&str, u64, u64, u64)> {
+        profiler
+            .funcs()
+            .iter()
+            .map(|f| {
+                let (calls, own, incl) = (f.calls, f.self_instructions, f.inclusive_instructions);
+                (f.name.as_str(), calls, own, incl)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_profiler() {
+        let wat = r#"
+            (func $add (param i32 i32) (result i32)
+              (i32.add (local.get 0) (local.get 1)))
+            (func $main (export "main") (result i32)
+              (call $add (call $add (i32.const 1) (i32.const 2)) (i32.const 3)))
+        "#;
+        let profiler = profile(wat, "main", &[]);
+        assert_eq!(counts(&profiler), [("add", 2, 6, 6), ("main", 1, 5, 11)]);
+        assert_eq!(profiler.folded(), "main 5\nmain;add 6\n");
+        let report = profiler.report();
+        assert!(report.lines().nth(1).unwrap().ends_with("  add"));
+    }
+
+    #[test]
+    fn test_profiler_recursion() {
+        let profiler = profile(
+            &std::fs::read_to_string("examples/recursion.wat").unwrap(),
+            "count",
+            &[Value::I32(2)],
+        );
+        // Each level runs 9 instructions, the last one 4.
+        assert_eq!(counts(&profiler), [("count", 3, 22, 22)]);
+        assert_eq!(
+            profiler.folded(),
+            "count 9\ncount;count 9\ncount;count;count 4\n"
+        );
+    }
+
+    #[test]
+    fn test_profiler_trap() {
+        let wat = r#"
+            (func $fail (unreachable))
+            (func (export "main") (call $fail))
+        "#;
+        let profiler = profile(wat, "main", &[]);
+        assert_eq!(counts(&profiler), [("fail", 1, 1, 1), ("func[1]", 1, 1, 2)]);
+    }
+}
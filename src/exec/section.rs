@@ -1,42 +1,206 @@
-use crate::exec::buffer::Buffer;
+use crate::exec::buffer::{Buffer, Span};
 use crate::exec::code_section::CodeSectionNode;
+use crate::exec::custom_section::CustomSectionNode;
+use crate::exec::data_section::DataSectionNode;
 use crate::exec::export_section::ExportSectionNode;
 use crate::exec::func_section::FunctionSectionNode;
+use crate::exec::global_section::GlobalSectionNode;
+use crate::exec::import_section::ImportSectionNode;
+use crate::exec::memory_section::MemorySectionNode;
+use crate::exec::start_section::StartSectionNode;
+use crate::exec::table_section::TableSectionNode;
 use crate::exec::type_section::TypeSectionNode;
+use crate::exec::writer::Writer;
 use anyhow::{anyhow, Result};
 
+/// Name of the section `id`, including the ones that are not decoded.
+/// https://webassembly.github.io/spec/core/binary/modules.html#sections
+pub fn section_name(id: u8) -> &'static str {
+    match id {
+        0 => "custom",
+        1 => "type",
+        2 => "import",
+        3 => "function",
+        4 => "table",
+        5 => "memory",
+        6 => "global",
+        7 => "export",
+        8 => "start",
+        9 => "element",
+        10 => "code",
+        11 => "data",
+        12 => "data count",
+        _ => "unknown",
+    }
+}
+
+/// Checks that the sections other than custom ones come at most once and in order,
+/// the data count section coming before the code section.
+#[derive(Debug, Default)]
+pub struct SectionOrder {
+    last: u8,
+}
+impl SectionOrder {
+    pub fn check(&mut self, id: u8) -> Result<()> {
+        let rank = match id {
+            1..=9 => id,
+            12 => 10,
+            10 => 11,
+            11 => 12,
+            // Custom sections go anywhere, unknown ones are rejected when decoded.
+            _ => return Ok(()),
+        };
+        if rank <= self.last {
+            return Err(anyhow!(
+                "Unexpected {} section, out of order or duplicated",
+                section_name(id)
+            ));
+        }
+        self.last = rank;
+        Ok(())
+    }
+}
+
+/// Id and position of a section, as read before its contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SectionHeader {
+    pub id: u8,
+    /// Bytes of the whole section, from its id to the end of its contents.
+    pub span: Span,
+    /// Offset of the contents in the module, after the id and size.
+    pub offset: u32,
+    pub size: u32,
+}
+
 #[derive(Debug, Clone)]
 pub enum SectionNode {
+    CustomSectionNode(CustomSectionNode),
     TypeSectionNode(TypeSectionNode),
+    ImportSectionNode(ImportSectionNode),
     FunctionSectionNode(FunctionSectionNode),
+    TableSectionNode(TableSectionNode),
+    MemorySectionNode(MemorySectionNode),
+    GlobalSectionNode(GlobalSectionNode),
     CodeSectionNode(CodeSectionNode),
     ExportSectionNode(ExportSectionNode),
+    StartSectionNode(StartSectionNode),
+    DataSectionNode(DataSectionNode),
 }
 impl SectionNode {
     pub fn create(section_id: u8) -> Result<SectionNode> {
         match section_id {
-            // 0 => CustomSectionNode::new(),
+            0 => Ok(SectionNode::CustomSectionNode(CustomSectionNode::new())),
             1 => Ok(SectionNode::TypeSectionNode(TypeSectionNode::new())),
-            // 2 => ImportSectionNode::new(),
+            2 => Ok(SectionNode::ImportSectionNode(ImportSectionNode::new())),
             3 => Ok(SectionNode::FunctionSectionNode(FunctionSectionNode::new())),
-            // 4 => TableSectionNode::new(),
-            // 5 => MemorySectionNode::new(),
-            // 6 => GlobalSectionNode::new(),
+            4 => Ok(SectionNode::TableSectionNode(TableSectionNode::new())),
+            5 => Ok(SectionNode::MemorySectionNode(MemorySectionNode::new())),
+            6 => Ok(SectionNode::GlobalSectionNode(GlobalSectionNode::new())),
             7 => Ok(SectionNode::ExportSectionNode(ExportSectionNode::new())),
-            // 8 => StartSectionNode::new(),
+            8 => Ok(SectionNode::StartSectionNode(StartSectionNode::new())),
             // 9 => ElementSectionNode::new(),
             10 => Ok(SectionNode::CodeSectionNode(CodeSectionNode::new())),
-            // 11 => DataSectionNode::new(),
+            11 => Ok(SectionNode::DataSectionNode(DataSectionNode::new())),
             _ => Err(anyhow!("Invalid section id: {:?}", section_id)),
         }
     }
 
+    /// https://webassembly.github.io/spec/core/binary/modules.html#sections
+    pub fn id(&self) -> u8 {
+        match self {
+            SectionNode::CustomSectionNode(_) => 0,
+            SectionNode::TypeSectionNode(_) => 1,
+            SectionNode::ImportSectionNode(_) => 2,
+            SectionNode::FunctionSectionNode(_) => 3,
+            SectionNode::TableSectionNode(_) => 4,
+            SectionNode::MemorySectionNode(_) => 5,
+            SectionNode::GlobalSectionNode(_) => 6,
+            SectionNode::ExportSectionNode(_) => 7,
+            SectionNode::StartSectionNode(_) => 8,
+            SectionNode::CodeSectionNode(_) => 10,
+            SectionNode::DataSectionNode(_) => 11,
+        }
+    }
+
+    /// Number of entries of the section, none for the custom and start sections which have no vector.
+    pub fn count(&self) -> Option<usize> {
+        match self {
+            SectionNode::CustomSectionNode(_) | SectionNode::StartSectionNode(_) => None,
+            SectionNode::TypeSectionNode(t) => Some(t.func_types.len()),
+            SectionNode::ImportSectionNode(i) => Some(i.imports.len()),
+            SectionNode::FunctionSectionNode(f) => Some(f.type_indices.len()),
+            SectionNode::TableSectionNode(t) => Some(t.table_types.len()),
+            SectionNode::MemorySectionNode(m) => Some(m.mem_types.len()),
+            SectionNode::GlobalSectionNode(g) => Some(g.globals.len()),
+            SectionNode::CodeSectionNode(c) => Some(c.codes.len()),
+            SectionNode::ExportSectionNode(e) => Some(e.exports.len()),
+            SectionNode::DataSectionNode(d) => Some(d.segments.len()),
+        }
+    }
+
+    /// Where the section was decoded from, none for built sections.
+    pub fn header(&self) -> Option<&SectionHeader> {
+        match self {
+            SectionNode::CustomSectionNode(c) => c.header.as_ref(),
+            SectionNode::TypeSectionNode(t) => t.header.as_ref(),
+            SectionNode::ImportSectionNode(i) => i.header.as_ref(),
+            SectionNode::FunctionSectionNode(f) => f.header.as_ref(),
+            SectionNode::TableSectionNode(t) => t.header.as_ref(),
+            SectionNode::MemorySectionNode(m) => m.header.as_ref(),
+            SectionNode::GlobalSectionNode(g) => g.header.as_ref(),
+            SectionNode::CodeSectionNode(c) => c.header.as_ref(),
+            SectionNode::ExportSectionNode(e) => e.header.as_ref(),
+            SectionNode::StartSectionNode(s) => s.header.as_ref(),
+            SectionNode::DataSectionNode(d) => d.header.as_ref(),
+        }
+    }
+
+    pub fn set_header(&mut self, header: SectionHeader) {
+        match self {
+            SectionNode::CustomSectionNode(c) => c.header = Some(header),
+            SectionNode::TypeSectionNode(t) => t.header = Some(header),
+            SectionNode::ImportSectionNode(i) => i.header = Some(header),
+            SectionNode::FunctionSectionNode(f) => f.header = Some(header),
+            SectionNode::TableSectionNode(t) => t.header = Some(header),
+            SectionNode::MemorySectionNode(m) => m.header = Some(header),
+            SectionNode::GlobalSectionNode(g) => g.header = Some(header),
+            SectionNode::CodeSectionNode(c) => c.header = Some(header),
+            SectionNode::ExportSectionNode(e) => e.header = Some(header),
+            SectionNode::StartSectionNode(s) => s.header = Some(header),
+            SectionNode::DataSectionNode(d) => d.header = Some(header),
+        }
+    }
+
     pub fn load(&mut self, _buf: &mut Buffer) -> Result<()> {
         match self {
+            SectionNode::CustomSectionNode(c) => c.load(_buf),
             SectionNode::TypeSectionNode(t) => t.load(_buf),
+            SectionNode::ImportSectionNode(i) => i.load(_buf),
             SectionNode::FunctionSectionNode(f) => f.load(_buf),
+            SectionNode::TableSectionNode(t) => t.load(_buf),
+            SectionNode::MemorySectionNode(m) => m.load(_buf),
+            SectionNode::GlobalSectionNode(g) => g.load(_buf),
             SectionNode::CodeSectionNode(c) => c.load(_buf),
             SectionNode::ExportSectionNode(e) => e.load(_buf),
+            SectionNode::StartSectionNode(s) => s.load(_buf),
+            SectionNode::DataSectionNode(d) => d.load(_buf),
+        }
+    }
+
+    /// Write the contents of the section, without its id and size.
+    pub fn write(&self, writer: &mut Writer) {
+        match self {
+            SectionNode::CustomSectionNode(c) => c.write(writer),
+            SectionNode::TypeSectionNode(t) => t.write(writer),
+            SectionNode::ImportSectionNode(i) => i.write(writer),
+            SectionNode::FunctionSectionNode(f) => f.write(writer),
+            SectionNode::TableSectionNode(t) => t.write(writer),
+            SectionNode::MemorySectionNode(m) => m.write(writer),
+            SectionNode::GlobalSectionNode(g) => g.write(writer),
+            SectionNode::CodeSectionNode(c) => c.write(writer),
+            SectionNode::ExportSectionNode(e) => e.write(writer),
+            SectionNode::StartSectionNode(s) => s.write(writer),
+            SectionNode::DataSectionNode(d) => d.write(writer),
         }
     }
 }
@@ -0,0 +1,35 @@
+use anyhow::Result;
+
+use crate::exec::buffer::Buffer;
+use crate::exec::section::SectionHeader;
+use crate::exec::writer::Writer;
+
+/// Function called when the module is instantiated.
+/// https://webassembly.github.io/spec/core/binary/modules.html#start-section
+#[derive(Debug, Clone)]
+pub struct StartSectionNode {
+    pub func_idx: u32,
+    pub header: Option<SectionHeader>,
+}
+impl Default for StartSectionNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl StartSectionNode {
+    pub fn new() -> StartSectionNode {
+        StartSectionNode {
+            func_idx: 0,
+            header: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.func_idx = buf.read_u32()?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_u32(self.func_idx);
+    }
+}
@@ -0,0 +1,498 @@
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::exec::code_section::FuncNode;
+use crate::exec::fuel::{Fuel, FuelCosts};
+use crate::exec::interrupt::InterruptHandle;
+use crate::exec::limits::{
+    ResourceLimiter, StackLimits, DEFAULT_MAX_MEMORY_SIZE, DEFAULT_MAX_TABLE_ELEMENTS,
+};
+use crate::exec::module::ModuleNode;
+use crate::exec::trace::Tracer;
+use crate::exec::trap::{Trap, TrapKind};
+use crate::exec::type_section::{
+    FunctionTypeNode, GlobalTypeNode, MemoryTypeNode, RefType, TableTypeNode, ValType,
+};
+use crate::exec::value::Value;
+
+/// Index of a function in `Store::funcs`.
+pub type FuncAddr = usize;
+/// Index of a memory in `Store::memories`.
+pub type MemAddr = usize;
+/// Index of a table in `Store::tables`.
+pub type TableAddr = usize;
+/// Index of a global in `Store::globals`.
+pub type GlobalAddr = usize;
+
+pub const PAGE_SIZE: usize = 65536;
+/// Memories are limited to 4GiB.
+const MAX_PAGES: u32 = 65536;
+
+/// Function implemented by the embedder, called with arguments matching its type.
+pub type HostFunc = Arc<dyn Fn(&mut Caller, &[Value]) -> Result<Vec<Value>, Trap> + Send + Sync>;
+
+/// What a host function can reach of the Wasm function calling it.
+pub struct Caller<'a> {
+    /// First memory of the calling instance, none when called from the embedder.
+    pub memory: Option<&'a mut MemInst>,
+}
+
+#[derive(Clone)]
+enum FuncBody {
+    Wasm {
+        module: Arc<ModuleNode>,
+        code_idx: usize,
+    },
+    Host(HostFunc),
+}
+impl fmt::Debug for FuncBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FuncBody::Wasm { code_idx, .. } => write!(f, "Wasm {{ code_idx: {} }}", code_idx),
+            FuncBody::Host(_) => write!(f, "Host"),
+        }
+    }
+}
+
+/// Runtime representation of a function.
+/// https://webassembly.github.io/spec/core/exec/runtime.html#function-instances
+#[derive(Debug, Clone)]
+pub struct FuncInst {
+    pub func_type: FunctionTypeNode,
+    /// Index of the instance in `Store::instances` the function belongs to.
+    pub instance: usize,
+    /// Index of the function in the function index space of its module.
+    pub func_idx: u32,
+    pub name: Option<String>,
+    body: FuncBody,
+}
+impl FuncInst {
+    pub fn new(
+        func_type: FunctionTypeNode,
+        instance: usize,
+        func_idx: u32,
+        name: Option<String>,
+        module: Arc<ModuleNode>,
+        code_idx: usize,
+    ) -> FuncInst {
+        FuncInst {
+            func_type,
+            instance,
+            func_idx,
+            name,
+            body: FuncBody::Wasm { module, code_idx },
+        }
+    }
+
+    pub fn host(
+        func_type: FunctionTypeNode,
+        instance: usize,
+        func_idx: u32,
+        name: Option<String>,
+        func: HostFunc,
+    ) -> FuncInst {
+        FuncInst {
+            func_type,
+            instance,
+            func_idx,
+            name,
+            body: FuncBody::Host(func),
+        }
+    }
+
+    /// Body of a Wasm function, `None` for host functions.
+    pub fn code(&self) -> Option<&FuncNode> {
+        match &self.body {
+            // Instantiation checked that every function has a body.
+            FuncBody::Wasm { module, code_idx } => {
+                Some(&module.code_section().unwrap().codes[*code_idx].func)
+            }
+            FuncBody::Host(_) => None,
+        }
+    }
+
+    pub fn host_func(&self) -> Option<&HostFunc> {
+        match &self.body {
+            FuncBody::Wasm { .. } => None,
+            FuncBody::Host(func) => Some(func),
+        }
+    }
+}
+
+/// Numbers that can be read from and written to linear memory.
+pub trait MemoryValue: Sized {
+    /// Size in bytes.
+    const SIZE: usize;
+    /// `bytes` is exactly `SIZE` bytes long.
+    fn from_le(bytes: &[u8]) -> Self;
+    /// `bytes` is exactly `SIZE` bytes long.
+    fn to_le(self, bytes: &mut [u8]);
+}
+macro_rules! impl_memory_value {
+    ($($t:ty),*) => {
+        $(
+            impl MemoryValue for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+
+                fn from_le(bytes: &[u8]) -> Self {
+                    <$t>::from_le_bytes(bytes.try_into().unwrap())
+                }
+
+                fn to_le(self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+impl_memory_value!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+
+/// https://webassembly.github.io/spec/core/exec/runtime.html#memory-instances
+#[derive(Debug, Clone)]
+pub struct MemInst {
+    pub data: Vec<u8>,
+    /// Maximum size in pages.
+    pub max: Option<u32>,
+}
+impl MemInst {
+    pub fn new(
+        mem_type: &MemoryTypeNode,
+        limiter: Option<&mut dyn ResourceLimiter>,
+    ) -> Result<MemInst> {
+        let limits = &mem_type.limits;
+        let mut mem = MemInst {
+            data: Vec::new(),
+            max: limits.max,
+        };
+        mem.grow(limits.min, limiter).ok_or(anyhow!(
+            "Memory of {} pages could not be allocated",
+            limits.min
+        ))?;
+        Ok(mem)
+    }
+
+    /// Size in pages.
+    pub fn size(&self) -> u32 {
+        (self.data.len() / PAGE_SIZE) as u32
+    }
+
+    /// Read `N` bytes at the effective address `addr + offset`.
+    pub fn load<const N: usize>(&self, addr: i32, offset: u32) -> Result<[u8; N], TrapKind> {
+        let start = self.effective_address(addr, offset, N)?;
+        Ok(self.data[start..start + N].try_into().unwrap())
+    }
+
+    /// Write `bytes` at the effective address `addr + offset`.
+    pub fn store<const N: usize>(
+        &mut self,
+        addr: i32,
+        offset: u32,
+        bytes: [u8; N],
+    ) -> Result<(), TrapKind> {
+        let start = self.effective_address(addr, offset, N)?;
+        self.data[start..start + N].copy_from_slice(&bytes);
+        Ok(())
+    }
+
+    /// Bytes in `offset..offset + len`.
+    pub fn read_bytes(&self, offset: usize, len: usize) -> Result<&[u8], TrapKind> {
+        let end = offset.checked_add(len).ok_or(TrapKind::MemoryOutOfBounds)?;
+        self.data
+            .get(offset..end)
+            .ok_or(TrapKind::MemoryOutOfBounds)
+    }
+
+    /// Mutable bytes in `offset..offset + len`.
+    pub fn bytes_mut(&mut self, offset: usize, len: usize) -> Result<&mut [u8], TrapKind> {
+        let end = offset.checked_add(len).ok_or(TrapKind::MemoryOutOfBounds)?;
+        self.data
+            .get_mut(offset..end)
+            .ok_or(TrapKind::MemoryOutOfBounds)
+    }
+
+    pub fn write_bytes(&mut self, offset: usize, bytes: &[u8]) -> Result<(), TrapKind> {
+        self.bytes_mut(offset, bytes.len())?.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Read a little-endian value at `offset`.
+    pub fn read<T: MemoryValue>(&self, offset: usize) -> Result<T, TrapKind> {
+        Ok(T::from_le(self.read_bytes(offset, T::SIZE)?))
+    }
+
+    /// Write `value` in little-endian at `offset`.
+    pub fn write<T: MemoryValue>(&mut self, offset: usize, value: T) -> Result<(), TrapKind> {
+        let end = offset
+            .checked_add(T::SIZE)
+            .ok_or(TrapKind::MemoryOutOfBounds)?;
+        let dest = self
+            .data
+            .get_mut(offset..end)
+            .ok_or(TrapKind::MemoryOutOfBounds)?;
+        value.to_le(dest);
+        Ok(())
+    }
+
+    fn effective_address(&self, addr: i32, offset: u32, size: usize) -> Result<usize, TrapKind> {
+        // The address operand is interpreted as unsigned.
+        let start = addr as u32 as u64 + offset as u64;
+        if start + size as u64 > self.data.len() as u64 {
+            return Err(TrapKind::MemoryOutOfBounds);
+        }
+        Ok(start as usize)
+    }
+
+    /// Grow by `delta` pages and return the previous size, or `None` if the maximum is
+    /// exceeded, `limiter` denies it, or the pages cannot be allocated.
+    /// Without a limiter, memories are capped at `DEFAULT_MAX_MEMORY_SIZE`.
+    pub fn grow(&mut self, delta: u32, limiter: Option<&mut dyn ResourceLimiter>) -> Option<u32> {
+        let old_size = self.size();
+        let new_size = old_size.checked_add(delta)?;
+        if new_size > self.max.unwrap_or(MAX_PAGES).min(MAX_PAGES) {
+            return None;
+        }
+        let (current, desired) = (self.data.len(), new_size as usize * PAGE_SIZE);
+        match limiter {
+            Some(limiter) => {
+                let max = self.max.map(|max| max as usize * PAGE_SIZE);
+                if !limiter.memory_growing(current, desired, max) {
+                    return None;
+                }
+                if self.data.try_reserve_exact(desired - current).is_err() {
+                    limiter.memory_grow_failed(current, desired);
+                    return None;
+                }
+            }
+            None => {
+                if desired > DEFAULT_MAX_MEMORY_SIZE
+                    || self.data.try_reserve_exact(desired - current).is_err()
+                {
+                    return None;
+                }
+            }
+        }
+        self.data.resize(desired, 0);
+        Some(old_size)
+    }
+}
+
+/// https://webassembly.github.io/spec/core/exec/runtime.html#table-instances
+#[derive(Debug, Clone)]
+pub struct TableInst {
+    pub ref_type: RefType,
+    pub elements: Vec<Value>,
+    pub max: Option<u32>,
+}
+impl TableInst {
+    pub fn new(
+        table_type: &TableTypeNode,
+        limiter: Option<&mut dyn ResourceLimiter>,
+    ) -> Result<TableInst> {
+        let limits = &table_type.limits;
+        let mut table = TableInst {
+            ref_type: table_type.ref_type.clone(),
+            elements: Vec::new(),
+            max: limits.max,
+        };
+        let null = Value::null_of(&table.ref_type);
+        table.grow(limits.min, null, limiter).ok_or(anyhow!(
+            "Table of {} elements could not be allocated",
+            limits.min
+        ))?;
+        Ok(table)
+    }
+
+    pub fn size(&self) -> u32 {
+        self.elements.len() as u32
+    }
+
+    pub fn get(&self, idx: u32) -> Result<Value, TrapKind> {
+        let element = self.elements.get(idx as usize);
+        element.copied().ok_or(TrapKind::TableOutOfBounds)
+    }
+
+    /// Fails if `value` is not a reference of the element type of the table.
+    pub fn set(&mut self, idx: u32, value: Value) -> Result<(), TrapKind> {
+        if value.val_type() != ValType::RefType(self.ref_type.clone()) {
+            return Err(TrapKind::TypeMismatch);
+        }
+        let element = self
+            .elements
+            .get_mut(idx as usize)
+            .ok_or(TrapKind::TableOutOfBounds)?;
+        *element = value;
+        Ok(())
+    }
+
+    /// Grow by `delta` elements set to `init` and return the previous size, or `None` if the
+    /// maximum is exceeded, `limiter` denies it, or the elements cannot be allocated.
+    /// Without a limiter, tables are capped at `DEFAULT_MAX_TABLE_ELEMENTS`.
+    pub fn grow(
+        &mut self,
+        delta: u32,
+        init: Value,
+        limiter: Option<&mut dyn ResourceLimiter>,
+    ) -> Option<u32> {
+        let old_size = self.size();
+        let new_size = old_size.checked_add(delta)?;
+        if new_size > self.max.unwrap_or(u32::MAX) {
+            return None;
+        }
+        match limiter {
+            Some(limiter) => {
+                if !limiter.table_growing(old_size, new_size, self.max) {
+                    return None;
+                }
+                if self.elements.try_reserve_exact(delta as usize).is_err() {
+                    limiter.table_grow_failed(old_size, new_size);
+                    return None;
+                }
+            }
+            None => {
+                if new_size > DEFAULT_MAX_TABLE_ELEMENTS
+                    || self.elements.try_reserve_exact(delta as usize).is_err()
+                {
+                    return None;
+                }
+            }
+        }
+        self.elements.resize(new_size as usize, init);
+        Some(old_size)
+    }
+}
+
+/// https://webassembly.github.io/spec/core/exec/runtime.html#global-instances
+#[derive(Debug, Clone)]
+pub struct GlobalInst {
+    pub global_type: GlobalTypeNode,
+    pub value: Value,
+}
+impl GlobalInst {
+    pub fn new(global_type: GlobalTypeNode, value: Value) -> GlobalInst {
+        GlobalInst { global_type, value }
+    }
+
+    pub fn get(&self) -> Value {
+        self.value
+    }
+
+    /// Fails if the global is immutable or `value` is not of its type.
+    pub fn set(&mut self, value: Value) -> Result<()> {
+        if !self.global_type.mutable {
+            return Err(anyhow!("Global is immutable"));
+        }
+        if value.val_type() != self.global_type.val_type {
+            return Err(anyhow!(
+                "Expected a value of type {:?}, got {:?}",
+                self.global_type.val_type,
+                value.val_type()
+            ));
+        }
+        self.value = value;
+        Ok(())
+    }
+}
+
+/// Value an export refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExternVal {
+    Func(FuncAddr),
+    Table(TableAddr),
+    Memory(MemAddr),
+    Global(GlobalAddr),
+}
+
+#[derive(Debug, Clone)]
+pub struct InstanceData {
+    pub module: Arc<ModuleNode>,
+    /// Address of each function in the function index space of the module.
+    pub func_addrs: Vec<FuncAddr>,
+    pub mem_addrs: Vec<MemAddr>,
+    pub table_addrs: Vec<TableAddr>,
+    pub global_addrs: Vec<GlobalAddr>,
+    pub exports: Vec<(String, ExternVal)>,
+}
+
+/// Holds every runtime object created by instantiating modules.
+/// https://webassembly.github.io/spec/core/exec/runtime.html#store
+#[derive(Default)]
+pub struct Store {
+    pub funcs: Vec<FuncInst>,
+    pub memories: Vec<MemInst>,
+    pub tables: Vec<TableInst>,
+    pub globals: Vec<GlobalInst>,
+    pub instances: Vec<InstanceData>,
+    pub limiter: Option<Box<dyn ResourceLimiter>>,
+    pub fuel: Fuel,
+    pub interrupt: InterruptHandle,
+    pub stack_limits: StackLimits,
+    pub tracer: Option<Box<dyn Tracer>>,
+}
+impl Store {
+    pub fn new() -> Store {
+        Store::default()
+    }
+
+    /// Limit the remaining execution to `fuel` units, see `FuelCosts`.
+    /// Running out of fuel raises an `OutOfFuel` trap.
+    pub fn set_fuel(&mut self, fuel: u64) {
+        self.fuel.set_remaining(fuel);
+    }
+
+    pub fn set_fuel_costs(&mut self, costs: FuelCosts) {
+        self.fuel.set_costs(costs);
+    }
+
+    /// `None` when fuel metering is disabled.
+    pub fn fuel_remaining(&self) -> Option<u64> {
+        self.fuel.remaining()
+    }
+
+    pub fn fuel_consumed(&self) -> u64 {
+        self.fuel.consumed()
+    }
+
+    pub fn set_stack_limits(&mut self, limits: StackLimits) {
+        self.stack_limits = limits;
+    }
+
+    pub fn limiter(&mut self) -> Option<&mut dyn ResourceLimiter> {
+        self.limiter
+            .as_mut()
+            .map(|l| l.as_mut() as &mut dyn ResourceLimiter)
+    }
+
+    /// Grow memory `addr` by `delta` pages, consulting the limiter.
+    /// Return the previous size, or `None` if the growth failed.
+    pub fn grow_memory(&mut self, addr: MemAddr, delta: u32) -> Option<u32> {
+        let limiter = self.limiter.as_mut().map(|l| l.as_mut() as _);
+        self.memories[addr].grow(delta, limiter)
+    }
+
+    /// Grow table `addr` by `delta` elements set to `init`, consulting the limiter.
+    /// Return the previous size, or `None` if the growth failed.
+    pub fn grow_table(&mut self, addr: TableAddr, delta: u32, init: Value) -> Result<Option<u32>> {
+        let table = &mut self.tables[addr];
+        if init.val_type() != ValType::RefType(table.ref_type.clone()) {
+            return Err(anyhow!("Expected a reference of type {:?}", table.ref_type));
+        }
+        let limiter = self.limiter.as_mut().map(|l| l.as_mut() as _);
+        Ok(table.grow(delta, init, limiter))
+    }
+
+    /// Consult `limiter` whenever a memory or table is created or grown.
+    pub fn set_limiter(&mut self, limiter: impl ResourceLimiter + 'static) {
+        self.limiter = Some(Box::new(limiter));
+    }
+
+    /// Report every instruction executed from now on to `tracer`.
+    pub fn set_tracer(&mut self, tracer: impl Tracer + 'static) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    /// Handle that can be sent to another thread to stop running code.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+}
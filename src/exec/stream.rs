@@ -0,0 +1,419 @@
+use anyhow::{anyhow, Result};
+use std::io::{ErrorKind, Read};
+
+use crate::errors::ExecError;
+use crate::exec::buffer::{Buffer, Span};
+use crate::exec::code_section::{CodeNode, CodeSectionNode};
+use crate::exec::module::ModuleNode;
+use crate::exec::section::{SectionHeader, SectionNode, SectionOrder};
+use crate::exec::validate::Context;
+
+/// Bytes asked from the reader at once.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Most bytes a LEB128 encoded u32 takes.
+const MAX_U32_LEN: usize = 5;
+
+pub const CODE_SECTION_ID: u8 = 10;
+
+/// Progress of `StreamDecoder`, the decoded nodes being added to `StreamDecoder::module`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// Magic number and version were read.
+    Header,
+    /// A section was decoded and is the last of the module. The code section comes
+    /// without its bodies, each followed by a `Code` event.
+    Section(SectionHeader),
+    /// The body of the function `func_idx` was decoded and is the last of the code section.
+    Code(u32),
+}
+
+#[derive(Debug)]
+enum State {
+    Header,
+    Sections,
+    /// Within the code section, which ends at `end`.
+    Code {
+        remaining: u32,
+        end: u32,
+    },
+    Done,
+}
+
+/// Decodes a binary module from `reader` chunk by chunk, each section being decoded as soon
+/// as its bytes arrived, and each function body of the code section on its own.
+/// Sections out of order are malformed.
+pub struct StreamDecoder<R> {
+    reader: R,
+    /// Bytes read but not all decoded yet, from `pos`.
+    pending: Vec<u8>,
+    pos: usize,
+    /// Offset in the module of `pending[0]`.
+    start: u32,
+    eof: bool,
+    state: State,
+    order: SectionOrder,
+    /// Whether to validate each function body once decoded.
+    validate: bool,
+    /// Sections the bodies can refer to, all before the code section.
+    context: Option<Context>,
+    module: ModuleNode,
+}
+impl<R: Read> StreamDecoder<R> {
+    pub fn new(reader: R) -> StreamDecoder<R> {
+        StreamDecoder {
+            reader,
+            pending: Vec::new(),
+            pos: 0,
+            start: 0,
+            eof: false,
+            state: State::Header,
+            order: SectionOrder::default(),
+            validate: false,
+            context: None,
+            module: ModuleNode::new(),
+        }
+    }
+
+    /// Decoder validating each function body as soon as it is decoded, failing with
+    /// `ExecError::Invalid` before reading the next one. The other sections are not validated.
+    pub fn validating(reader: R) -> StreamDecoder<R> {
+        StreamDecoder {
+            validate: true,
+            ..StreamDecoder::new(reader)
+        }
+    }
+
+    /// Module decoded so far.
+    pub fn module(&self) -> &ModuleNode {
+        &self.module
+    }
+
+    pub fn into_module(self) -> ModuleNode {
+        self.module
+    }
+
+    /// Decode the whole module.
+    pub fn decode(mut self) -> Result<ModuleNode> {
+        while self.next_event()?.is_some() {}
+        Ok(self.module)
+    }
+
+    /// Read until the next node is decoded, `None` at the end of the module.
+    pub fn next_event(&mut self) -> Result<Option<Event>> {
+        loop {
+            match self.state {
+                State::Header => {
+                    self.fill(8)?;
+                    let mut buf = self.buffer();
+                    let magic = buf.read_bytes(4)?.to_vec();
+                    let version = buf.read_bytes(4)?.to_vec();
+                    self.consume(8);
+                    if magic != [0x00, 0x61, 0x73, 0x6d] {
+                        return Err(anyhow!("Invalid wasm magic number"));
+                    }
+                    self.module.magic = magic;
+                    self.module.version = version;
+                    self.state = State::Sections;
+                    return Ok(Some(Event::Header));
+                }
+                State::Sections => {
+                    if !self.fill(1)? {
+                        self.state = State::Done;
+                        continue;
+                    }
+                    return self.section().map(Some);
+                }
+                State::Code { remaining: 0, end } => {
+                    if self.offset() != end {
+                        return Err(size_mismatch());
+                    }
+                    self.state = State::Sections;
+                }
+                State::Code { remaining, end } => {
+                    let func_idx = self.code(end)?;
+                    self.state = State::Code {
+                        remaining: remaining - 1,
+                        end,
+                    };
+                    return Ok(Some(Event::Code(func_idx)));
+                }
+                State::Done => return Ok(None),
+            }
+        }
+    }
+
+    fn section(&mut self) -> Result<Event> {
+        let start = self.offset();
+        self.fill(1 + MAX_U32_LEN)?;
+        let mut buf = self.buffer();
+        let id = buf.read_byte()?;
+        let size = buf.read_u32()?;
+        let header_len = buf.cursor() as usize;
+        let header = SectionHeader {
+            id,
+            span: Span {
+                start,
+                end: buf.offset() + size,
+            },
+            offset: buf.offset(),
+            size,
+        };
+        self.consume(header_len);
+        self.order.check(id)?;
+
+        if id == CODE_SECTION_ID {
+            self.fill(MAX_U32_LEN)?;
+            let mut buf = self.buffer();
+            let count = buf.read_u32()?;
+            let count_len = buf.cursor() as usize;
+            self.consume(count_len);
+            // The function section comes first, so a body is never decoded without its type.
+            let func_count = self
+                .module
+                .function_section()
+                .map_or(0, |f| f.type_indices.len());
+            if count as usize != func_count {
+                return Err(anyhow!(
+                    "Function and code section have inconsistent lengths: {} and {}",
+                    func_count,
+                    count
+                ));
+            }
+            self.state = State::Code {
+                remaining: count,
+                end: header.offset + size,
+            };
+            if self.validate {
+                self.context = Some(Context::new(&self.module));
+            }
+            self.push_section(header, SectionNode::CodeSectionNode(CodeSectionNode::new()));
+            return Ok(Event::Section(header));
+        }
+
+        self.fill(size as usize)?;
+        let mut section_buf = self.buffer().read_buffer(size)?;
+        let mut section = SectionNode::create(id)?;
+        section.load(&mut section_buf)?;
+        if !section_buf.eof() {
+            return Err(size_mismatch());
+        }
+        self.consume(size as usize);
+        self.push_section(header, section);
+        Ok(Event::Section(header))
+    }
+
+    fn push_section(&mut self, header: SectionHeader, mut section: SectionNode) {
+        section.set_header(header);
+        self.module.sections.push(section);
+    }
+
+    /// Decode the next function body of the code section ending at `end`, giving its index.
+    fn code(&mut self, end: u32) -> Result<u32> {
+        self.fill(MAX_U32_LEN)?;
+        let mut buf = self.buffer();
+        let size = buf.read_u32()?;
+        let len = buf.cursor() as usize + size as usize;
+        if self.offset() as usize + len > end as usize {
+            return Err(anyhow!("Buffer too small"));
+        }
+        self.fill(len)?;
+        let mut code = CodeNode::new();
+        code.load(&mut self.buffer())?;
+        self.consume(len);
+
+        let imported_funcs = self.module.imported_func_count();
+        let Some(SectionNode::CodeSectionNode(code_section)) = self.module.sections.last_mut()
+        else {
+            unreachable!("bodies are decoded right after their section");
+        };
+        let func_idx = imported_funcs + code_section.codes.len() as u32;
+        if let Some(context) = &self.context {
+            context
+                .validate_func(func_idx, &code.func)
+                .map_err(|e| ExecError::Invalid(e.to_string()))?;
+        }
+        code_section.codes.push(code);
+        Ok(func_idx)
+    }
+
+    /// Offset in the module of the next byte to decode.
+    fn offset(&self) -> u32 {
+        self.start + self.pos as u32
+    }
+
+    /// Bytes read but not decoded yet, at their offset in the module.
+    fn buffer(&self) -> Buffer<'_> {
+        Buffer::at(&self.pending[self.pos..], self.offset())
+    }
+
+    fn consume(&mut self, len: usize) {
+        self.pos += len;
+    }
+
+    /// Read until `len` bytes are available to decode, or the end of the input.
+    /// Returns whether there are enough.
+    fn fill(&mut self, len: usize) -> Result<bool> {
+        while self.pending.len() - self.pos < len && !self.eof {
+            // Drop the decoded bytes before reading more, rather than after every node.
+            self.pending.drain(..self.pos);
+            self.start += self.pos as u32;
+            self.pos = 0;
+
+            let read = self.pending.len();
+            self.pending.resize(read + CHUNK_SIZE, 0);
+            let result = self.reader.read(&mut self.pending[read..]);
+            self.pending.truncate(read + *result.as_ref().unwrap_or(&0));
+            match result {
+                Ok(0) => self.eof = true,
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(self.pending.len() - self.pos >= len)
+    }
+}
+
+fn size_mismatch() -> anyhow::Error {
+    ExecError::Malformed("section size mismatch".to_string()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Reader giving one byte at a time, as a pipe might.
+    struct Trickle<'a>(&'a [u8]);
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let Some((first, rest)) = self.0.split_first() else {
+                return Ok(0);
+            };
+            buf[0] = *first;
+            self.0 = rest;
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn test_decode_examples() {
+        let mut paths: Vec<_> = fs::read_dir("examples")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "wasm"))
+            .collect();
+        paths.sort();
+        assert!(!paths.is_empty());
+        for path in paths {
+            let bytes = fs::read(&path).unwrap();
+            let mut expected = ModuleNode::new();
+            expected.load(&mut Buffer::new(&bytes)).unwrap();
+            for module in [
+                StreamDecoder::new(&bytes[..]).decode().unwrap(),
+                StreamDecoder::new(Trickle(&bytes)).decode().unwrap(),
+            ] {
+                assert_eq!(
+                    format!("{:?}", module),
+                    format!("{:?}", expected),
+                    "{}",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_events() {
+        let bytes = fs::read("examples/call.wasm").unwrap();
+        let mut decoder = StreamDecoder::new(Trickle(&bytes));
+        let mut events = Vec::new();
+        while let Some(event) = decoder.next_event().unwrap() {
+            events.push(match event {
+                Event::Header => "header".to_string(),
+                Event::Section(header) => format!("section {}", header.id),
+                Event::Code(func_idx) => format!("code {}", func_idx),
+            });
+        }
+        assert_eq!(
+            events,
+            [
+                "header",
+                "section 1",
+                "section 3",
+                "section 7",
+                "section 10",
+                "code 0",
+                "code 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_section_order() {
+        let module = |sections: &[u8]| [b"\0asm\x01\0\0\0", sections].concat();
+        // Empty type, export and custom sections.
+        let (types, exports, custom): (&[u8], &[u8], &[u8]) =
+            (&[1, 1, 0], &[7, 1, 0], b"\0\x02\x01x");
+        let ordered = module(&[custom, types, custom, exports].concat());
+        assert!(StreamDecoder::new(&ordered[..]).decode().is_ok());
+        for sections in [[exports, types].concat(), [types, types].concat()] {
+            let bytes = module(&sections);
+            let error = StreamDecoder::new(&bytes[..]).decode().unwrap_err();
+            assert!(error.to_string().starts_with("Unexpected type section"));
+            assert!(ModuleNode::new().load(&mut Buffer::new(&bytes)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_validating() {
+        let bytes = crate::wat::module::parse_str(
+            "(module (func (result i32) (i32.const 0)) (func (result i32) (i64.const 0)))",
+        )
+        .unwrap();
+        assert!(StreamDecoder::new(&bytes[..]).decode().is_ok());
+        let mut decoder = StreamDecoder::validating(&bytes[..]);
+        let mut events = Vec::new();
+        let error = loop {
+            match decoder.next_event() {
+                Ok(event) => events.push(event.unwrap()),
+                Err(e) => break e,
+            }
+        };
+        assert_eq!(events.last(), Some(&Event::Code(0)));
+        assert_eq!(
+            error.downcast::<ExecError>().unwrap().to_string(),
+            "Invalid module: func[1]: type mismatch: expected i32, found i64"
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        let bytes = fs::read("examples/call.wasm").unwrap();
+        for len in [4, 9, 12, bytes.len() - 1] {
+            let result = StreamDecoder::new(Trickle(&bytes[..len])).decode();
+            assert!(result.is_err(), "{} bytes", len);
+        }
+    }
+
+    #[test]
+    fn test_trailing_bytes() {
+        let header = [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00];
+        let types = [0x01, 0x04, 0x01, 0x60, 0x00, 0x00, 0x03, 0x02, 0x01, 0x00];
+        let code = [0x0a, 0x04, 0x01, 0x02, 0x00, 0x0b];
+        let bytes = [&header[..], &types, &code].concat();
+        StreamDecoder::new(Trickle(&bytes)).decode().unwrap();
+
+        // A byte after the last body, then after the only type.
+        let code = [0x0a, 0x05, 0x01, 0x02, 0x00, 0x0b, 0x00];
+        let types = [0x01, 0x05, 0x01, 0x60, 0x00, 0x00, 0x00];
+        for bytes in [
+            [&header[..], &bytes[8..18], &code].concat(),
+            [&header[..], &types].concat(),
+        ] {
+            let err = StreamDecoder::new(Trickle(&bytes)).decode().unwrap_err();
+            assert_eq!(err.to_string(), "Malformed module: section size mismatch");
+        }
+    }
+}
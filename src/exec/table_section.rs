@@ -0,0 +1,39 @@
+use anyhow::Result;
+
+use crate::exec::buffer::Buffer;
+use crate::exec::section::SectionHeader;
+use crate::exec::type_section::TableTypeNode;
+use crate::exec::writer::Writer;
+
+#[derive(Debug, Clone)]
+pub struct TableSectionNode {
+    pub table_types: Vec<TableTypeNode>,
+    pub header: Option<SectionHeader>,
+}
+impl Default for TableSectionNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl TableSectionNode {
+    pub fn new() -> TableSectionNode {
+        TableSectionNode {
+            table_types: Vec::new(),
+            header: None,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        let f = |buf: &mut Buffer| -> Result<TableTypeNode> {
+            let mut table_type = TableTypeNode::new();
+            table_type.load(buf)?;
+            Ok(table_type)
+        };
+        self.table_types = buf.read_vec::<TableTypeNode>(Box::new(f))?;
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_vec(&self.table_types, |w, item| item.write(w));
+    }
+}
@@ -0,0 +1,193 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::exec::code_section::IntrinsicNode;
+use crate::exec::store::FuncAddr;
+use crate::exec::value::Value;
+use crate::wat::printer::print_instr;
+
+/// Instruction about to be executed by a `Machine`.
+#[derive(Debug, Clone, Copy)]
+pub struct InstructionEvent<'a> {
+    /// Address of the executing function in `Store::funcs`.
+    pub func: FuncAddr,
+    /// Index of the executing function in the function index space of its module.
+    pub func_idx: u32,
+    /// Name from the name section, if the module has one.
+    pub func_name: Option<&'a str>,
+    /// Offset of the instruction in the module.
+    pub offset: u32,
+    pub intrinsic: &'a IntrinsicNode,
+    /// Operand stack before the instruction, its top being the last value.
+    pub stack: &'a [Value],
+}
+
+/// Function entered or left by a `Machine`.
+#[derive(Debug, Clone, Copy)]
+pub struct CallEvent<'a> {
+    /// Address of the function in `Store::funcs`.
+    pub func: FuncAddr,
+    pub func_idx: u32,
+    pub func_name: Option<&'a str>,
+    /// Whether the function is implemented by the embedder, e.g. a WASI function.
+    pub is_host: bool,
+}
+
+/// Observes the execution of Wasm code, see `Store::set_tracer`.
+pub trait Tracer {
+    /// Called before each instruction is executed.
+    fn instruction(&mut self, event: &InstructionEvent);
+
+    /// Called when a function is called, before its first instruction.
+    fn enter(&mut self, _event: &CallEvent) {}
+
+    /// Called when a function returns, or is unwound by a trap.
+    fn exit(&mut self, _event: &CallEvent) {}
+}
+
+/// Lets the caller keep a handle on a tracer moved into the store, to read what it collected.
+impl<T: Tracer> Tracer for Rc<RefCell<T>> {
+    fn instruction(&mut self, event: &InstructionEvent) {
+        self.borrow_mut().instruction(event);
+    }
+
+    fn enter(&mut self, event: &CallEvent) {
+        self.borrow_mut().enter(event);
+    }
+
+    fn exit(&mut self, event: &CallEvent) {
+        self.borrow_mut().exit(event);
+    }
+}
+
+/// Reports every event to each tracer in order.
+impl Tracer for Vec<Box<dyn Tracer>> {
+    fn instruction(&mut self, event: &InstructionEvent) {
+        self.iter_mut().for_each(|tracer| tracer.instruction(event));
+    }
+
+    fn enter(&mut self, event: &CallEvent) {
+        self.iter_mut().for_each(|tracer| tracer.enter(event));
+    }
+
+    fn exit(&mut self, event: &CallEvent) {
+        self.iter_mut().for_each(|tracer| tracer.exit(event));
+    }
+}
+
+/// `Tracer` writing one line per executed instruction, e.g.
+/// `func[0] <loop> @ 0x0012: i32.add ; top: 14:i32`.
+pub struct WriteTracer {
+    out: Box<dyn Write>,
+    /// Only trace functions with this index or name.
+    filter: Option<String>,
+}
+impl WriteTracer {
+    pub fn new(out: impl Write + 'static, filter: Option<String>) -> WriteTracer {
+        WriteTracer {
+            out: Box::new(out),
+            filter,
+        }
+    }
+
+    fn traces(&self, event: &InstructionEvent) -> bool {
+        match &self.filter {
+            Some(filter) => {
+                *filter == event.func_idx.to_string() || Some(filter.as_str()) == event.func_name
+            }
+            None => true,
+        }
+    }
+
+    fn write_event(&mut self, event: &InstructionEvent) -> io::Result<()> {
+        match event.func_name {
+            Some(name) => write!(self.out, "func[{}] <{}>", event.func_idx, name)?,
+            None => write!(self.out, "func[{}]", event.func_idx)?,
+        }
+        write!(
+            self.out,
+            " @ {:#06x}: {}",
+            event.offset,
+            print_instr(event.intrinsic)
+        )?;
+        match event.stack.last() {
+            Some(top) => writeln!(self.out, " ; top: {}", top),
+            None => writeln!(self.out, " ; top: -"),
+        }
+    }
+}
+impl Tracer for WriteTracer {
+    fn instruction(&mut self, event: &InstructionEvent) {
+        if !self.traces(event) {
+            return;
+        }
+        // A broken output must not abort the traced execution.
+        let _ = self.write_event(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::buffer::Buffer;
+    use crate::exec::instance::Instance;
+    use crate::exec::module::ModuleNode;
+    use crate::exec::store::Store;
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+
+    /// Output shared with the test once the tracer is moved into the store.
+    #[derive(Clone, Default)]
+    struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn trace(path: &str, name: &str, filter: Option<&str>) -> String {
+        let mut module = ModuleNode::new();
+        module
+            .load(&mut Buffer::new(&fs::read(path).unwrap()))
+            .unwrap();
+        let mut store = Store::new();
+        let output = SharedOutput::default();
+        store.set_tracer(WriteTracer::new(output.clone(), filter.map(str::to_string)));
+        let instance = Instance::new(&mut store, module).unwrap();
+        instance.invoke(&mut store, name, &[]).unwrap();
+        let bytes = output.0.lock().unwrap().clone();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_write_tracer() {
+        let output = trace("examples/loop.wasm", "loop", None);
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(
+            lines[..4],
+            [
+                "func[0] @ 0x0024: i32.const 0 ; top: -",
+                "func[0] @ 0x0026: local.set 1 ; top: 0:i32",
+                "func[0] @ 0x0028: i32.const 0 ; top: -",
+                "func[0] @ 0x002a: local.set 0 ; top: 0:i32",
+            ]
+        );
+        assert!(lines.contains(&"func[0] @ 0x002c: block ; top: -"));
+        assert_eq!(lines.len(), 50);
+        assert_eq!(
+            lines.last(),
+            Some(&"func[0] @ 0x0049: local.get 1 ; top: -")
+        );
+    }
+
+    #[test]
+    fn test_write_tracer_filter() {
+        assert_eq!(trace("examples/loop.wasm", "loop", Some("1")), "");
+        assert_ne!(trace("examples/loop.wasm", "loop", Some("0")), "");
+    }
+}
@@ -0,0 +1,111 @@
+use std::fmt;
+
+/// Frames beyond this are elided when displaying a backtrace, e.g. after a stack overflow.
+const MAX_DISPLAYED_FRAMES: usize = 32;
+
+/// Reason why execution was aborted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapKind {
+    /// An `unreachable` instruction was executed.
+    Unreachable,
+    /// Signed division overflowed, e.g. `i32.div_s` of `i32::MIN` by `-1`,
+    /// or a float truncated to an integer was out of its range.
+    IntegerOverflow,
+    IntegerDivideByZero,
+    MemoryOutOfBounds,
+    TableOutOfBounds,
+    /// The index given to `call_indirect` is past the end of the table.
+    UndefinedElement,
+    /// The element called by `call_indirect` is a null reference.
+    UninitializedElement,
+    /// The signature of the callee of `call_indirect` did not match the expected type.
+    IndirectCallTypeMismatch,
+    /// The call stack or the operand stack exceeded its limit.
+    StackOverflow,
+    /// A NaN could not be truncated to an integer. Out of range floats raise `IntegerOverflow`.
+    InvalidConversionToInteger,
+    /// The fuel budget of the store was exhausted.
+    OutOfFuel,
+    /// The execution was stopped through an `InterruptHandle`.
+    Interrupted,
+    /// The operand stack did not hold the values an instruction expected.
+    /// Only happens for modules that would not pass validation.
+    TypeMismatch,
+    /// The guest asked to terminate with this exit status, through WASI `proc_exit`.
+    Exit(u32),
+}
+impl fmt::Display for TrapKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            TrapKind::Exit(status) => return write!(f, "exited with status {}", status),
+            TrapKind::Unreachable => "unreachable executed",
+            TrapKind::IntegerOverflow => "integer overflow",
+            TrapKind::IntegerDivideByZero => "integer divide by zero",
+            TrapKind::MemoryOutOfBounds => "out of bounds memory access",
+            TrapKind::TableOutOfBounds => "out of bounds table access",
+            TrapKind::UndefinedElement => "undefined element",
+            TrapKind::UninitializedElement => "uninitialized element",
+            TrapKind::IndirectCallTypeMismatch => "indirect call type mismatch",
+            TrapKind::StackOverflow => "call stack exhausted",
+            TrapKind::InvalidConversionToInteger => "invalid conversion to integer",
+            TrapKind::OutOfFuel => "all fuel consumed",
+            TrapKind::Interrupted => "interrupted",
+            TrapKind::TypeMismatch => "type mismatch",
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for TrapKind {}
+
+/// One entry of the Wasm backtrace of a trap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameInfo {
+    pub func_idx: u32,
+    /// Name from the name section, if the module has one.
+    pub func_name: Option<String>,
+    /// Offset in the module of the instruction being executed.
+    pub offset: u32,
+}
+impl fmt::Display for FrameInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.func_name {
+            Some(name) => write!(f, "func[{}] <{}>", self.func_idx, name)?,
+            None => write!(f, "func[{}]", self.func_idx)?,
+        }
+        write!(f, " @ {:#06x}", self.offset)
+    }
+}
+
+/// Error raised when the execution of Wasm code fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trap {
+    pub kind: TrapKind,
+    /// Frames active when the trap occurred, innermost first.
+    pub backtrace: Vec<FrameInfo>,
+}
+impl Trap {
+    pub fn new(kind: TrapKind) -> Trap {
+        Trap {
+            kind,
+            backtrace: Vec::new(),
+        }
+    }
+}
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "wasm trap: {}", self.kind)?;
+        if !self.backtrace.is_empty() {
+            write!(f, "\nwasm backtrace:")?;
+            for (i, frame) in self.backtrace.iter().take(MAX_DISPLAYED_FRAMES).enumerate() {
+                write!(f, "\n  {}: {}", i, frame)?;
+            }
+            if self.backtrace.len() > MAX_DISPLAYED_FRAMES {
+                let rest = self.backtrace.len() - MAX_DISPLAYED_FRAMES;
+                write!(f, "\n  ... {} more frames", rest)?;
+            }
+        }
+        Ok(())
+    }
+}
+impl std::error::Error for Trap {}
@@ -1,12 +1,15 @@
 use anyhow::{anyhow, Result};
+use std::fmt;
 
 use crate::exec::buffer::Buffer;
+use crate::exec::section::SectionHeader;
+use crate::exec::writer::Writer;
 
-const I32: u8 = 0x7f;
-const I64: u8 = 0x7e;
-const F32: u8 = 0x7d;
-const F64: u8 = 0x7c;
-#[derive(Debug, Clone)]
+pub const I32: u8 = 0x7f;
+pub const I64: u8 = 0x7e;
+pub const F32: u8 = 0x7d;
+pub const F64: u8 = 0x7c;
+#[derive(Debug, Clone, PartialEq)]
 pub enum NumType {
     I32(u8),
     I64(u8),
@@ -23,12 +26,20 @@ impl NumType {
             _ => None,
         }
     }
+
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            NumType::I32(byte) | NumType::I64(byte) | NumType::F32(byte) | NumType::F64(byte) => {
+                *byte
+            }
+        }
+    }
 }
 
-const FuncRef: u8 = 0x70;
-const ExternRef: u8 = 0x6f;
+pub const FUNC_REF: u8 = 0x70;
+pub const EXTERN_REF: u8 = 0x6f;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum RefType {
     FuncRef(u8),
     ExternRef(u8),
@@ -36,19 +47,32 @@ pub enum RefType {
 impl RefType {
     pub fn from_u8(value: u8) -> Option<RefType> {
         match value {
-            FuncRef => Some(RefType::FuncRef(value)),
-            ExternRef => Some(RefType::ExternRef(value)),
+            FUNC_REF => Some(RefType::FuncRef(value)),
+            EXTERN_REF => Some(RefType::ExternRef(value)),
             _ => None,
         }
     }
+
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            RefType::FuncRef(byte) | RefType::ExternRef(byte) => *byte,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ValType {
     NumType(NumType),
     RefType(RefType),
 }
 impl ValType {
+    pub const I32: ValType = ValType::NumType(NumType::I32(I32));
+    pub const I64: ValType = ValType::NumType(NumType::I64(I64));
+    pub const F32: ValType = ValType::NumType(NumType::F32(F32));
+    pub const F64: ValType = ValType::NumType(NumType::F64(F64));
+    pub const FUNC_REF: ValType = ValType::RefType(RefType::FuncRef(FUNC_REF));
+    pub const EXTERN_REF: ValType = ValType::RefType(RefType::ExternRef(EXTERN_REF));
+
     pub fn from_u8(value: u8) -> Option<ValType> {
         match (NumType::from_u8(value), RefType::from_u8(value)) {
             (Some(num_type), _) => Some(ValType::NumType(num_type)),
@@ -56,11 +80,49 @@ impl ValType {
             _ => None,
         }
     }
+
+    pub fn to_u8(&self) -> u8 {
+        match self {
+            ValType::NumType(num_type) => num_type.to_u8(),
+            ValType::RefType(ref_type) => ref_type.to_u8(),
+        }
+    }
+}
+
+impl fmt::Display for NumType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            NumType::I32(_) => "i32",
+            NumType::I64(_) => "i64",
+            NumType::F32(_) => "f32",
+            NumType::F64(_) => "f64",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for RefType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RefType::FuncRef(_) => write!(f, "funcref"),
+            RefType::ExternRef(_) => write!(f, "externref"),
+        }
+    }
+}
+
+impl fmt::Display for ValType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValType::NumType(num_type) => num_type.fmt(f),
+            ValType::RefType(ref_type) => ref_type.fmt(f),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct TypeSectionNode {
-    func_types: Vec<FunctionTypeNode>,
+    pub func_types: Vec<FunctionTypeNode>,
+    pub header: Option<SectionHeader>,
 }
 impl Default for TypeSectionNode {
     fn default() -> Self {
@@ -71,6 +133,7 @@ impl TypeSectionNode {
     pub fn new() -> TypeSectionNode {
         TypeSectionNode {
             func_types: Vec::new(),
+            header: None,
         }
     }
 
@@ -83,12 +146,16 @@ impl TypeSectionNode {
         self.func_types = buf.read_vec::<FunctionTypeNode>(Box::new(f))?;
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_vec(&self.func_types, |w, item| item.write(w));
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct FunctionTypeNode {
-    param_type: ResultTypeNode,
-    result_type: ResultTypeNode,
+    pub param_type: ResultTypeNode,
+    pub result_type: ResultTypeNode,
 }
 impl Default for FunctionTypeNode {
     fn default() -> Self {
@@ -118,11 +185,17 @@ impl FunctionTypeNode {
 
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_byte(self.tag());
+        self.param_type.write(writer);
+        self.result_type.write(writer);
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ResultTypeNode {
-    val_types: Vec<ValType>,
+    pub val_types: Vec<ValType>,
 }
 impl Default for ResultTypeNode {
     fn default() -> Self {
@@ -139,10 +212,153 @@ impl ResultTypeNode {
     pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
         let f = |buf: &mut Buffer| -> Result<ValType> {
             let byte = buf.read_byte()?;
-            ValType::from_u8(byte).ok_or(anyhow!("Invalid value type: {}", byte))
+            ValType::from_u8(byte).ok_or_else(|| anyhow!("Invalid value type: {}", byte))
         };
         self.val_types = buf.read_vec::<ValType>(Box::new(f))?;
 
         Ok(())
     }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_vec(&self.val_types, |w, val_type| {
+            w.write_byte(val_type.to_u8())
+        });
+    }
+}
+
+/// https://webassembly.github.io/spec/core/binary/types.html#limits
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitsNode {
+    pub min: u32,
+    pub max: Option<u32>,
+}
+impl Default for LimitsNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl LimitsNode {
+    pub fn new() -> LimitsNode {
+        LimitsNode { min: 0, max: None }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        let flag = buf.read_byte()?;
+        self.min = buf.read_u32()?;
+        self.max = match flag {
+            0x00 => None,
+            0x01 => Some(buf.read_u32()?),
+            _ => return Err(anyhow!("Invalid limits flag: {}", flag)),
+        };
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        match self.max {
+            Some(max) => {
+                writer.write_byte(0x01);
+                writer.write_u32(self.min);
+                writer.write_u32(max);
+            }
+            None => {
+                writer.write_byte(0x00);
+                writer.write_u32(self.min);
+            }
+        }
+    }
+}
+
+/// https://webassembly.github.io/spec/core/binary/types.html#memory-types
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryTypeNode {
+    pub limits: LimitsNode,
+}
+impl Default for MemoryTypeNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MemoryTypeNode {
+    pub fn new() -> MemoryTypeNode {
+        MemoryTypeNode {
+            limits: LimitsNode::new(),
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        self.limits.load(buf)
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        self.limits.write(writer);
+    }
+}
+
+/// https://webassembly.github.io/spec/core/binary/types.html#table-types
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableTypeNode {
+    pub ref_type: RefType,
+    pub limits: LimitsNode,
+}
+impl Default for TableTypeNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl TableTypeNode {
+    pub fn new() -> TableTypeNode {
+        TableTypeNode {
+            ref_type: RefType::FuncRef(FUNC_REF),
+            limits: LimitsNode::new(),
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        let byte = buf.read_byte()?;
+        self.ref_type =
+            RefType::from_u8(byte).ok_or_else(|| anyhow!("Invalid reference type: {}", byte))?;
+        self.limits.load(buf)
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_byte(self.ref_type.to_u8());
+        self.limits.write(writer);
+    }
+}
+
+/// https://webassembly.github.io/spec/core/binary/types.html#global-types
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalTypeNode {
+    pub val_type: ValType,
+    pub mutable: bool,
+}
+impl Default for GlobalTypeNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl GlobalTypeNode {
+    pub fn new() -> GlobalTypeNode {
+        GlobalTypeNode {
+            val_type: ValType::NumType(NumType::I32(I32)),
+            mutable: false,
+        }
+    }
+
+    pub fn load(&mut self, buf: &mut Buffer) -> Result<()> {
+        let byte = buf.read_byte()?;
+        self.val_type =
+            ValType::from_u8(byte).ok_or_else(|| anyhow!("Invalid value type: {}", byte))?;
+        self.mutable = match buf.read_byte()? {
+            0x00 => false,
+            0x01 => true,
+            flag => return Err(anyhow!("Invalid mutability flag: {}", flag)),
+        };
+        Ok(())
+    }
+
+    pub fn write(&self, writer: &mut Writer) {
+        writer.write_byte(self.val_type.to_u8());
+        writer.write_byte(self.mutable as u8);
+    }
 }
@@ -0,0 +1,202 @@
+use std::marker::PhantomData;
+
+use crate::exec::machine::Machine;
+use crate::exec::store::{FuncAddr, Store};
+use crate::exec::trap::Trap;
+use crate::exec::type_section::{NumType, ValType, F32, F64, I32, I64};
+use crate::exec::value::Value;
+
+/// Rust type of a Wasm number.
+pub trait WasmTy: Copy {
+    fn val_type() -> ValType;
+    fn into_value(self) -> Value;
+    fn from_value(value: Value) -> Option<Self>;
+}
+macro_rules! impl_wasm_ty {
+    ($t:ty, $variant:ident, $byte:ident) => {
+        impl WasmTy for $t {
+            fn val_type() -> ValType {
+                ValType::NumType(NumType::$variant($byte))
+            }
+
+            fn into_value(self) -> Value {
+                Value::$variant(self)
+            }
+
+            fn from_value(value: Value) -> Option<Self> {
+                match value {
+                    Value::$variant(v) => Some(v),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+impl_wasm_ty!(i32, I32, I32);
+impl_wasm_ty!(i64, I64, I64);
+impl_wasm_ty!(f32, F32, F32);
+impl_wasm_ty!(f64, F64, F64);
+
+/// Parameters of a `TypedFunc`: `()`, a single `WasmTy` or a tuple of them.
+pub trait WasmParams {
+    fn val_types() -> Vec<ValType>;
+    /// Push the parameters in order.
+    fn push(self, stack: &mut Vec<Value>);
+}
+
+/// Results of a `TypedFunc`: `()`, a single `WasmTy` or a tuple of them.
+pub trait WasmResults: Sized {
+    fn val_types() -> Vec<ValType>;
+    /// Pop the results, the last one being on top of `stack`.
+    fn pop(stack: &mut Vec<Value>) -> Option<Self>;
+}
+
+impl<T: WasmTy> WasmParams for T {
+    fn val_types() -> Vec<ValType> {
+        vec![T::val_type()]
+    }
+
+    fn push(self, stack: &mut Vec<Value>) {
+        stack.push(self.into_value());
+    }
+}
+impl<T: WasmTy> WasmResults for T {
+    fn val_types() -> Vec<ValType> {
+        vec![T::val_type()]
+    }
+
+    fn pop(stack: &mut Vec<Value>) -> Option<Self> {
+        T::from_value(stack.pop()?)
+    }
+}
+
+macro_rules! impl_wasm_tuple {
+    ($n:expr, $($t:ident),*) => {
+        impl<$($t: WasmTy),*> WasmParams for ($($t,)*) {
+            fn val_types() -> Vec<ValType> {
+                vec![$($t::val_type()),*]
+            }
+
+            #[allow(non_snake_case, unused_variables)]
+            fn push(self, stack: &mut Vec<Value>) {
+                let ($($t,)*) = self;
+                $(stack.push($t.into_value());)*
+            }
+        }
+        impl<$($t: WasmTy),*> WasmResults for ($($t,)*) {
+            fn val_types() -> Vec<ValType> {
+                vec![$($t::val_type()),*]
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn pop(stack: &mut Vec<Value>) -> Option<Self> {
+                let start = stack.len().checked_sub($n)?;
+                let mut values = stack.drain(start..);
+                Some(($($t::from_value(values.next()?)?,)*))
+            }
+        }
+    };
+}
+impl_wasm_tuple!(0,);
+impl_wasm_tuple!(1, A);
+impl_wasm_tuple!(2, A, B);
+impl_wasm_tuple!(3, A, B, C);
+impl_wasm_tuple!(4, A, B, C, D);
+impl_wasm_tuple!(5, A, B, C, D, E);
+impl_wasm_tuple!(6, A, B, C, D, E, F);
+impl_wasm_tuple!(7, A, B, C, D, E, F, G);
+impl_wasm_tuple!(8, A, B, C, D, E, F, G, H);
+
+/// Function whose signature was checked once against `P` and `R`,
+/// so calls need no `Value` slices.
+/// Each call still runs on a fresh `Machine`, allocating its operand stack and frames.
+/// Obtained with `Instance::get_typed_func`.
+#[derive(Debug)]
+pub struct TypedFunc<P, R> {
+    addr: FuncAddr,
+    _signature: PhantomData<fn(P) -> R>,
+}
+impl<P, R> Clone for TypedFunc<P, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<P, R> Copy for TypedFunc<P, R> {}
+impl<P: WasmParams, R: WasmResults> TypedFunc<P, R> {
+    /// The caller checks that the function at `addr` has the signature `P -> R`.
+    pub(crate) fn new(addr: FuncAddr) -> TypedFunc<P, R> {
+        TypedFunc {
+            addr,
+            _signature: PhantomData,
+        }
+    }
+
+    pub fn addr(&self) -> FuncAddr {
+        self.addr
+    }
+
+    pub fn call(&self, store: &mut Store, params: P) -> Result<R, Trap> {
+        Machine::new(store).invoke_typed(self.addr, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::buffer::Buffer;
+    use crate::exec::instance::Instance;
+    use crate::exec::module::ModuleNode;
+    use crate::exec::trap::TrapKind;
+    use std::fs;
+
+    fn instantiate(path: &str) -> (Store, Instance) {
+        let mut module = ModuleNode::new();
+        module
+            .load(&mut Buffer::new(&fs::read(path).unwrap()))
+            .unwrap();
+        let mut store = Store::new();
+        let instance = Instance::new(&mut store, module).unwrap();
+        (store, instance)
+    }
+
+    #[test]
+    fn test_call() {
+        let (mut store, instance) = instantiate("examples/add.wasm");
+        let add = instance
+            .get_typed_func::<(i32, i32), i32>(&store, "add")
+            .unwrap();
+        assert_eq!(add.call(&mut store, (1, 2)), Ok(3));
+        assert_eq!(add.call(&mut store, (-5, 2)), Ok(-3));
+
+        let (mut store, instance) = instantiate("examples/loop.wasm");
+        let looped = instance.get_typed_func::<(), i32>(&store, "loop").unwrap();
+        assert_eq!(looped.call(&mut store, ()), Ok(42));
+    }
+
+    #[test]
+    fn test_call_trap() {
+        let (mut store, instance) = instantiate("examples/trap.wasm");
+        let div = instance
+            .get_typed_func::<(i32, i32), i32>(&store, "div")
+            .unwrap();
+        let trap = div.call(&mut store, (1, 0)).unwrap_err();
+        assert_eq!(trap.kind, TrapKind::IntegerDivideByZero);
+        assert_eq!(trap.backtrace.len(), 2);
+    }
+
+    #[test]
+    fn test_signature_mismatch() {
+        let (store, instance) = instantiate("examples/add.wasm");
+        assert!(instance
+            .get_typed_func::<(i32, i32), i32>(&store, "add")
+            .is_ok());
+        assert!(instance
+            .get_typed_func::<(i32, i64), i32>(&store, "add")
+            .is_err());
+        assert!(instance
+            .get_typed_func::<(i32, i32), ()>(&store, "add")
+            .is_err());
+        assert!(instance.get_typed_func::<i32, i32>(&store, "add").is_err());
+        assert!(instance.get_typed_func::<(), ()>(&store, "sub").is_err());
+    }
+}
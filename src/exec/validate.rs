@@ -0,0 +1,882 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::exec::code_section::{BlockType, ExprNode, FuncNode, IntrinsicNode, MemArgNode, Op};
+use crate::exec::data_section::DataMode;
+use crate::exec::export_section::ExportKind;
+use crate::exec::import_section::ImportDescNode;
+use crate::exec::module::ModuleNode;
+use crate::exec::type_section::{
+    FunctionTypeNode, GlobalTypeNode, LimitsNode, MemoryTypeNode, RefType, TableTypeNode, ValType,
+};
+
+/// Memories are limited to 4GiB.
+const MAX_PAGES: u32 = 65536;
+/// Maximum number of locals of a function, its params included, as in other engines.
+const MAX_LOCALS: u32 = 50_000;
+
+/// Reason why a module is invalid, with where it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// Function whose body is invalid.
+    pub func_idx: Option<u32>,
+    /// Offset of the invalid instruction in the module.
+    pub offset: Option<u32>,
+    pub message: String,
+}
+impl ValidationError {
+    fn new(message: impl Into<String>) -> ValidationError {
+        ValidationError {
+            func_idx: None,
+            offset: None,
+            message: message.into(),
+        }
+    }
+}
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(func_idx) = self.func_idx {
+            write!(f, "func[{}]", func_idx)?;
+            if let Some(offset) = self.offset {
+                write!(f, " @ {:#06x}", offset)?;
+            }
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+type Result<T> = std::result::Result<T, ValidationError>;
+
+/// Check that `module` is valid, returning every error found.
+/// https://webassembly.github.io/spec/core/valid/modules.html
+pub fn validate(module: &ModuleNode) -> Vec<ValidationError> {
+    let context = Context::new(module);
+    let mut errors = validate_sections(module);
+    let codes = module.code_section().map_or(&[][..], |c| &c.codes);
+    let imported_funcs = module.imported_func_count();
+    for (i, code) in codes.iter().enumerate() {
+        if let Err(e) = context.validate_func(imported_funcs + i as u32, &code.func) {
+            errors.push(e);
+        }
+    }
+    errors
+}
+
+/// Everything `validate` checks but the function bodies, for when they were checked
+/// while being decoded.
+pub fn validate_sections(module: &ModuleNode) -> Vec<ValidationError> {
+    let context = Context::new(module);
+    let mut errors = Vec::new();
+
+    let types = module.type_section().map_or(&[][..], |t| &t.func_types);
+    let imports = module.import_section().map_or(&[][..], |i| &i.imports);
+    for import in imports {
+        let checked = match &import.desc {
+            ImportDescNode::Func(type_idx) => context.func_type(*type_idx).map(|_| ()),
+            ImportDescNode::Table(table_type) => check_table_type(table_type),
+            ImportDescNode::Memory(mem_type) => check_memory_type(mem_type),
+            ImportDescNode::Global(_) => Ok(()),
+        };
+        if let Err(e) = checked {
+            errors.push(ValidationError::new(format!(
+                "import \"{}\" \"{}\": {}",
+                import.module, import.name, e.message
+            )));
+        }
+    }
+
+    let type_indices = module
+        .function_section()
+        .map_or(&[][..], |f| &f.type_indices);
+    for type_idx in type_indices {
+        if *type_idx as usize >= types.len() {
+            errors.push(ValidationError::new(format!("unknown type {}", type_idx)));
+        }
+    }
+    let tables = module.table_section().map_or(&[][..], |t| &t.table_types);
+    errors.extend(tables.iter().filter_map(|t| check_table_type(t).err()));
+    let memories = module.memory_section().map_or(&[][..], |m| &m.mem_types);
+    errors.extend(memories.iter().filter_map(|m| check_memory_type(m).err()));
+    if context.memories.len() > 1 {
+        errors.push(ValidationError::new("multiple memories"));
+    }
+
+    let globals = module.global_section().map_or(&[][..], |g| &g.globals);
+    for (i, global) in globals.iter().enumerate() {
+        let expected = &global.global_type.val_type;
+        if let Err(e) = context.check_const_expr(&global.init, expected) {
+            let idx = context.imported_globals + i;
+            errors.push(ValidationError::new(format!(
+                "global {}: {}",
+                idx, e.message
+            )));
+        }
+    }
+
+    let exports = module.export_section().map_or(&[][..], |e| &e.exports);
+    let mut names = HashSet::new();
+    for export in exports {
+        if !names.insert(export.name.as_str()) {
+            errors.push(ValidationError::new(format!(
+                "duplicate export name \"{}\"",
+                export.name
+            )));
+        }
+        let idx = export.desc.index as usize;
+        let known = match export.desc.kind() {
+            Ok(ExportKind::Func) => idx < context.funcs.len(),
+            Ok(ExportKind::Table) => idx < context.tables.len(),
+            Ok(ExportKind::Memory) => idx < context.memories.len(),
+            Ok(ExportKind::Global) => idx < context.globals.len(),
+            Err(_) => false,
+        };
+        if !known {
+            errors.push(ValidationError::new(format!(
+                "export \"{}\": unknown {} {}",
+                export.name,
+                kind_name(export.desc.kind().ok()),
+                idx
+            )));
+        }
+    }
+
+    if let Some(start) = module.start_section() {
+        if let Err(e) = context.check_start(start.func_idx) {
+            errors.push(ValidationError::new(format!("start: {}", e.message)));
+        }
+    }
+
+    let segments = module.data_section().map_or(&[][..], |d| &d.segments);
+    for (i, segment) in segments.iter().enumerate() {
+        let DataMode::Active { mem_idx, offset } = &segment.mode else {
+            continue;
+        };
+        let checked = context
+            .memory(*mem_idx)
+            .and_then(|_| context.check_const_expr(offset, &ValType::I32));
+        if let Err(e) = checked {
+            errors.push(ValidationError::new(format!("data {}: {}", i, e.message)));
+        }
+    }
+
+    let codes = module.code_section().map_or(&[][..], |c| &c.codes);
+    if codes.len() != type_indices.len() {
+        errors.push(ValidationError::new(
+            "function and code section have inconsistent lengths",
+        ));
+    }
+    errors
+}
+
+fn check_limits(limits: &LimitsNode, max: u32, message: &str) -> Result<()> {
+    if limits.max.is_some_and(|m| m < limits.min) {
+        return Err(ValidationError::new(
+            "size minimum must not be greater than maximum",
+        ));
+    }
+    if limits.min > max || limits.max.is_some_and(|m| m > max) {
+        return Err(ValidationError::new(message));
+    }
+    Ok(())
+}
+
+fn check_table_type(table_type: &TableTypeNode) -> Result<()> {
+    check_limits(&table_type.limits, u32::MAX, "table size out of range")
+}
+
+fn check_memory_type(mem_type: &MemoryTypeNode) -> Result<()> {
+    check_limits(
+        &mem_type.limits,
+        MAX_PAGES,
+        "memory size must be at most 65536 pages (4GiB)",
+    )
+}
+
+fn kind_name(kind: Option<ExportKind>) -> &'static str {
+    match kind {
+        Some(ExportKind::Func) => "function",
+        Some(ExportKind::Table) => "table",
+        Some(ExportKind::Memory) => "memory",
+        Some(ExportKind::Global) => "global",
+        None => "export kind",
+    }
+}
+
+/// Types of everything a function body can refer to, imports first.
+/// https://webassembly.github.io/spec/core/valid/conventions.html#contexts
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    types: Vec<FunctionTypeNode>,
+    /// Type index of each function.
+    funcs: Vec<u32>,
+    tables: Vec<TableTypeNode>,
+    memories: Vec<MemoryTypeNode>,
+    globals: Vec<GlobalTypeNode>,
+    imported_globals: usize,
+}
+impl Context {
+    /// Context of the sections `module` has so far,
+    /// enough to validate bodies while the code section is being decoded.
+    pub fn new(module: &ModuleNode) -> Context {
+        let mut context = Context {
+            types: module
+                .type_section()
+                .map_or(Vec::new(), |t| t.func_types.clone()),
+            ..Context::default()
+        };
+        let imports = module.import_section().map_or(&[][..], |i| &i.imports);
+        for import in imports {
+            match &import.desc {
+                ImportDescNode::Func(type_idx) => context.funcs.push(*type_idx),
+                ImportDescNode::Table(table_type) => context.tables.push(table_type.clone()),
+                ImportDescNode::Memory(mem_type) => context.memories.push(mem_type.clone()),
+                ImportDescNode::Global(global_type) => context.globals.push(global_type.clone()),
+            }
+        }
+        context.imported_globals = context.globals.len();
+        if let Some(functions) = module.function_section() {
+            context.funcs.extend(&functions.type_indices);
+        }
+        if let Some(tables) = module.table_section() {
+            context.tables.extend(tables.table_types.iter().cloned());
+        }
+        if let Some(memories) = module.memory_section() {
+            context.memories.extend(memories.mem_types.iter().cloned());
+        }
+        if let Some(globals) = module.global_section() {
+            let types = globals.globals.iter().map(|g| g.global_type.clone());
+            context.globals.extend(types);
+        }
+        context
+    }
+
+    /// Check the body of the function `func_idx` against its type.
+    pub fn validate_func(&self, func_idx: u32, func: &FuncNode) -> Result<()> {
+        let at_func = |e: ValidationError| ValidationError {
+            func_idx: Some(func_idx),
+            ..e
+        };
+        let type_idx = self
+            .funcs
+            .get(func_idx as usize)
+            .ok_or(ValidationError::new(format!(
+                "unknown function {}",
+                func_idx
+            )))
+            .map_err(at_func)?;
+        let func_type = self.func_type(*type_idx).map_err(at_func)?;
+        // Count the locals before expanding them, the declared counts being arbitrary.
+        let local_count = func
+            .locals
+            .iter()
+            .try_fold(
+                func_type.param_type.val_types.len() as u32,
+                |count, local| count.checked_add(local.num),
+            )
+            .filter(|count| *count <= MAX_LOCALS)
+            .ok_or(ValidationError::new("too many locals"))
+            .map_err(at_func)?;
+        let mut locals = Vec::with_capacity(local_count as usize);
+        locals.extend(func_type.param_type.val_types.iter().cloned());
+        for local in &func.locals {
+            locals.extend(std::iter::repeat_n(
+                local.val_type.clone(),
+                local.num as usize,
+            ));
+        }
+        let results = func_type.result_type.val_types.clone();
+        let mut validator = FuncValidator {
+            context: self,
+            locals,
+            results: results.clone(),
+            operands: Vec::new(),
+            frames: Vec::new(),
+            offset: None,
+        };
+        validator.push_frame(Vec::new(), results.clone(), results);
+        validator.validate_expr(&func.expr).map_err(at_func)?;
+        // Errors at the end of the body are reported for the function as a whole.
+        validator.offset = None;
+        validator.pop_frame().map_err(at_func)?;
+        Ok(())
+    }
+
+    /// https://webassembly.github.io/spec/core/valid/modules.html#start-function
+    fn check_start(&self, func_idx: u32) -> Result<()> {
+        let type_idx = self
+            .funcs
+            .get(func_idx as usize)
+            .ok_or(ValidationError::new(format!(
+                "unknown function {}",
+                func_idx
+            )))?;
+        let func_type = self.func_type(*type_idx)?;
+        if !func_type.param_type.val_types.is_empty() || !func_type.result_type.val_types.is_empty()
+        {
+            return Err(ValidationError::new(
+                "start function must have type [] -> []",
+            ));
+        }
+        Ok(())
+    }
+
+    fn func_type(&self, type_idx: u32) -> Result<&FunctionTypeNode> {
+        let func_type = self.types.get(type_idx as usize);
+        func_type.ok_or(ValidationError::new(format!("unknown type {}", type_idx)))
+    }
+
+    fn memory(&self, mem_idx: u32) -> Result<&MemoryTypeNode> {
+        let memory = self.memories.get(mem_idx as usize);
+        memory.ok_or(ValidationError::new(format!("unknown memory {}", mem_idx)))
+    }
+
+    fn table(&self, table_idx: u32) -> Result<&TableTypeNode> {
+        let table = self.tables.get(table_idx as usize);
+        table.ok_or(ValidationError::new(format!("unknown table {}", table_idx)))
+    }
+
+    fn global(&self, global_idx: u32) -> Result<&GlobalTypeNode> {
+        let global = self.globals.get(global_idx as usize);
+        global.ok_or(ValidationError::new(format!(
+            "unknown global {}",
+            global_idx
+        )))
+    }
+
+    /// Check that `expr` is a constant expression producing a value of type `expected`.
+    /// https://webassembly.github.io/spec/core/valid/instructions.html#constant-expressions
+    fn check_const_expr(&self, expr: &ExprNode, expected: &ValType) -> Result<()> {
+        let val_type = match expr.intrinsics.as_slice() {
+            [IntrinsicNode::I32ConstIntrinsicNode(_)] => ValType::I32,
+            [IntrinsicNode::I64ConstIntrinsicNode(_)] => ValType::I64,
+            [IntrinsicNode::F32ConstIntrinsicNode(_)] => ValType::F32,
+            [IntrinsicNode::F64ConstIntrinsicNode(_)] => ValType::F64,
+            [IntrinsicNode::RefNullIntrinsicNode(node)] => ValType::RefType(node.ref_type.clone()),
+            // Only imported globals are initialized before the module's own.
+            [IntrinsicNode::GlobalGetIntrinsicNode(node)]
+                if (node.global_idx as usize) < self.imported_globals =>
+            {
+                self.global(node.global_idx)?.val_type.clone()
+            }
+            [IntrinsicNode::GlobalGetIntrinsicNode(node)] => {
+                return Err(ValidationError::new(format!(
+                    "unknown global {}",
+                    node.global_idx
+                )));
+            }
+            [] | [_] => return Err(type_mismatch(Some(expected), None)),
+            _ => return Err(ValidationError::new("constant expression required")),
+        };
+        if val_type != *expected {
+            return Err(type_mismatch(Some(expected), Some(&val_type)));
+        }
+        Ok(())
+    }
+}
+
+fn type_mismatch(expected: Option<&ValType>, found: Option<&ValType>) -> ValidationError {
+    let expected = expected.map_or("a value".to_string(), |t| t.to_string());
+    let found = found.map_or("nothing".to_string(), |t| t.to_string());
+    ValidationError::new(format!(
+        "type mismatch: expected {}, found {}",
+        expected, found
+    ))
+}
+
+/// Block being validated.
+#[derive(Debug)]
+struct Frame {
+    /// Types a branch to the block carries.
+    label_types: Vec<ValType>,
+    end_types: Vec<ValType>,
+    /// Height of the operand stack when the block was entered.
+    height: usize,
+    /// Whether the rest of the block is unreachable, its stack being polymorphic.
+    unreachable: bool,
+}
+
+/// Type checks a function body with the algorithm of the spec appendix.
+/// https://webassembly.github.io/spec/core/appendix/algorithm.html
+struct FuncValidator<'a> {
+    context: &'a Context,
+    locals: Vec<ValType>,
+    results: Vec<ValType>,
+    /// Operand types, `None` being unknown in unreachable code.
+    operands: Vec<Option<ValType>>,
+    frames: Vec<Frame>,
+    /// Offset of the instruction being validated.
+    offset: Option<u32>,
+}
+impl FuncValidator<'_> {
+    /// Error at the current instruction.
+    fn error(&self, message: impl Into<String>) -> ValidationError {
+        ValidationError {
+            func_idx: None,
+            offset: self.offset,
+            message: message.into(),
+        }
+    }
+
+    fn push(&mut self, val_type: ValType) {
+        self.operands.push(Some(val_type));
+    }
+
+    fn push_all(&mut self, val_types: &[ValType]) {
+        self.operands.extend(val_types.iter().cloned().map(Some));
+    }
+
+    /// Pop an operand, of type `expected` if given.
+    fn pop(&mut self, expected: Option<&ValType>) -> Result<Option<ValType>> {
+        let frame = self.frames.last().unwrap();
+        if self.operands.len() == frame.height {
+            if frame.unreachable {
+                return Ok(expected.cloned());
+            }
+            return self.at(Err(type_mismatch(expected, None)));
+        }
+        let actual = self.operands.pop().unwrap();
+        match (&actual, expected) {
+            (Some(actual), Some(expected)) if actual != expected => {
+                self.at(Err(type_mismatch(Some(expected), Some(actual))))
+            }
+            (None, _) => Ok(expected.cloned()),
+            _ => Ok(actual),
+        }
+    }
+
+    fn pop_all(&mut self, val_types: &[ValType]) -> Result<()> {
+        for val_type in val_types.iter().rev() {
+            self.pop(Some(val_type))?;
+        }
+        Ok(())
+    }
+
+    fn push_frame(&mut self, params: Vec<ValType>, label_types: Vec<ValType>, ends: Vec<ValType>) {
+        self.frames.push(Frame {
+            label_types,
+            end_types: ends,
+            height: self.operands.len(),
+            unreachable: false,
+        });
+        self.push_all(&params);
+    }
+
+    /// Check that the innermost block ends with its results, and leave it.
+    fn pop_frame(&mut self) -> Result<Vec<ValType>> {
+        let end_types = self.frames.last().unwrap().end_types.clone();
+        self.pop_all(&end_types)?;
+        let frame = self.frames.pop().unwrap();
+        if self.operands.len() != frame.height {
+            return Err(self.error("type mismatch: values remaining on the stack at end of block"));
+        }
+        Ok(frame.end_types)
+    }
+
+    fn set_unreachable(&mut self) {
+        let frame = self.frames.last_mut().unwrap();
+        self.operands.truncate(frame.height);
+        frame.unreachable = true;
+    }
+
+    fn label_types(&self, label_idx: u32) -> Result<Vec<ValType>> {
+        let idx = self.frames.len().checked_sub(label_idx as usize + 1);
+        let frame = idx.map(|idx| &self.frames[idx]);
+        let frame = frame.ok_or(self.error(format!("unknown label {}", label_idx)))?;
+        Ok(frame.label_types.clone())
+    }
+
+    /// Parameter and result types of a block.
+    fn block_type(&self, block_type: &BlockType) -> Result<(Vec<ValType>, Vec<ValType>)> {
+        match block_type {
+            BlockType::Empty => Ok((Vec::new(), Vec::new())),
+            BlockType::ValType(val_type) => Ok((Vec::new(), vec![val_type.clone()])),
+            BlockType::S33(type_idx) => {
+                let func_type = self.at(self.context.func_type(*type_idx as u32))?;
+                Ok((
+                    func_type.param_type.val_types.clone(),
+                    func_type.result_type.val_types.clone(),
+                ))
+            }
+        }
+    }
+
+    /// Locate an error of the context at the current instruction.
+    fn at<T>(&self, result: Result<T>) -> Result<T> {
+        result.map_err(|e| ValidationError {
+            offset: self.offset,
+            ..e
+        })
+    }
+
+    fn check_mem_arg(&self, mem_arg: &MemArgNode, natural_align: u32) -> Result<()> {
+        self.at(self.context.memory(0))?;
+        if mem_arg.align > natural_align {
+            return Err(self.error("alignment must not be larger than natural"));
+        }
+        Ok(())
+    }
+
+    fn validate_expr(&mut self, expr: &ExprNode) -> Result<()> {
+        // Built modules have no spans, their errors no offsets.
+        for intrinsic in &expr.intrinsics {
+            self.offset = intrinsic.span().map(|span| span.start);
+            self.validate_intrinsic(intrinsic)?;
+        }
+        Ok(())
+    }
+
+    fn validate_intrinsic(&mut self, intrinsic: &IntrinsicNode) -> Result<()> {
+        let i32 = ValType::I32;
+        // Errors at the end of a block are reported at its start.
+        let offset = self.offset;
+        match intrinsic {
+            IntrinsicNode::UnreachableIntrinsicNode(_) => self.set_unreachable(),
+            IntrinsicNode::NopIntrinsicNode(_) => {}
+            IntrinsicNode::ReturnIntrinsicNode(_) => {
+                self.pop_all(&self.results.clone())?;
+                self.set_unreachable();
+            }
+            IntrinsicNode::DropIntrinsicNode(_) => {
+                self.pop(None)?;
+            }
+            IntrinsicNode::LocalGetIntrinsicNode(node) => {
+                let local = self.local(node.local_idx)?;
+                self.push(local);
+            }
+            IntrinsicNode::LocalSetIntrinsicNode(node) => {
+                let local = self.local(node.local_idx)?;
+                self.pop(Some(&local))?;
+            }
+            IntrinsicNode::GlobalGetIntrinsicNode(node) => {
+                let global = self.at(self.context.global(node.global_idx))?;
+                self.push(global.val_type.clone());
+            }
+            IntrinsicNode::GlobalSetIntrinsicNode(node) => {
+                let global = self.at(self.context.global(node.global_idx))?.clone();
+                if !global.mutable {
+                    return Err(self.error("global is immutable"));
+                }
+                self.pop(Some(&global.val_type))?;
+            }
+            IntrinsicNode::I32LoadIntrinsicNode(node) => {
+                self.check_mem_arg(&node.mem_arg, 2)?;
+                self.pop(Some(&i32))?;
+                self.push(i32);
+            }
+            IntrinsicNode::I32StoreIntrinsicNode(node) => {
+                self.check_mem_arg(&node.mem_arg, 2)?;
+                self.pop(Some(&i32))?;
+                self.pop(Some(&i32))?;
+            }
+            IntrinsicNode::MemorySizeIntrinsicNode(node) => {
+                self.at(self.context.memory(node.mem_idx))?;
+                self.push(i32);
+            }
+            IntrinsicNode::MemoryGrowIntrinsicNode(node) => {
+                self.at(self.context.memory(node.mem_idx))?;
+                self.pop(Some(&i32))?;
+                self.push(i32);
+            }
+            IntrinsicNode::I32ConstIntrinsicNode(_) => self.push(i32),
+            IntrinsicNode::I64ConstIntrinsicNode(_) => self.push(ValType::I64),
+            IntrinsicNode::F32ConstIntrinsicNode(_) => self.push(ValType::F32),
+            IntrinsicNode::F64ConstIntrinsicNode(_) => self.push(ValType::F64),
+            IntrinsicNode::I32EqaIntrinsicNode(_) => {
+                self.pop(Some(&i32))?;
+                self.push(i32);
+            }
+            IntrinsicNode::I32LtSIntrinsicNode(_)
+            | IntrinsicNode::I32GeSIntrinsicNode(_)
+            | IntrinsicNode::I32AddIntrinsicNode(_)
+            | IntrinsicNode::I32SubIntrinsicNode(_)
+            | IntrinsicNode::I32MulIntrinsicNode(_)
+            | IntrinsicNode::I32DivSIntrinsicNode(_)
+            | IntrinsicNode::I32DivUIntrinsicNode(_)
+            | IntrinsicNode::I32RemsIntrinsicNode(_)
+            | IntrinsicNode::I32RemUIntrinsicNode(_) => {
+                self.pop(Some(&i32))?;
+                self.pop(Some(&i32))?;
+                self.push(i32);
+            }
+            IntrinsicNode::BlockIntrinsicNode(node) => {
+                let (params, results) = self.block_type(&node.block_type)?;
+                self.pop_all(&params)?;
+                self.push_frame(params, results.clone(), results);
+                self.validate_block(&node.expr, offset)?;
+            }
+            IntrinsicNode::LoopIntrinsicNode(node) => {
+                let (params, results) = self.block_type(&node.block_type)?;
+                self.pop_all(&params)?;
+                self.push_frame(params.clone(), params, results);
+                self.validate_block(&node.expr, offset)?;
+            }
+            IntrinsicNode::IfIntrinsicNode(node) => {
+                let (params, results) = self.block_type(&node.block_type)?;
+                self.pop(Some(&i32))?;
+                self.pop_all(&params)?;
+                self.push_frame(params.clone(), results.clone(), results.clone());
+                self.validate_expr(&node.then_expr)?;
+                self.offset = offset;
+                self.pop_frame()?;
+                // Without an `else`, the missing branch passes its parameters through.
+                self.push_frame(params.clone(), results.clone(), results.clone());
+                if node.then_expr.end_op == Some(Op::Else) {
+                    self.validate_expr(&node.else_expr)?;
+                    self.offset = offset;
+                } else if params != results {
+                    return Err(
+                        self.error("type mismatch: if without else must not change the stack")
+                    );
+                }
+                let results = self.pop_frame()?;
+                self.push_all(&results);
+            }
+            IntrinsicNode::BrIntrinsicNode(node) => {
+                let label_types = self.label_types(node.label_idx)?;
+                self.pop_all(&label_types)?;
+                self.set_unreachable();
+            }
+            IntrinsicNode::BrIfIntrinsicNode(node) => {
+                let label_types = self.label_types(node.label_idx)?;
+                self.pop(Some(&i32))?;
+                self.pop_all(&label_types)?;
+                self.push_all(&label_types);
+            }
+            IntrinsicNode::CallIntrinsicNode(node) => {
+                let type_idx = self.context.funcs.get(node.func_idx as usize);
+                let type_idx =
+                    type_idx.ok_or(self.error(format!("unknown function {}", node.func_idx)))?;
+                let func_type = self.at(self.context.func_type(*type_idx))?.clone();
+                self.pop_all(&func_type.param_type.val_types)?;
+                self.push_all(&func_type.result_type.val_types);
+            }
+            IntrinsicNode::CallIndirectIntrinsicNode(node) => {
+                let table = self.at(self.context.table(node.table_idx))?;
+                if !matches!(table.ref_type, RefType::FuncRef(_)) {
+                    return Err(self.error("type mismatch: call_indirect on a table of externref"));
+                }
+                let func_type = self.at(self.context.func_type(node.type_idx))?.clone();
+                self.pop(Some(&i32))?;
+                self.pop_all(&func_type.param_type.val_types)?;
+                self.push_all(&func_type.result_type.val_types);
+            }
+            IntrinsicNode::I32TruncF32SIntrinsicNode(_)
+            | IntrinsicNode::I32TruncF32UIntrinsicNode(_) => {
+                self.pop(Some(&ValType::F32))?;
+                self.push(i32);
+            }
+            IntrinsicNode::I32TruncF64SIntrinsicNode(_)
+            | IntrinsicNode::I32TruncF64UIntrinsicNode(_) => {
+                self.pop(Some(&ValType::F64))?;
+                self.push(i32);
+            }
+            IntrinsicNode::I64TruncF32SIntrinsicNode(_)
+            | IntrinsicNode::I64TruncF32UIntrinsicNode(_) => {
+                self.pop(Some(&ValType::F32))?;
+                self.push(ValType::I64);
+            }
+            IntrinsicNode::I64TruncF64SIntrinsicNode(_)
+            | IntrinsicNode::I64TruncF64UIntrinsicNode(_) => {
+                self.pop(Some(&ValType::F64))?;
+                self.push(ValType::I64);
+            }
+            IntrinsicNode::RefNullIntrinsicNode(node) => {
+                self.push(ValType::RefType(node.ref_type.clone()));
+            }
+            IntrinsicNode::TableGrowIntrinsicNode(node) => {
+                let table = self.at(self.context.table(node.table_idx))?;
+                let ref_type: RefType = table.ref_type.clone();
+                self.pop(Some(&i32))?;
+                self.pop(Some(&ValType::RefType(ref_type)))?;
+                self.push(i32);
+            }
+            IntrinsicNode::TableSizeIntrinsicNode(node) => {
+                self.at(self.context.table(node.table_idx))?;
+                self.push(i32);
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the body of the innermost block, leaving it with its results on the stack.
+    fn validate_block(&mut self, expr: &ExprNode, offset: Option<u32>) -> Result<()> {
+        self.validate_expr(expr)?;
+        self.offset = offset;
+        let results = self.pop_frame()?;
+        self.push_all(&results);
+        Ok(())
+    }
+
+    fn local(&self, local_idx: u32) -> Result<ValType> {
+        let local = self.locals.get(local_idx as usize);
+        local
+            .cloned()
+            .ok_or(self.error(format!("unknown local {}", local_idx)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::buffer::Buffer;
+    use crate::exec::code_section::LocalNode;
+    use crate::exec::section::SectionNode;
+    use crate::wat::module::parse_str;
+    use rstest::rstest;
+    use std::fs;
+
+    fn errors(wat: &str) -> Vec<String> {
+        let mut module = ModuleNode::new();
+        module
+            .load(&mut Buffer::new(&parse_str(wat).unwrap()))
+            .unwrap();
+        validate(&module).iter().map(|e| e.to_string()).collect()
+    }
+
+    #[rstest]
+    #[case("add.wat")]
+    #[case("call.wat")]
+    #[case("const.wat")]
+    #[case("exports.wat")]
+    #[case("if.wat")]
+    #[case("lib.wat")]
+    #[case("local_var.wat")]
+    #[case("loop.wat")]
+    #[case("main.wat")]
+    #[case("memory.wat")]
+    #[case("one_module.wat")]
+    #[case("recursion.wat")]
+    #[case("spin.wat")]
+    #[case("start.wat")]
+    #[case("trap.wat")]
+    fn test_validate_examples(#[case] file: &str) {
+        let wat = fs::read_to_string(format!("examples/{}", file)).unwrap();
+        assert_eq!(errors(&wat), Vec::<String>::new());
+    }
+
+    #[rstest]
+    #[case(
+        "(func (result i32) (i64.const 1))",
+        "func[0]: type mismatch: expected i32, found i64"
+    )]
+    #[case(
+        "(func (result i32))",
+        "func[0]: type mismatch: expected i32, found nothing"
+    )]
+    #[case(
+        "(func (i32.const 1))",
+        "func[0]: type mismatch: values remaining on the stack at end of block"
+    )]
+    #[case(
+        "(func (block (i32.const 1)))",
+        "func[0] @ 0x0017: type mismatch: values remaining on the stack at end of block"
+    )]
+    #[case("(func (local.get 0) (drop))", "func[0] @ 0x0017: unknown local 0")]
+    #[case("(func (call 3))", "func[0] @ 0x0017: unknown function 3")]
+    #[case("(func (block (br 2)))", "func[0] @ 0x0019: unknown label 2")]
+    #[case("(func (drop (memory.size)))", "func[0] @ 0x0017: unknown memory 0")]
+    #[case(
+        "(global i32 (i32.const 0)) (func (global.set 0 (i32.const 1)))",
+        "func[0] @ 0x0021: global is immutable"
+    )]
+    #[case(
+        "(func (result i32) (if (result i32) (i32.const 1) (then (i32.const 2))))",
+        "func[0] @ 0x001a: type mismatch: if without else must not change the stack"
+    )]
+    #[case(
+        "(global i32 (i64.const 0))",
+        "global 0: type mismatch: expected i32, found i64"
+    )]
+    #[case("(memory 2 1)", "size minimum must not be greater than maximum")]
+    #[case(
+        "(table 1 funcref) (func (drop (table.size 1)))",
+        "func[0] @ 0x001d: unknown table 1"
+    )]
+    #[case(
+        "(func (param i32)) (start 0)",
+        "start: start function must have type [] -> []"
+    )]
+    fn test_validate_errors(#[case] wat: &str, #[case] error: &str) {
+        assert_eq!(errors(&format!("(module {})", wat)), [error]);
+    }
+
+    #[test]
+    fn test_validate_without_spans() {
+        let mut module = ModuleNode::new();
+        let wasm = parse_str("(module (func))").unwrap();
+        module.load(&mut Buffer::new(&wasm)).unwrap();
+        for section in &mut module.sections {
+            if let SectionNode::CodeSectionNode(code_section) = section {
+                // `local.get 0` after a `nop`, none of them decoded.
+                code_section.codes[0].func.expr.intrinsics = [Op::Nop, Op::LocalGet, Op::Drop]
+                    .into_iter()
+                    .map(IntrinsicNode::new)
+                    .collect();
+            }
+        }
+        let errors: Vec<String> = validate(&module).iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors, ["func[0]: unknown local 0"]);
+    }
+
+    #[test]
+    fn test_validate_local_count() {
+        let with_locals = |counts: &[u32]| {
+            let mut module = ModuleNode::new();
+            let wasm = parse_str("(module (func (param i32)))").unwrap();
+            module.load(&mut Buffer::new(&wasm)).unwrap();
+            for section in &mut module.sections {
+                if let SectionNode::CodeSectionNode(code_section) = section {
+                    code_section.codes[0].func.locals = counts
+                        .iter()
+                        .map(|num| LocalNode {
+                            num: *num,
+                            val_type: ValType::I64,
+                        })
+                        .collect();
+                }
+            }
+            let errors: Vec<String> = validate(&module).iter().map(|e| e.to_string()).collect();
+            errors
+        };
+        assert_eq!(with_locals(&[49_999]), Vec::<String>::new());
+        assert_eq!(with_locals(&[50_000]), ["func[0]: too many locals"]);
+        assert_eq!(with_locals(&[u32::MAX; 4]), ["func[0]: too many locals"]);
+    }
+
+    #[test]
+    fn test_validate_exports() {
+        // The text format rejects these, so the decoded module is edited.
+        let mut module = ModuleNode::new();
+        let wasm = parse_str(r#"(module (func (export "f")))"#).unwrap();
+        module.load(&mut Buffer::new(&wasm)).unwrap();
+        for section in &mut module.sections {
+            if let SectionNode::ExportSectionNode(exports) = section {
+                let mut export = exports.exports[0].clone();
+                exports.exports.push(export.clone());
+                export.name = "g".to_string();
+                export.desc.index = 1;
+                exports.exports.push(export);
+            }
+        }
+        let errors: Vec<String> = validate(&module).iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            [
+                "duplicate export name \"f\"",
+                "export \"g\": unknown function 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_unreachable() {
+        // The stack is polymorphic after `unreachable` and branches.
+        let wat = r#"(module
+          (func (result i32) (unreachable) (i32.add))
+          (func (result i32) (block (result i32) (br 0 (i32.const 1)) (i64.const 2) (drop)))
+          (func (param i32) (result i32) (return (local.get 0)) (drop)))
+        "#;
+        assert_eq!(errors(wat), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,83 @@
+use std::fmt;
+
+use crate::exec::store::FuncAddr;
+use crate::exec::type_section::{
+    NumType, RefType, ValType, EXTERN_REF, F32, F64, FUNC_REF, I32, I64,
+};
+
+/// A runtime value held in locals and on the operand stack.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    /// `None` is the null reference.
+    FuncRef(Option<FuncAddr>),
+    /// Opaque reference to a host value, `None` is the null reference.
+    ExternRef(Option<u32>),
+}
+impl Value {
+    /// The zero value locals of `val_type` are initialized with.
+    pub fn default_of(val_type: &ValType) -> Value {
+        match val_type {
+            ValType::NumType(NumType::I32(_)) => Value::I32(0),
+            ValType::NumType(NumType::I64(_)) => Value::I64(0),
+            ValType::NumType(NumType::F32(_)) => Value::F32(0.0),
+            ValType::NumType(NumType::F64(_)) => Value::F64(0.0),
+            ValType::RefType(ref_type) => Value::null_of(ref_type),
+        }
+    }
+
+    pub fn null_of(ref_type: &RefType) -> Value {
+        match ref_type {
+            RefType::FuncRef(_) => Value::FuncRef(None),
+            RefType::ExternRef(_) => Value::ExternRef(None),
+        }
+    }
+
+    pub fn val_type(&self) -> ValType {
+        match self {
+            Value::I32(_) => ValType::NumType(NumType::I32(I32)),
+            Value::I64(_) => ValType::NumType(NumType::I64(I64)),
+            Value::F32(_) => ValType::NumType(NumType::F32(F32)),
+            Value::F64(_) => ValType::NumType(NumType::F64(F64)),
+            Value::FuncRef(_) => ValType::RefType(RefType::FuncRef(FUNC_REF)),
+            Value::ExternRef(_) => ValType::RefType(RefType::ExternRef(EXTERN_REF)),
+        }
+    }
+}
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::I32(v) => write!(f, "{}:i32", v),
+            Value::I64(v) => write!(f, "{}:i64", v),
+            Value::F32(v) => write!(f, "{}:f32", v),
+            Value::F64(v) => write!(f, "{}:f64", v),
+            Value::FuncRef(Some(addr)) => write!(f, "ref.func {}", addr),
+            Value::FuncRef(None) => write!(f, "ref.null func"),
+            Value::ExternRef(Some(v)) => write!(f, "ref.extern {}", v),
+            Value::ExternRef(None) => write!(f, "ref.null extern"),
+        }
+    }
+}
+impl From<i32> for Value {
+    fn from(v: i32) -> Self {
+        Value::I32(v)
+    }
+}
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::I64(v)
+    }
+}
+impl From<f32> for Value {
+    fn from(v: f32) -> Self {
+        Value::F32(v)
+    }
+}
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::F64(v)
+    }
+}
@@ -0,0 +1,133 @@
+/// Counterpart of `Buffer` producing the binary format.
+#[derive(Debug, Default)]
+pub struct Writer {
+    buf: Vec<u8>,
+}
+impl Writer {
+    pub fn new() -> Writer {
+        Writer::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+
+    pub fn write_byte(&mut self, byte: u8) {
+        self.buf.push(byte);
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Write a 32-bit unsigned integer in LEB128.
+    pub fn write_u32(&mut self, value: u32) {
+        let mut value = value;
+        loop {
+            let byte = (value & 0b01111111) as u8;
+            value >>= 7;
+            if value == 0 {
+                self.buf.push(byte);
+                return;
+            }
+            self.buf.push(byte | 0b10000000);
+        }
+    }
+
+    /// Write a 32-bit signed integer in LEB128.
+    pub fn write_i32(&mut self, value: i32) {
+        self.write_i64(value as i64);
+    }
+
+    /// Write a 64-bit signed integer in LEB128.
+    pub fn write_i64(&mut self, value: i64) {
+        let mut value = value;
+        loop {
+            let byte = (value & 0b01111111) as u8;
+            value >>= 7;
+            // Stop once the remaining bits are the sign extension of the byte.
+            let sign_bit = byte & 0b01000000 != 0;
+            if (value == 0 && !sign_bit) || (value == -1 && sign_bit) {
+                self.buf.push(byte);
+                return;
+            }
+            self.buf.push(byte | 0b10000000);
+        }
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_name(&mut self, name: &str) {
+        self.write_u32(name.len() as u32);
+        self.write_bytes(name.as_bytes());
+    }
+
+    /// Write the length of `items` followed by each item.
+    pub fn write_vec<T>(&mut self, items: &[T], mut f: impl FnMut(&mut Writer, &T)) {
+        self.write_u32(items.len() as u32);
+        for item in items {
+            f(self, item);
+        }
+    }
+
+    /// Write `id` and the size of `payload` followed by `payload`.
+    pub fn write_section(&mut self, id: u8, payload: &[u8]) {
+        self.write_byte(id);
+        self.write_u32(payload.len() as u32);
+        self.write_bytes(payload);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::buffer::Buffer;
+    use rstest::rstest;
+
+    #[rstest(
+        value,
+        expected,
+        case(0, vec![0x00]),
+        case(624485, vec![0xe5, 0x8e, 0x26]),
+        case(u32::MAX, vec![0xff, 0xff, 0xff, 0xff, 0x0f])
+    )]
+    fn test_write_u32(value: u32, expected: Vec<u8>) {
+        let mut writer = Writer::new();
+        writer.write_u32(value);
+        assert_eq!(writer.into_bytes(), expected);
+    }
+
+    #[rstest(value, case(0), case(-1), case(63), case(64), case(-64), case(-65), case(i32::MIN), case(i32::MAX))]
+    fn test_write_i32_round_trip(value: i32) {
+        let mut writer = Writer::new();
+        writer.write_i32(value);
+        let bytes = writer.into_bytes();
+        let mut buffer = Buffer::new(&bytes);
+        assert_eq!(buffer.read_i32().unwrap(), value);
+        assert!(buffer.eof());
+    }
+
+    #[rstest(value, case(0), case(-2), case(0xffff_ffff), case(i64::MIN), case(i64::MAX))]
+    fn test_write_i64_round_trip(value: i64) {
+        let mut writer = Writer::new();
+        writer.write_i64(value);
+        let bytes = writer.into_bytes();
+        let mut buffer = Buffer::new(&bytes);
+        assert_eq!(buffer.read_i64().unwrap(), value);
+        assert!(buffer.eof());
+    }
+}
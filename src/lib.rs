@@ -0,0 +1,7 @@
+//! WebAssembly decoder, validator and interpreter, with the WASI, text format and spec test
+//! support the command line tool is built on.
+pub mod errors;
+pub mod exec;
+pub mod wasi;
+pub mod wast;
+pub mod wat;
@@ -1,28 +1,199 @@
 use anyhow::Result;
 mod cmd;
-mod errors;
-pub mod exec;
-use clap::Parser;
+mod debugger;
+mod json;
+mod repl;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+use toy_wasm_runtime::{errors, exec, wasi, wast, wat};
 
-/// Run a wasm file.
+/// Run, inspect and convert wasm files.
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    after_help = "Exit status: 0 on success, 1 on other errors, 2 on usage errors, \
+                  3 for malformed modules, 4 for invalid modules, 5 when the code traps."
+)]
 struct Args {
-    /// Name of the wasm file to run.
-    #[arg(short, long)]
-    file: String,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Validate a wasm file and call its `_start` export or the `--invoke` one.
+    Run {
+        /// Path of the .wasm or .wat file, `-` reading a .wasm one from stdin.
+        file: PathBuf,
+
+        /// Name of the exported function to invoke instead of `_start`.
+        #[arg(short, long)]
+        invoke: Option<String>,
+
+        /// Amount of fuel the invoked function may consume, one unit per instruction.
+        #[arg(long)]
+        fuel: Option<u64>,
+
+        /// Interrupt the invoked function after the given number of milliseconds.
+        #[arg(long)]
+        timeout: Option<u64>,
+
+        /// Maximum size in bytes of the linear memories together.
+        #[arg(long)]
+        max_memory: Option<usize>,
+
+        /// Maximum number of elements of the tables together.
+        #[arg(long)]
+        max_table_elements: Option<u32>,
+
+        /// Log every instruction the invoked function executes to stderr.
+        #[arg(long)]
+        trace: bool,
+
+        /// Write the trace to this file instead of stderr.
+        #[arg(long, requires = "trace")]
+        trace_output: Option<PathBuf>,
+
+        /// Only trace the function with this index or name.
+        #[arg(long, requires = "trace")]
+        trace_func: Option<String>,
 
-    /// Print the wasm file.
-    #[arg(short, long)]
-    print: bool,
+        /// Report calls, executed instructions and time per function to stderr.
+        #[arg(long)]
+        profile: bool,
+
+        /// Write the folded stacks of the profile to this file, for flame graph tools.
+        #[arg(long, requires = "profile")]
+        profile_folded: Option<PathBuf>,
+
+        /// Print the functions annotated with how often each instruction ran to stderr.
+        #[arg(long)]
+        coverage: bool,
+
+        /// Write the coverage as lcov records to this file.
+        #[arg(long, requires = "coverage")]
+        coverage_lcov: Option<PathBuf>,
+
+        /// Arguments passed to the invoked function.
+        #[arg(allow_negative_numbers = true)]
+        args: Vec<String>,
+    },
+    /// Print a summary of a wasm file, its decoded nodes or its text format.
+    Inspect {
+        /// Path of the .wasm or .wat file, `-` reading a .wasm one from stdin.
+        file: PathBuf,
+
+        /// What to print.
+        #[arg(short, long, value_enum, default_value = "summary")]
+        print: cmd::PrintFormat,
+    },
+    /// Check that a wasm file is valid, printing every error found.
+    Validate {
+        /// Path of the .wasm or .wat file, `-` reading a .wasm one from stdin.
+        file: PathBuf,
+    },
+    /// Print the sections of a binary wasm file with their offsets and contents in hex.
+    Dump {
+        /// Path of the .wasm file, `-` reading it from stdin.
+        file: PathBuf,
+    },
+    /// Translate a .wat file to the binary format.
+    Wat2wasm {
+        /// Path of the .wat file.
+        input: PathBuf,
+
+        /// Path of the .wasm file to write, the input one with the .wasm extension by default.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Translate a .wasm file to the text format.
+    Wasm2wat {
+        /// Path of the .wasm file, `-` reading it from stdin.
+        input: PathBuf,
+
+        /// Path of the .wat file to write, stdout by default.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Run the exports of a wasm file from a command prompt with breakpoints and stepping.
+    Debug {
+        /// Path of the .wasm or .wat file.
+        path: PathBuf,
+    },
+    /// Load modules and invoke their exports from a command prompt.
+    Repl,
+    /// Run a WebAssembly spec test script and summarize its passed and failed directives.
+    Wast {
+        /// Path of the .wast script.
+        path: PathBuf,
+    },
 }
 
-fn main() -> Result<()> {
+fn main() -> ExitCode {
     let args = Args::parse();
-    cmd::exec(cmd::ExecInput {
-        path: PathBuf::from(args.file),
-        print: args.print,
-    })?;
-    Ok(())
+    match run(args.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::from(cmd::exit_code(&e))
+        }
+    }
+}
+
+fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Run {
+            file,
+            invoke,
+            fuel,
+            timeout,
+            max_memory,
+            max_table_elements,
+            trace,
+            trace_output,
+            trace_func,
+            profile,
+            profile_folded,
+            coverage,
+            coverage_lcov,
+            args,
+        } => {
+            cmd::exec(cmd::ExecInput {
+                path: file,
+                invoke,
+                args,
+                fuel,
+                timeout: timeout.map(Duration::from_millis),
+                max_memory,
+                max_table_elements,
+                trace: trace.then_some(cmd::TraceOptions {
+                    output: trace_output,
+                    func: trace_func,
+                }),
+                profile: profile.then_some(cmd::ProfileOptions {
+                    folded: profile_folded,
+                }),
+                coverage: coverage.then_some(cmd::CoverageOptions {
+                    lcov: coverage_lcov,
+                }),
+            })?;
+            Ok(())
+        }
+        Command::Inspect { file, print } => cmd::inspect(&file, print),
+        Command::Validate { file } => cmd::check(&file),
+        Command::Dump { file } => cmd::dump(&file),
+        Command::Wat2wasm { input, output } => cmd::wat2wasm(&input, output.as_deref()),
+        Command::Wasm2wat { input, output } => cmd::wasm2wat(&input, output.as_deref()),
+        Command::Debug { path } => cmd::debug(&path),
+        Command::Repl => cmd::repl(),
+        Command::Wast { path } => {
+            cmd::wast(&path)?;
+            Ok(())
+        }
+    }
 }
@@ -0,0 +1,261 @@
+use anyhow::{anyhow, Result};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cmd::{load_valid_module, parse_args};
+use crate::debugger::{dump_memory, prompt};
+use crate::errors::ExecError;
+use crate::exec::instance::Instance;
+use crate::exec::store::{ExternVal, GlobalInst, Store};
+use crate::exec::trap::Trap;
+use crate::wat::printer::print_signature;
+
+const PROMPT: &str = "wasm> ";
+
+const HELP: &str = "\
+load <path>             instantiate a .wasm or .wat file
+reload                  load the same file again, e.g. after rebuilding it
+exports                 list the exports with their types
+invoke <export> [args]  call an exported function
+globals                 show the globals
+memory                  show the size of each memory
+memory <addr> [len]     show len bytes of the first memory from addr
+quit, q                 exit";
+
+/// Module instantiated by `load`.
+struct Loaded {
+    path: PathBuf,
+    store: Store,
+    instance: Instance,
+}
+
+/// Prompt loading modules and calling their exports, the store being kept between calls.
+#[derive(Default)]
+pub struct Repl {
+    loaded: Option<Loaded>,
+}
+impl Repl {
+    pub fn new() -> Repl {
+        Repl::default()
+    }
+
+    /// Read commands from `input` until `quit` or its end.
+    pub fn run(&mut self, input: &mut impl BufRead, out: &mut impl Write) -> Result<()> {
+        while let Some(line) = prompt(PROMPT, input, out)? {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let result = match words.as_slice() {
+                ["quit" | "q"] => return Ok(()),
+                words => self.command(words, out),
+            };
+            if let Err(e) = result {
+                match e.downcast_ref::<Trap>() {
+                    Some(trap) => writeln!(out, "{}", trap)?,
+                    None => writeln!(out, "error: {}", e)?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, words: &[&str], out: &mut impl Write) -> Result<()> {
+        match words {
+            [] => {}
+            ["help" | "h"] => writeln!(out, "{}", HELP)?,
+            ["load", path] => self.load(Path::new(path), out)?,
+            ["reload"] => {
+                let path = self.loaded()?.path.clone();
+                self.load(&path, out)?;
+            }
+            ["exports"] => {
+                let Loaded {
+                    store, instance, ..
+                } = self.loaded()?;
+                for (name, value) in &instance.data(store).exports {
+                    let desc = match value {
+                        ExternVal::Func(addr) => {
+                            let signature = print_signature(&store.funcs[*addr].func_type);
+                            format!("func {}", signature).trim_end().to_string()
+                        }
+                        ExternVal::Table(addr) => {
+                            let table = &store.tables[*addr];
+                            format!("table {}, {} elements", table.ref_type, table.size())
+                        }
+                        ExternVal::Memory(addr) => {
+                            format!("memory, {} pages", store.memories[*addr].size())
+                        }
+                        ExternVal::Global(addr) => {
+                            format!("global {}", global_type(&store.globals[*addr]))
+                        }
+                    };
+                    writeln!(out, "{}: {}", name, desc)?;
+                }
+            }
+            ["invoke", name, args @ ..] => {
+                let Loaded {
+                    store, instance, ..
+                } = self.loaded_mut()?;
+                let addr = instance
+                    .get_func(store, name)
+                    .ok_or(ExecError::ExportNotFound(name.to_string()))?;
+                let params = &store.funcs[addr].func_type.param_type.val_types;
+                let args = parse_args(name, params, args)?;
+                let results: Vec<String> = instance
+                    .invoke(store, name, &args)?
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect();
+                writeln!(out, "[{}]", results.join(" "))?;
+            }
+            ["globals"] => {
+                let Loaded {
+                    store, instance, ..
+                } = self.loaded()?;
+                let data = instance.data(store);
+                for (idx, addr) in data.global_addrs.iter().enumerate() {
+                    let global = &store.globals[*addr];
+                    let export = data.exports.iter().find_map(|(name, value)| match value {
+                        ExternVal::Global(a) if a == addr => Some(name),
+                        _ => None,
+                    });
+                    let name = export.map_or(String::new(), |name| format!(" \"{}\"", name));
+                    let val_type = global_type(global);
+                    writeln!(out, "{}{}: {} = {}", idx, name, val_type, global.get())?;
+                }
+            }
+            ["memory"] => {
+                let Loaded {
+                    store, instance, ..
+                } = self.loaded()?;
+                for (idx, addr) in instance.data(store).mem_addrs.iter().enumerate() {
+                    writeln!(out, "{}: {} pages", idx, store.memories[*addr].size())?;
+                }
+            }
+            ["memory", range @ ..] => {
+                let Loaded {
+                    store, instance, ..
+                } = self.loaded()?;
+                let addr = instance.data(store).mem_addrs.first();
+                let memory = addr.map(|addr| &store.memories[*addr]);
+                dump_memory(memory.ok_or(anyhow!("no memory"))?, range, out)?;
+            }
+            [command, ..] => return Err(anyhow!("unknown command `{}`, try `help`", command)),
+        }
+        Ok(())
+    }
+
+    /// Instantiate the module at `path` in a new store, keeping the loaded one if it fails.
+    fn load(&mut self, path: &Path, out: &mut impl Write) -> Result<()> {
+        let module = load_valid_module(path)?;
+        let mut store = Store::new();
+        let instance = Instance::new(&mut store, module)?;
+        let exports = instance.data(&store).exports.len();
+        writeln!(out, "loaded {} with {} exports", path.display(), exports)?;
+        self.loaded = Some(Loaded {
+            path: path.to_path_buf(),
+            store,
+            instance,
+        });
+        Ok(())
+    }
+
+    fn loaded(&self) -> Result<&Loaded> {
+        self.loaded
+            .as_ref()
+            .ok_or(anyhow!("no module loaded, see `load`"))
+    }
+
+    fn loaded_mut(&mut self) -> Result<&mut Loaded> {
+        self.loaded
+            .as_mut()
+            .ok_or(anyhow!("no module loaded, see `load`"))
+    }
+}
+
+/// `t` or `(mut t)`.
+fn global_type(global: &GlobalInst) -> String {
+    let val_type = &global.global_type.val_type;
+    if global.global_type.mutable {
+        format!("(mut {})", val_type)
+    } else {
+        val_type.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Output of a new REPL reading `commands`, without its prompts.
+    fn repl(commands: &str) -> String {
+        let mut out = Vec::new();
+        Repl::new().run(&mut commands.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap().replace(PROMPT, "")
+    }
+
+    #[test]
+    fn test_invoke() {
+        assert_eq!(
+            repl(
+                "invoke add42 5\nload examples/call.wasm\nexports\ninvoke add42 5\ninvoke add42\n"
+            ),
+            "error: no module loaded, see `load`\n\
+             loaded examples/call.wasm with 1 exports\n\
+             add42: func (param i32) (result i32)\n\
+             [47:i32]\n\
+             error: Invalid arguments: add42 expects 1 arguments, got 0\n"
+        );
+    }
+
+    #[test]
+    fn test_state() {
+        let commands =
+            "load examples/exports.wat\ninvoke incr\ninvoke incr\nglobals\nmemory\nmemory 0 4\n";
+        assert_eq!(
+            repl(commands),
+            "loaded examples/exports.wat with 6 exports\n\
+             [1:i32]\n\
+             [2:i32]\n\
+             0 \"counter\": (mut i32) = 2:i32\n\
+             1 \"answer\": i64 = 42:i64\n\
+             0: 1 pages\n\
+             0x00000000: 00 00 00 00\n"
+        );
+    }
+
+    #[test]
+    fn test_reload() {
+        let path = std::env::temp_dir().join(format!("repl-{}.wat", std::process::id()));
+        let module = |value: i32| {
+            format!(
+                r#"(module (func (export "f") (result i32) (i32.const {})))"#,
+                value
+            )
+        };
+        fs::write(&path, module(1)).unwrap();
+        let mut repl = Repl::new();
+        let mut out = Vec::new();
+        let commands = format!("load {}\ninvoke f\n", path.display());
+        repl.run(&mut commands.as_bytes(), &mut out).unwrap();
+
+        fs::write(&path, module(2)).unwrap();
+        repl.run(&mut "reload\ninvoke f\n".as_bytes(), &mut out)
+            .unwrap();
+        // A module failing to load leaves the previous one loaded.
+        fs::write(&path, "(module").unwrap();
+        repl.run(&mut "reload\ninvoke f\n".as_bytes(), &mut out)
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let output = String::from_utf8(out).unwrap().replace(PROMPT, "");
+        let results: Vec<&str> = output.lines().filter(|l| l.starts_with('[')).collect();
+        assert_eq!(results, ["[1:i32]", "[2:i32]", "[2:i32]"]);
+        assert!(output.contains("error: "));
+    }
+
+    #[test]
+    fn test_trap() {
+        let output = repl("load examples/trap.wat\ninvoke div 1 0\n");
+        assert!(output.contains("\nwasm trap: integer divide by zero\n"));
+    }
+}
@@ -0,0 +1,389 @@
+use std::collections::BTreeMap;
+use std::io::{self, Read, SeekFrom, Write};
+
+use crate::wasi::errno::Errno;
+use crate::wasi::stdio::Stdio;
+use crate::wasi::vfs::{join_path, FileType, Handle, OpenOptions, Vfs};
+
+pub type Fd = u32;
+
+const STDIN: Fd = 0;
+const STDOUT: Fd = 1;
+const STDERR: Fd = 2;
+
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-filetype-variant
+pub const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+pub const FILETYPE_DIRECTORY: u8 = 3;
+pub const FILETYPE_REGULAR_FILE: u8 = 4;
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-fdflags-record
+pub const FDFLAGS_APPEND: u16 = 1 << 0;
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-rights-record
+pub const RIGHTS_FD_READ: u64 = 1 << 1;
+pub const RIGHTS_FD_SEEK: u64 = 1 << 2;
+pub const RIGHTS_FD_TELL: u64 = 1 << 5;
+pub const RIGHTS_FD_WRITE: u64 = 1 << 6;
+pub const RIGHTS_PATH_CREATE_DIRECTORY: u64 = 1 << 9;
+pub const RIGHTS_PATH_CREATE_FILE: u64 = 1 << 10;
+pub const RIGHTS_PATH_OPEN: u64 = 1 << 13;
+pub const RIGHTS_FD_READDIR: u64 = 1 << 14;
+pub const RIGHTS_PATH_REMOVE_DIRECTORY: u64 = 1 << 25;
+pub const RIGHTS_PATH_UNLINK_FILE: u64 = 1 << 26;
+
+/// Rights of the operations supported on files and on directories.
+const FILE_RIGHTS: u64 = RIGHTS_FD_READ | RIGHTS_FD_SEEK | RIGHTS_FD_TELL | RIGHTS_FD_WRITE;
+const DIR_RIGHTS: u64 = RIGHTS_PATH_CREATE_DIRECTORY
+    | RIGHTS_PATH_CREATE_FILE
+    | RIGHTS_PATH_OPEN
+    | RIGHTS_FD_READDIR
+    | RIGHTS_PATH_REMOVE_DIRECTORY
+    | RIGHTS_PATH_UNLINK_FILE;
+
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-fdstat-record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FdStat {
+    pub filetype: u8,
+    pub flags: u16,
+    pub rights_base: u64,
+    pub rights_inheriting: u64,
+}
+
+#[derive(Debug)]
+enum FdEntry {
+    Stdio,
+    /// A directory of the filesystem `fs`.
+    /// `preopen` is the name the guest sees for preopened directories.
+    Dir {
+        fs: usize,
+        path: String,
+        preopen: Option<String>,
+    },
+    /// A file opened with `options`, which give its rights.
+    File {
+        fs: usize,
+        handle: Handle,
+        options: OpenOptions,
+    },
+}
+
+/// State shared by the WASI functions of one instance:
+/// the standard streams and the file descriptor table.
+pub struct WasiCtx {
+    args: Vec<String>,
+    stdin: Stdio,
+    stdout: Stdio,
+    stderr: Stdio,
+    filesystems: Vec<Box<dyn Vfs>>,
+    fds: BTreeMap<Fd, FdEntry>,
+}
+impl Default for WasiCtx {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl WasiCtx {
+    pub fn new() -> WasiCtx {
+        let mut fds = BTreeMap::new();
+        for fd in [STDIN, STDOUT, STDERR] {
+            fds.insert(fd, FdEntry::Stdio);
+        }
+        WasiCtx {
+            args: Vec::new(),
+            stdin: Stdio::Inherit,
+            stdout: Stdio::Inherit,
+            stderr: Stdio::Inherit,
+            filesystems: Vec::new(),
+            fds,
+        }
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    pub fn set_stdin(&mut self, stdin: Stdio) {
+        self.stdin = stdin;
+    }
+
+    pub fn set_stdout(&mut self, stdout: Stdio) {
+        self.stdout = stdout;
+    }
+
+    pub fn set_stderr(&mut self, stderr: Stdio) {
+        self.stderr = stderr;
+    }
+
+    /// Make the root of `fs` visible to the guest as the directory `guest_path`.
+    pub fn preopen_dir(&mut self, guest_path: &str, fs: impl Vfs + 'static) -> Fd {
+        self.filesystems.push(Box::new(fs));
+        self.push_fd(FdEntry::Dir {
+            fs: self.filesystems.len() - 1,
+            path: String::new(),
+            preopen: Some(guest_path.to_string()),
+        })
+    }
+
+    /// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-fd_prestat_dir_namefd-fd-path-pointeru8-path_len-size---result-errno
+    pub fn fd_prestat_dir_name(&self, fd: Fd) -> Result<&str, Errno> {
+        match self.fds.get(&fd) {
+            Some(FdEntry::Dir {
+                preopen: Some(name),
+                ..
+            }) => Ok(name),
+            Some(_) => Err(Errno::Inval),
+            None => Err(Errno::Badf),
+        }
+    }
+
+    /// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-fd_readfd-fd-iovs-iovec_array---resultsize-errno
+    pub fn fd_read(&mut self, fd: Fd, buf: &mut [u8]) -> Result<usize, Errno> {
+        match self.fds.get(&fd).ok_or(Errno::Badf)? {
+            FdEntry::Stdio if fd == STDIN => match &self.stdin {
+                Stdio::Inherit => Ok(io::stdin().read(buf)?),
+                Stdio::Pipe(pipe) => Ok(pipe.read(buf)),
+                Stdio::Null => Ok(0),
+            },
+            FdEntry::Stdio => Err(Errno::Badf),
+            FdEntry::Dir { .. } => Err(Errno::Isdir),
+            FdEntry::File { fs, handle, .. } => self.filesystems[*fs].read(*handle, buf),
+        }
+    }
+
+    /// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-fd_writefd-fd-iovs-ciovec_array---resultsize-errno
+    pub fn fd_write(&mut self, fd: Fd, buf: &[u8]) -> Result<usize, Errno> {
+        match self.fds.get(&fd).ok_or(Errno::Badf)? {
+            FdEntry::Stdio if fd == STDOUT => match &self.stdout {
+                Stdio::Inherit => Ok(io::stdout().write(buf)?),
+                Stdio::Pipe(pipe) => Ok(pipe.write(buf)),
+                Stdio::Null => Ok(buf.len()),
+            },
+            FdEntry::Stdio if fd == STDERR => match &self.stderr {
+                Stdio::Inherit => Ok(io::stderr().write(buf)?),
+                Stdio::Pipe(pipe) => Ok(pipe.write(buf)),
+                Stdio::Null => Ok(buf.len()),
+            },
+            FdEntry::Stdio => Err(Errno::Badf),
+            FdEntry::Dir { .. } => Err(Errno::Isdir),
+            FdEntry::File { fs, handle, .. } => self.filesystems[*fs].write(*handle, buf),
+        }
+    }
+
+    /// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-fd_seekfd-fd-offset-filedelta-whence-whence---resultfilesize-errno
+    pub fn fd_seek(&mut self, fd: Fd, pos: SeekFrom) -> Result<u64, Errno> {
+        match self.fds.get(&fd).ok_or(Errno::Badf)? {
+            FdEntry::Stdio => Err(Errno::Spipe),
+            FdEntry::Dir { .. } => Err(Errno::Isdir),
+            FdEntry::File { fs, handle, .. } => self.filesystems[*fs].seek(*handle, pos),
+        }
+    }
+
+    /// Directories have every right the files and directories inside them may get.
+    /// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-fd_fdstat_getfd-fd---resultfdstat-errno
+    pub fn fd_fdstat_get(&self, fd: Fd) -> Result<FdStat, Errno> {
+        let stat = |filetype, rights_base, rights_inheriting| FdStat {
+            filetype,
+            flags: 0,
+            rights_base,
+            rights_inheriting,
+        };
+        Ok(match self.fds.get(&fd).ok_or(Errno::Badf)? {
+            FdEntry::Stdio if fd == STDIN => stat(FILETYPE_CHARACTER_DEVICE, RIGHTS_FD_READ, 0),
+            FdEntry::Stdio => stat(FILETYPE_CHARACTER_DEVICE, RIGHTS_FD_WRITE, 0),
+            FdEntry::Dir { .. } => stat(FILETYPE_DIRECTORY, DIR_RIGHTS, DIR_RIGHTS | FILE_RIGHTS),
+            FdEntry::File { options, .. } => {
+                let mut rights = RIGHTS_FD_SEEK | RIGHTS_FD_TELL;
+                if options.read {
+                    rights |= RIGHTS_FD_READ;
+                }
+                if options.write || options.append {
+                    rights |= RIGHTS_FD_WRITE;
+                }
+                FdStat {
+                    flags: if options.append { FDFLAGS_APPEND } else { 0 },
+                    ..stat(FILETYPE_REGULAR_FILE, rights, 0)
+                }
+            }
+        })
+    }
+
+    /// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-fd_closefd-fd---result-errno
+    pub fn fd_close(&mut self, fd: Fd) -> Result<(), Errno> {
+        match self.fds.remove(&fd).ok_or(Errno::Badf)? {
+            FdEntry::File { fs, handle, .. } => self.filesystems[fs].close(handle),
+            _ => Ok(()),
+        }
+    }
+
+    /// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-path_openfd-fd-dirflags-lookupflags-path-string-oflags-oflags-fs_rights_base-rights-fs_rights_inheriting-rights-fdflags-fdflags---resultfd-errno
+    pub fn path_open(
+        &mut self,
+        dir_fd: Fd,
+        path: &str,
+        directory: bool,
+        options: OpenOptions,
+    ) -> Result<Fd, Errno> {
+        let (fs, path) = self.resolve(dir_fd, path)?;
+        if directory {
+            if self.filesystems[fs].stat(&path)?.file_type != FileType::Directory {
+                return Err(Errno::Notdir);
+            }
+            return Ok(self.push_fd(FdEntry::Dir {
+                fs,
+                path,
+                preopen: None,
+            }));
+        }
+
+        let handle = self.filesystems[fs].open(&path, options)?;
+        Ok(self.push_fd(FdEntry::File {
+            fs,
+            handle,
+            options,
+        }))
+    }
+
+    /// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-fd_readdirfd-fd-buf-pointeru8-buf_len-size-cookie-dircookie---resultsize-errno
+    pub fn fd_readdir(&mut self, fd: Fd) -> Result<Vec<String>, Errno> {
+        match self.fds.get(&fd).ok_or(Errno::Badf)? {
+            FdEntry::Dir { fs, path, .. } => self.filesystems[*fs].read_dir(path),
+            _ => Err(Errno::Notdir),
+        }
+    }
+
+    /// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-path_create_directoryfd-fd-path-string---result-errno
+    pub fn path_create_directory(&mut self, dir_fd: Fd, path: &str) -> Result<(), Errno> {
+        let (fs, path) = self.resolve(dir_fd, path)?;
+        self.filesystems[fs].create_dir(&path)
+    }
+
+    /// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-path_unlink_filefd-fd-path-string---result-errno
+    pub fn path_unlink_file(&mut self, dir_fd: Fd, path: &str) -> Result<(), Errno> {
+        let (fs, path) = self.resolve(dir_fd, path)?;
+        self.filesystems[fs].remove_file(&path)
+    }
+
+    /// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-path_remove_directoryfd-fd-path-string---result-errno
+    pub fn path_remove_directory(&mut self, dir_fd: Fd, path: &str) -> Result<(), Errno> {
+        let (fs, path) = self.resolve(dir_fd, path)?;
+        self.filesystems[fs].remove_dir(&path)
+    }
+
+    /// Find the filesystem and the path inside it for `path` relative to `dir_fd`.
+    fn resolve(&self, dir_fd: Fd, path: &str) -> Result<(usize, String), Errno> {
+        match self.fds.get(&dir_fd).ok_or(Errno::Badf)? {
+            FdEntry::Dir { fs, path: dir, .. } => Ok((*fs, join_path(dir, path)?)),
+            _ => Err(Errno::Notdir),
+        }
+    }
+
+    fn push_fd(&mut self, entry: FdEntry) -> Fd {
+        let fd = self.fds.keys().last().map(|fd| fd + 1).unwrap_or(0);
+        self.fds.insert(fd, entry);
+        fd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasi::memory_fs::MemoryFs;
+    use crate::wasi::stdio::Pipe;
+
+    #[test]
+    fn test_captured_stdio() {
+        let stdin = Pipe::with_contents("input");
+        let stdout = Pipe::new();
+        let stderr = Pipe::new();
+
+        let mut ctx = WasiCtx::new();
+        ctx.set_stdin(Stdio::Pipe(stdin));
+        ctx.set_stdout(Stdio::Pipe(stdout.clone()));
+        ctx.set_stderr(Stdio::Pipe(stderr.clone()));
+
+        let mut buf = [0u8; 16];
+        let size = ctx.fd_read(STDIN, &mut buf).unwrap();
+        ctx.fd_write(STDOUT, &buf[..size]).unwrap();
+        ctx.fd_write(STDERR, b"done").unwrap();
+
+        assert_eq!(stdout.contents_string(), "input");
+        assert_eq!(stderr.contents_string(), "done");
+        assert_eq!(ctx.fd_read(STDOUT, &mut buf), Err(Errno::Badf));
+        assert_eq!(ctx.fd_seek(STDIN, SeekFrom::Start(0)), Err(Errno::Spipe));
+    }
+
+    #[test]
+    fn test_path_open_on_memory_fs() {
+        let fs = MemoryFs::new();
+        fs.add_file("in/a.txt", "abc").unwrap();
+
+        let mut ctx = WasiCtx::new();
+        let root = ctx.preopen_dir("/sandbox", fs.clone());
+        assert_eq!(root, 3);
+        assert_eq!(ctx.fd_prestat_dir_name(root), Ok("/sandbox"));
+
+        let options = OpenOptions {
+            read: true,
+            ..Default::default()
+        };
+        let input = ctx.path_open(root, "in/a.txt", false, options).unwrap();
+        let mut buf = [0u8; 8];
+        let size = ctx.fd_read(input, &mut buf).unwrap();
+        ctx.fd_close(input).unwrap();
+
+        let dir = ctx
+            .path_open(root, "in", true, OpenOptions::default())
+            .unwrap();
+        assert_eq!(ctx.fd_readdir(dir), Ok(vec!["a.txt".to_string()]));
+
+        let options = OpenOptions {
+            write: true,
+            create: true,
+            ..Default::default()
+        };
+        let output = ctx.path_open(dir, "../out.txt", false, options).unwrap();
+        ctx.fd_write(output, &buf[..size]).unwrap();
+        ctx.fd_close(output).unwrap();
+
+        assert_eq!(fs.file_contents("out.txt"), Some(b"abc".to_vec()));
+        assert_eq!(ctx.fd_read(output, &mut buf), Err(Errno::Badf));
+        assert_eq!(
+            ctx.path_open(root, "../escape.txt", false, options),
+            Err(Errno::Notcapable)
+        );
+    }
+
+    #[test]
+    fn test_fdstat() {
+        let fs = MemoryFs::new();
+        fs.add_file("a.txt", "abc").unwrap();
+        let mut ctx = WasiCtx::new();
+        let root = ctx.preopen_dir("/", fs);
+        let options = OpenOptions {
+            append: true,
+            ..Default::default()
+        };
+        let file = ctx.path_open(root, "a.txt", false, options).unwrap();
+
+        let stdout = ctx.fd_fdstat_get(STDOUT).unwrap();
+        assert_eq!(stdout.filetype, FILETYPE_CHARACTER_DEVICE);
+        assert_eq!(stdout.rights_base, RIGHTS_FD_WRITE);
+        let dir = ctx.fd_fdstat_get(root).unwrap();
+        assert_eq!(dir.filetype, FILETYPE_DIRECTORY);
+        assert_ne!(dir.rights_base & RIGHTS_PATH_OPEN, 0);
+        assert_ne!(dir.rights_inheriting & RIGHTS_FD_READ, 0);
+        assert_eq!(
+            ctx.fd_fdstat_get(file),
+            Ok(FdStat {
+                filetype: FILETYPE_REGULAR_FILE,
+                flags: FDFLAGS_APPEND,
+                rights_base: RIGHTS_FD_SEEK | RIGHTS_FD_TELL | RIGHTS_FD_WRITE,
+                rights_inheriting: 0,
+            })
+        );
+        ctx.fd_close(file).unwrap();
+        assert_eq!(ctx.fd_fdstat_get(file), Err(Errno::Badf));
+    }
+}
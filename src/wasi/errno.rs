@@ -0,0 +1,58 @@
+use std::io;
+use thiserror::Error;
+
+/// Error codes returned to the guest by WASI functions.
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-errno-variant
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u16)]
+pub enum Errno {
+    #[error("Permission denied")]
+    Acces = 2,
+    #[error("Bad file descriptor")]
+    Badf = 8,
+    #[error("File exists")]
+    Exist = 20,
+    #[error("Bad address")]
+    Fault = 21,
+    #[error("File too large")]
+    Fbig = 22,
+    #[error("Invalid argument")]
+    Inval = 28,
+    #[error("I/O error")]
+    Io = 29,
+    #[error("Is a directory")]
+    Isdir = 31,
+    #[error("No such file or directory")]
+    Noent = 44,
+    #[error("No space left on device")]
+    Nospc = 51,
+    #[error("Not a directory")]
+    Notdir = 54,
+    #[error("Directory not empty")]
+    Notempty = 55,
+    #[error("Operation not permitted")]
+    Perm = 63,
+    #[error("Invalid seek")]
+    Spipe = 70,
+    #[error("Capabilities insufficient")]
+    Notcapable = 76,
+}
+impl Errno {
+    pub fn code(&self) -> u16 {
+        *self as u16
+    }
+}
+impl From<io::Error> for Errno {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => Errno::Noent,
+            io::ErrorKind::PermissionDenied => Errno::Acces,
+            io::ErrorKind::AlreadyExists => Errno::Exist,
+            io::ErrorKind::InvalidInput => Errno::Inval,
+            io::ErrorKind::NotADirectory => Errno::Notdir,
+            io::ErrorKind::IsADirectory => Errno::Isdir,
+            io::ErrorKind::DirectoryNotEmpty => Errno::Notempty,
+            _ => Errno::Io,
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use crate::wasi::errno::Errno;
+use crate::wasi::vfs::{normalize_path, FileStat, FileType, Handle, OpenOptions, Vfs};
+
+/// Filesystem backed by a directory of the host.
+#[derive(Debug)]
+pub struct HostFs {
+    root: PathBuf,
+    handles: HashMap<Handle, File>,
+    next_handle: Handle,
+}
+
+impl HostFs {
+    pub fn new(root: impl Into<PathBuf>) -> HostFs {
+        HostFs {
+            root: root.into(),
+            handles: HashMap::new(),
+            next_handle: 0,
+        }
+    }
+
+    fn host_path(&self, path: &str) -> Result<PathBuf, Errno> {
+        Ok(self.root.join(normalize_path(path)?))
+    }
+
+    fn file(&mut self, handle: Handle) -> Result<&mut File, Errno> {
+        self.handles.get_mut(&handle).ok_or(Errno::Badf)
+    }
+}
+
+impl Vfs for HostFs {
+    fn open(&mut self, path: &str, options: OpenOptions) -> Result<Handle, Errno> {
+        let host_path = self.host_path(path)?;
+        if host_path.is_dir() {
+            return Err(Errno::Isdir);
+        }
+
+        let file = fs::OpenOptions::new()
+            .read(options.read)
+            .write(options.write)
+            .append(options.append)
+            .create(options.create && !options.exclusive)
+            .create_new(options.create && options.exclusive)
+            .truncate(options.truncate)
+            .open(host_path)?;
+
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        self.handles.insert(handle, file);
+        Ok(handle)
+    }
+
+    fn read(&mut self, handle: Handle, buf: &mut [u8]) -> Result<usize, Errno> {
+        Ok(self.file(handle)?.read(buf)?)
+    }
+
+    fn write(&mut self, handle: Handle, buf: &[u8]) -> Result<usize, Errno> {
+        Ok(self.file(handle)?.write(buf)?)
+    }
+
+    fn seek(&mut self, handle: Handle, pos: SeekFrom) -> Result<u64, Errno> {
+        Ok(self.file(handle)?.seek(pos)?)
+    }
+
+    fn close(&mut self, handle: Handle) -> Result<(), Errno> {
+        self.handles.remove(&handle).ok_or(Errno::Badf)?;
+        Ok(())
+    }
+
+    fn stat(&mut self, path: &str) -> Result<FileStat, Errno> {
+        let metadata = fs::metadata(self.host_path(path)?)?;
+        let file_type = if metadata.is_dir() {
+            FileType::Directory
+        } else {
+            FileType::RegularFile
+        };
+        Ok(FileStat {
+            file_type,
+            size: metadata.len(),
+        })
+    }
+
+    fn read_dir(&mut self, path: &str) -> Result<Vec<String>, Errno> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(self.host_path(path)?)? {
+            names.push(entry?.file_name().to_string_lossy().into_owned());
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<(), Errno> {
+        Ok(fs::create_dir(self.host_path(path)?)?)
+    }
+
+    fn remove_file(&mut self, path: &str) -> Result<(), Errno> {
+        Ok(fs::remove_file(self.host_path(path)?)?)
+    }
+
+    fn remove_dir(&mut self, path: &str) -> Result<(), Errno> {
+        if normalize_path(path)?.is_empty() {
+            return Err(Errno::Perm);
+        }
+        Ok(fs::remove_dir(self.host_path(path)?)?)
+    }
+}
@@ -0,0 +1,394 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::SeekFrom;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::wasi::errno::Errno;
+use crate::wasi::vfs::{
+    normalize_path, seek_position, FileStat, FileType, Handle, OpenOptions, Vfs,
+};
+
+/// Size beyond which writes to a file fail with `Fbig`.
+pub const MAX_FILE_SIZE: usize = 1 << 30;
+
+/// Filesystem that lives entirely in memory.
+/// Cloning shares the contents, so the embedder can keep a clone to
+/// pre-fill files before the run and inspect them after it.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFs {
+    inner: Arc<Mutex<MemoryFsInner>>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryFsInner {
+    /// Every file and directory except the root, keyed by normalized path.
+    entries: BTreeMap<String, Entry>,
+    handles: HashMap<Handle, OpenFile>,
+    next_handle: Handle,
+}
+
+#[derive(Debug)]
+enum Entry {
+    File(Vec<u8>),
+    Dir,
+}
+
+#[derive(Debug)]
+struct OpenFile {
+    path: String,
+    pos: u64,
+    options: OpenOptions,
+}
+
+impl MemoryFs {
+    pub fn new() -> MemoryFs {
+        MemoryFs::default()
+    }
+
+    /// Create or replace a file, creating missing parent directories.
+    pub fn add_file(&self, path: &str, contents: impl Into<Vec<u8>>) -> Result<(), Errno> {
+        let path = normalize_path(path)?;
+        let mut inner = self.lock();
+        inner.create_parents(&path)?;
+        if let Some(Entry::Dir) = inner.entries.get(&path) {
+            return Err(Errno::Isdir);
+        }
+        inner.entries.insert(path, Entry::File(contents.into()));
+        Ok(())
+    }
+
+    /// Create a directory, creating missing parent directories.
+    pub fn add_dir(&self, path: &str) -> Result<(), Errno> {
+        let path = normalize_path(path)?;
+        let mut inner = self.lock();
+        inner.create_parents(&path)?;
+        match inner.entries.get(&path) {
+            Some(Entry::File(_)) => Err(Errno::Exist),
+            Some(Entry::Dir) => Ok(()),
+            None => {
+                inner.entries.insert(path, Entry::Dir);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn file_contents(&self, path: &str) -> Option<Vec<u8>> {
+        let path = normalize_path(path).ok()?;
+        match self.lock().entries.get(&path) {
+            Some(Entry::File(data)) => Some(data.clone()),
+            _ => None,
+        }
+    }
+
+    /// Paths of every file and directory, in lexical order.
+    pub fn paths(&self) -> Vec<String> {
+        self.lock().entries.keys().cloned().collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemoryFsInner> {
+        self.inner.lock().unwrap()
+    }
+}
+
+impl MemoryFsInner {
+    fn file_type(&self, path: &str) -> Option<FileType> {
+        if path.is_empty() {
+            return Some(FileType::Directory);
+        }
+        match self.entries.get(path)? {
+            Entry::File(_) => Some(FileType::RegularFile),
+            Entry::Dir => Some(FileType::Directory),
+        }
+    }
+
+    fn parent_of(path: &str) -> &str {
+        path.rsplit_once('/')
+            .map(|(parent, _)| parent)
+            .unwrap_or("")
+    }
+
+    fn create_parents(&mut self, path: &str) -> Result<(), Errno> {
+        let mut parent = String::new();
+        let components: Vec<&str> = path.split('/').collect();
+        for component in &components[..components.len() - 1] {
+            if !parent.is_empty() {
+                parent.push('/');
+            }
+            parent.push_str(component);
+            match self.entries.get(&parent) {
+                Some(Entry::File(_)) => return Err(Errno::Notdir),
+                Some(Entry::Dir) => {}
+                None => {
+                    self.entries.insert(parent.clone(), Entry::Dir);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_parent(&self, path: &str) -> Result<(), Errno> {
+        match self.file_type(Self::parent_of(path)) {
+            Some(FileType::Directory) => Ok(()),
+            Some(FileType::RegularFile) => Err(Errno::Notdir),
+            None => Err(Errno::Noent),
+        }
+    }
+
+    fn children(&self, path: &str) -> Vec<String> {
+        self.entries
+            .keys()
+            .filter(|p| Self::parent_of(p) == path)
+            .map(|p| p.rsplit('/').next().unwrap().to_string())
+            .collect()
+    }
+
+    fn open_file(&mut self, handle: Handle) -> Result<&mut OpenFile, Errno> {
+        self.handles.get_mut(&handle).ok_or(Errno::Badf)
+    }
+
+    fn data(&mut self, path: &str) -> Result<&mut Vec<u8>, Errno> {
+        match self.entries.get_mut(path) {
+            Some(Entry::File(data)) => Ok(data),
+            Some(Entry::Dir) => Err(Errno::Isdir),
+            None => Err(Errno::Noent),
+        }
+    }
+}
+
+impl Vfs for MemoryFs {
+    fn open(&mut self, path: &str, options: OpenOptions) -> Result<Handle, Errno> {
+        let path = normalize_path(path)?;
+        let mut inner = self.lock();
+        match inner.file_type(&path) {
+            Some(FileType::Directory) => return Err(Errno::Isdir),
+            Some(FileType::RegularFile) if options.create && options.exclusive => {
+                return Err(Errno::Exist)
+            }
+            Some(FileType::RegularFile) => {
+                if options.truncate {
+                    inner.data(&path)?.clear();
+                }
+            }
+            None if options.create => {
+                inner.check_parent(&path)?;
+                inner.entries.insert(path.clone(), Entry::File(Vec::new()));
+            }
+            None => return Err(Errno::Noent),
+        }
+
+        let handle = inner.next_handle;
+        inner.next_handle += 1;
+        inner.handles.insert(
+            handle,
+            OpenFile {
+                path,
+                pos: 0,
+                options,
+            },
+        );
+        Ok(handle)
+    }
+
+    fn read(&mut self, handle: Handle, buf: &mut [u8]) -> Result<usize, Errno> {
+        let mut inner = self.lock();
+        let file = inner.open_file(handle)?;
+        if !file.options.read {
+            return Err(Errno::Badf);
+        }
+        let (path, pos) = (file.path.clone(), file.pos as usize);
+
+        let data = inner.data(&path)?;
+        let start = pos.min(data.len());
+        let size = buf.len().min(data.len() - start);
+        buf[..size].copy_from_slice(&data[start..start + size]);
+
+        inner.open_file(handle)?.pos += size as u64;
+        Ok(size)
+    }
+
+    fn write(&mut self, handle: Handle, buf: &[u8]) -> Result<usize, Errno> {
+        let mut inner = self.lock();
+        let file = inner.open_file(handle)?;
+        if !file.options.write {
+            return Err(Errno::Badf);
+        }
+        let (path, pos, append) = (file.path.clone(), file.pos, file.options.append);
+
+        let data = inner.data(&path)?;
+        let start = if append { data.len() as u64 } else { pos };
+        // The position may be anywhere after a seek, so check the size before allocating.
+        let end = start
+            .checked_add(buf.len() as u64)
+            .filter(|end| *end <= MAX_FILE_SIZE as u64)
+            .ok_or(Errno::Fbig)? as usize;
+        if data.len() < end {
+            data.try_reserve_exact(end - data.len())
+                .map_err(|_| Errno::Nospc)?;
+            data.resize(end, 0);
+        }
+        data[end - buf.len()..end].copy_from_slice(buf);
+
+        inner.open_file(handle)?.pos = end as u64;
+        Ok(buf.len())
+    }
+
+    fn seek(&mut self, handle: Handle, pos: SeekFrom) -> Result<u64, Errno> {
+        let mut inner = self.lock();
+        let file = inner.open_file(handle)?;
+        let (path, current) = (file.path.clone(), file.pos);
+        let size = inner.data(&path)?.len() as u64;
+
+        let new_pos = seek_position(current, size, pos)?;
+        inner.open_file(handle)?.pos = new_pos;
+        Ok(new_pos)
+    }
+
+    fn close(&mut self, handle: Handle) -> Result<(), Errno> {
+        self.lock().handles.remove(&handle).ok_or(Errno::Badf)?;
+        Ok(())
+    }
+
+    fn stat(&mut self, path: &str) -> Result<FileStat, Errno> {
+        let path = normalize_path(path)?;
+        let mut inner = self.lock();
+        let file_type = inner.file_type(&path).ok_or(Errno::Noent)?;
+        let size = match file_type {
+            FileType::RegularFile => inner.data(&path)?.len() as u64,
+            FileType::Directory => 0,
+        };
+        Ok(FileStat { file_type, size })
+    }
+
+    fn read_dir(&mut self, path: &str) -> Result<Vec<String>, Errno> {
+        let path = normalize_path(path)?;
+        let inner = self.lock();
+        match inner.file_type(&path) {
+            Some(FileType::Directory) => Ok(inner.children(&path)),
+            Some(FileType::RegularFile) => Err(Errno::Notdir),
+            None => Err(Errno::Noent),
+        }
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<(), Errno> {
+        let path = normalize_path(path)?;
+        let mut inner = self.lock();
+        if inner.file_type(&path).is_some() {
+            return Err(Errno::Exist);
+        }
+        inner.check_parent(&path)?;
+        inner.entries.insert(path, Entry::Dir);
+        Ok(())
+    }
+
+    fn remove_file(&mut self, path: &str) -> Result<(), Errno> {
+        let path = normalize_path(path)?;
+        let mut inner = self.lock();
+        match inner.file_type(&path) {
+            Some(FileType::RegularFile) => {
+                inner.entries.remove(&path);
+                Ok(())
+            }
+            Some(FileType::Directory) => Err(Errno::Isdir),
+            None => Err(Errno::Noent),
+        }
+    }
+
+    fn remove_dir(&mut self, path: &str) -> Result<(), Errno> {
+        let path = normalize_path(path)?;
+        let mut inner = self.lock();
+        match inner.file_type(&path) {
+            _ if path.is_empty() => Err(Errno::Perm),
+            Some(FileType::Directory) if !inner.children(&path).is_empty() => Err(Errno::Notempty),
+            Some(FileType::Directory) => {
+                inner.entries.remove(&path);
+                Ok(())
+            }
+            Some(FileType::RegularFile) => Err(Errno::Notdir),
+            None => Err(Errno::Noent),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const READ: OpenOptions = OpenOptions {
+        read: true,
+        write: false,
+        append: false,
+        create: false,
+        exclusive: false,
+        truncate: false,
+    };
+
+    #[test]
+    fn test_read_prefilled_file() {
+        let mut fs = MemoryFs::new();
+        fs.add_file("data/input.txt", "hello world").unwrap();
+
+        let handle = fs.open("data/input.txt", READ).unwrap();
+        fs.seek(handle, SeekFrom::Start(6)).unwrap();
+        let mut buf = [0u8; 16];
+        let size = fs.read(handle, &mut buf).unwrap();
+        assert_eq!(&buf[..size], b"world");
+        fs.close(handle).unwrap();
+
+        assert_eq!(fs.read_dir("").unwrap(), vec!["data".to_string()]);
+        assert_eq!(fs.read_dir("data").unwrap(), vec!["input.txt".to_string()]);
+    }
+
+    #[test]
+    fn test_written_file_is_visible_to_embedder() {
+        let fs = MemoryFs::new();
+        let mut guest_fs = fs.clone();
+
+        let options = OpenOptions {
+            write: true,
+            create: true,
+            ..Default::default()
+        };
+        let handle = guest_fs.open("out.txt", options).unwrap();
+        guest_fs.write(handle, b"result").unwrap();
+        guest_fs.close(handle).unwrap();
+
+        assert_eq!(fs.file_contents("out.txt"), Some(b"result".to_vec()));
+    }
+
+    #[test]
+    fn test_write_past_max_file_size() {
+        let mut fs = MemoryFs::new();
+        let options = OpenOptions {
+            write: true,
+            create: true,
+            ..Default::default()
+        };
+        let handle = fs.open("out.txt", options).unwrap();
+        fs.seek(handle, SeekFrom::Start(1 << 40)).unwrap();
+        assert_eq!(fs.write(handle, b"x"), Err(Errno::Fbig));
+        fs.seek(handle, SeekFrom::Start(u64::MAX)).unwrap();
+        assert_eq!(fs.write(handle, b"x"), Err(Errno::Fbig));
+        assert_eq!(fs.file_contents("out.txt"), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_open_missing_file() {
+        let mut fs = MemoryFs::new();
+        assert_eq!(fs.open("missing.txt", READ), Err(Errno::Noent));
+        let options = OpenOptions {
+            write: true,
+            create: true,
+            ..Default::default()
+        };
+        assert_eq!(fs.open("missing/out.txt", options), Err(Errno::Noent));
+    }
+
+    #[test]
+    fn test_remove_dir() {
+        let mut fs = MemoryFs::new();
+        fs.add_file("a/b.txt", "").unwrap();
+        assert_eq!(fs.remove_dir("a"), Err(Errno::Notempty));
+        fs.remove_file("a/b.txt").unwrap();
+        fs.remove_dir("a").unwrap();
+        assert!(fs.paths().is_empty());
+    }
+}
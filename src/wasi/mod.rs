@@ -0,0 +1,7 @@
+pub mod ctx;
+pub mod errno;
+pub mod host_fs;
+pub mod memory_fs;
+pub mod preview1;
+pub mod stdio;
+pub mod vfs;
@@ -0,0 +1,457 @@
+use std::io::SeekFrom;
+use std::sync::{Arc, Mutex};
+
+use crate::exec::instance::{HostExtern, Instance};
+use crate::exec::store::{Caller, HostFunc, MemInst, MemoryValue, Store};
+use crate::exec::trap::{Trap, TrapKind};
+use crate::exec::type_section::{FunctionTypeNode, ValType};
+use crate::exec::value::Value;
+use crate::wasi::ctx::{Fd, WasiCtx, FDFLAGS_APPEND, RIGHTS_FD_READ, RIGHTS_FD_WRITE};
+use crate::wasi::errno::Errno;
+use crate::wasi::vfs::OpenOptions;
+
+/// Module the WASI functions are imported from.
+pub const MODULE: &str = "wasi_snapshot_preview1";
+
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-fdstat-record
+const FDSTAT_SIZE: u64 = 24;
+
+/// Size of a `dirent` before its name.
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-dirent-record
+const DIRENT_SIZE: usize = 24;
+
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md#-oflags-record
+const OFLAGS_CREAT: u32 = 1 << 0;
+const OFLAGS_DIRECTORY: u32 = 1 << 1;
+const OFLAGS_EXCL: u32 = 1 << 2;
+const OFLAGS_TRUNC: u32 = 1 << 3;
+
+/// WASI function taking its arguments as `u32`s, or `u64`s for the 64-bit ones,
+/// with the memory of the calling instance.
+type WasiFn = fn(&mut WasiCtx, &mut MemInst, &[u64]) -> Result<(), Errno>;
+
+const I32: ValType = ValType::I32;
+const I64: ValType = ValType::I64;
+
+/// Functions returning an `errno`, with their parameter types.
+const FUNCS: [(&str, &[ValType], WasiFn); 16] = [
+    ("args_get", &[I32, I32], args_get),
+    ("args_sizes_get", &[I32, I32], args_sizes_get),
+    // Writes nothing, which is right only while `environ_sizes_get` reports no variables.
+    ("environ_get", &[I32, I32], |_, _, _| Ok(())),
+    ("environ_sizes_get", &[I32, I32], environ_sizes_get),
+    ("fd_close", &[I32], fd_close),
+    ("fd_fdstat_get", &[I32, I32], fd_fdstat_get),
+    ("fd_prestat_get", &[I32, I32], fd_prestat_get),
+    ("fd_prestat_dir_name", &[I32, I32, I32], fd_prestat_dir_name),
+    ("fd_read", &[I32, I32, I32, I32], fd_read),
+    ("fd_readdir", &[I32, I32, I32, I64, I32], fd_readdir),
+    ("fd_seek", &[I32, I64, I32, I32], fd_seek),
+    ("fd_write", &[I32, I32, I32, I32], fd_write),
+    (
+        "path_create_directory",
+        &[I32, I32, I32],
+        path_create_directory,
+    ),
+    (
+        "path_open",
+        &[I32, I32, I32, I32, I32, I64, I64, I32, I32],
+        path_open,
+    ),
+    (
+        "path_remove_directory",
+        &[I32, I32, I32],
+        path_remove_directory,
+    ),
+    ("path_unlink_file", &[I32, I32, I32], path_unlink_file),
+];
+
+/// Instance exporting the WASI functions backed by `ctx`,
+/// to be registered in a `Linker` as `MODULE`.
+/// https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md
+pub fn instantiate(store: &mut Store, ctx: WasiCtx) -> Instance {
+    let ctx = Arc::new(Mutex::new(ctx));
+    let mut exports = Vec::new();
+    for (name, params, wasi_fn) in FUNCS {
+        let mut func_type = FunctionTypeNode::new();
+        func_type.param_type.val_types = params.to_vec();
+        func_type.result_type.val_types = vec![ValType::I32];
+        let ctx = ctx.clone();
+        let func: HostFunc = Arc::new(move |caller: &mut Caller, args: &[Value]| {
+            let args: Vec<u64> = args.iter().map(arg).collect();
+            let result = match caller.memory.as_deref_mut() {
+                Some(memory) => wasi_fn(&mut ctx.lock().unwrap(), memory, &args),
+                None => Err(Errno::Fault),
+            };
+            let errno = result.err().map_or(0, |e| e.code());
+            Ok(vec![Value::I32(errno as i32)])
+        });
+        exports.push((name.to_string(), HostExtern::Func(func_type, func)));
+    }
+
+    let mut func_type = FunctionTypeNode::new();
+    func_type.param_type.val_types = vec![ValType::I32];
+    let proc_exit: HostFunc = Arc::new(|_, args| {
+        let status = args.first().map_or(0, arg) as u32;
+        Err(Trap::new(TrapKind::Exit(status)))
+    });
+    exports.push((
+        "proc_exit".to_string(),
+        HostExtern::Func(func_type, proc_exit),
+    ));
+    Instance::host(store, exports)
+}
+
+/// Pointers and sizes are unsigned.
+fn arg(value: &Value) -> u64 {
+    match value {
+        Value::I32(v) => *v as u32 as u64,
+        Value::I64(v) => *v as u64,
+        _ => 0,
+    }
+}
+
+fn read_u32(memory: &MemInst, ptr: u64) -> Result<u32, Errno> {
+    memory.read(ptr as usize).map_err(|_| Errno::Fault)
+}
+
+fn write<T: MemoryValue>(memory: &mut MemInst, ptr: u64, value: T) -> Result<(), Errno> {
+    memory.write(ptr as usize, value).map_err(|_| Errno::Fault)
+}
+
+fn read_bytes(memory: &MemInst, ptr: u64, len: u64) -> Result<&[u8], Errno> {
+    memory
+        .read_bytes(ptr as usize, len as usize)
+        .map_err(|_| Errno::Fault)
+}
+
+fn bytes_mut(memory: &mut MemInst, ptr: u64, len: u64) -> Result<&mut [u8], Errno> {
+    memory
+        .bytes_mut(ptr as usize, len as usize)
+        .map_err(|_| Errno::Fault)
+}
+
+fn write_bytes(memory: &mut MemInst, ptr: u64, bytes: &[u8]) -> Result<(), Errno> {
+    memory
+        .write_bytes(ptr as usize, bytes)
+        .map_err(|_| Errno::Fault)
+}
+
+fn read_str(memory: &MemInst, ptr: u64, len: u64) -> Result<&str, Errno> {
+    std::str::from_utf8(read_bytes(memory, ptr, len)?).map_err(|_| Errno::Inval)
+}
+
+/// Buffers of an `iovec_array` as `(pointer, length)` pairs.
+fn iovecs(memory: &MemInst, iovs: u64, iovs_len: u64) -> Result<Vec<(u64, u64)>, Errno> {
+    (0..iovs_len)
+        .map(|i| {
+            let iov = iovs + i * 8;
+            let buf = read_u32(memory, iov)?;
+            let buf_len = read_u32(memory, iov + 4)?;
+            Ok((buf as u64, buf_len as u64))
+        })
+        .collect()
+}
+
+fn args_get(ctx: &mut WasiCtx, memory: &mut MemInst, args: &[u64]) -> Result<(), Errno> {
+    let (mut argv, mut argv_buf) = (args[0], args[1]);
+    for arg in ctx.args() {
+        write(memory, argv, argv_buf as u32)?;
+        write_bytes(memory, argv_buf, arg.as_bytes())?;
+        write(memory, argv_buf + arg.len() as u64, 0u8)?;
+        argv += 4;
+        argv_buf += arg.len() as u64 + 1;
+    }
+    Ok(())
+}
+
+fn args_sizes_get(ctx: &mut WasiCtx, memory: &mut MemInst, args: &[u64]) -> Result<(), Errno> {
+    let buf_size: usize = ctx.args().iter().map(|arg| arg.len() + 1).sum();
+    write(memory, args[0], ctx.args().len() as u32)?;
+    write(memory, args[1], buf_size as u32)
+}
+
+/// The guest has no environment variables.
+fn environ_sizes_get(_: &mut WasiCtx, memory: &mut MemInst, args: &[u64]) -> Result<(), Errno> {
+    write(memory, args[0], 0u32)?;
+    write(memory, args[1], 0u32)
+}
+
+fn fd_close(ctx: &mut WasiCtx, _: &mut MemInst, args: &[u64]) -> Result<(), Errno> {
+    ctx.fd_close(args[0] as Fd)
+}
+
+fn fd_fdstat_get(ctx: &mut WasiCtx, memory: &mut MemInst, args: &[u64]) -> Result<(), Errno> {
+    let stat = ctx.fd_fdstat_get(args[0] as Fd)?;
+    // Clear the padding too.
+    bytes_mut(memory, args[1], FDSTAT_SIZE)?.fill(0);
+    write(memory, args[1], stat.filetype)?;
+    write(memory, args[1] + 2, stat.flags)?;
+    write(memory, args[1] + 8, stat.rights_base)?;
+    write(memory, args[1] + 16, stat.rights_inheriting)
+}
+
+/// Only directories are preopened, described by the length of their name.
+fn fd_prestat_get(ctx: &mut WasiCtx, memory: &mut MemInst, args: &[u64]) -> Result<(), Errno> {
+    let name_len = ctx.fd_prestat_dir_name(args[0] as Fd)?.len();
+    write(memory, args[1], 0u8)?;
+    write(memory, args[1] + 4, name_len as u32)
+}
+
+fn fd_prestat_dir_name(ctx: &mut WasiCtx, memory: &mut MemInst, args: &[u64]) -> Result<(), Errno> {
+    let name = ctx.fd_prestat_dir_name(args[0] as Fd)?;
+    let len = name.len().min(args[2] as usize);
+    write_bytes(memory, args[1], &name.as_bytes()[..len])
+}
+
+fn fd_read(ctx: &mut WasiCtx, memory: &mut MemInst, args: &[u64]) -> Result<(), Errno> {
+    let mut total = 0;
+    for (buf, buf_len) in iovecs(memory, args[1], args[2])? {
+        // Read straight into the guest memory, whose bounds limit the buffer.
+        let size = ctx.fd_read(args[0] as Fd, bytes_mut(memory, buf, buf_len)?)?;
+        total += size;
+        if size < buf_len as usize {
+            break;
+        }
+    }
+    write(memory, args[3], total as u32)
+}
+
+/// Entries are numbered from 0, a cookie being the number of the next one to read.
+fn fd_readdir(ctx: &mut WasiCtx, memory: &mut MemInst, args: &[u64]) -> Result<(), Errno> {
+    let (buf, buf_len, cookie) = (args[1], args[2] as usize, args[3]);
+    let mut bytes = Vec::new();
+    for (i, name) in ctx.fd_readdir(args[0] as Fd)?.iter().enumerate() {
+        if (i as u64) < cookie {
+            continue;
+        }
+        let mut dirent = [0; DIRENT_SIZE];
+        dirent[..8].copy_from_slice(&(i as u64 + 1).to_le_bytes());
+        dirent[16..20].copy_from_slice(&(name.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&dirent);
+        bytes.extend_from_slice(name.as_bytes());
+        if bytes.len() >= buf_len {
+            break;
+        }
+    }
+    // A full buffer tells the guest to call again from the last entry.
+    bytes.truncate(buf_len);
+    write_bytes(memory, buf, &bytes)?;
+    write(memory, args[4], bytes.len() as u32)
+}
+
+fn fd_seek(ctx: &mut WasiCtx, memory: &mut MemInst, args: &[u64]) -> Result<(), Errno> {
+    let offset = args[1] as i64;
+    let pos = match args[2] {
+        0 => SeekFrom::Start(offset as u64),
+        1 => SeekFrom::Current(offset),
+        2 => SeekFrom::End(offset),
+        _ => return Err(Errno::Inval),
+    };
+    let position = ctx.fd_seek(args[0] as Fd, pos)?;
+    write(memory, args[3], position)
+}
+
+fn fd_write(ctx: &mut WasiCtx, memory: &mut MemInst, args: &[u64]) -> Result<(), Errno> {
+    let mut total = 0;
+    for (buf, buf_len) in iovecs(memory, args[1], args[2])? {
+        let size = ctx.fd_write(args[0] as Fd, read_bytes(memory, buf, buf_len)?)?;
+        total += size;
+        if size < buf_len as usize {
+            break;
+        }
+    }
+    write(memory, args[3], total as u32)
+}
+
+fn path_create_directory(
+    ctx: &mut WasiCtx,
+    memory: &mut MemInst,
+    args: &[u64],
+) -> Result<(), Errno> {
+    let path = read_str(memory, args[1], args[2])?;
+    ctx.path_create_directory(args[0] as Fd, path)
+}
+
+fn path_open(ctx: &mut WasiCtx, memory: &mut MemInst, args: &[u64]) -> Result<(), Errno> {
+    let path = read_str(memory, args[2], args[3])?.to_string();
+    let (oflags, rights, fdflags) = (args[4] as u32, args[5], args[7] as u16);
+    let options = OpenOptions {
+        read: rights & RIGHTS_FD_READ != 0,
+        write: rights & RIGHTS_FD_WRITE != 0,
+        append: fdflags & FDFLAGS_APPEND != 0,
+        create: oflags & OFLAGS_CREAT != 0,
+        exclusive: oflags & OFLAGS_EXCL != 0,
+        truncate: oflags & OFLAGS_TRUNC != 0,
+    };
+    let directory = oflags & OFLAGS_DIRECTORY != 0;
+    let fd = ctx.path_open(args[0] as Fd, &path, directory, options)?;
+    write(memory, args[8], fd)
+}
+
+fn path_remove_directory(
+    ctx: &mut WasiCtx,
+    memory: &mut MemInst,
+    args: &[u64],
+) -> Result<(), Errno> {
+    let path = read_str(memory, args[1], args[2])?;
+    ctx.path_remove_directory(args[0] as Fd, path)
+}
+
+fn path_unlink_file(ctx: &mut WasiCtx, memory: &mut MemInst, args: &[u64]) -> Result<(), Errno> {
+    let path = read_str(memory, args[1], args[2])?;
+    ctx.path_unlink_file(args[0] as Fd, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::buffer::Buffer;
+    use crate::exec::linker::Linker;
+    use crate::exec::module::ModuleNode;
+    use crate::wasi::ctx::FILETYPE_CHARACTER_DEVICE;
+    use crate::wasi::memory_fs::MemoryFs;
+    use crate::wasi::stdio::{Pipe, Stdio};
+    use crate::wat::module::parse_str;
+
+    /// Instantiate `wat` with the WASI functions over `ctx` and call its `_start`.
+    fn run(wat: &str, ctx: WasiCtx) -> anyhow::Result<Vec<Value>> {
+        let mut store = Store::new();
+        let mut linker = Linker::new();
+        let wasi = instantiate(&mut store, ctx);
+        linker.register(MODULE, wasi);
+        let mut module = ModuleNode::new();
+        module.load(&mut Buffer::new(&parse_str(wat)?))?;
+        let instance = linker.instantiate(&mut store, module)?;
+        instance.invoke(&mut store, "_start", &[])
+    }
+
+    #[test]
+    fn test_write_to_captured_stdout() {
+        let stdout = Pipe::new();
+        let mut ctx = WasiCtx::new();
+        ctx.set_stdout(Stdio::Pipe(stdout.clone()));
+        let wat = r#"(module
+          (import "wasi_snapshot_preview1" "fd_write"
+            (func $fd_write (param i32 i32 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (func (export "_start") (result i32)
+            ;; "hello\n" at 16, in two buffers.
+            (i32.store (i32.const 16) (i32.const 0x6c6c6568))
+            (i32.store (i32.const 20) (i32.const 0x0a6f))
+            (i32.store (i32.const 0) (i32.const 16))
+            (i32.store (i32.const 4) (i32.const 4))
+            (i32.store (i32.const 8) (i32.const 20))
+            (i32.store (i32.const 12) (i32.const 2))
+            (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 2) (i32.const 32)))
+            ;; Bytes written.
+            (i32.load (i32.const 32))))
+        "#;
+        assert_eq!(run(wat, ctx).unwrap(), [Value::I32(6)]);
+        assert_eq!(stdout.contents_string(), "hello\n");
+    }
+
+    #[test]
+    fn test_read_preopened_file() {
+        let fs = MemoryFs::new();
+        fs.add_file("in.txt", "from the vfs").unwrap();
+        let stdout = Pipe::new();
+        let mut ctx = WasiCtx::new();
+        ctx.set_stdout(Stdio::Pipe(stdout.clone()));
+        ctx.preopen_dir("/data", fs);
+        let wat = r#"(module
+          (import "wasi_snapshot_preview1" "path_open"
+            (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+          (import "wasi_snapshot_preview1" "fd_read"
+            (func $fd_read (param i32 i32 i32 i32) (result i32)))
+          (import "wasi_snapshot_preview1" "fd_write"
+            (func $fd_write (param i32 i32 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (func (export "_start") (result i32)
+            ;; "in.txt" at 64, opened for reading from the preopened directory 3.
+            (i32.store (i32.const 64) (i32.const 0x742e6e69))
+            (i32.store (i32.const 68) (i32.const 0x7478))
+            (if (call $path_open (i32.const 3) (i32.const 0) (i32.const 64) (i32.const 6)
+                  (i32.const 0) (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 96))
+              (then (return (i32.const -1))))
+            ;; Read up to 32 bytes at 128, then write what was read to stdout.
+            (i32.store (i32.const 0) (i32.const 128))
+            (i32.store (i32.const 4) (i32.const 32))
+            (drop (call $fd_read (i32.load (i32.const 96)) (i32.const 0) (i32.const 1) (i32.const 4)))
+            (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8))))
+        "#;
+        assert_eq!(run(wat, ctx).unwrap(), [Value::I32(0)]);
+        assert_eq!(stdout.contents_string(), "from the vfs");
+    }
+
+    #[test]
+    fn test_args_and_errno() {
+        let mut ctx = WasiCtx::new();
+        ctx.set_args(vec!["prog".to_string(), "arg".to_string()]);
+        let wat = r#"(module
+          (import "wasi_snapshot_preview1" "args_sizes_get"
+            (func $args_sizes_get (param i32 i32) (result i32)))
+          (import "wasi_snapshot_preview1" "fd_close" (func $fd_close (param i32) (result i32)))
+          (memory (export "memory") 1)
+          (func (export "_start") (result i32 i32 i32)
+            (drop (call $args_sizes_get (i32.const 0) (i32.const 4)))
+            (i32.load (i32.const 0))
+            (i32.load (i32.const 4))
+            (call $fd_close (i32.const 9))))
+        "#;
+        let results = run(wat, ctx).unwrap();
+        let badf = Value::I32(Errno::Badf.code() as i32);
+        assert_eq!(results, [Value::I32(2), Value::I32(9), badf]);
+    }
+
+    #[test]
+    fn test_fdstat_of_stdout() {
+        let wat = r#"(module
+          (import "wasi_snapshot_preview1" "fd_fdstat_get"
+            (func $fd_fdstat_get (param i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (func (export "_start") (result i32 i32 i32 i32 i32)
+            ;; The filetype then the flags after a byte of padding,
+            ;; padding, and the low halves of the rights.
+            (i32.store (i32.const 16) (i32.const -1))
+            (i32.store (i32.const 20) (i32.const -1))
+            (call $fd_fdstat_get (i32.const 1) (i32.const 16))
+            (i32.load (i32.const 16))
+            (i32.load (i32.const 20))
+            (i32.load (i32.const 24))
+            (i32.load (i32.const 32))))
+        "#;
+        let results = run(wat, WasiCtx::new()).unwrap();
+        let filetype = FILETYPE_CHARACTER_DEVICE as i32;
+        let rights = RIGHTS_FD_WRITE as i32;
+        assert_eq!(results, [0, filetype, 0, rights, 0].map(Value::I32));
+    }
+
+    #[test]
+    fn test_read_into_buffer_past_memory() {
+        let mut ctx = WasiCtx::new();
+        ctx.set_stdin(Stdio::Pipe(Pipe::with_contents("input")));
+        let wat = r#"(module
+          (import "wasi_snapshot_preview1" "fd_read"
+            (func $fd_read (param i32 i32 i32 i32) (result i32)))
+          (memory (export "memory") 1)
+          (func (export "_start") (result i32)
+            ;; A single 4 GiB buffer at 16.
+            (i32.store (i32.const 0) (i32.const 16))
+            (i32.store (i32.const 4) (i32.const -1))
+            (call $fd_read (i32.const 0) (i32.const 0) (i32.const 1) (i32.const 8))))
+        "#;
+        let fault = Value::I32(Errno::Fault.code() as i32);
+        assert_eq!(run(wat, ctx).unwrap(), [fault]);
+    }
+
+    #[test]
+    fn test_proc_exit() {
+        let wat = r#"(module
+          (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+          (func (export "_start") (call $proc_exit (i32.const 3)) (unreachable)))
+        "#;
+        let trap = run(wat, WasiCtx::new()).unwrap_err();
+        assert_eq!(trap.downcast::<Trap>().unwrap().kind, TrapKind::Exit(3));
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::{Arc, Mutex};
+
+/// Where a standard stream of the guest is connected to.
+#[derive(Debug, Clone, Default)]
+pub enum Stdio {
+    /// Use the file descriptor of the host process.
+    #[default]
+    Inherit,
+    /// Use an in-memory pipe shared with the embedder.
+    Pipe(Pipe),
+    /// Reads return EOF and writes are discarded.
+    Null,
+}
+
+/// In-memory byte stream.
+/// Cloning a pipe shares the underlying buffer, so the embedder can keep a clone
+/// to pre-fill stdin before the run or inspect stdout/stderr after it.
+#[derive(Debug, Clone, Default)]
+pub struct Pipe {
+    inner: Arc<Mutex<PipeInner>>,
+}
+
+#[derive(Debug, Default)]
+struct PipeInner {
+    data: Vec<u8>,
+    read_pos: usize,
+}
+
+impl Pipe {
+    pub fn new() -> Pipe {
+        Pipe::default()
+    }
+
+    pub fn with_contents(contents: impl Into<Vec<u8>>) -> Pipe {
+        let pipe = Pipe::new();
+        pipe.write(&contents.into());
+        pipe
+    }
+
+    pub fn write(&self, buf: &[u8]) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        inner.data.extend_from_slice(buf);
+        buf.len()
+    }
+
+    /// Read unread bytes into `buf`. Returns 0 when everything has been consumed.
+    pub fn read(&self, buf: &mut [u8]) -> usize {
+        let mut inner = self.inner.lock().unwrap();
+        let start = inner.read_pos;
+        let size = buf.len().min(inner.data.len() - start);
+        buf[..size].copy_from_slice(&inner.data[start..start + size]);
+        inner.read_pos += size;
+        size
+    }
+
+    /// Everything that has been written to the pipe, including already read bytes.
+    pub fn contents(&self) -> Vec<u8> {
+        self.inner.lock().unwrap().data.clone()
+    }
+
+    pub fn contents_string(&self) -> String {
+        String::from_utf8_lossy(&self.contents()).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipe_read_in_chunks() {
+        let pipe = Pipe::with_contents("hello");
+        let mut buf = [0u8; 3];
+        assert_eq!(pipe.read(&mut buf), 3);
+        assert_eq!(&buf, b"hel");
+        assert_eq!(pipe.read(&mut buf), 2);
+        assert_eq!(&buf[..2], b"lo");
+        assert_eq!(pipe.read(&mut buf), 0);
+    }
+
+    #[test]
+    fn test_pipe_clone_shares_buffer() {
+        let pipe = Pipe::new();
+        let captured = pipe.clone();
+        pipe.write(b"out");
+        pipe.write(b"put");
+        assert_eq!(captured.contents_string(), "output");
+    }
+}
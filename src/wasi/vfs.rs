@@ -0,0 +1,102 @@
+use std::io::SeekFrom;
+
+use crate::wasi::errno::Errno;
+
+/// Identifies a file opened through a `Vfs`.
+pub type Handle = u64;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenOptions {
+    pub read: bool,
+    pub write: bool,
+    pub append: bool,
+    pub create: bool,
+    pub exclusive: bool,
+    pub truncate: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileType {
+    RegularFile,
+    Directory,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStat {
+    pub file_type: FileType,
+    pub size: u64,
+}
+
+/// Filesystem backend behind a preopened directory.
+/// Paths are relative to the root of the backend and use `/` as separator.
+pub trait Vfs: Send {
+    fn open(&mut self, path: &str, options: OpenOptions) -> Result<Handle, Errno>;
+    fn read(&mut self, handle: Handle, buf: &mut [u8]) -> Result<usize, Errno>;
+    fn write(&mut self, handle: Handle, buf: &[u8]) -> Result<usize, Errno>;
+    fn seek(&mut self, handle: Handle, pos: SeekFrom) -> Result<u64, Errno>;
+    fn close(&mut self, handle: Handle) -> Result<(), Errno>;
+    fn stat(&mut self, path: &str) -> Result<FileStat, Errno>;
+    fn read_dir(&mut self, path: &str) -> Result<Vec<String>, Errno>;
+    fn create_dir(&mut self, path: &str) -> Result<(), Errno>;
+    fn remove_file(&mut self, path: &str) -> Result<(), Errno>;
+    fn remove_dir(&mut self, path: &str) -> Result<(), Errno>;
+}
+
+/// Resolve `.` and `..` in a guest path and return it as `a/b/c`.
+/// The root itself is the empty string; escaping it is not allowed.
+pub fn normalize_path(path: &str) -> Result<String, Errno> {
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                components.pop().ok_or(Errno::Notcapable)?;
+            }
+            _ => components.push(component),
+        }
+    }
+    Ok(components.join("/"))
+}
+
+/// Join a path relative to a directory that was already normalized.
+pub fn join_path(dir: &str, path: &str) -> Result<String, Errno> {
+    if path.starts_with('/') {
+        return Err(Errno::Notcapable);
+    }
+    normalize_path(&format!("{}/{}", dir, path))
+}
+
+/// Compute the new position of a cursor for `seek`.
+pub fn seek_position(current: u64, size: u64, pos: SeekFrom) -> Result<u64, Errno> {
+    let new_pos = match pos {
+        SeekFrom::Start(offset) => Some(offset),
+        SeekFrom::Current(offset) => current.checked_add_signed(offset),
+        SeekFrom::End(offset) => size.checked_add_signed(offset),
+    };
+    new_pos.ok_or(Errno::Inval)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest(
+        input,
+        expected,
+        case("", Ok("".to_string())),
+        case("/", Ok("".to_string())),
+        case("a/./b", Ok("a/b".to_string())),
+        case("a/b/../c/", Ok("a/c".to_string())),
+        case("a/../..", Err(Errno::Notcapable))
+    )]
+    fn test_normalize_path(input: &str, expected: Result<String, Errno>) {
+        assert_eq!(normalize_path(input), expected);
+    }
+
+    #[test]
+    fn test_join_path_rejects_absolute() {
+        assert_eq!(join_path("a", "/etc/passwd"), Err(Errno::Notcapable));
+        assert_eq!(join_path("a", "../b"), Ok("b".to_string()));
+    }
+}
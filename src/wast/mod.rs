@@ -0,0 +1,3 @@
+pub mod runner;
+pub mod script;
+pub mod spectest;
@@ -0,0 +1,265 @@
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::errors::ExecError;
+use crate::exec::buffer::Buffer;
+use crate::exec::instance::Instance;
+use crate::exec::linker::Linker;
+use crate::exec::module::ModuleNode;
+use crate::exec::store::Store;
+use crate::exec::trap::Trap;
+use crate::exec::validate::validate;
+use crate::exec::value::Value;
+use crate::wast::script::{parse_script, Action, Command, Directive};
+use crate::wast::spectest;
+use crate::wat::module::encode_module;
+use crate::wat::sexpr::SExpr;
+
+/// Passed and failed directives of a script.
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// Number of passed and failed directives, by keyword.
+    pub counts: BTreeMap<&'static str, (u32, u32)>,
+    /// Each failure, prefixed with the position of its directive.
+    pub failures: Vec<String>,
+}
+impl Summary {
+    pub fn passed(&self) -> u32 {
+        self.counts.values().map(|(passed, _)| passed).sum()
+    }
+
+    pub fn failed(&self) -> u32 {
+        self.counts.values().map(|(_, failed)| failed).sum()
+    }
+}
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<20}{:>8}{:>8}", "directive", "passed", "failed")?;
+        for (kind, (passed, failed)) in &self.counts {
+            writeln!(f, "{:<20}{:>8}{:>8}", kind, passed, failed)?;
+        }
+        writeln!(f, "{:<20}{:>8}{:>8}", "total", self.passed(), self.failed())
+    }
+}
+
+/// Runs the directives of scripts against a store shared by all their modules.
+pub struct Runner {
+    store: Store,
+    linker: Linker,
+    /// Most recently instantiated module, the target of actions without a `$id`.
+    current: Option<Instance>,
+    instances: HashMap<String, Instance>,
+}
+impl Runner {
+    /// Runner with the `spectest` module registered.
+    pub fn new() -> Result<Runner> {
+        let mut store = Store::new();
+        let mut linker = Linker::new();
+        linker.register("spectest", spectest::instantiate(&mut store)?);
+        Ok(Runner {
+            store,
+            linker,
+            current: None,
+            instances: HashMap::new(),
+        })
+    }
+
+    pub fn run(&mut self, commands: &[Command]) -> Summary {
+        let mut summary = Summary::default();
+        for command in commands {
+            let kind = command.directive.kind();
+            let counts = summary.counts.entry(kind).or_default();
+            match self.run_directive(&command.directive) {
+                Ok(()) => counts.0 += 1,
+                Err(e) => {
+                    counts.1 += 1;
+                    summary
+                        .failures
+                        .push(format!("{}: {}: {}", command.pos, kind, e));
+                }
+            }
+        }
+        summary
+    }
+
+    fn run_directive(&mut self, directive: &Directive) -> Result<()> {
+        match directive {
+            Directive::Module(module) => {
+                // A module failing to instantiate leaves no current module to act on.
+                self.current = None;
+                let instance = self.instantiate(module)?;
+                self.current = Some(instance);
+                if let Some(id) = module_id(module) {
+                    self.instances.insert(id.to_string(), instance);
+                }
+                Ok(())
+            }
+            Directive::Register { name, module } => {
+                let instance = self.resolve(module.as_deref())?;
+                self.linker.register(name, instance);
+                Ok(())
+            }
+            Directive::Action(action) => self.perform(action).map(|_| ()),
+            Directive::AssertReturn { action, expected } => {
+                let results = self.perform(action)?;
+                let matches = results.len() == expected.len()
+                    && expected.iter().zip(&results).all(|(e, v)| e.matches(v));
+                if matches {
+                    return Ok(());
+                }
+                let expected: Vec<String> = expected.iter().map(|e| e.to_string()).collect();
+                let results: Vec<String> = results.iter().map(|v| v.to_string()).collect();
+                Err(anyhow!(
+                    "expected [{}], got [{}]",
+                    expected.join(" "),
+                    results.join(" ")
+                ))
+            }
+            Directive::AssertTrap { action, message }
+            | Directive::AssertExhaustion { action, message } => {
+                let result = self.perform(action).map(|_| ());
+                expect_trap(result, message)
+            }
+            Directive::AssertTrapModule { module, message } => {
+                let result = self.instantiate(module).map(|_| ());
+                expect_trap(result, message)
+            }
+            // Messages differ between implementations, any rejection at the right stage counts.
+            Directive::AssertInvalid { module, message } => {
+                let node = decode(module)?;
+                match validate(&node).first() {
+                    Some(_) => Ok(()),
+                    None => Err(anyhow!("expected \"{}\", module was valid", message)),
+                }
+            }
+            Directive::AssertMalformed { module, message } => match decode(module) {
+                Ok(_) => Err(anyhow!("expected \"{}\", module was decoded", message)),
+                Err(_) => Ok(()),
+            },
+        }
+    }
+
+    fn instantiate(&mut self, module: &SExpr) -> Result<Instance> {
+        let node = decode(module)?;
+        if let Some(error) = validate(&node).first() {
+            return Err(ExecError::Invalid(error.to_string()).into());
+        }
+        self.linker.instantiate(&mut self.store, node)
+    }
+
+    /// Instance named `$id`, or the current one.
+    fn resolve(&self, id: Option<&str>) -> Result<Instance> {
+        match id {
+            Some(id) => self
+                .instances
+                .get(id)
+                .copied()
+                .ok_or(anyhow!("unknown module ${}", id)),
+            None => self.current.ok_or(anyhow!("no module to act on")),
+        }
+    }
+
+    fn perform(&mut self, action: &Action) -> Result<Vec<Value>> {
+        match action {
+            Action::Invoke { module, name, args } => {
+                let instance = self.resolve(module.as_deref())?;
+                instance.invoke(&mut self.store, name, args)
+            }
+            Action::Get { module, name } => {
+                let instance = self.resolve(module.as_deref())?;
+                let addr = instance
+                    .get_global(&self.store, name)
+                    .ok_or(ExecError::ExportNotFound(name.to_string()))?;
+                Ok(vec![self.store.globals[addr].get()])
+            }
+        }
+    }
+}
+
+/// Encode and decode a module, whether in the text format, quoted or binary.
+fn decode(module: &SExpr) -> Result<ModuleNode> {
+    let bytes = encode_module(module)?;
+    let mut node = ModuleNode::new();
+    node.load(&mut Buffer::new(&bytes))?;
+    Ok(node)
+}
+
+/// `$id` of a `(module $id ...)`.
+fn module_id(module: &SExpr) -> Option<&str> {
+    module.list()?.get(1)?.atom()?.strip_prefix('$')
+}
+
+/// Check that `result` is a trap whose message starts with `message`,
+/// spec messages being shorter than ours, e.g. `unreachable` for `unreachable executed`.
+fn expect_trap(result: Result<()>, message: &str) -> Result<()> {
+    let e = match result {
+        Ok(()) => return Err(anyhow!("expected trap \"{}\", returned", message)),
+        Err(e) => e,
+    };
+    match e.downcast_ref::<Trap>() {
+        Some(trap) if trap.kind.to_string().starts_with(message) => Ok(()),
+        Some(trap) => Err(anyhow!(
+            "expected trap \"{}\", got \"{}\"",
+            message,
+            trap.kind
+        )),
+        None => Err(e),
+    }
+}
+
+/// Parse and run a whole script.
+pub fn run_script(src: &str) -> Result<Summary> {
+    let commands = parse_script(src)?;
+    Ok(Runner::new()?.run(&commands))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_run_script() {
+        let src = fs::read_to_string("examples/spec.wast").unwrap();
+        let summary = run_script(&src).unwrap();
+        assert_eq!(summary.failures, Vec::<String>::new());
+        assert_eq!(summary.counts["assert_return"], (6, 0));
+        assert_eq!(summary.counts["assert_trap"], (2, 0));
+        assert_eq!(summary.counts["assert_exhaustion"], (1, 0));
+        assert_eq!(summary.counts["assert_malformed"], (2, 0));
+        assert_eq!(summary.counts["assert_invalid"], (2, 0));
+        assert_eq!(summary.counts["register"], (1, 0));
+    }
+
+    #[test]
+    fn test_failures() {
+        let src = r#"
+            (module (func (export "one") (result i32) (i32.const 1)))
+            (assert_return (invoke "one") (i32.const 2))
+            (assert_trap (invoke "one") "unreachable")
+            (assert_malformed (module quote "(func)") "unexpected token")
+            (invoke "missing")
+            (assert_invalid (module (func unreachable) (start 0)) "type mismatch")
+            (assert_malformed (module (import "nope" "f" (func))) "unknown import")
+            (assert_invalid (module (func (i32.const))) "type mismatch")
+        "#;
+        let summary = run_script(src).unwrap();
+        assert_eq!(summary.passed(), 1);
+        assert_eq!(summary.failed(), 7);
+        assert_eq!(
+            summary.failures,
+            [
+                "3:13: assert_return: expected [2:i32], got [1:i32]".to_string(),
+                "4:13: assert_trap: expected trap \"unreachable\", returned".to_string(),
+                "5:13: assert_malformed: expected \"unexpected token\", module was decoded"
+                    .to_string(),
+                "6:13: invoke: Export not found: missing".to_string(),
+                "7:13: assert_invalid: expected \"type mismatch\", module was valid".to_string(),
+                "8:13: assert_malformed: expected \"unknown import\", module was decoded"
+                    .to_string(),
+                "9:13: assert_invalid: 9:43: expected a keyword or a number".to_string(),
+            ]
+        );
+    }
+}
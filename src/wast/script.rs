@@ -0,0 +1,305 @@
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+use crate::exec::type_section::ValType;
+use crate::exec::value::Value;
+use crate::wat::lexer::Pos;
+use crate::wat::number::{parse_f32, parse_f64, parse_i32, parse_i64, parse_u32};
+use crate::wat::sexpr::{Cursor, SExpr};
+
+/// https://github.com/WebAssembly/spec/tree/main/interpreter#scripts
+#[derive(Debug, Clone)]
+pub enum Directive {
+    /// `(module ...)` in any of its forms, kept as is until it is run.
+    Module(SExpr),
+    Register {
+        name: String,
+        module: Option<String>,
+    },
+    Action(Action),
+    AssertReturn {
+        action: Action,
+        expected: Vec<Expected>,
+    },
+    AssertTrap {
+        action: Action,
+        message: String,
+    },
+    /// `assert_trap` on the instantiation of a module.
+    AssertTrapModule {
+        module: SExpr,
+        message: String,
+    },
+    AssertExhaustion {
+        action: Action,
+        message: String,
+    },
+    AssertInvalid {
+        module: SExpr,
+        message: String,
+    },
+    AssertMalformed {
+        module: SExpr,
+        message: String,
+    },
+}
+impl Directive {
+    /// Keyword of the directive, under which it is counted in the summary.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Directive::Module(_) => "module",
+            Directive::Register { .. } => "register",
+            Directive::Action(Action::Invoke { .. }) => "invoke",
+            Directive::Action(Action::Get { .. }) => "get",
+            Directive::AssertReturn { .. } => "assert_return",
+            Directive::AssertTrap { .. } | Directive::AssertTrapModule { .. } => "assert_trap",
+            Directive::AssertExhaustion { .. } => "assert_exhaustion",
+            Directive::AssertInvalid { .. } => "assert_invalid",
+            Directive::AssertMalformed { .. } => "assert_malformed",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Action {
+    Invoke {
+        module: Option<String>,
+        name: String,
+        args: Vec<Value>,
+    },
+    Get {
+        module: Option<String>,
+        name: String,
+    },
+}
+
+/// Result of an action an `assert_return` expects.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expected {
+    Value(Value),
+    /// `nan:canonical` of type f32 or f64.
+    CanonicalNan(ValType),
+    /// `nan:arithmetic`, any quiet NaN.
+    ArithmeticNan(ValType),
+    /// `(ref.func)` or `(ref.extern)`, any non-null reference.
+    NonNullRef(ValType),
+}
+impl Expected {
+    pub fn matches(&self, value: &Value) -> bool {
+        match (self, value) {
+            // Floats are compared by bits so that NaN payloads and signed zeros count.
+            (Expected::Value(Value::F32(e)), Value::F32(v)) => e.to_bits() == v.to_bits(),
+            (Expected::Value(Value::F64(e)), Value::F64(v)) => e.to_bits() == v.to_bits(),
+            (Expected::Value(expected), value) => expected == value,
+            (Expected::CanonicalNan(_), Value::F32(v)) => v.to_bits() & 0x7fff_ffff == 0x7fc0_0000,
+            (Expected::CanonicalNan(_), Value::F64(v)) => {
+                v.to_bits() & 0x7fff_ffff_ffff_ffff == 0x7ff8_0000_0000_0000
+            }
+            (Expected::ArithmeticNan(_), Value::F32(v)) => v.to_bits() & 0x7fc0_0000 == 0x7fc0_0000,
+            (Expected::ArithmeticNan(_), Value::F64(v)) => {
+                v.to_bits() & 0x7ff8_0000_0000_0000 == 0x7ff8_0000_0000_0000
+            }
+            (Expected::NonNullRef(_), Value::FuncRef(r)) => r.is_some(),
+            (Expected::NonNullRef(_), Value::ExternRef(r)) => r.is_some(),
+            _ => false,
+        }
+    }
+}
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Value(value) => value.fmt(f),
+            Expected::CanonicalNan(val_type) => write!(f, "nan:canonical:{}", val_type),
+            Expected::ArithmeticNan(val_type) => write!(f, "nan:arithmetic:{}", val_type),
+            Expected::NonNullRef(val_type) => write!(f, "non-null {}", val_type),
+        }
+    }
+}
+
+/// A directive with its position in the script.
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub pos: Pos,
+    pub directive: Directive,
+}
+
+/// Parse every directive of a script.
+pub fn parse_script(src: &str) -> Result<Vec<Command>> {
+    let exprs = SExpr::parse_all(src)?;
+    exprs
+        .iter()
+        .map(|expr| {
+            Ok(Command {
+                pos: expr.pos(),
+                directive: parse_directive(expr)?,
+            })
+        })
+        .collect()
+}
+
+fn parse_directive(expr: &SExpr) -> Result<Directive> {
+    match expr.head() {
+        Some("module") => return Ok(Directive::Module(expr.clone())),
+        Some("invoke" | "get") => return Ok(Directive::Action(parse_action(expr)?)),
+        _ => {}
+    }
+    let mut cursor = Cursor::of_list(expr)?;
+    let directive = match expr.head() {
+        Some("register") => {
+            let name = cursor.expect_name()?;
+            let module = cursor.id().map(str::to_string);
+            Directive::Register { name, module }
+        }
+        Some("assert_return") => {
+            let action = parse_action(cursor.next().ok_or(cursor.error("expected an action"))?)?;
+            let mut expected = Vec::new();
+            for result in cursor.by_ref() {
+                expected.push(parse_expected(result)?);
+            }
+            Directive::AssertReturn { action, expected }
+        }
+        Some("assert_trap") => {
+            let target = cursor.next().ok_or(cursor.error("expected an action"))?;
+            let message = cursor.expect_name()?;
+            if target.head() == Some("module") {
+                Directive::AssertTrapModule {
+                    module: target.clone(),
+                    message,
+                }
+            } else {
+                let action = parse_action(target)?;
+                Directive::AssertTrap { action, message }
+            }
+        }
+        Some("assert_exhaustion") => {
+            let action = parse_action(cursor.next().ok_or(cursor.error("expected an action"))?)?;
+            let message = cursor.expect_name()?;
+            Directive::AssertExhaustion { action, message }
+        }
+        Some(kind @ ("assert_invalid" | "assert_malformed")) => {
+            let module = cursor
+                .list("module")
+                .ok_or(cursor.error("expected a module"))?
+                .clone();
+            let message = cursor.expect_name()?;
+            if kind == "assert_invalid" {
+                Directive::AssertInvalid { module, message }
+            } else {
+                Directive::AssertMalformed { module, message }
+            }
+        }
+        _ => return Err(anyhow!("{}: unknown directive", expr.pos())),
+    };
+    cursor.expect_end()?;
+    Ok(directive)
+}
+
+fn parse_action(expr: &SExpr) -> Result<Action> {
+    let mut cursor = Cursor::of_list(expr)?;
+    let module = cursor.id().map(str::to_string);
+    let name = cursor.expect_name()?;
+    let action = match expr.head() {
+        Some("invoke") => {
+            let mut args = Vec::new();
+            for arg in cursor.by_ref() {
+                match parse_expected(arg)? {
+                    Expected::Value(value) => args.push(value),
+                    _ => return Err(anyhow!("{}: expected a constant", arg.pos())),
+                }
+            }
+            Action::Invoke { module, name, args }
+        }
+        Some("get") => Action::Get { module, name },
+        _ => return Err(anyhow!("{}: expected an action", expr.pos())),
+    };
+    cursor.expect_end()?;
+    Ok(action)
+}
+
+/// `(t.const c)` and reference results, arguments being the ones without patterns.
+fn parse_expected(expr: &SExpr) -> Result<Expected> {
+    let mut cursor = Cursor::of_list(expr)?;
+    let pos_cursor = cursor.clone();
+    let invalid = || pos_cursor.error("invalid constant");
+    let expected = match expr.head() {
+        Some("i32.const") => Expected::Value(Value::I32(
+            parse_i32(cursor.expect_atom()?).ok_or_else(invalid)?,
+        )),
+        Some("i64.const") => Expected::Value(Value::I64(
+            parse_i64(cursor.expect_atom()?).ok_or_else(invalid)?,
+        )),
+        Some("f32.const") => match cursor.expect_atom()? {
+            "nan:canonical" => Expected::CanonicalNan(ValType::F32),
+            "nan:arithmetic" => Expected::ArithmeticNan(ValType::F32),
+            atom => Expected::Value(Value::F32(parse_f32(atom).ok_or_else(invalid)?)),
+        },
+        Some("f64.const") => match cursor.expect_atom()? {
+            "nan:canonical" => Expected::CanonicalNan(ValType::F64),
+            "nan:arithmetic" => Expected::ArithmeticNan(ValType::F64),
+            atom => Expected::Value(Value::F64(parse_f64(atom).ok_or_else(invalid)?)),
+        },
+        Some("ref.null") => match cursor.expect_atom()? {
+            "func" => Expected::Value(Value::FuncRef(None)),
+            "extern" => Expected::Value(Value::ExternRef(None)),
+            _ => return Err(pos_cursor.error("unknown heap type")),
+        },
+        Some("ref.extern") if cursor.is_empty() => Expected::NonNullRef(ValType::EXTERN_REF),
+        Some("ref.extern") => Expected::Value(Value::ExternRef(Some(
+            parse_u32(cursor.expect_atom()?).ok_or_else(invalid)?,
+        ))),
+        Some("ref.func") => Expected::NonNullRef(ValType::FUNC_REF),
+        _ => return Err(anyhow!("{}: unknown constant", expr.pos())),
+    };
+    cursor.expect_end()?;
+    Ok(expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let commands = parse_script(
+            r#"
+            (module $m (func (export "f") (result i32) (i32.const 1)))
+            (register "m" $m)
+            (assert_return (invoke $m "f" (i32.const -1) (f32.const 1.5)) (f64.const nan:canonical))
+            (assert_trap (invoke "f") "unreachable")
+            (assert_malformed (module quote "(func") "unexpected end")
+            "#,
+        )
+        .unwrap();
+        let kinds: Vec<_> = commands.iter().map(|c| c.directive.kind()).collect();
+        assert_eq!(
+            kinds,
+            [
+                "module",
+                "register",
+                "assert_return",
+                "assert_trap",
+                "assert_malformed"
+            ]
+        );
+        assert_eq!(commands[1].pos, Pos { line: 3, col: 13 });
+        let Directive::AssertReturn { action, expected } = &commands[2].directive else {
+            panic!("not an assert_return");
+        };
+        let Action::Invoke { module, name, args } = action else {
+            panic!("not an invoke");
+        };
+        assert_eq!((module.as_deref(), name.as_str()), (Some("m"), "f"));
+        assert_eq!(args, &[Value::I32(-1), Value::F32(1.5)]);
+        assert_eq!(expected, &[Expected::CanonicalNan(ValType::F64)]);
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(Expected::Value(Value::F32(f32::NAN)).matches(&Value::F32(f32::NAN)));
+        assert!(!Expected::Value(Value::F64(0.0)).matches(&Value::F64(-0.0)));
+        assert!(Expected::CanonicalNan(ValType::F32).matches(&Value::F32(-f32::NAN)));
+        let signaling = f32::from_bits(0x7f80_0001);
+        assert!(!Expected::ArithmeticNan(ValType::F32).matches(&Value::F32(signaling)));
+        assert!(Expected::NonNullRef(ValType::FUNC_REF).matches(&Value::FuncRef(Some(0))));
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::Result;
+use std::sync::Arc;
+
+use crate::exec::instance::{HostExtern, Instance};
+use crate::exec::store::{GlobalInst, HostFunc, MemInst, Store, TableInst};
+use crate::exec::type_section::{
+    FunctionTypeNode, GlobalTypeNode, LimitsNode, MemoryTypeNode, RefType, TableTypeNode, ValType,
+    FUNC_REF,
+};
+use crate::exec::value::Value;
+
+/// Instantiate the `spectest` module the spec test scripts import from.
+/// https://github.com/WebAssembly/spec/tree/main/interpreter#spectest-host-module
+pub fn instantiate(store: &mut Store) -> Result<Instance> {
+    let mut exports = Vec::new();
+    let funcs: [(&str, &[ValType]); 7] = [
+        ("print", &[]),
+        ("print_i32", &[ValType::I32]),
+        ("print_i64", &[ValType::I64]),
+        ("print_f32", &[ValType::F32]),
+        ("print_f64", &[ValType::F64]),
+        ("print_i32_f32", &[ValType::I32, ValType::F32]),
+        ("print_f64_f64", &[ValType::F64, ValType::F64]),
+    ];
+    for (name, params) in funcs {
+        let mut func_type = FunctionTypeNode::new();
+        func_type.param_type.val_types = params.to_vec();
+        let print: HostFunc = Arc::new(|_, args| {
+            let args: Vec<String> = args.iter().map(|v| v.to_string()).collect();
+            println!("{}", args.join(" "));
+            Ok(Vec::new())
+        });
+        exports.push((name.to_string(), HostExtern::Func(func_type, print)));
+    }
+
+    let globals = [
+        ("global_i32", Value::I32(666)),
+        ("global_i64", Value::I64(666)),
+        ("global_f32", Value::F32(666.6)),
+        ("global_f64", Value::F64(666.6)),
+    ];
+    for (name, value) in globals {
+        let global_type = GlobalTypeNode {
+            val_type: value.val_type(),
+            mutable: false,
+        };
+        let global = GlobalInst::new(global_type, value);
+        exports.push((name.to_string(), HostExtern::Global(global)));
+    }
+
+    let table_type = TableTypeNode {
+        ref_type: RefType::FuncRef(FUNC_REF),
+        limits: LimitsNode {
+            min: 10,
+            max: Some(20),
+        },
+    };
+    let table = TableInst::new(&table_type, None)?;
+    exports.push(("table".to_string(), HostExtern::Table(table)));
+
+    let mem_type = MemoryTypeNode {
+        limits: LimitsNode {
+            min: 1,
+            max: Some(2),
+        },
+    };
+    let memory = MemInst::new(&mem_type, None)?;
+    exports.push(("memory".to_string(), HostExtern::Memory(memory)));
+
+    Ok(Instance::host(store, exports))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_instantiate() {
+        let mut store = Store::new();
+        let spectest = instantiate(&mut store).unwrap();
+        let results = spectest.invoke(
+            &mut store,
+            "print_i32_f32",
+            &[Value::I32(1), Value::F32(2.0)],
+        );
+        assert_eq!(results.unwrap(), vec![]);
+        let global = spectest.get_global(&store, "global_i32").unwrap();
+        assert_eq!(store.globals[global].get(), Value::I32(666));
+        let table = spectest.get_table(&store, "table").unwrap();
+        assert_eq!(store.tables[table].size(), 10);
+        let memory = spectest.get_memory(&store, "memory").unwrap();
+        assert_eq!(store.memories[memory].size(), 1);
+    }
+}
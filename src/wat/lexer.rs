@@ -0,0 +1,227 @@
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+/// Position in the source text, both 1-based.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Pos {
+    pub line: u32,
+    pub col: u32,
+}
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.col)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    LParen,
+    RParen,
+    /// Keyword, number or `$id`.
+    Atom(String),
+    /// Strings may hold arbitrary bytes through `\hh` escapes.
+    String(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub pos: Pos,
+}
+
+/// https://webassembly.github.io/spec/core/text/lexical.html
+pub struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    pos: Pos,
+}
+impl<'a> Lexer<'a> {
+    pub fn new(src: &'a str) -> Lexer<'a> {
+        Lexer {
+            chars: src.chars().peekable(),
+            pos: Pos { line: 1, col: 1 },
+        }
+    }
+
+    pub fn tokenize(mut self) -> Result<Vec<Token>> {
+        let mut tokens = Vec::new();
+        while let Some(token) = self.next_token()? {
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.pos.line += 1;
+            self.pos.col = 1;
+        } else {
+            self.pos.col += 1;
+        }
+        Some(c)
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>> {
+        loop {
+            let pos = self.pos;
+            let Some(c) = self.bump() else {
+                return Ok(None);
+            };
+            let kind = match c {
+                c if c.is_whitespace() => continue,
+                ';' if self.chars.peek() == Some(&';') => {
+                    while self.chars.peek().is_some_and(|c| *c != '\n') {
+                        self.bump();
+                    }
+                    continue;
+                }
+                '(' if self.chars.peek() == Some(&';') => {
+                    self.bump();
+                    self.block_comment(pos)?;
+                    continue;
+                }
+                '(' => TokenKind::LParen,
+                ')' => TokenKind::RParen,
+                '"' => TokenKind::String(self.string(pos)?),
+                c => {
+                    let mut atom = c.to_string();
+                    while let Some(&c) = self.chars.peek() {
+                        if c.is_whitespace() || matches!(c, '(' | ')' | '"' | ';') {
+                            break;
+                        }
+                        atom.push(c);
+                        self.bump();
+                    }
+                    TokenKind::Atom(atom)
+                }
+            };
+            return Ok(Some(Token { kind, pos }));
+        }
+    }
+
+    /// Skip a possibly nested `(; ... ;)` comment whose `(;` was consumed.
+    fn block_comment(&mut self, start: Pos) -> Result<()> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.bump() {
+                Some('(') if self.chars.peek() == Some(&';') => {
+                    self.bump();
+                    depth += 1;
+                }
+                Some(';') if self.chars.peek() == Some(&')') => {
+                    self.bump();
+                    depth -= 1;
+                }
+                Some(_) => {}
+                None => return Err(anyhow!("{}: unterminated block comment", start)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the rest of a string whose opening quote was consumed.
+    fn string(&mut self, start: Pos) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        loop {
+            let c = self
+                .bump()
+                .ok_or(anyhow!("{}: unterminated string", start))?;
+            match c {
+                '"' => return Ok(bytes),
+                '\\' => {
+                    let pos = self.pos;
+                    let escape = self
+                        .bump()
+                        .ok_or(anyhow!("{}: unterminated string", start))?;
+                    match escape {
+                        'n' => bytes.push(b'\n'),
+                        't' => bytes.push(b'\t'),
+                        'r' => bytes.push(b'\r'),
+                        '"' => bytes.push(b'"'),
+                        '\'' => bytes.push(b'\''),
+                        '\\' => bytes.push(b'\\'),
+                        'u' => {
+                            let c = self.unicode_escape(pos)?;
+                            let mut utf8 = [0; 4];
+                            bytes.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+                        }
+                        high => {
+                            let low = self.bump().unwrap_or(' ');
+                            let byte = high
+                                .to_digit(16)
+                                .zip(low.to_digit(16))
+                                .map(|(high, low)| (high * 16 + low) as u8)
+                                .ok_or(anyhow!("{}: invalid escape", pos))?;
+                            bytes.push(byte);
+                        }
+                    }
+                }
+                c => {
+                    let mut utf8 = [0; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+                }
+            }
+        }
+    }
+
+    /// `\u{hex}`, the `u` being consumed.
+    fn unicode_escape(&mut self, pos: Pos) -> Result<char> {
+        let invalid = || anyhow!("{}: invalid unicode escape", pos);
+        if self.bump() != Some('{') {
+            return Err(invalid());
+        }
+        let mut hex = String::new();
+        loop {
+            match self.bump() {
+                Some('}') => break,
+                Some(c) if c.is_ascii_hexdigit() || c == '_' => hex.push(c),
+                _ => return Err(invalid()),
+            }
+        }
+        let code = u32::from_str_radix(&hex.replace('_', ""), 16).map_err(|_| invalid())?;
+        char::from_u32(code).ok_or_else(invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(src: &str) -> Vec<TokenKind> {
+        let tokens = Lexer::new(src).tokenize().unwrap();
+        tokens.into_iter().map(|t| t.kind).collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let atom = |s: &str| TokenKind::Atom(s.to_string());
+        assert_eq!(
+            kinds("(func $f ;; comment\n (; nested (; block ;) ;) i32.const -1)"),
+            vec![
+                TokenKind::LParen,
+                atom("func"),
+                atom("$f"),
+                atom("i32.const"),
+                atom("-1"),
+                TokenKind::RParen,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        assert_eq!(
+            kinds(r#""a\n\"\00\ff\u{e9}""#),
+            vec![TokenKind::String(vec![
+                b'a', b'\n', b'"', 0x00, 0xff, 0xc3, 0xa9
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_position() {
+        let tokens = Lexer::new("(module\n  (func))").tokenize().unwrap();
+        assert_eq!(tokens[3].pos, Pos { line: 2, col: 4 });
+        assert!(Lexer::new("(module \"x").tokenize().is_err());
+    }
+}
@@ -0,0 +1,5 @@
+pub mod lexer;
+pub mod module;
+pub mod number;
+pub mod printer;
+pub mod sexpr;
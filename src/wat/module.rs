@@ -0,0 +1,1251 @@
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::exec::code_section::Op;
+use crate::exec::export_section::ExportKind;
+use crate::exec::type_section::{EXTERN_REF, F32, F64, FUNC_REF, I32, I64};
+use crate::exec::writer::Writer;
+use crate::wat::number::{parse_f32, parse_f64, parse_i32, parse_i64, parse_u32};
+use crate::wat::sexpr::{Cursor, SExpr};
+
+const MAGIC: [u8; 4] = [0x00, 0x61, 0x73, 0x6d];
+const VERSION: [u8; 4] = [0x01, 0x00, 0x00, 0x00];
+const EMPTY_BLOCK_TYPE: u8 = 0x40;
+const FUNC_TYPE: u8 = 0x60;
+
+/// Translate a module in the text format to the binary format.
+/// `src` is either a `(module ...)` or the fields of a module.
+pub fn parse_str(src: &str) -> Result<Vec<u8>> {
+    let exprs = SExpr::parse_all(src)?;
+    match exprs.as_slice() {
+        [module] if module.head() == Some("module") => encode_module(module),
+        fields => ModuleEncoder::new(None).encode(fields),
+    }
+}
+
+/// Translate a `(module ...)` S-expression to the binary format,
+/// including the `(module binary ...)` and `(module quote ...)` forms of scripts.
+pub fn encode_module(module: &SExpr) -> Result<Vec<u8>> {
+    let mut cursor = Cursor::of_list(module)?;
+    let id = cursor.id();
+    if cursor.keyword("binary") {
+        let mut bytes = Vec::new();
+        while !cursor.is_empty() {
+            bytes.extend_from_slice(cursor.expect_string()?);
+        }
+        return Ok(bytes);
+    }
+    if cursor.keyword("quote") {
+        let mut text = Vec::new();
+        while !cursor.is_empty() {
+            text.extend_from_slice(cursor.expect_string()?);
+            text.push(b' ');
+        }
+        let text = String::from_utf8(text).map_err(|_| cursor.error("malformed UTF-8 encoding"))?;
+        return parse_str(&text);
+    }
+    let fields = module.list().unwrap_or(&[]);
+    let skip = if id.is_some() { 2 } else { 1 };
+    ModuleEncoder::new(id).encode(&fields[skip..])
+}
+
+/// Index space of one kind of entity, with the `$id`s naming its entries.
+#[derive(Debug, Default)]
+struct Space {
+    len: u32,
+    ids: HashMap<String, u32>,
+}
+impl Space {
+    fn define(&mut self, id: Option<&str>, cursor: &Cursor, kind: &str) -> Result<u32> {
+        let idx = self.len;
+        if let Some(id) = id {
+            if self.ids.insert(id.to_string(), idx).is_some() {
+                return Err(cursor.error(&format!("duplicate {} ${}", kind, id)));
+            }
+        }
+        self.len += 1;
+        Ok(idx)
+    }
+
+    /// Consume an index, given as a number or a `$id`.
+    fn index(&self, cursor: &mut Cursor, kind: &str) -> Result<u32> {
+        let pos = cursor.pos();
+        let atom = cursor.expect_atom()?;
+        let idx = match atom.strip_prefix('$') {
+            Some(id) => self.ids.get(id).copied(),
+            None => parse_u32(atom),
+        };
+        idx.ok_or(anyhow!("{}: unknown {} {}", pos, kind, atom))
+    }
+
+    /// Consume an index if the next item is one.
+    fn optional_index(&self, cursor: &mut Cursor, kind: &str) -> Result<Option<u32>> {
+        let is_index = cursor
+            .peek()
+            .and_then(|item| item.atom())
+            .is_some_and(|atom| atom.starts_with('$') || parse_u32(atom).is_some());
+        if !is_index {
+            return Ok(None);
+        }
+        self.index(cursor, kind).map(Some)
+    }
+
+    /// `$id`s by index, for the name section.
+    fn names(&self) -> BTreeMap<u32, String> {
+        self.ids
+            .iter()
+            .map(|(id, idx)| (*idx, id.clone()))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct FuncType {
+    params: Vec<u8>,
+    results: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct FuncDef<'a> {
+    type_idx: u32,
+    param_ids: Vec<Option<String>>,
+    /// Locals and instructions.
+    body: Cursor<'a>,
+}
+
+#[derive(Debug)]
+enum ExportTarget<'a> {
+    Index(u32),
+    /// Index to resolve once every entity is defined.
+    Ref(Cursor<'a>),
+}
+
+#[derive(Debug)]
+struct Export<'a> {
+    name: String,
+    kind: ExportKind,
+    target: ExportTarget<'a>,
+}
+
+/// Collects the fields of a module in a first pass, assigning indices to every `$id`,
+/// then encodes the sections once every index can be resolved.
+struct ModuleEncoder<'a> {
+    id: Option<&'a str>,
+    types: Vec<FuncType>,
+    type_space: Space,
+    funcs: Space,
+    tables: Space,
+    memories: Space,
+    globals: Space,
+    /// Encoded import entries.
+    imports: Vec<Vec<u8>>,
+    func_defs: Vec<FuncDef<'a>>,
+    /// Encoded table and memory types.
+    table_defs: Vec<Vec<u8>>,
+    memory_defs: Vec<Vec<u8>>,
+    /// Encoded global type and initializer.
+    global_defs: Vec<(Vec<u8>, Cursor<'a>)>,
+    exports: Vec<Export<'a>>,
+    /// Reference to the start function.
+    start: Option<Cursor<'a>>,
+    local_names: BTreeMap<u32, BTreeMap<u32, String>>,
+}
+impl<'a> ModuleEncoder<'a> {
+    fn new(id: Option<&'a str>) -> ModuleEncoder<'a> {
+        ModuleEncoder {
+            id,
+            types: Vec::new(),
+            type_space: Space::default(),
+            funcs: Space::default(),
+            tables: Space::default(),
+            memories: Space::default(),
+            globals: Space::default(),
+            imports: Vec::new(),
+            func_defs: Vec::new(),
+            table_defs: Vec::new(),
+            memory_defs: Vec::new(),
+            global_defs: Vec::new(),
+            exports: Vec::new(),
+            start: None,
+            local_names: BTreeMap::new(),
+        }
+    }
+
+    fn encode(mut self, fields: &'a [SExpr]) -> Result<Vec<u8>> {
+        // Explicit types come first in the type index space, whatever their position.
+        for field in fields.iter().filter(|f| f.head() == Some("type")) {
+            self.type_field(field)?;
+        }
+        for field in fields {
+            let mut cursor = Cursor::of_list(field)
+                .map_err(|_| anyhow!("{}: expected a module field", field.pos()))?;
+            match field.head() {
+                Some("type") => {}
+                Some("import") => self.import_field(&mut cursor)?,
+                Some("func") => self.func_field(&mut cursor)?,
+                Some("table") => self.table_field(&mut cursor)?,
+                Some("memory") => self.memory_field(&mut cursor)?,
+                Some("global") => self.global_field(&mut cursor)?,
+                Some("export") => self.export_field(&mut cursor)?,
+                Some("start") => self.start_field(cursor, field)?,
+                Some(head @ ("elem" | "data")) => {
+                    return Err(anyhow!("{}: {} is not supported", field.pos(), head))
+                }
+                _ => return Err(anyhow!("{}: unknown module field", field.pos())),
+            }
+        }
+        self.write()
+    }
+
+    fn type_field(&mut self, field: &'a SExpr) -> Result<()> {
+        let mut cursor = Cursor::of_list(field)?;
+        let id = cursor.id();
+        let func = cursor
+            .list("func")
+            .ok_or(cursor.error("expected a function type"))?;
+        cursor.expect_end()?;
+        let mut func_cursor = Cursor::of_list(func)?;
+        let (func_type, _) = func_signature(&mut func_cursor)?;
+        func_cursor.expect_end()?;
+        self.type_space.define(id, &cursor, "type")?;
+        self.types.push(func_type);
+        Ok(())
+    }
+
+    /// Index of an existing identical type, or of a new one appended to the type section.
+    fn intern(&mut self, func_type: FuncType) -> u32 {
+        match self.types.iter().position(|t| *t == func_type) {
+            Some(idx) => idx as u32,
+            None => {
+                self.types.push(func_type);
+                self.type_space.len += 1;
+                self.type_space.len - 1
+            }
+        }
+    }
+
+    /// `(type $t)?`, params and results of a function.
+    fn type_use(&mut self, cursor: &mut Cursor<'a>) -> Result<(u32, Vec<Option<String>>)> {
+        let explicit = match cursor.list("type") {
+            Some(type_ref) => {
+                let mut type_cursor = Cursor::of_list(type_ref)?;
+                let idx = self.type_space.index(&mut type_cursor, "type")?;
+                type_cursor.expect_end()?;
+                Some(idx)
+            }
+            None => None,
+        };
+        let pos_cursor = cursor.clone();
+        let (func_type, param_ids) = func_signature(cursor)?;
+        match explicit {
+            Some(idx) => {
+                let declared = self
+                    .types
+                    .get(idx as usize)
+                    .ok_or(pos_cursor.error("unknown type"))?;
+                let inline = !func_type.params.is_empty() || !func_type.results.is_empty();
+                if inline && *declared != func_type {
+                    return Err(pos_cursor.error("inline function type does not match"));
+                }
+                let param_ids = if inline {
+                    param_ids
+                } else {
+                    vec![None; declared.params.len()]
+                };
+                Ok((idx, param_ids))
+            }
+            None => Ok((self.intern(func_type), param_ids)),
+        }
+    }
+
+    /// `(export "name")*` of a definition.
+    fn inline_exports(
+        &mut self,
+        cursor: &mut Cursor<'a>,
+        kind: ExportKind,
+        idx: u32,
+    ) -> Result<()> {
+        while let Some(export) = cursor.list("export") {
+            let mut export_cursor = Cursor::of_list(export)?;
+            let name = export_cursor.expect_name()?;
+            export_cursor.expect_end()?;
+            self.exports.push(Export {
+                name,
+                kind,
+                target: ExportTarget::Index(idx),
+            });
+        }
+        Ok(())
+    }
+
+    /// `(import "module" "name")` of a definition.
+    fn inline_import(&mut self, cursor: &mut Cursor<'a>) -> Result<Option<(String, String)>> {
+        let Some(import) = cursor.list("import") else {
+            return Ok(None);
+        };
+        let mut import_cursor = Cursor::of_list(import)?;
+        let module = import_cursor.expect_name()?;
+        let name = import_cursor.expect_name()?;
+        import_cursor.expect_end()?;
+        Ok(Some((module, name)))
+    }
+
+    fn add_import(
+        &mut self,
+        cursor: &Cursor,
+        module: &str,
+        name: &str,
+        kind: ExportKind,
+        desc: &[u8],
+    ) -> Result<()> {
+        let defined = match kind {
+            ExportKind::Func => !self.func_defs.is_empty(),
+            ExportKind::Table => !self.table_defs.is_empty(),
+            ExportKind::Memory => !self.memory_defs.is_empty(),
+            ExportKind::Global => !self.global_defs.is_empty(),
+        };
+        if defined {
+            return Err(cursor.error("import after definition"));
+        }
+        let mut writer = Writer::new();
+        writer.write_name(module);
+        writer.write_name(name);
+        writer.write_byte(kind as u8);
+        writer.write_bytes(desc);
+        self.imports.push(writer.into_bytes());
+        Ok(())
+    }
+
+    fn import_field(&mut self, cursor: &mut Cursor<'a>) -> Result<()> {
+        let module = cursor.expect_name()?;
+        let name = cursor.expect_name()?;
+        let desc = cursor
+            .next()
+            .ok_or(cursor.error("expected an import description"))?;
+        cursor.expect_end()?;
+        let mut desc_cursor = Cursor::of_list(desc)?;
+        let id = desc_cursor.id();
+        let import = Some((module, name));
+        match desc.head() {
+            Some("func") => self.func(id, import, &mut desc_cursor)?,
+            Some("table") => self.table(id, import, &mut desc_cursor)?,
+            Some("memory") => self.memory(id, import, &mut desc_cursor)?,
+            Some("global") => self.global(id, import, &mut desc_cursor)?,
+            _ => return Err(anyhow!("{}: unknown import kind", desc.pos())),
+        }
+        desc_cursor.expect_end()
+    }
+
+    fn func_field(&mut self, cursor: &mut Cursor<'a>) -> Result<()> {
+        let id = cursor.id();
+        let idx = self.funcs.len;
+        self.inline_exports(cursor, ExportKind::Func, idx)?;
+        let import = self.inline_import(cursor)?;
+        self.func(id, import, cursor)
+    }
+
+    fn func(
+        &mut self,
+        id: Option<&str>,
+        import: Option<(String, String)>,
+        cursor: &mut Cursor<'a>,
+    ) -> Result<()> {
+        let idx = self.funcs.define(id, cursor, "function")?;
+        let (type_idx, param_ids) = self.type_use(cursor)?;
+        if param_ids.iter().any(|id| id.is_some()) {
+            let names = param_ids.iter().enumerate();
+            let names = names.filter_map(|(i, id)| Some((i as u32, id.clone()?)));
+            self.local_names.insert(idx, names.collect());
+        }
+        match import {
+            Some((module, name)) => {
+                cursor.expect_end()?;
+                let mut desc = Writer::new();
+                desc.write_u32(type_idx);
+                self.add_import(cursor, &module, &name, ExportKind::Func, &desc.into_bytes())
+            }
+            None => {
+                let body = std::mem::replace(cursor, Cursor::new(&[], cursor.pos()));
+                self.func_defs.push(FuncDef {
+                    type_idx,
+                    param_ids,
+                    body,
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn table_field(&mut self, cursor: &mut Cursor<'a>) -> Result<()> {
+        let id = cursor.id();
+        let idx = self.tables.len;
+        self.inline_exports(cursor, ExportKind::Table, idx)?;
+        let import = self.inline_import(cursor)?;
+        self.table(id, import, cursor)
+    }
+
+    fn table(
+        &mut self,
+        id: Option<&str>,
+        import: Option<(String, String)>,
+        cursor: &mut Cursor<'a>,
+    ) -> Result<()> {
+        self.tables.define(id, cursor, "table")?;
+        let mut table_type = Writer::new();
+        if cursor
+            .peek()
+            .and_then(|item| item.atom())
+            .is_some_and(is_ref_type)
+        {
+            return Err(cursor.error("element segments are not supported"));
+        }
+        let limits = limits(cursor)?;
+        table_type.write_byte(ref_type(cursor)?);
+        table_type.write_bytes(&limits);
+        cursor.expect_end()?;
+        let table_type = table_type.into_bytes();
+        match import {
+            Some((module, name)) => {
+                self.add_import(cursor, &module, &name, ExportKind::Table, &table_type)
+            }
+            None => {
+                self.table_defs.push(table_type);
+                Ok(())
+            }
+        }
+    }
+
+    fn memory_field(&mut self, cursor: &mut Cursor<'a>) -> Result<()> {
+        let id = cursor.id();
+        let idx = self.memories.len;
+        self.inline_exports(cursor, ExportKind::Memory, idx)?;
+        let import = self.inline_import(cursor)?;
+        self.memory(id, import, cursor)
+    }
+
+    fn memory(
+        &mut self,
+        id: Option<&str>,
+        import: Option<(String, String)>,
+        cursor: &mut Cursor<'a>,
+    ) -> Result<()> {
+        self.memories.define(id, cursor, "memory")?;
+        if cursor.list("data").is_some() {
+            return Err(cursor.error("data segments are not supported"));
+        }
+        let mem_type = limits(cursor)?;
+        cursor.expect_end()?;
+        match import {
+            Some((module, name)) => {
+                self.add_import(cursor, &module, &name, ExportKind::Memory, &mem_type)
+            }
+            None => {
+                self.memory_defs.push(mem_type);
+                Ok(())
+            }
+        }
+    }
+
+    fn global_field(&mut self, cursor: &mut Cursor<'a>) -> Result<()> {
+        let id = cursor.id();
+        let idx = self.globals.len;
+        self.inline_exports(cursor, ExportKind::Global, idx)?;
+        let import = self.inline_import(cursor)?;
+        self.global(id, import, cursor)
+    }
+
+    fn global(
+        &mut self,
+        id: Option<&str>,
+        import: Option<(String, String)>,
+        cursor: &mut Cursor<'a>,
+    ) -> Result<()> {
+        self.globals.define(id, cursor, "global")?;
+        let global_type = match cursor.list("mut") {
+            Some(mutable) => {
+                let mut mut_cursor = Cursor::of_list(mutable)?;
+                let val_type = val_type(&mut mut_cursor)?;
+                mut_cursor.expect_end()?;
+                vec![val_type, 0x01]
+            }
+            None => vec![val_type(cursor)?, 0x00],
+        };
+        match import {
+            Some((module, name)) => {
+                cursor.expect_end()?;
+                self.add_import(cursor, &module, &name, ExportKind::Global, &global_type)
+            }
+            None => {
+                let init = std::mem::replace(cursor, Cursor::new(&[], cursor.pos()));
+                self.global_defs.push((global_type, init));
+                Ok(())
+            }
+        }
+    }
+
+    fn export_field(&mut self, cursor: &mut Cursor<'a>) -> Result<()> {
+        let name = cursor.expect_name()?;
+        let desc = cursor
+            .next()
+            .ok_or(cursor.error("expected an export description"))?;
+        cursor.expect_end()?;
+        let kind = match desc.head() {
+            Some("func") => ExportKind::Func,
+            Some("table") => ExportKind::Table,
+            Some("memory") => ExportKind::Memory,
+            Some("global") => ExportKind::Global,
+            _ => return Err(anyhow!("{}: unknown export kind", desc.pos())),
+        };
+        self.exports.push(Export {
+            name,
+            kind,
+            target: ExportTarget::Ref(Cursor::of_list(desc)?),
+        });
+        Ok(())
+    }
+
+    fn start_field(&mut self, cursor: Cursor<'a>, field: &SExpr) -> Result<()> {
+        if self.start.is_some() {
+            return Err(anyhow!("{}: multiple start sections", field.pos()));
+        }
+        self.start = Some(cursor);
+        Ok(())
+    }
+
+    fn space(&self, kind: ExportKind) -> (&Space, &'static str) {
+        match kind {
+            ExportKind::Func => (&self.funcs, "function"),
+            ExportKind::Table => (&self.tables, "table"),
+            ExportKind::Memory => (&self.memories, "memory"),
+            ExportKind::Global => (&self.globals, "global"),
+        }
+    }
+
+    fn write(mut self) -> Result<Vec<u8>> {
+        let mut module = Writer::new();
+        module.write_bytes(&MAGIC);
+        module.write_bytes(&VERSION);
+
+        let mut section = Writer::new();
+        section.write_vec(&self.types, |w, func_type| {
+            w.write_byte(FUNC_TYPE);
+            w.write_vec(&func_type.params, |w, t| w.write_byte(*t));
+            w.write_vec(&func_type.results, |w, t| w.write_byte(*t));
+        });
+        write_section(&mut module, 1, self.types.is_empty(), section);
+
+        let mut section = Writer::new();
+        section.write_vec(&self.imports, |w, import| w.write_bytes(import));
+        write_section(&mut module, 2, self.imports.is_empty(), section);
+
+        let mut section = Writer::new();
+        section.write_vec(&self.func_defs, |w, func| w.write_u32(func.type_idx));
+        write_section(&mut module, 3, self.func_defs.is_empty(), section);
+
+        let mut section = Writer::new();
+        section.write_vec(&self.table_defs, |w, table| w.write_bytes(table));
+        write_section(&mut module, 4, self.table_defs.is_empty(), section);
+
+        let mut section = Writer::new();
+        section.write_vec(&self.memory_defs, |w, memory| w.write_bytes(memory));
+        write_section(&mut module, 5, self.memory_defs.is_empty(), section);
+
+        let mut globals = Vec::new();
+        for (global_type, init) in &self.global_defs {
+            let mut global = Writer::new();
+            global.write_bytes(global_type);
+            let mut encoder = InstrEncoder::new(&self, Space::default());
+            encoder.instrs(&mut init.clone())?;
+            global.write_bytes(&encoder.finish()?);
+            globals.push(global.into_bytes());
+        }
+        let mut section = Writer::new();
+        section.write_vec(&globals, |w, global| w.write_bytes(global));
+        write_section(&mut module, 6, globals.is_empty(), section);
+
+        let mut exports = Vec::new();
+        for export in &self.exports {
+            let idx = match &export.target {
+                ExportTarget::Index(idx) => *idx,
+                ExportTarget::Ref(cursor) => {
+                    let mut cursor = cursor.clone();
+                    let (space, kind) = self.space(export.kind);
+                    let idx = space.index(&mut cursor, kind)?;
+                    cursor.expect_end()?;
+                    idx
+                }
+            };
+            if exports.iter().any(|(name, _, _)| *name == export.name) {
+                return Err(anyhow!("duplicate export \"{}\"", export.name));
+            }
+            exports.push((export.name.clone(), export.kind, idx));
+        }
+        let mut section = Writer::new();
+        section.write_vec(&exports, |w, (name, kind, idx)| {
+            w.write_name(name);
+            w.write_byte(*kind as u8);
+            w.write_u32(*idx);
+        });
+        write_section(&mut module, 7, exports.is_empty(), section);
+
+        if let Some(cursor) = &self.start {
+            let mut cursor = cursor.clone();
+            let func_idx = self.funcs.index(&mut cursor, "function")?;
+            cursor.expect_end()?;
+            let mut section = Writer::new();
+            section.write_u32(func_idx);
+            write_section(&mut module, 8, false, section);
+        }
+
+        let imported_funcs = self.funcs.len - self.func_defs.len() as u32;
+        let mut codes = Vec::new();
+        for (i, func) in self.func_defs.iter().enumerate() {
+            let func_idx = imported_funcs + i as u32;
+            let (code, local_names) = self.code(func)?;
+            codes.push(code);
+            if !local_names.is_empty() {
+                self.local_names.insert(func_idx, local_names);
+            }
+        }
+        let mut section = Writer::new();
+        section.write_vec(&codes, |w, code| {
+            w.write_u32(code.len() as u32);
+            w.write_bytes(code);
+        });
+        write_section(&mut module, 10, codes.is_empty(), section);
+
+        self.write_name_section(&mut module);
+        Ok(module.into_bytes())
+    }
+
+    /// Encode the locals and the body of a function, returning the names of its locals too.
+    fn code(&self, func: &FuncDef<'a>) -> Result<(Vec<u8>, BTreeMap<u32, String>)> {
+        let mut locals = Space::default();
+        let mut body = func.body.clone();
+        for id in &func.param_ids {
+            locals.define(id.as_deref(), &body, "local")?;
+        }
+        let mut local_types = Vec::new();
+        while let Some(local) = body.list("local") {
+            let mut local_cursor = Cursor::of_list(local)?;
+            match local_cursor.id() {
+                Some(id) => {
+                    locals.define(Some(id), &local_cursor, "local")?;
+                    local_types.push(val_type(&mut local_cursor)?);
+                    local_cursor.expect_end()?;
+                }
+                None => {
+                    while !local_cursor.is_empty() {
+                        locals.define(None, &local_cursor, "local")?;
+                        local_types.push(val_type(&mut local_cursor)?);
+                    }
+                }
+            }
+        }
+
+        // Consecutive locals of the same type are declared together.
+        let mut groups: Vec<(u32, u8)> = Vec::new();
+        for local_type in local_types {
+            match groups.last_mut() {
+                Some((count, t)) if *t == local_type => *count += 1,
+                _ => groups.push((1, local_type)),
+            }
+        }
+        let local_names = locals.names();
+        let mut code = Writer::new();
+        code.write_vec(&groups, |w, (count, t)| {
+            w.write_u32(*count);
+            w.write_byte(*t);
+        });
+        let mut encoder = InstrEncoder::new(self, locals);
+        encoder.instrs(&mut body)?;
+        code.write_bytes(&encoder.finish()?);
+        Ok((code.into_bytes(), local_names))
+    }
+
+    fn write_name_section(&self, module: &mut Writer) {
+        let mut names = Writer::new();
+        if let Some(id) = self.id {
+            let mut subsection = Writer::new();
+            subsection.write_name(id);
+            names.write_section(0, &subsection.into_bytes());
+        }
+        let func_names: Vec<_> = self.funcs.names().into_iter().collect();
+        if !func_names.is_empty() {
+            let mut subsection = Writer::new();
+            write_name_map(&mut subsection, &func_names);
+            names.write_section(1, &subsection.into_bytes());
+        }
+        if !self.local_names.is_empty() {
+            let local_names: Vec<_> = self.local_names.iter().collect();
+            let mut subsection = Writer::new();
+            subsection.write_vec(&local_names, |w, (func_idx, names)| {
+                w.write_u32(**func_idx);
+                let names: Vec<_> = names.iter().map(|(i, n)| (*i, n.clone())).collect();
+                write_name_map(w, &names);
+            });
+            names.write_section(2, &subsection.into_bytes());
+        }
+        if names.is_empty() {
+            return;
+        }
+        let mut section = Writer::new();
+        section.write_name("name");
+        section.write_bytes(&names.into_bytes());
+        module.write_section(0, &section.into_bytes());
+    }
+}
+
+/// Sections without entries are omitted.
+fn write_section(module: &mut Writer, id: u8, empty: bool, section: Writer) {
+    if !empty {
+        module.write_section(id, &section.into_bytes());
+    }
+}
+
+fn write_name_map(writer: &mut Writer, names: &[(u32, String)]) {
+    writer.write_vec(names, |w, (idx, name)| {
+        w.write_u32(*idx);
+        w.write_name(name);
+    });
+}
+
+/// `(param ...)*` followed by `(result ...)*`, with the `$id` of each param.
+fn func_signature(cursor: &mut Cursor) -> Result<(FuncType, Vec<Option<String>>)> {
+    let mut func_type = FuncType {
+        params: Vec::new(),
+        results: Vec::new(),
+    };
+    let mut param_ids = Vec::new();
+    while let Some(param) = cursor.list("param") {
+        let mut param_cursor = Cursor::of_list(param)?;
+        match param_cursor.id() {
+            Some(id) => {
+                func_type.params.push(val_type(&mut param_cursor)?);
+                param_ids.push(Some(id.to_string()));
+                param_cursor.expect_end()?;
+            }
+            None => {
+                while !param_cursor.is_empty() {
+                    func_type.params.push(val_type(&mut param_cursor)?);
+                    param_ids.push(None);
+                }
+            }
+        }
+    }
+    while let Some(result) = cursor.list("result") {
+        let mut result_cursor = Cursor::of_list(result)?;
+        while !result_cursor.is_empty() {
+            func_type.results.push(val_type(&mut result_cursor)?);
+        }
+    }
+    Ok((func_type, param_ids))
+}
+
+fn val_type(cursor: &mut Cursor) -> Result<u8> {
+    let pos_cursor = cursor.clone();
+    match cursor.expect_atom()? {
+        "i32" => Ok(I32),
+        "i64" => Ok(I64),
+        "f32" => Ok(F32),
+        "f64" => Ok(F64),
+        "funcref" => Ok(FUNC_REF),
+        "externref" => Ok(EXTERN_REF),
+        _ => Err(pos_cursor.error("unknown value type")),
+    }
+}
+
+fn is_ref_type(atom: &str) -> bool {
+    matches!(atom, "funcref" | "externref")
+}
+
+fn ref_type(cursor: &mut Cursor) -> Result<u8> {
+    let pos_cursor = cursor.clone();
+    match cursor.expect_atom()? {
+        "funcref" => Ok(FUNC_REF),
+        "externref" => Ok(EXTERN_REF),
+        _ => Err(pos_cursor.error("unknown reference type")),
+    }
+}
+
+/// `min max?`, encoded with its flag.
+fn limits(cursor: &mut Cursor) -> Result<Vec<u8>> {
+    let number = |cursor: &mut Cursor| -> Result<Option<u32>> {
+        let Some(atom) = cursor.peek().and_then(|item| item.atom()) else {
+            return Ok(None);
+        };
+        let Some(value) = parse_u32(atom) else {
+            return Ok(None);
+        };
+        cursor.next();
+        Ok(Some(value))
+    };
+    let min = number(cursor)?.ok_or(cursor.error("expected a limit"))?;
+    let max = number(cursor)?;
+    let mut writer = Writer::new();
+    writer.write_byte(max.is_some() as u8);
+    writer.write_u32(min);
+    if let Some(max) = max {
+        writer.write_u32(max);
+    }
+    Ok(writer.into_bytes())
+}
+
+/// Encodes a sequence of plain and folded instructions.
+/// https://webassembly.github.io/spec/core/text/instructions.html
+struct InstrEncoder<'m, 'a> {
+    module: &'m ModuleEncoder<'a>,
+    locals: Space,
+    /// `$id` of each enclosing block, innermost last.
+    labels: Vec<Option<&'a str>>,
+    out: Writer,
+}
+impl<'m, 'a> InstrEncoder<'m, 'a> {
+    fn new(module: &'m ModuleEncoder<'a>, locals: Space) -> InstrEncoder<'m, 'a> {
+        InstrEncoder {
+            module,
+            locals,
+            labels: Vec::new(),
+            out: Writer::new(),
+        }
+    }
+
+    /// Terminate the expression with `end`.
+    fn finish(mut self) -> Result<Vec<u8>> {
+        if !self.labels.is_empty() {
+            return Err(anyhow!("unclosed block"));
+        }
+        Op::End.write(&mut self.out);
+        Ok(self.out.into_bytes())
+    }
+
+    fn instrs(&mut self, cursor: &mut Cursor<'a>) -> Result<()> {
+        while let Some(item) = cursor.peek() {
+            match item {
+                SExpr::List(..) => {
+                    cursor.next();
+                    self.folded(item)?;
+                }
+                SExpr::Atom(..) => self.plain(cursor)?,
+                SExpr::String(..) => return Err(cursor.error("expected an instruction")),
+            }
+        }
+        Ok(())
+    }
+
+    fn plain(&mut self, cursor: &mut Cursor<'a>) -> Result<()> {
+        let pos_cursor = cursor.clone();
+        let name = cursor.expect_atom()?;
+        match name {
+            "block" | "loop" | "if" => {
+                let label = cursor.id();
+                let block_type = block_type(cursor)?;
+                Op::from_name(name).unwrap().write(&mut self.out);
+                self.out.write_byte(block_type);
+                self.labels.push(label);
+            }
+            "else" | "end" => {
+                let label = *self
+                    .labels
+                    .last()
+                    .ok_or(pos_cursor.error(&format!("unexpected {}", name)))?;
+                if let Some(id) = cursor.id() {
+                    if label != Some(id) {
+                        return Err(pos_cursor.error("mismatching label"));
+                    }
+                }
+                if name == "end" {
+                    self.labels.pop();
+                }
+                Op::from_name(name).unwrap().write(&mut self.out);
+            }
+            _ => {
+                let op = Op::from_name(name).ok_or(pos_cursor.error("unknown operator"))?;
+                let mut instr = Writer::new();
+                self.instr(op, cursor, &mut instr)?;
+                self.out.write_bytes(&instr.into_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    fn folded(&mut self, expr: &'a SExpr) -> Result<()> {
+        let mut cursor = Cursor::of_list(expr)?;
+        let name = expr
+            .head()
+            .ok_or(anyhow!("{}: expected an instruction", expr.pos()))?;
+        match name {
+            "block" | "loop" => {
+                let label = cursor.id();
+                let block_type = block_type(&mut cursor)?;
+                Op::from_name(name).unwrap().write(&mut self.out);
+                self.out.write_byte(block_type);
+                self.labels.push(label);
+                self.instrs(&mut cursor)?;
+                self.labels.pop();
+                Op::End.write(&mut self.out);
+            }
+            "if" => {
+                let label = cursor.id();
+                let block_type = block_type(&mut cursor)?;
+                while let Some(condition) = cursor.peek() {
+                    if condition.head() == Some("then") {
+                        break;
+                    }
+                    if condition.list().is_none() {
+                        return Err(cursor.error("expected a folded instruction"));
+                    }
+                    cursor.next();
+                    self.folded(condition)?;
+                }
+                Op::If.write(&mut self.out);
+                self.out.write_byte(block_type);
+                self.labels.push(label);
+                let then = cursor
+                    .list("then")
+                    .ok_or(cursor.error("expected (then ...)"))?;
+                self.instrs(&mut Cursor::of_list(then)?)?;
+                if let Some(else_) = cursor.list("else") {
+                    Op::Else.write(&mut self.out);
+                    self.instrs(&mut Cursor::of_list(else_)?)?;
+                }
+                cursor.expect_end()?;
+                self.labels.pop();
+                Op::End.write(&mut self.out);
+            }
+            _ => {
+                let op = Op::from_name(name).ok_or(anyhow!("{}: unknown operator", expr.pos()))?;
+                let mut instr = Writer::new();
+                self.instr(op, &mut cursor, &mut instr)?;
+                // Operands are evaluated before the instruction.
+                for operand in cursor {
+                    if operand.list().is_none() {
+                        return Err(anyhow!("{}: expected a folded instruction", operand.pos()));
+                    }
+                    self.folded(operand)?;
+                }
+                self.out.write_bytes(&instr.into_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    /// Write `op` followed by its immediates read from `cursor`.
+    fn instr(&self, op: Op, cursor: &mut Cursor<'a>, out: &mut Writer) -> Result<()> {
+        let module = self.module;
+        op.write(out);
+        match op {
+            Op::LocalGet | Op::LocalSet => out.write_u32(self.locals.index(cursor, "local")?),
+            Op::GlobalGet | Op::GlobalSet => out.write_u32(module.globals.index(cursor, "global")?),
+            Op::Call => out.write_u32(module.funcs.index(cursor, "function")?),
+            Op::CallIndirect => {
+                let table_idx = module.tables.optional_index(cursor, "table")?;
+                let type_ref = cursor
+                    .list("type")
+                    .ok_or(cursor.error("expected (type ...)"))?;
+                let mut type_cursor = Cursor::of_list(type_ref)?;
+                out.write_u32(module.type_space.index(&mut type_cursor, "type")?);
+                type_cursor.expect_end()?;
+                out.write_u32(table_idx.unwrap_or(0));
+            }
+            Op::Br | Op::BrIf => out.write_u32(self.label(cursor)?),
+            Op::I32Const => out.write_i32(literal(cursor, parse_i32)?),
+            Op::I64Const => out.write_i64(literal(cursor, parse_i64)?),
+            Op::F32Const => out.write_f32(literal(cursor, parse_f32)?),
+            Op::F64Const => out.write_f64(literal(cursor, parse_f64)?),
+            Op::I32Load | Op::I32Store => mem_arg(cursor, 4, out)?,
+            Op::MemorySize | Op::MemoryGrow => {
+                let idx = module.memories.optional_index(cursor, "memory")?;
+                out.write_byte(idx.unwrap_or(0) as u8);
+            }
+            Op::RefNull => {
+                let pos_cursor = cursor.clone();
+                match cursor.expect_atom()? {
+                    "func" => out.write_byte(FUNC_REF),
+                    "extern" => out.write_byte(EXTERN_REF),
+                    _ => return Err(pos_cursor.error("unknown heap type")),
+                }
+            }
+            Op::TableGrow | Op::TableSize => {
+                let idx = module.tables.optional_index(cursor, "table")?;
+                out.write_u32(idx.unwrap_or(0));
+            }
+            Op::Block | Op::Loop | Op::If | Op::Else | Op::End => {
+                return Err(cursor.error("unexpected block instruction"))
+            }
+            Op::Unreachable
+            | Op::Nop
+            | Op::Return
+            | Op::Drop
+            | Op::I32Eqa
+            | Op::I32LtS
+            | Op::I32GeS
+            | Op::I32Add
+            | Op::I32Sub
+            | Op::I32Mul
+            | Op::I32DivS
+            | Op::I32DivU
+            | Op::I32Rems
+            | Op::I32RemU
+            | Op::I32TruncF32S
+            | Op::I32TruncF32U
+            | Op::I32TruncF64S
+            | Op::I32TruncF64U
+            | Op::I64TruncF32S
+            | Op::I64TruncF32U
+            | Op::I64TruncF64S
+            | Op::I64TruncF64U => {}
+        }
+        Ok(())
+    }
+
+    /// Depth of the label given by `$id` or number.
+    fn label(&self, cursor: &mut Cursor) -> Result<u32> {
+        let pos_cursor = cursor.clone();
+        let atom = cursor.expect_atom()?;
+        let depth = match atom.strip_prefix('$') {
+            Some(id) => self
+                .labels
+                .iter()
+                .rev()
+                .position(|label| *label == Some(id))
+                .map(|depth| depth as u32),
+            None => parse_u32(atom),
+        };
+        depth.ok_or(pos_cursor.error(&format!("unknown label {}", atom)))
+    }
+}
+
+fn literal<T>(cursor: &mut Cursor, parse: fn(&str) -> Option<T>) -> Result<T> {
+    let pos_cursor = cursor.clone();
+    let atom = cursor.expect_atom()?;
+    parse(atom).ok_or(pos_cursor.error("constant out of range"))
+}
+
+/// `(result t)?` of a block.
+fn block_type(cursor: &mut Cursor) -> Result<u8> {
+    if cursor.list("type").is_some() || cursor.list("param").is_some() {
+        return Err(cursor.error("block types with parameters are not supported"));
+    }
+    let mut results = Vec::new();
+    while let Some(result) = cursor.list("result") {
+        let mut result_cursor = Cursor::of_list(result)?;
+        while !result_cursor.is_empty() {
+            results.push(val_type(&mut result_cursor)?);
+        }
+    }
+    match results.as_slice() {
+        [] => Ok(EMPTY_BLOCK_TYPE),
+        [result] => Ok(*result),
+        _ => Err(cursor.error("blocks with several results are not supported")),
+    }
+}
+
+/// `offset=N? align=N?`, the alignment defaulting to `natural` bytes.
+fn mem_arg(cursor: &mut Cursor, natural: u32, out: &mut Writer) -> Result<()> {
+    let mut immediate = |key: &str| -> Result<Option<u32>> {
+        let Some(value) = cursor
+            .peek()
+            .and_then(|item| item.atom())
+            .and_then(|atom| atom.strip_prefix(key))
+        else {
+            return Ok(None);
+        };
+        let value = parse_u32(value).ok_or(cursor.error("invalid memory argument"))?;
+        cursor.next();
+        Ok(Some(value))
+    };
+    let offset = immediate("offset=")?.unwrap_or(0);
+    let align = immediate("align=")?.unwrap_or(natural);
+    if !align.is_power_of_two() {
+        return Err(cursor.error("alignment must be a power of two"));
+    }
+    out.write_u32(align.trailing_zeros());
+    out.write_u32(offset);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::buffer::Buffer;
+    use crate::exec::instance::Instance;
+    use crate::exec::module::ModuleNode;
+    use crate::exec::store::Store;
+    use crate::exec::value::Value;
+    use rstest::rstest;
+    use std::fs;
+
+    /// Drop the custom sections, i.e. the names the hand-built binaries may lack.
+    fn strip_custom_sections(bytes: &[u8]) -> Vec<u8> {
+        let mut buf = Buffer::new(bytes);
+        let mut stripped = buf.read_bytes(8).unwrap().to_vec();
+        while !buf.eof() {
+            let id = buf.read_byte().unwrap();
+            let size = buf.read_u32().unwrap();
+            let payload = buf.read_bytes(size).unwrap();
+            if id != 0 {
+                let mut writer = Writer::new();
+                writer.write_section(id, payload);
+                stripped.extend(writer.into_bytes());
+            }
+        }
+        stripped
+    }
+
+    fn instantiate(src: &str) -> (Store, Instance) {
+        let mut module = ModuleNode::new();
+        module
+            .load(&mut Buffer::new(&parse_str(src).unwrap()))
+            .unwrap();
+        let mut store = Store::new();
+        let instance = Instance::new(&mut store, module).unwrap();
+        (store, instance)
+    }
+
+    #[rstest(
+        name,
+        case("add"),
+        case("call"),
+        case("const"),
+        case("if"),
+        case("local_var"),
+        case("loop"),
+        case("one_module"),
+        case("recursion"),
+        case("spin"),
+        case("trap"),
+        case("memory"),
+        case("exports"),
+        case("lib"),
+        case("main")
+    )]
+    fn test_examples_match_binaries(name: &str) {
+        let wat = fs::read_to_string(format!("examples/{}.wat", name)).unwrap();
+        let wasm = fs::read(format!("examples/{}.wasm", name)).unwrap();
+        let bytes = parse_str(&wat).unwrap();
+        assert_eq!(strip_custom_sections(&bytes), strip_custom_sections(&wasm));
+    }
+
+    #[test]
+    fn test_names() {
+        let wat = fs::read_to_string("examples/trap.wat").unwrap();
+        let mut module = ModuleNode::new();
+        module
+            .load(&mut Buffer::new(&parse_str(&wat).unwrap()))
+            .unwrap();
+        let names = module.name_section().unwrap();
+        assert_eq!(names.func_names.get(&0), Some(&"div_s".to_string()));
+        assert_eq!(names.local_names[&1].get(&1), Some(&"b".to_string()));
+    }
+
+    #[test]
+    fn test_plain_instructions() {
+        let (mut store, instance) = instantiate(
+            r#"
+            (func (export "max") (param $a i32) (param $b i32) (result i32)
+              local.get $a
+              local.get $b
+              i32.lt_s
+              if $lt (result i32)
+                local.get $b
+              else $lt
+                local.get $a
+              end $lt)
+            (func (export "sum") (param $n i32) (result i32) (local $sum i32)
+              block $done
+                loop $next
+                  local.get $n
+                  i32.eqz
+                  br_if $done
+                  (local.set $sum (i32.add (local.get $sum) (local.get $n)))
+                  (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                  br $next
+                end
+              end
+              local.get $sum)
+            (func (export "abs") (param $n i32) (result i32)
+              (if (i32.lt_s (local.get $n) (i32.const 0))
+                (then (local.set $n (i32.sub (i32.const 0) (local.get $n)))))
+              local.get $n)
+            (func (export "countdown") (param $n i32) (result i32)
+              (loop $again (result i32)
+                (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                (br_if $again (local.get $n))
+                (i32.const 5)))
+            "#,
+        );
+        let max = |store: &mut Store, a, b| {
+            let args = [Value::I32(a), Value::I32(b)];
+            instance.invoke(store, "max", &args).unwrap()
+        };
+        assert_eq!(max(&mut store, 3, 7), vec![Value::I32(7)]);
+        assert_eq!(max(&mut store, 7, -3), vec![Value::I32(7)]);
+        let results = instance
+            .invoke(&mut store, "sum", &[Value::I32(10)])
+            .unwrap();
+        assert_eq!(results, vec![Value::I32(55)]);
+        for n in [-4, 4] {
+            let results = instance
+                .invoke(&mut store, "abs", &[Value::I32(n)])
+                .unwrap();
+            assert_eq!(results, vec![Value::I32(4)]);
+        }
+        let results = instance
+            .invoke(&mut store, "countdown", &[Value::I32(3)])
+            .unwrap();
+        assert_eq!(results, vec![Value::I32(5)]);
+    }
+
+    #[test]
+    fn test_types_and_imports() {
+        let bytes = parse_str(
+            r#"
+            (module $m
+              (type $binary (func (param i32 i32) (result i32)))
+              (import "env" "f" (func $f (type $binary)))
+              (func (import "env" "g") (param f64))
+              (func $h (type $binary) (call $f (local.get 0) (local.get 1))))
+            "#,
+        )
+        .unwrap();
+        let mut module = ModuleNode::new();
+        module.load(&mut Buffer::new(&bytes)).unwrap();
+        assert_eq!(module.type_section().unwrap().func_types.len(), 2);
+        assert_eq!(module.import_section().unwrap().imports.len(), 2);
+        assert_eq!(module.function_section().unwrap().type_indices, vec![0]);
+        let names = module.name_section().unwrap();
+        assert_eq!(names.module_name, Some("m".to_string()));
+        assert_eq!(names.func_names.get(&2), Some(&"h".to_string()));
+    }
+
+    #[rstest(
+        src,
+        message,
+        case("(func (local.get $x))", "unknown local $x"),
+        case("(func (br $l))", "unknown label $l"),
+        case("(func (i32.const 4294967296))", "constant out of range"),
+        case("(func (i32.foo))", "unknown operator"),
+        case("(func $f) (func $f)", "duplicate function $f"),
+        case("(func) (import \"m\" \"f\" (func))", "import after definition"),
+        case("(func block)", "unclosed block")
+    )]
+    fn test_errors(src: &str, message: &str) {
+        let err = parse_str(src).unwrap_err().to_string();
+        assert!(err.contains(message), "{}", err);
+    }
+
+    #[test]
+    fn test_binary_and_quote_modules() {
+        let exprs = SExpr::parse_all(r#"(module binary "\00asm" "\01\00\00\00")"#).unwrap();
+        assert_eq!(encode_module(&exprs[0]).unwrap(), [MAGIC, VERSION].concat());
+
+        let exprs = SExpr::parse_all(r#"(module quote "(func" "(export \"f\"))")"#).unwrap();
+        let (mut store, instance) = instantiate("(func (export \"f\"))");
+        assert!(instance.invoke(&mut store, "f", &[]).is_ok());
+        assert_eq!(
+            encode_module(&exprs[0]).unwrap(),
+            parse_str("(func (export \"f\"))").unwrap()
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! Numeric literals of the text format.
+//! https://webassembly.github.io/spec/core/text/values.html
+
+/// Split an optional sign, returning whether the literal is negative.
+fn split_sign(s: &str) -> (bool, &str) {
+    match s.as_bytes().first() {
+        Some(b'-') => (true, &s[1..]),
+        Some(b'+') => (false, &s[1..]),
+        _ => (false, s),
+    }
+}
+
+/// Digits with the `_` separators removed, which may only appear between digits.
+fn digits(s: &str, radix: u32) -> Option<String> {
+    if s.is_empty() || s.starts_with('_') || s.ends_with('_') || s.contains("__") {
+        return None;
+    }
+    let digits: String = s.chars().filter(|c| *c != '_').collect();
+    digits.chars().all(|c| c.is_digit(radix)).then_some(digits)
+}
+
+/// Magnitude of an unsigned decimal or `0x` hexadecimal integer.
+fn parse_magnitude(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(&digits(hex, 16)?, 16).ok(),
+        None => digits(s, 10)?.parse().ok(),
+    }
+}
+
+pub fn parse_u32(s: &str) -> Option<u32> {
+    parse_magnitude(s)?.try_into().ok()
+}
+
+/// Signed or unsigned literal of 32 bits, e.g. both `-1` and `0xffffffff` are -1.
+pub fn parse_i32(s: &str) -> Option<i32> {
+    let (negative, rest) = split_sign(s);
+    let magnitude = parse_magnitude(rest)?;
+    if negative {
+        (magnitude <= 1 << 31).then(|| (magnitude as i64).wrapping_neg() as i32)
+    } else {
+        (magnitude <= u32::MAX as u64).then_some(magnitude as u32 as i32)
+    }
+}
+
+/// Signed or unsigned literal of 64 bits.
+pub fn parse_i64(s: &str) -> Option<i64> {
+    let (negative, rest) = split_sign(s);
+    let magnitude = parse_magnitude(rest)?;
+    if negative {
+        (magnitude <= 1 << 63).then(|| (magnitude as i64).wrapping_neg())
+    } else {
+        Some(magnitude as i64)
+    }
+}
+
+pub fn parse_f32(s: &str) -> Option<f32> {
+    let (negative, rest) = split_sign(s);
+    let sign = if negative { 1 << 31 } else { 0 };
+    if let Some(payload) = nan_payload(rest, 1 << 22)? {
+        let payload = u32::try_from(payload).ok().filter(|p| *p < 1 << 23)?;
+        return Some(f32::from_bits(sign | 0x7f80_0000 | payload));
+    }
+    let value = match rest.strip_prefix("0x") {
+        Some(hex) => parse_hex_float(hex)? as f32,
+        None => parse_decimal_float(rest)?.parse::<f32>().ok()?,
+    };
+    Some(if negative { -value } else { value })
+}
+
+pub fn parse_f64(s: &str) -> Option<f64> {
+    let (negative, rest) = split_sign(s);
+    let sign = if negative { 1 << 63 } else { 0 };
+    if let Some(payload) = nan_payload(rest, 1 << 51)? {
+        let payload = Some(payload).filter(|p| *p < 1 << 52)?;
+        return Some(f64::from_bits(sign | 0x7ff0_0000_0000_0000 | payload));
+    }
+    let value = match rest.strip_prefix("0x") {
+        Some(hex) => parse_hex_float(hex)?,
+        None => parse_decimal_float(rest)?.parse::<f64>().ok()?,
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// Payload of `nan` or `nan:0x...`, `Some(None)` if `s` is not a NaN.
+fn nan_payload(s: &str, canonical: u64) -> Option<Option<u64>> {
+    match s {
+        "nan" => Some(Some(canonical)),
+        _ => match s.strip_prefix("nan:0x") {
+            Some(hex) => {
+                let payload = u64::from_str_radix(&digits(hex, 16)?, 16).ok()?;
+                // A zero payload would be an infinity.
+                Some(Some(payload).filter(|p| *p != 0))
+            }
+            None => Some(None),
+        },
+    }
+}
+
+/// Decimal float in the syntax of Rust, `inf` included.
+fn parse_decimal_float(s: &str) -> Option<String> {
+    if s == "inf" {
+        return Some(s.to_string());
+    }
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+    let (int, frac) = match mantissa.split_once('.') {
+        Some((int, frac)) => (int, Some(frac)),
+        None => (mantissa, None),
+    };
+    let mut float = digits(int, 10)?;
+    if let Some(frac) = frac.filter(|f| !f.is_empty()) {
+        float = format!("{}.{}", float, digits(frac, 10)?);
+    }
+    if let Some(exponent) = exponent {
+        let (negative, exponent) = split_sign(exponent);
+        let sign = if negative { "-" } else { "" };
+        float = format!("{}e{}{}", float, sign, digits(exponent, 10)?);
+    }
+    Some(float)
+}
+
+/// Hexadecimal float after `0x`, e.g. `1.8p3`.
+fn parse_hex_float(s: &str) -> Option<f64> {
+    let (mantissa, exponent) = match s.find(['p', 'P']) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+    let (int, frac) = match mantissa.split_once('.') {
+        Some((int, frac)) => (int, frac),
+        None => (mantissa, ""),
+    };
+    let int = digits(int, 16)?;
+    let frac = if frac.is_empty() {
+        String::new()
+    } else {
+        digits(frac, 16)?
+    };
+
+    // Accumulate the significant digits, counting the dropped ones in the exponent.
+    let mut significand = 0_u128;
+    let mut exponent_adjust = -4 * frac.len() as i64;
+    let mut sticky = false;
+    for c in int.chars().chain(frac.chars()) {
+        let digit = c.to_digit(16).unwrap() as u128;
+        if significand >> 120 == 0 {
+            significand = significand << 4 | digit;
+        } else {
+            exponent_adjust += 4;
+            sticky |= digit != 0;
+        }
+    }
+    // Keep dropped digits from rounding as if they were zeros.
+    significand |= sticky as u128;
+
+    let exponent = match exponent {
+        Some(exponent) => {
+            let (negative, exponent) = split_sign(exponent);
+            let exponent: i64 = digits(exponent, 10)?.parse().ok()?;
+            if negative {
+                -exponent
+            } else {
+                exponent
+            }
+        }
+        None => 0,
+    };
+    let mut value = significand as f64;
+    let mut exponent = exponent.saturating_add(exponent_adjust);
+    // Scale in steps so intermediate powers of two stay representable.
+    while exponent > 0 && value.is_finite() && value != 0.0 {
+        let step = exponent.min(1000);
+        value *= 2_f64.powi(step as i32);
+        exponent -= step;
+    }
+    while exponent < 0 && value != 0.0 {
+        let step = exponent.max(-1000);
+        value *= 2_f64.powi(step as i32);
+        exponent -= step;
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest(
+        input,
+        expected,
+        case("0", Some(0)),
+        case("-1", Some(-1)),
+        case("0xffffffff", Some(-1)),
+        case("4294967295", Some(-1)),
+        case("-2147483648", Some(i32::MIN)),
+        case("1_000", Some(1000)),
+        case("4294967296", None),
+        case("-2147483649", None),
+        case("1__0", None),
+        case("x", None)
+    )]
+    fn test_parse_i32(input: &str, expected: Option<i32>) {
+        assert_eq!(parse_i32(input), expected);
+    }
+
+    #[rstest(
+        input,
+        expected,
+        case("0x7fffffffffffffff", Some(i64::MAX)),
+        case("-0x8000000000000000", Some(i64::MIN)),
+        case("18446744073709551615", Some(-1))
+    )]
+    fn test_parse_i64(input: &str, expected: Option<i64>) {
+        assert_eq!(parse_i64(input), expected);
+    }
+
+    #[rstest(
+        input,
+        expected,
+        case("1.5", 1.5),
+        case("-0.25e1", -2.5),
+        case("1e3", 1000.0),
+        case("1.", 1.0),
+        case("0x1.8p1", 3.0),
+        case("-0x10", -16.0),
+        case("0x1p-1074", 5e-324),
+        case("inf", f64::INFINITY),
+        case("-inf", f64::NEG_INFINITY)
+    )]
+    fn test_parse_f64(input: &str, expected: f64) {
+        assert_eq!(parse_f64(input), Some(expected));
+    }
+
+    #[test]
+    fn test_parse_nan() {
+        assert_eq!(parse_f32("nan").unwrap().to_bits(), 0x7fc0_0000);
+        assert_eq!(parse_f32("-nan:0x1").unwrap().to_bits(), 0xff80_0001);
+        assert_eq!(parse_f64("nan").unwrap().to_bits(), 0x7ff8_0000_0000_0000);
+        assert_eq!(parse_f32("nan:0x0"), None);
+    }
+}
@@ -0,0 +1,549 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::exec::code_section::{BlockType, ExprNode, IntrinsicNode, MemArgNode};
+use crate::exec::export_section::ExportKind;
+use crate::exec::import_section::ImportDescNode;
+use crate::exec::module::ModuleNode;
+use crate::exec::type_section::{
+    FunctionTypeNode, GlobalTypeNode, LimitsNode, RefType, TableTypeNode, ValType,
+};
+
+const INDENT: &str = "  ";
+
+/// Render `module` in the text format, naming functions and locals after the name section.
+pub fn print_module(module: &ModuleNode) -> String {
+    let mut printer = Printer::new(module);
+    printer.module();
+    printer.lines.join("\n") + "\n"
+}
+
+/// `$id`s of an index space, kept only when they are valid and unique identifiers.
+fn ids(names: &BTreeMap<u32, String>) -> BTreeMap<u32, String> {
+    let mut seen = HashSet::new();
+    names
+        .iter()
+        .filter(|(_, name)| is_id(name) && seen.insert(name.as_str()))
+        .map(|(idx, name)| (*idx, format!("${}", name)))
+        .collect()
+}
+
+/// https://webassembly.github.io/spec/core/text/values.html#text-id
+fn is_id(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-./:<=>?@\\^_`|~".contains(c))
+}
+
+struct Printer<'a> {
+    module: &'a ModuleNode,
+    func_ids: BTreeMap<u32, String>,
+    local_ids: BTreeMap<u32, BTreeMap<u32, String>>,
+    module_id: Option<String>,
+    lines: Vec<String>,
+    depth: usize,
+}
+impl<'a> Printer<'a> {
+    fn new(module: &'a ModuleNode) -> Printer<'a> {
+        let names = module.name_section().unwrap_or_default();
+        Printer {
+            module,
+            func_ids: ids(&names.func_names),
+            local_ids: names
+                .local_names
+                .iter()
+                .map(|(func_idx, names)| (*func_idx, ids(names)))
+                .collect(),
+            module_id: names.module_name.filter(|name| is_id(name)),
+            lines: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        self.lines
+            .push(format!("{}{}", INDENT.repeat(self.depth), text));
+    }
+
+    /// Close the S-expression opened by a previous line.
+    fn close(&mut self) {
+        if let Some(line) = self.lines.last_mut() {
+            line.push(')');
+        }
+    }
+
+    fn func_ref(&self, func_idx: u32) -> String {
+        match self.func_ids.get(&func_idx) {
+            Some(id) => id.clone(),
+            None => func_idx.to_string(),
+        }
+    }
+
+    /// `$id` of a function definition, or its index as a comment.
+    fn func_id(&self, func_idx: u32) -> String {
+        match self.func_ids.get(&func_idx) {
+            Some(id) => id.clone(),
+            None => format!("(;{};)", func_idx),
+        }
+    }
+
+    fn local_ref(&self, func_idx: u32, local_idx: u32) -> String {
+        let id = self
+            .local_ids
+            .get(&func_idx)
+            .and_then(|ids| ids.get(&local_idx));
+        match id {
+            Some(id) => id.clone(),
+            None => local_idx.to_string(),
+        }
+    }
+
+    fn func_type(&self, type_idx: u32) -> Option<&'a FunctionTypeNode> {
+        self.module
+            .type_section()?
+            .func_types
+            .get(type_idx as usize)
+    }
+
+    fn module(&mut self) {
+        match &self.module_id {
+            Some(id) => self.line(&format!("(module {}", id)),
+            None => self.line("(module"),
+        }
+        self.depth += 1;
+
+        if let Some(type_section) = self.module.type_section() {
+            for (i, func_type) in type_section.func_types.iter().enumerate() {
+                let signature = signature(func_type, |_| None);
+                self.line(&format!("(type (;{};) (func{}))", i, signature));
+            }
+        }
+
+        let mut imported_funcs = 0;
+        let mut imported_tables = 0;
+        let mut imported_memories = 0;
+        let mut imported_globals = 0;
+        if let Some(import_section) = self.module.import_section() {
+            for import in &import_section.imports {
+                let desc = match &import.desc {
+                    ImportDescNode::Func(type_idx) => {
+                        imported_funcs += 1;
+                        let id = self.func_id(imported_funcs - 1);
+                        format!("(func {} (type {}))", id, type_idx)
+                    }
+                    ImportDescNode::Table(table_type) => {
+                        imported_tables += 1;
+                        format!("(table (;{};) {})", imported_tables - 1, table(table_type))
+                    }
+                    ImportDescNode::Memory(mem_type) => {
+                        imported_memories += 1;
+                        let limits = limits(&mem_type.limits);
+                        format!("(memory (;{};) {})", imported_memories - 1, limits)
+                    }
+                    ImportDescNode::Global(global_type) => {
+                        imported_globals += 1;
+                        format!(
+                            "(global (;{};) {})",
+                            imported_globals - 1,
+                            global(global_type)
+                        )
+                    }
+                };
+                let line = format!(
+                    "(import {} {} {})",
+                    string(&import.module),
+                    string(&import.name),
+                    desc
+                );
+                self.line(&line);
+            }
+        }
+
+        self.funcs(imported_funcs);
+
+        if let Some(table_section) = self.module.table_section() {
+            for (i, table_type) in table_section.table_types.iter().enumerate() {
+                let line = format!("(table (;{};) {})", imported_tables + i, table(table_type));
+                self.line(&line);
+            }
+        }
+        if let Some(memory_section) = self.module.memory_section() {
+            for (i, mem_type) in memory_section.mem_types.iter().enumerate() {
+                let line = format!(
+                    "(memory (;{};) {})",
+                    imported_memories + i,
+                    limits(&mem_type.limits)
+                );
+                self.line(&line);
+            }
+        }
+        if let Some(global_section) = self.module.global_section() {
+            for (i, node) in global_section.globals.iter().enumerate() {
+                let init: Vec<String> = node
+                    .init
+                    .intrinsics
+                    .iter()
+                    .map(|intrinsic| format!("({})", self.instr(intrinsic, 0)))
+                    .collect();
+                let line = format!(
+                    "(global (;{};) {} {})",
+                    imported_globals + i,
+                    global(&node.global_type),
+                    init.join(" ")
+                );
+                self.line(&line);
+            }
+        }
+
+        if let Some(export_section) = self.module.export_section() {
+            for export in &export_section.exports {
+                let index = export.desc.index;
+                let desc = match export.desc.kind() {
+                    Ok(ExportKind::Func) => format!("func {}", self.func_ref(index)),
+                    Ok(ExportKind::Table) => format!("table {}", index),
+                    Ok(ExportKind::Memory) => format!("memory {}", index),
+                    Ok(ExportKind::Global) => format!("global {}", index),
+                    Err(_) => continue,
+                };
+                self.line(&format!("(export {} ({}))", string(&export.name), desc));
+            }
+        }
+        if let Some(start) = self.module.start_section() {
+            self.line(&format!("(start {})", self.func_ref(start.func_idx)));
+        }
+
+        self.depth -= 1;
+        self.close();
+    }
+
+    fn funcs(&mut self, imported_funcs: u32) {
+        let (Some(function_section), Some(code_section)) =
+            (self.module.function_section(), self.module.code_section())
+        else {
+            return;
+        };
+        for (i, (type_idx, code)) in function_section
+            .type_indices
+            .iter()
+            .zip(&code_section.codes)
+            .enumerate()
+        {
+            let func_idx = imported_funcs + i as u32;
+            let mut header = format!("(func {} (type {})", self.func_id(func_idx), type_idx);
+            let param_count = match self.func_type(*type_idx) {
+                Some(func_type) => {
+                    let local_ids = self.local_ids.get(&func_idx);
+                    let id = |idx: u32| local_ids.and_then(|ids| ids.get(&idx)).cloned();
+                    header.push_str(&signature(func_type, id));
+                    func_type.param_type.val_types.len() as u32
+                }
+                None => 0,
+            };
+            self.line(&header);
+            self.depth += 1;
+
+            let mut local_idx = param_count;
+            for local in &code.func.locals {
+                for _ in 0..local.num {
+                    let line = match self
+                        .local_ids
+                        .get(&func_idx)
+                        .and_then(|ids| ids.get(&local_idx))
+                    {
+                        Some(id) => format!("(local {} {})", id, local.val_type),
+                        None => format!("(local {})", local.val_type),
+                    };
+                    self.line(&line);
+                    local_idx += 1;
+                }
+            }
+            self.expr(&code.func.expr, func_idx);
+
+            self.depth -= 1;
+            self.close();
+        }
+    }
+
+    fn expr(&mut self, expr: &ExprNode, func_idx: u32) {
+        for intrinsic in &expr.intrinsics {
+            match intrinsic {
+                IntrinsicNode::BlockIntrinsicNode(node) => {
+                    self.line(&format!("block{}", block_type(&node.block_type)));
+                    self.nested(&node.expr, func_idx);
+                    self.line("end");
+                }
+                IntrinsicNode::LoopIntrinsicNode(node) => {
+                    self.line(&format!("loop{}", block_type(&node.block_type)));
+                    self.nested(&node.expr, func_idx);
+                    self.line("end");
+                }
+                IntrinsicNode::IfIntrinsicNode(node) => {
+                    self.line(&format!("if{}", block_type(&node.block_type)));
+                    self.nested(&node.then_expr, func_idx);
+                    if !node.else_expr.intrinsics.is_empty() {
+                        self.line("else");
+                        self.nested(&node.else_expr, func_idx);
+                    }
+                    self.line("end");
+                }
+                _ => {
+                    let line = self.instr(intrinsic, func_idx);
+                    self.line(&line);
+                }
+            }
+        }
+    }
+
+    fn nested(&mut self, expr: &ExprNode, func_idx: u32) {
+        self.depth += 1;
+        self.expr(expr, func_idx);
+        self.depth -= 1;
+    }
+
+    /// Plain instruction with its immediates, locals and functions referenced by `$id`.
+    fn instr(&self, intrinsic: &IntrinsicNode, func_idx: u32) -> String {
+        let name = intrinsic.opcode().name();
+        match intrinsic {
+            IntrinsicNode::LocalGetIntrinsicNode(node) => {
+                format!("{} {}", name, self.local_ref(func_idx, node.local_idx))
+            }
+            IntrinsicNode::LocalSetIntrinsicNode(node) => {
+                format!("{} {}", name, self.local_ref(func_idx, node.local_idx))
+            }
+            IntrinsicNode::CallIntrinsicNode(node) => {
+                format!("{} {}", name, self.func_ref(node.func_idx))
+            }
+            _ => print_instr(intrinsic),
+        }
+    }
+}
+
+/// Single instruction with its immediates, indices being numeric.
+/// Blocks are printed without their body, e.g. `loop (result i32)`.
+pub fn print_instr(intrinsic: &IntrinsicNode) -> String {
+    let name = intrinsic.opcode().name();
+    let immediate = match intrinsic {
+        IntrinsicNode::BlockIntrinsicNode(node) => {
+            return name.to_string() + &block_type(&node.block_type)
+        }
+        IntrinsicNode::LoopIntrinsicNode(node) => {
+            return name.to_string() + &block_type(&node.block_type)
+        }
+        IntrinsicNode::IfIntrinsicNode(node) => {
+            return name.to_string() + &block_type(&node.block_type)
+        }
+        IntrinsicNode::LocalGetIntrinsicNode(node) => node.local_idx.to_string(),
+        IntrinsicNode::LocalSetIntrinsicNode(node) => node.local_idx.to_string(),
+        IntrinsicNode::GlobalGetIntrinsicNode(node) => node.global_idx.to_string(),
+        IntrinsicNode::GlobalSetIntrinsicNode(node) => node.global_idx.to_string(),
+        IntrinsicNode::CallIntrinsicNode(node) => node.func_idx.to_string(),
+        IntrinsicNode::CallIndirectIntrinsicNode(node) => {
+            format!("{} (type {})", node.table_idx, node.type_idx)
+        }
+        IntrinsicNode::BrIntrinsicNode(node) => node.label_idx.to_string(),
+        IntrinsicNode::BrIfIntrinsicNode(node) => node.label_idx.to_string(),
+        IntrinsicNode::I32ConstIntrinsicNode(node) => node.val.to_string(),
+        IntrinsicNode::I64ConstIntrinsicNode(node) => node.val.to_string(),
+        IntrinsicNode::F32ConstIntrinsicNode(node) => {
+            float(node.val, node.val.is_nan(), node.val.to_bits() as u64, 32)
+        }
+        IntrinsicNode::F64ConstIntrinsicNode(node) => {
+            float(node.val, node.val.is_nan(), node.val.to_bits(), 64)
+        }
+        IntrinsicNode::I32LoadIntrinsicNode(node) => mem_arg(&node.mem_arg, 2),
+        IntrinsicNode::I32StoreIntrinsicNode(node) => mem_arg(&node.mem_arg, 2),
+        IntrinsicNode::RefNullIntrinsicNode(node) => match node.ref_type {
+            RefType::FuncRef(_) => "func".to_string(),
+            RefType::ExternRef(_) => "extern".to_string(),
+        },
+        IntrinsicNode::TableGrowIntrinsicNode(node) => node.table_idx.to_string(),
+        IntrinsicNode::TableSizeIntrinsicNode(node) => node.table_idx.to_string(),
+        _ => String::new(),
+    };
+    if immediate.is_empty() {
+        name.to_string()
+    } else {
+        format!("{} {}", name, immediate)
+    }
+}
+
+/// `(param ...) (result ...)` of `func_type`, empty if it has neither.
+pub fn print_signature(func_type: &FunctionTypeNode) -> String {
+    signature(func_type, |_| None).trim_start().to_string()
+}
+
+/// ` (param ...) (result ...)`, params named by `id` get one `(param $id t)` each.
+fn signature(func_type: &FunctionTypeNode, id: impl Fn(u32) -> Option<String>) -> String {
+    let mut text = String::new();
+    let params = &func_type.param_type.val_types;
+    if !params.is_empty() {
+        if (0..params.len() as u32).any(|i| id(i).is_some()) {
+            for (i, val_type) in params.iter().enumerate() {
+                match id(i as u32) {
+                    Some(id) => text.push_str(&format!(" (param {} {})", id, val_type)),
+                    None => text.push_str(&format!(" (param {})", val_type)),
+                }
+            }
+        } else {
+            text.push_str(&format!(" (param {})", val_types(params)));
+        }
+    }
+    let results = &func_type.result_type.val_types;
+    if !results.is_empty() {
+        text.push_str(&format!(" (result {})", val_types(results)));
+    }
+    text
+}
+
+/// String literal, escaping every byte but printable ASCII.
+fn string(name: &str) -> String {
+    let mut text = String::from("\"");
+    for byte in name.bytes() {
+        match byte {
+            b'"' | b'\\' => text.push_str(&format!("\\{}", byte as char)),
+            0x20..=0x7e => text.push(byte as char),
+            _ => text.push_str(&format!("\\{:02x}", byte)),
+        }
+    }
+    text.push('"');
+    text
+}
+
+fn val_types(val_types: &[ValType]) -> String {
+    let names: Vec<String> = val_types.iter().map(|t| t.to_string()).collect();
+    names.join(" ")
+}
+
+fn block_type(block_type: &BlockType) -> String {
+    match block_type {
+        BlockType::Empty => String::new(),
+        BlockType::ValType(val_type) => format!(" (result {})", val_type),
+        BlockType::S33(type_idx) => format!(" (type {})", type_idx),
+    }
+}
+
+fn limits(limits: &LimitsNode) -> String {
+    match limits.max {
+        Some(max) => format!("{} {}", limits.min, max),
+        None => limits.min.to_string(),
+    }
+}
+
+fn table(table_type: &TableTypeNode) -> String {
+    format!("{} {}", limits(&table_type.limits), table_type.ref_type)
+}
+
+fn global(global_type: &GlobalTypeNode) -> String {
+    if global_type.mutable {
+        format!("(mut {})", global_type.val_type)
+    } else {
+        global_type.val_type.to_string()
+    }
+}
+
+/// `offset=` and `align=` when they differ from the defaults.
+fn mem_arg(mem_arg: &MemArgNode, natural_align: u32) -> String {
+    let mut parts = Vec::new();
+    if mem_arg.offset != 0 {
+        parts.push(format!("offset={}", mem_arg.offset));
+    }
+    if mem_arg.align != natural_align {
+        parts.push(format!("align={}", 1_u64 << mem_arg.align));
+    }
+    parts.join(" ")
+}
+
+/// Float literal that parses back to the same bits, NaN payloads included.
+/// `width` is 32 or 64, the size of `bits`.
+fn float(value: impl std::fmt::Display, is_nan: bool, bits: u64, width: u32) -> String {
+    if !is_nan {
+        return value.to_string();
+    }
+    let mantissa_bits = if width == 32 { 23 } else { 52 };
+    let sign = if bits >> (width - 1) & 1 == 1 {
+        "-"
+    } else {
+        ""
+    };
+    let payload = bits & ((1 << mantissa_bits) - 1);
+    if payload == 1 << (mantissa_bits - 1) {
+        format!("{}nan", sign)
+    } else {
+        format!("{}nan:{:#x}", sign, payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::buffer::Buffer;
+    use crate::wat::module::parse_str;
+    use rstest::rstest;
+    use std::fs;
+
+    fn load(bytes: Vec<u8>) -> ModuleNode {
+        let mut module = ModuleNode::new();
+        module.load(&mut Buffer::new(&bytes)).unwrap();
+        module
+    }
+
+    #[test]
+    fn test_print_module() {
+        let module = load(fs::read("examples/if.wasm").unwrap());
+        let expected = r#"(module
+  (type (;0;) (func (param i32) (result i32)))
+  (func (;0;) (type 0) (param i32) (result i32)
+    local.get 0
+    i32.const 10
+    i32.ge_s
+    if (result i32)
+      i32.const 1
+    else
+      i32.const 0
+    end)
+  (export "ge10" (func 0)))
+"#;
+        assert_eq!(print_module(&module), expected);
+    }
+
+    #[test]
+    fn test_print_names() {
+        let module = load(parse_str(&fs::read_to_string("examples/trap.wat").unwrap()).unwrap());
+        let text = print_module(&module);
+        assert!(text.contains("(func $div_s (type 0) (param $a i32) (param $b i32) (result i32)"));
+        assert!(text.contains("local.get $b"));
+    }
+
+    #[test]
+    fn test_string() {
+        assert_eq!(string("a\"b\\é\0"), r#""a\"b\\\c3\a9\00""#);
+    }
+
+    #[rstest(value, expected, case(1.5, "1.5"), case(f64::NEG_INFINITY, "-inf"), case(-f64::NAN, "-nan"))]
+    fn test_float(value: f64, expected: &str) {
+        assert_eq!(float(value, value.is_nan(), value.to_bits(), 64), expected);
+        assert_eq!(float(f32::NAN, true, 0x7f80_0001, 32), "nan:0x1");
+    }
+
+    /// Printing then parsing a module gives back the same module.
+    #[rstest(
+        name,
+        case("add"),
+        case("call"),
+        case("if"),
+        case("local_var"),
+        case("loop"),
+        case("recursion"),
+        case("trap"),
+        case("memory"),
+        case("exports"),
+        case("lib"),
+        case("main")
+    )]
+    fn test_round_trip(name: &str) {
+        let wasm = fs::read(format!("examples/{}.wasm", name)).unwrap();
+        let text = print_module(&load(wasm.clone()));
+        let bytes = parse_str(&text).unwrap();
+        assert_eq!(print_module(&load(bytes)), text);
+    }
+}
@@ -0,0 +1,201 @@
+use anyhow::{anyhow, Result};
+
+use crate::wat::lexer::{Lexer, Pos, Token, TokenKind};
+
+/// Node of the S-expression tree the text format is made of.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SExpr {
+    Atom(String, Pos),
+    String(Vec<u8>, Pos),
+    List(Vec<SExpr>, Pos),
+}
+impl SExpr {
+    /// Parse every top-level S-expression of `src`.
+    pub fn parse_all(src: &str) -> Result<Vec<SExpr>> {
+        let tokens = Lexer::new(src).tokenize()?;
+        let mut tokens = tokens.into_iter();
+        let mut exprs = Vec::new();
+        while let Some(token) = tokens.next() {
+            exprs.push(Self::parse_token(token, &mut tokens)?);
+        }
+        Ok(exprs)
+    }
+
+    fn parse_token(token: Token, tokens: &mut impl Iterator<Item = Token>) -> Result<SExpr> {
+        match token.kind {
+            TokenKind::Atom(atom) => Ok(SExpr::Atom(atom, token.pos)),
+            TokenKind::String(bytes) => Ok(SExpr::String(bytes, token.pos)),
+            TokenKind::RParen => Err(anyhow!("{}: unexpected `)`", token.pos)),
+            TokenKind::LParen => {
+                let mut items = Vec::new();
+                loop {
+                    let next = tokens
+                        .next()
+                        .ok_or(anyhow!("{}: unclosed `(`", token.pos))?;
+                    if next.kind == TokenKind::RParen {
+                        return Ok(SExpr::List(items, token.pos));
+                    }
+                    items.push(Self::parse_token(next, tokens)?);
+                }
+            }
+        }
+    }
+
+    pub fn pos(&self) -> Pos {
+        match self {
+            SExpr::Atom(_, pos) | SExpr::String(_, pos) | SExpr::List(_, pos) => *pos,
+        }
+    }
+
+    pub fn atom(&self) -> Option<&str> {
+        match self {
+            SExpr::Atom(atom, _) => Some(atom),
+            _ => None,
+        }
+    }
+
+    pub fn list(&self) -> Option<&[SExpr]> {
+        match self {
+            SExpr::List(items, _) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Keyword starting a list, e.g. `func` for `(func ...)`.
+    pub fn head(&self) -> Option<&str> {
+        self.list()?.first()?.atom()
+    }
+}
+
+/// Reads the items of a list one after the other.
+#[derive(Debug, Clone)]
+pub struct Cursor<'a> {
+    items: &'a [SExpr],
+    /// Position of the list, for errors at its end.
+    pos: Pos,
+}
+impl<'a> Cursor<'a> {
+    pub fn new(items: &'a [SExpr], pos: Pos) -> Cursor<'a> {
+        Cursor { items, pos }
+    }
+
+    /// Cursor over the items of `list` following its head keyword.
+    pub fn of_list(list: &'a SExpr) -> Result<Cursor<'a>> {
+        let items = list
+            .list()
+            .ok_or(anyhow!("{}: expected a list", list.pos()))?;
+        Ok(Cursor::new(items.get(1..).unwrap_or(&[]), list.pos()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn peek(&self) -> Option<&'a SExpr> {
+        self.items.first()
+    }
+
+    /// Position of the next item, or of the list if there is none.
+    pub fn pos(&self) -> Pos {
+        self.peek().map_or(self.pos, |item| item.pos())
+    }
+
+    pub fn error(&self, message: &str) -> anyhow::Error {
+        anyhow!("{}: {}", self.pos(), message)
+    }
+
+    pub fn expect_atom(&mut self) -> Result<&'a str> {
+        match self.peek().and_then(|item| item.atom()) {
+            Some(atom) => {
+                self.next();
+                Ok(atom)
+            }
+            None => Err(self.error("expected a keyword or a number")),
+        }
+    }
+
+    pub fn expect_string(&mut self) -> Result<&'a [u8]> {
+        match self.peek() {
+            Some(SExpr::String(bytes, _)) => {
+                self.next();
+                Ok(bytes)
+            }
+            _ => Err(self.error("expected a string")),
+        }
+    }
+
+    pub fn expect_name(&mut self) -> Result<String> {
+        let pos = self.pos();
+        let bytes = self.expect_string()?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| anyhow!("{}: malformed UTF-8 encoding", pos))
+    }
+
+    pub fn expect_end(&self) -> Result<()> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error("unexpected token")),
+        }
+    }
+
+    /// Consume the next item if it is the atom `keyword`.
+    pub fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek().and_then(|item| item.atom()) == Some(keyword);
+        if found {
+            self.next();
+        }
+        found
+    }
+
+    /// Consume a `$id` if there is one, returning it without the `$`.
+    pub fn id(&mut self) -> Option<&'a str> {
+        let id = self.peek()?.atom()?.strip_prefix('$')?;
+        self.next();
+        Some(id)
+    }
+
+    /// Consume the next item if it is a list starting with `keyword`.
+    pub fn list(&mut self, keyword: &str) -> Option<&'a SExpr> {
+        let item = self.peek()?;
+        if item.head() != Some(keyword) {
+            return None;
+        }
+        self.next();
+        Some(item)
+    }
+}
+
+impl<'a> Iterator for Cursor<'a> {
+    type Item = &'a SExpr;
+
+    fn next(&mut self) -> Option<&'a SExpr> {
+        let (first, rest) = self.items.split_first()?;
+        self.items = rest;
+        Some(first)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_all() {
+        let exprs = SExpr::parse_all("(module (func $f)) (invoke \"f\")").unwrap();
+        assert_eq!(exprs.len(), 2);
+        assert_eq!(exprs[0].head(), Some("module"));
+
+        let mut cursor = Cursor::of_list(&exprs[0]).unwrap();
+        let func = cursor.list("func").unwrap();
+        assert!(cursor.is_empty());
+        assert_eq!(Cursor::of_list(func).unwrap().id(), Some("f"));
+
+        let mut cursor = Cursor::of_list(&exprs[1]).unwrap();
+        assert_eq!(cursor.expect_name().unwrap(), "f");
+    }
+
+    #[test]
+    fn test_unbalanced() {
+        assert!(SExpr::parse_all("(module").is_err());
+        assert!(SExpr::parse_all("(module))").is_err());
+    }
+}